@@ -0,0 +1,11 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Stores the build time in `OTTERNEL_BUILD_TIMESTAMP` (Unix seconds), shown by `GET /api/debug/info`.
+fn main() {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    println!("cargo:rustc-env=OTTERNEL_BUILD_TIMESTAMP={}", timestamp);
+    println!("cargo:rerun-if-changed=src");
+}
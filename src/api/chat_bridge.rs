@@ -0,0 +1,110 @@
+use axum::extract::{Path, Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::Response;
+use axum::routing::post;
+use axum::{Json, Router};
+use log::debug;
+use serde::Deserialize;
+
+use crate::app::AppContext;
+use crate::helper::rcon_helper::{RconHelper, RconHelperError};
+
+/// Longest message relayed, longer ones are cut
+const MAX_MESSAGE_CHARS: usize = 256;
+
+/// Body of `POST /servers/{active_id}/say`.
+#[derive(Deserialize)]
+pub struct SayRequest {
+    /// Name shown before the message (the Discord user)
+    pub author: String,
+    pub message: String,
+}
+
+/// Builds the routes of the Discord -> game chat bridge, all behind `CHAT_BRIDGE_TOKEN`.
+pub fn router(ctx: AppContext) -> Router {
+    Router::new()
+        .route("/servers/{active_id}/say", post(say))
+        .layer(middleware::from_fn_with_state(ctx.clone(), require_bridge_token))
+        .with_state(ctx)
+}
+
+/// Rejects the requests without the `Authorization: Bearer <CHAT_BRIDGE_TOKEN>` header.
+/// Without a token set, every request is rejected : the bridge writes in the game chats.
+async fn require_bridge_token(State(ctx): State<AppContext>, request: Request, next: Next) -> Result<Response, StatusCode> {
+    let token = ctx.config.chat_bridge_token.as_str();
+    let authorized = !token.is_empty()
+        && request
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .is_some_and(|given| given == token);
+
+    if authorized {
+        Ok(next.run(request).await)
+    } else {
+        Err(StatusCode::UNAUTHORIZED)
+    }
+}
+
+/// `POST /servers/{active_id}/say` : sends a Discord message in the chat of an active server,
+/// with `tellraw` for Minecraft and `Broadcast` for Palworld.
+async fn say(
+    State(ctx): State<AppContext>,
+    Path(active_id): Path<u64>,
+    Json(request): Json<SayRequest>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let author = one_line(&request.author);
+    let message = one_line(&request.message);
+    if author.is_empty() || message.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "author and message must not be empty".to_string()));
+    }
+
+    let Some(db) = ctx.db.clone() else {
+        return Err((StatusCode::SERVICE_UNAVAILABLE, "No database available".to_string()));
+    };
+    let lookup_db = db.clone();
+    let server = tokio::task::spawn_blocking(move || lookup_db.get_server_by_active_server_id(active_id))
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or((StatusCode::NOT_FOUND, format!("No active server with id {}", active_id)))?;
+
+    let command = match server.jeu.to_lowercase().as_str() {
+        "minecraft" => tellraw_command(&author, &message),
+        "palworld" => format!("Broadcast [{}] {}", author, message),
+        other => return Err((StatusCode::BAD_REQUEST, format!("No chat relay for the game {}", other))),
+    };
+
+    let rcon = RconHelper { db: (*db).clone() };
+    match rcon.execute_command(active_id, &command).await {
+        Ok(_) => {
+            debug!("Discord message of {} relayed to server {}", author, server.nom);
+            Ok(StatusCode::NO_CONTENT)
+        }
+        Err(RconHelperError::ServerNotFound(id)) => Err((StatusCode::NOT_FOUND, format!("No active server with id {}", id))),
+        Err(e) => Err((StatusCode::BAD_GATEWAY, e.to_string())),
+    }
+}
+
+/// Builds the `tellraw` of a Discord message : `[Discord] <author> message`.
+/// The texts go through serde_json, so quotes and backslashes can't break the JSON component.
+fn tellraw_command(author: &str, message: &str) -> String {
+    let component = serde_json::json!([
+        {"text": "[Discord] ", "color": "blue"},
+        {"text": format!("<{}> ", author), "color": "aqua"},
+        {"text": message, "color": "white"},
+    ]);
+    format!("tellraw @a {}", component)
+}
+
+/// Keeps a message on one line (a line break would end the RCON command) and cuts it to `MAX_MESSAGE_CHARS`.
+fn one_line(text: &str) -> String {
+    text.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .chars()
+        .take(MAX_MESSAGE_CHARS)
+        .collect()
+}
@@ -0,0 +1,142 @@
+use std::path::Path;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::Json;
+use chrono::DateTime;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::app::AppContext;
+use crate::serverlog::log_watcher;
+
+/// Body of `GET /api/debug/info`.
+#[derive(Serialize)]
+pub struct DebugInfo {
+    pub version: &'static str,
+    /// Build time, RFC 3339
+    pub build_timestamp: Option<String>,
+    /// Configuration without its secrets, see [`crate::config::Config::redacted_summary`]
+    pub config: Map<String, Value>,
+    /// Names of the loaded triggers
+    pub triggers: Vec<String>,
+    pub watched_folders: Vec<WatchedFolder>,
+    /// Number of active servers, `None` without database
+    pub active_servers: Option<usize>,
+}
+
+/// A folder watched for logs, with its number of `.log` files.
+#[derive(Serialize)]
+pub struct WatchedFolder {
+    pub path: String,
+    pub log_files: usize,
+}
+
+/// `GET /api/debug/info` : version, configuration summary, triggers, watched folders and active servers,
+/// to help an admin remotely. Refused when `API_TOKEN` isn't set, as the answer describes the whole setup.
+pub async fn debug_info(State(ctx): State<AppContext>) -> Result<Json<DebugInfo>, (StatusCode, String)> {
+    if ctx.config.api_token.is_empty() {
+        return Err((StatusCode::FORBIDDEN, "API_TOKEN must be set to use the debug endpoint".to_string()));
+    }
+
+    let folder = ctx.config.serverlog_folder.clone();
+    let log_files = tokio::task::spawn_blocking(move || count_log_files(Path::new(&folder)))
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let active_servers = match ctx.db.clone() {
+        Some(db) => db
+            .call(|db| db.get_all_active_servers())
+            .await
+            .map(|servers| Some(servers.len()))
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?,
+        None => None,
+    };
+
+    let triggers = log_watcher::loaded_trigger_names();
+    Ok(Json(DebugInfo {
+        version: env!("CARGO_PKG_VERSION"),
+        build_timestamp: env!("OTTERNEL_BUILD_TIMESTAMP")
+            .parse()
+            .ok()
+            .and_then(|secs| DateTime::from_timestamp(secs, 0))
+            .map(|date| date.to_rfc3339()),
+        config: ctx.config.redacted_summary(),
+        triggers,
+        watched_folders: vec![WatchedFolder {
+            path: ctx.config.serverlog_folder.clone(),
+            log_files,
+        }],
+        active_servers,
+    }))
+}
+
+/// Counts the `.log` files of a folder and its sub folders, as watched by the log watcher.
+fn count_log_files(folder: &Path) -> usize {
+    let Ok(entries) = std::fs::read_dir(folder) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .map(|entry| {
+            let path = entry.path();
+            if path.is_dir() {
+                count_log_files(&path)
+            } else {
+                usize::from(path.extension().is_some_and(|ext| ext == "log"))
+            }
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use axum::body::Body;
+    use axum::http::{header, Request};
+    use http_body_util::BodyExt;
+    use tower::ServiceExt;
+
+    fn request(authorization: &str) -> Request<Body> {
+        Request::get("/api/debug/info").header(header::AUTHORIZATION, authorization).body(Body::empty()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn refused_without_api_token() {
+        let router = crate::api::router(AppContext::for_tests(Config::for_tests(&[]), &[]));
+        let response = router.oneshot(request("Bearer anything")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn info_of_the_setup_without_secrets() {
+        let folder = std::env::temp_dir().join(format!("otternel-debug-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(folder.join("survie")).unwrap();
+        for file in ["a.log", "survie/b.log", "survie/b.log.1", "notes.txt"] {
+            std::fs::write(folder.join(file), "").unwrap();
+        }
+        let config = Config::for_tests(&[
+            ("API_TOKEN", "hunter2-api-token"),
+            ("SERVERLOG_FOLDER", folder.to_str().unwrap()),
+            ("CHAT_BRIDGE_TOKEN", "hunter2-bridge-token"),
+            ("WEBHOOK_VALHEIM_URL", "https://discord.com/api/webhooks/6/hunter2-valheim"),
+        ]);
+        let router = crate::api::router(AppContext::for_tests(config, &[]));
+
+        let response = router.oneshot(request("Bearer hunter2-api-token")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let text = String::from_utf8(body.to_vec()).unwrap();
+        assert!(!text.contains("hunter2"), "{text}");
+
+        let info: Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(info["version"], env!("CARGO_PKG_VERSION"));
+        assert!(info["build_timestamp"].as_str().is_some_and(|date| DateTime::parse_from_rfc3339(date).is_ok()));
+        assert!(info["triggers"].is_array());
+        assert_eq!(info["watched_folders"][0]["log_files"], 2);
+        assert!(info["active_servers"].is_null());
+        assert!(info["config"]["webhooks"].as_array().unwrap().iter().any(|w| w["name"] == "valheim"));
+
+        let _ = std::fs::remove_dir_all(&folder);
+    }
+}
@@ -0,0 +1,272 @@
+use std::collections::{BTreeMap, HashMap};
+use axum::extract::{Path, State};
+use axum::http::{header, StatusCode};
+use axum::routing::get;
+use axum::{Json, Router};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::app::jobs::JobOutcome;
+use crate::app::{AppContext, TaskStatus};
+use crate::helper::{active_servers, metrics, player_privacy, webhook_check};
+use crate::serverlog::{log_watcher, online_tracker, processing_lag};
+
+/// The watcher is down when its loop hasn't turned for this long
+const WATCHER_STALE_AFTER: chrono::Duration = chrono::Duration::minutes(5);
+/// Job of the periodic player stats fetch
+const PERIODIC_JOB: &str = "player_stats_sync";
+
+/// Body of `GET /healthz`.
+#[derive(Serialize)]
+pub struct Health {
+    /// "ok", "degraded" (a task is degraded, a webhook is invalid or a log file is lagging) or "failed" (a task or a component failed)
+    pub status: &'static str,
+    /// Status of each task, by task name
+    pub tasks: BTreeMap<String, String>,
+    /// Invalid webhooks found by the last check, by identity
+    pub invalid_webhooks: BTreeMap<String, String>,
+    /// Log files whose processing is behind real time by more than `PROCESSING_LAG_THRESHOLD_SEC`
+    pub lagging_files: Vec<String>,
+    /// Servers of the active server list, as of its last refresh
+    pub active_servers: usize,
+    pub watcher: WatcherHealth,
+    pub database: DatabaseHealth,
+    pub periodic: PeriodicHealth,
+}
+
+#[derive(Serialize)]
+pub struct WatcherHealth {
+    pub ok: bool,
+    pub last_heartbeat: Option<DateTime<Utc>>,
+    pub last_event: Option<DateTime<Utc>>,
+}
+
+/// The error of a failed ping isn't given : it may contain the address of the database.
+#[derive(Serialize)]
+pub struct DatabaseHealth {
+    pub ok: bool,
+    pub checked_at: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+pub struct PeriodicHealth {
+    pub ok: bool,
+    /// Whether the periodic player stats fetch runs (`GET_PLAYER_STATS_ENABLED`)
+    pub enabled: bool,
+    pub last_run: Option<DateTime<Utc>>,
+    /// "ok" or "error", `None` before the first run
+    pub last_outcome: Option<&'static str>,
+}
+
+/// `GET /healthz`, served by the API and by the healthcheck server : health of the tasks, of the webhooks,
+/// of the watcher (turned less than 5 minutes ago), of the database (answers to a ping) and of the last periodic run.
+/// Answers 503 when a task or a component failed, 200 otherwise.
+pub async fn healthz(State(ctx): State<AppContext>) -> (StatusCode, Json<Health>) {
+    let now = Utc::now();
+
+    let last_heartbeat = log_watcher::last_heartbeat();
+    let watcher = WatcherHealth {
+        ok: last_heartbeat.is_some_and(|heartbeat| now - heartbeat < WATCHER_STALE_AFTER),
+        last_heartbeat,
+        last_event: log_watcher::last_event(),
+    };
+
+    let database_ok = match ctx.db.clone() {
+        Some(db) => db.call(|db| db.ping().is_ok()).await,
+        None => false,
+    };
+    let database = DatabaseHealth { ok: database_ok, checked_at: Utc::now() };
+
+    let job = ctx.jobs.snapshot().into_iter().find(|job| job.name == PERIODIC_JOB);
+    let last_outcome = job.as_ref().and_then(|job| job.last_outcome.as_ref()).map(|outcome| match outcome {
+        JobOutcome::Ok => "ok",
+        JobOutcome::Error(_) => "error",
+    });
+    let periodic = PeriodicHealth {
+        ok: last_outcome != Some("error"),
+        enabled: job.is_some(),
+        last_run: job.and_then(|job| job.last_start),
+        last_outcome,
+    };
+
+    let invalid_webhooks = webhook_check::invalid_webhooks()
+        .into_iter()
+        .map(|problem| (problem.identity, problem.reason))
+        .collect();
+
+    let mut lagging_files: Vec<String> =
+        processing_lag::drowning_files().iter().map(|path| path.display().to_string()).collect();
+    lagging_files.sort();
+
+    let (code, health) = assess(ctx.task_statuses(), invalid_webhooks, lagging_files, watcher, database, periodic);
+    (code, Json(Health { active_servers: active_servers::snapshot().len(), ..health }))
+}
+
+/// Gives the overall status from the status of each part : a failed task or component fails the health (503),
+/// a degraded task, an invalid webhook or a lagging log file only degrades it (200).
+fn assess(
+    statuses: HashMap<String, TaskStatus>,
+    invalid_webhooks: BTreeMap<String, String>,
+    lagging_files: Vec<String>,
+    watcher: WatcherHealth,
+    database: DatabaseHealth,
+    periodic: PeriodicHealth,
+) -> (StatusCode, Health) {
+    let failed = statuses.values().any(|status| matches!(status, TaskStatus::Failed(_)));
+    let degraded = statuses.values().any(|status| matches!(status, TaskStatus::Degraded(_)));
+
+    let tasks = statuses
+        .into_iter()
+        .map(|(name, status)| {
+            let status = match status {
+                TaskStatus::Starting => "starting".to_string(),
+                TaskStatus::Running => "running".to_string(),
+                TaskStatus::Degraded(reason) => format!("degraded ({})", reason),
+                TaskStatus::Stopped => "stopped".to_string(),
+                TaskStatus::Failed(reason) => format!("failed ({})", reason),
+            };
+            (name, status)
+        })
+        .collect();
+
+    let (code, status) = if failed || !watcher.ok || !database.ok || !periodic.ok {
+        (StatusCode::SERVICE_UNAVAILABLE, "failed")
+    } else if degraded || !invalid_webhooks.is_empty() || !lagging_files.is_empty() {
+        (StatusCode::OK, "degraded")
+    } else {
+        (StatusCode::OK, "ok")
+    };
+    (code, Health { status, tasks, invalid_webhooks, lagging_files, active_servers: 0, watcher, database, periodic })
+}
+
+/// Builds the routes of the healthcheck server (`HEALTHCHECK_LISTEN_ADDR`), without any token : it exposes no secret.
+pub fn healthcheck_router(ctx: AppContext) -> Router {
+    Router::new()
+        .route("/healthz", get(healthz))
+        .route("/metrics", get(prometheus_metrics))
+        .route("/servers/{id}/online", get(online_players))
+        .with_state(ctx)
+}
+
+/// `GET /metrics` : counters of Otternel in the Prometheus text format, for Grafana.
+pub async fn prometheus_metrics() -> ([(header::HeaderName, &'static str); 1], String) {
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], metrics::render())
+}
+
+/// Body of `GET /servers/{id}/online`.
+#[derive(Serialize)]
+pub struct OnlinePlayers {
+    pub serverlog_id: u32,
+    /// Players online, hidden ones included
+    pub count: usize,
+    /// Names of the players online, without the hidden ones
+    pub players: Vec<String>,
+}
+
+/// `GET /servers/{id}/online` : players online on an active server, as tracked from its join/leave lines.
+/// A server that just started has no player, a server missing from the active server list answers 404.
+pub async fn online_players(Path(serverlog_id): Path<u32>) -> Result<Json<OnlinePlayers>, (StatusCode, String)> {
+    if !active_servers::includes(&active_servers::snapshot(), |server| server.active_id == serverlog_id as u64) {
+        return Err((StatusCode::NOT_FOUND, format!("No active server with id {}", serverlog_id)));
+    }
+    let online = online_tracker::players_of(serverlog_id);
+    let count = online.len();
+    let players = online.into_iter().filter(|player| !player_privacy::is_hidden("minecraft", player)).collect();
+    Ok(Json(OnlinePlayers { serverlog_id, count, players }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use axum::body::Body;
+    use axum::http::Request;
+    use http_body_util::BodyExt;
+    use tower::ServiceExt;
+
+    fn healthy_parts() -> (WatcherHealth, DatabaseHealth, PeriodicHealth) {
+        let now = Utc::now();
+        (
+            WatcherHealth { ok: true, last_heartbeat: Some(now), last_event: None },
+            DatabaseHealth { ok: true, checked_at: now },
+            PeriodicHealth { ok: true, enabled: true, last_run: None, last_outcome: Some("ok") },
+        )
+    }
+
+    fn statuses(tasks: &[(&str, TaskStatus)]) -> HashMap<String, TaskStatus> {
+        tasks.iter().map(|(name, status)| (name.to_string(), status.clone())).collect()
+    }
+
+    #[test]
+    fn everything_running_is_ok() {
+        let (watcher, database, periodic) = healthy_parts();
+        let (code, health) = assess(statuses(&[("log_watcher", TaskStatus::Running)]), BTreeMap::new(), Vec::new(), watcher, database, periodic);
+        assert_eq!(code, StatusCode::OK);
+        assert_eq!(health.status, "ok");
+        assert_eq!(health.tasks["log_watcher"], "running");
+    }
+
+    #[test]
+    fn lagging_watcher_invalid_webhook_or_lagging_file_is_degraded_but_answers_200() {
+        let (watcher, database, periodic) = healthy_parts();
+        let lagging = statuses(&[("log_watcher", TaskStatus::Degraded("120s behind".to_string()))]);
+        let (code, health) = assess(lagging, BTreeMap::new(), Vec::new(), watcher, database, periodic);
+        assert_eq!((code, health.status), (StatusCode::OK, "degraded"));
+        assert_eq!(health.tasks["log_watcher"], "degraded (120s behind)");
+
+        let (watcher, database, periodic) = healthy_parts();
+        let invalid = BTreeMap::from([("mineotter".to_string(), "404 Not Found".to_string())]);
+        let (code, health) = assess(statuses(&[]), invalid, Vec::new(), watcher, database, periodic);
+        assert_eq!((code, health.status), (StatusCode::OK, "degraded"));
+
+        let (watcher, database, periodic) = healthy_parts();
+        let lagging = vec!["/srv/12/latest.log".to_string()];
+        let (code, health) = assess(statuses(&[]), BTreeMap::new(), lagging, watcher, database, periodic);
+        assert_eq!((code, health.status), (StatusCode::OK, "degraded"));
+        assert_eq!(health.lagging_files, ["/srv/12/latest.log"]);
+    }
+
+    #[test]
+    fn failed_task_or_component_answers_503() {
+        let (watcher, database, periodic) = healthy_parts();
+        let failed = statuses(&[("api", TaskStatus::Failed("bind".to_string())), ("log_watcher", TaskStatus::Running)]);
+        let (code, health) = assess(failed, BTreeMap::new(), Vec::new(), watcher, database, periodic);
+        assert_eq!((code, health.status), (StatusCode::SERVICE_UNAVAILABLE, "failed"));
+
+        let (watcher, mut database, periodic) = healthy_parts();
+        database.ok = false;
+        let (code, _) = assess(statuses(&[]), BTreeMap::new(), Vec::new(), watcher, database, periodic);
+        assert_eq!(code, StatusCode::SERVICE_UNAVAILABLE);
+
+        let (mut watcher, database, periodic) = healthy_parts();
+        watcher.ok = false;
+        let (code, _) = assess(statuses(&[]), BTreeMap::new(), Vec::new(), watcher, database, periodic);
+        assert_eq!(code, StatusCode::SERVICE_UNAVAILABLE);
+
+        let (watcher, database, mut periodic) = healthy_parts();
+        periodic.ok = false;
+        let (code, _) = assess(statuses(&[]), BTreeMap::new(), Vec::new(), watcher, database, periodic);
+        assert_eq!(code, StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    /// The API and the healthcheck server answer the same body, here 503 as the tests have no database
+    #[tokio::test]
+    async fn both_servers_serve_the_same_healthz() {
+        let ctx = AppContext::for_tests(Config::for_tests(&[]), &[("log_watcher", TaskStatus::Running)]);
+        for router in [crate::api::router(ctx.clone()), healthcheck_router(ctx)] {
+            let response = router
+                .oneshot(Request::get("/healthz").body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(body["status"], "failed");
+            assert_eq!(body["database"]["ok"], false);
+            assert_eq!(body["tasks"]["log_watcher"], "running");
+            assert!(body["invalid_webhooks"].is_object());
+            assert!(body["lagging_files"].is_array());
+            assert!(body["active_servers"].is_u64());
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::Json;
+
+use crate::app::AppContext;
+use crate::app::jobs::{JobSnapshot, RunNowError};
+
+/// `GET /api/jobs` : lists the scheduled jobs with their last run and next due time.
+pub async fn list_jobs(State(ctx): State<AppContext>) -> Json<Vec<JobSnapshot>> {
+    Json(ctx.jobs.snapshot())
+}
+
+/// `POST /api/jobs/{name}/run` : runs a scheduled job now, unless it is already running.
+pub async fn run_job(
+    State(ctx): State<AppContext>,
+    Path(name): Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    match ctx.jobs.run_now(&name) {
+        Ok(()) => Ok(StatusCode::ACCEPTED),
+        Err(RunNowError::UnknownJob) => Err((StatusCode::NOT_FOUND, format!("Unknown job {}", name))),
+        Err(RunNowError::AlreadyRunning) => Err((StatusCode::CONFLICT, format!("Job {} is already running", name))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::app::AppContext;
+    use crate::config::Config;
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use http_body_util::BodyExt;
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    fn context() -> AppContext {
+        let ctx = AppContext::for_tests(Config::for_tests(&[]), &[]);
+        ctx.jobs.register("stats_sync", Duration::from_secs(3600), chrono::Utc::now());
+        ctx
+    }
+
+    #[tokio::test]
+    async fn jobs_are_listed() {
+        let response = crate::api::router(context())
+            .oneshot(Request::get("/api/jobs").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let jobs: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(jobs[0]["name"], "stats_sync");
+        assert_eq!(jobs[0]["every_sec"], 3600);
+        assert_eq!(jobs[0]["running"], false);
+        assert!(jobs[0]["last_outcome"].is_null());
+        assert!(jobs[0]["next_due"].is_string());
+    }
+
+    #[tokio::test]
+    async fn a_run_is_accepted_refused_while_running_and_unknown_jobs_are_404() {
+        let ctx = context();
+        let post = |uri: &str| Request::post(uri).body(Body::empty()).unwrap();
+        let router = crate::api::router(ctx.clone());
+
+        let response = router.clone().oneshot(post("/api/jobs/stats_sync/run")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        let response = router.clone().oneshot(post("/api/jobs/nope/run")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let (release, released) = tokio::sync::oneshot::channel::<()>();
+        let jobs = ctx.jobs.clone();
+        let running = tokio::spawn(async move { jobs.run("stats_sync", false, async { released.await.map_err(|e| e.to_string()) }).await });
+        while !ctx.jobs.snapshot()[0].running {
+            tokio::task::yield_now().await;
+        }
+        let response = router.oneshot(post("/api/jobs/stats_sync/run")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        release.send(()).unwrap();
+        running.await.unwrap();
+    }
+}
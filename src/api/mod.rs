@@ -0,0 +1,102 @@
+pub mod chat_bridge;
+pub mod debug;
+pub mod health;
+pub mod jobs;
+pub mod mutes;
+pub mod players;
+
+use axum::extract::{Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::Response;
+use axum::routing::{get, post, put};
+use axum::Router;
+
+use crate::app::AppContext;
+
+/// Builds the routes of the HTTP API. `/healthz` is left out of the token check, for the monitoring probes.
+pub fn router(ctx: AppContext) -> Router {
+    Router::new()
+        .route("/api/debug/info", get(debug::debug_info))
+        .route("/api/jobs", get(jobs::list_jobs))
+        .route("/api/jobs/{name}/run", post(jobs::run_job))
+        .route("/api/mutes", get(mutes::list_mutes))
+        .route("/api/servers/{id}/mute", post(mutes::mute_server).delete(mutes::unmute_server))
+        .route("/api/players/linked", get(players::linked_players))
+        .route("/api/players/{game}/{playername}/visibility", put(players::set_visibility))
+        .route("/api/players/{id}/servers/{server_id}/session-time", get(players::session_time))
+        .layer(middleware::from_fn_with_state(ctx.clone(), require_token))
+        .route("/healthz", get(health::healthz))
+        .with_state(ctx)
+}
+
+/// Rejects the requests without the `Authorization: Bearer <API_TOKEN>` header, when `API_TOKEN` is set.
+async fn require_token(State(ctx): State<AppContext>, request: Request, next: Next) -> Result<Response, StatusCode> {
+    let token = ctx.config.api_token.as_str();
+    if token.is_empty() {
+        return Ok(next.run(request).await);
+    }
+
+    let authorized = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|given| given == token);
+
+    if authorized {
+        Ok(next.run(request).await)
+    } else {
+        Err(StatusCode::UNAUTHORIZED)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use axum::body::Body;
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    async fn status_of(router: Router, uri: &str, authorization: Option<&str>) -> StatusCode {
+        let mut request = Request::get(uri);
+        if let Some(authorization) = authorization {
+            request = request.header(header::AUTHORIZATION, authorization);
+        }
+        router.oneshot(request.body(Body::empty()).unwrap()).await.unwrap().status()
+    }
+
+    fn router_with_token(token: &str) -> Router {
+        router(AppContext::for_tests(Config::for_tests(&[("API_TOKEN", token)]), &[]))
+    }
+
+    #[tokio::test]
+    async fn requests_without_the_token_are_rejected() {
+        let router = router_with_token("s3cret");
+        assert_eq!(status_of(router.clone(), "/api/jobs", None).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status_of(router.clone(), "/api/jobs", Some("Bearer wrong")).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status_of(router.clone(), "/api/jobs", Some("s3cret")).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status_of(router.clone(), "/api/jobs", Some("Basic s3cret")).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status_of(router, "/api/jobs", Some("Bearer s3cret")).await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn unknown_routes_are_rejected_before_saying_they_do_not_exist() {
+        let router = router_with_token("s3cret");
+        assert_eq!(status_of(router.clone(), "/api/nothing", None).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status_of(router, "/api/nothing", Some("Bearer s3cret")).await, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn healthz_is_exempt_from_the_token() {
+        // 503 as the tests have no database, but not 401
+        let status = status_of(router_with_token("s3cret"), "/healthz", None).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[tokio::test]
+    async fn without_api_token_everything_is_open() {
+        assert_eq!(status_of(router_with_token(""), "/api/jobs", None).await, StatusCode::OK);
+    }
+}
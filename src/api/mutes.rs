@@ -0,0 +1,67 @@
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::Json;
+use serde::Deserialize;
+
+use crate::app::AppContext;
+use crate::db::models::ServeurMute;
+use crate::helper::server_mute;
+
+/// Body of `POST /api/servers/{id}/mute`.
+#[derive(Deserialize)]
+pub struct MuteRequest {
+    /// Duration of the mute, in minutes
+    pub duration_min: u64,
+    pub reason: Option<String>,
+}
+
+/// `GET /api/mutes` : lists the active mutes.
+pub async fn list_mutes() -> Json<Vec<ServeurMute>> {
+    Json(server_mute::active_mutes())
+}
+
+/// `POST /api/servers/{id}/mute` : mutes the announcements of an active server for a duration.
+pub async fn mute_server(
+    State(ctx): State<AppContext>,
+    Path(id): Path<u64>,
+    Json(request): Json<MuteRequest>,
+) -> Result<Json<ServeurMute>, (StatusCode, String)> {
+    if request.duration_min == 0 {
+        return Err((StatusCode::BAD_REQUEST, "duration_min must be greater than 0".to_string()));
+    }
+    let duration = i64::try_from(request.duration_min)
+        .ok()
+        .and_then(chrono::Duration::try_minutes)
+        .ok_or((StatusCode::BAD_REQUEST, "duration_min is too large".to_string()))?;
+
+    let mute = tokio::task::spawn_blocking(move || {
+        let db = ctx.db.as_deref();
+        if let Some(db) = db {
+            match db.get_server_by_active_server_id(id) {
+                Ok(Some(_)) => {}
+                Ok(None) => return Err((StatusCode::NOT_FOUND, format!("No active server with id {}", id))),
+                Err(e) => return Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+            }
+        }
+        Ok(server_mute::mute(db, id, duration, request.reason))
+    })
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))??;
+
+    Ok(Json(mute))
+}
+
+/// `DELETE /api/servers/{id}/mute` : ends the mute of an active server early.
+pub async fn unmute_server(
+    State(ctx): State<AppContext>,
+    Path(id): Path<u64>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let removed = tokio::task::spawn_blocking(move || server_mute::unmute(ctx.db.as_deref(), id))
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    match removed {
+        Some(_) => Ok(StatusCode::NO_CONTENT),
+        None => Err((StatusCode::NOT_FOUND, format!("Server {} is not muted", id))),
+    }
+}
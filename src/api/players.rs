@@ -0,0 +1,123 @@
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::Json;
+use serde::{Deserialize, Serialize};
+
+use crate::app::AppContext;
+use crate::db::models::JoueurLie;
+use crate::helper::player_privacy;
+
+/// Body of `PUT /api/players/{game}/{playername}/visibility`.
+#[derive(Deserialize)]
+pub struct VisibilityRequest {
+    /// `false` to stop announcing the player's activity
+    pub visible: bool,
+}
+
+/// `PUT /api/players/{game}/{playername}/visibility` : shows or hides the activity of a player.
+/// A hidden player still gets their stats recorded.
+pub async fn set_visibility(
+    State(ctx): State<AppContext>,
+    Path((game, playername)): Path<(String, String)>,
+    Json(request): Json<VisibilityRequest>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let db = ctx
+        .db
+        .clone()
+        .ok_or((StatusCode::SERVICE_UNAVAILABLE, "Database unavailable".to_string()))?;
+
+    let found = tokio::task::spawn_blocking(move || player_privacy::set_visible(&db, &game, &playername, request.visible))
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+
+    if found {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err((StatusCode::NOT_FOUND, "No such player".to_string()))
+    }
+}
+
+/// Body of the answer of `GET /api/players/{id}/servers/{server_id}/session-time`.
+#[derive(Serialize)]
+pub struct SessionTime {
+    pub joueur_id: u64,
+    pub serveur_id: u64,
+    /// Time played, summed over the closed sessions
+    pub total_sec: u64,
+}
+
+/// `GET /api/players/{id}/servers/{server_id}/session-time` : total time played by a player on a server.
+pub async fn session_time(
+    State(ctx): State<AppContext>,
+    Path((joueur_id, serveur_id)): Path<(u64, u64)>,
+) -> Result<Json<SessionTime>, (StatusCode, String)> {
+    let db = ctx
+        .db
+        .clone()
+        .ok_or((StatusCode::SERVICE_UNAVAILABLE, "Database unavailable".to_string()))?;
+
+    let total_sec = db
+        .call(move |db| db.get_total_session_time(joueur_id, serveur_id))
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(SessionTime { joueur_id, serveur_id, total_sec }))
+}
+
+/// Query of `GET /api/players/linked`.
+#[derive(Deserialize)]
+pub struct LinkedQuery {
+    /// Only the links seen after this date (RFC 3339, ex: `2026-10-01T00:00:00Z`)
+    pub since: chrono::DateTime<chrono::Utc>,
+}
+
+/// `GET /api/players/linked?since=<date>` : player accounts linked to a user of the website after `since`,
+/// as dated by the account links task.
+pub async fn linked_players(
+    State(ctx): State<AppContext>,
+    Query(query): Query<LinkedQuery>,
+) -> Result<Json<Vec<JoueurLie>>, (StatusCode, String)> {
+    let db = ctx
+        .db
+        .clone()
+        .ok_or((StatusCode::SERVICE_UNAVAILABLE, "Database unavailable".to_string()))?;
+
+    let since = query.since.naive_utc();
+    let players = db
+        .call(move |db| db.get_recently_linked_players(since))
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(players))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::app::AppContext;
+    use crate::config::Config;
+    use axum::body::Body;
+    use axum::http::{header, Request, StatusCode};
+    use tower::ServiceExt;
+
+    async fn put_visibility(body: &str) -> StatusCode {
+        let request = Request::put("/api/players/minecraft/Loutre/visibility")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let router = crate::api::router(AppContext::for_tests(Config::for_tests(&[]), &[]));
+        router.oneshot(request).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn visibility_needs_the_database() {
+        assert_eq!(put_visibility(r#"{"visible": false}"#).await, StatusCode::SERVICE_UNAVAILABLE);
+        assert!(!crate::helper::player_privacy::is_hidden("minecraft", "Loutre"));
+    }
+
+    #[tokio::test]
+    async fn visibility_without_a_boolean_is_rejected() {
+        assert_eq!(put_visibility(r#"{"visible": "off"}"#).await, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(put_visibility("{}").await, StatusCode::UNPROCESSABLE_ENTITY);
+    }
+}
@@ -0,0 +1,206 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use chrono::{DateTime, Utc};
+use colored::Colorize;
+use log::{info, warn};
+use serde::Serialize;
+use tokio::sync::Notify;
+
+/// Result of the last run of a scheduled job.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "status", content = "message", rename_all = "lowercase")]
+pub enum JobOutcome {
+    Ok,
+    Error(String),
+}
+
+/// State of a scheduled job, as returned by `GET /api/jobs`.
+#[derive(Debug, Clone, Serialize)]
+pub struct JobSnapshot {
+    pub name: String,
+    pub every_sec: u64,
+    pub running: bool,
+    pub last_start: Option<DateTime<Utc>>,
+    pub last_duration_ms: Option<u64>,
+    pub last_outcome: Option<JobOutcome>,
+    pub next_due: Option<DateTime<Utc>>,
+}
+
+/// Why a job couldn't be run on demand.
+#[derive(Debug, PartialEq)]
+pub enum RunNowError {
+    UnknownJob,
+    AlreadyRunning,
+}
+
+struct JobEntry {
+    snapshot: JobSnapshot,
+    trigger: Arc<Notify>,
+}
+
+/// Scheduled jobs of the application (player stats sync, integrity report...), shared through the `AppContext`.
+/// The tasks register their job and report each run, the API reads the snapshots and triggers runs on demand.
+#[derive(Default)]
+pub struct JobRegistry {
+    jobs: RwLock<HashMap<String, JobEntry>>,
+}
+
+impl JobRegistry {
+    /// Registers a job running every `every`, first due at `first_due`.
+    ///
+    /// # Returns
+    /// The trigger notified when a run is requested on demand. The task waits on it next to its interval.
+    pub fn register(&self, name: &str, every: Duration, first_due: DateTime<Utc>) -> Arc<Notify> {
+        let trigger = Arc::new(Notify::new());
+        let entry = JobEntry {
+            snapshot: JobSnapshot {
+                name: name.to_string(),
+                every_sec: every.as_secs(),
+                running: false,
+                last_start: None,
+                last_duration_ms: None,
+                last_outcome: None,
+                next_due: Some(first_due),
+            },
+            trigger: trigger.clone(),
+        };
+        self.write().insert(name.to_string(), entry);
+        trigger
+    }
+
+    /// Runs a job and records its start, duration and outcome.
+    ///
+    /// # Arguments
+    /// * `name` - The name the job was registered with.
+    /// * `scheduled` - True for a run of the interval, which moves the next due time. On demand runs keep it.
+    /// * `job` - The run itself.
+    pub async fn run<F>(&self, name: &str, scheduled: bool, job: F)
+    where
+        F: Future<Output = Result<(), String>>,
+    {
+        let started_at = Utc::now();
+        let started = Instant::now();
+        self.update(name, |snapshot| {
+            snapshot.running = true;
+            snapshot.last_start = Some(started_at);
+            if scheduled {
+                snapshot.next_due = chrono::Duration::from_std(Duration::from_secs(snapshot.every_sec))
+                    .ok()
+                    .map(|every| started_at + every);
+            }
+        });
+
+        let outcome = match job.await {
+            Ok(()) => JobOutcome::Ok,
+            Err(e) => {
+                warn!("Job {} failed: {}", name.yellow().bold(), e);
+                JobOutcome::Error(e)
+            }
+        };
+
+        let duration_ms = started.elapsed().as_millis() as u64;
+        self.update(name, |snapshot| {
+            snapshot.running = false;
+            snapshot.last_duration_ms = Some(duration_ms);
+            snapshot.last_outcome = Some(outcome);
+        });
+    }
+
+    /// Requests a run of a job now. Refused if the job is unknown or already running.
+    pub fn run_now(&self, name: &str) -> Result<(), RunNowError> {
+        let jobs = self.read();
+        let entry = jobs.get(name).ok_or(RunNowError::UnknownJob)?;
+        if entry.snapshot.running {
+            return Err(RunNowError::AlreadyRunning);
+        }
+        info!("Run of job {} requested", name.green().bold());
+        entry.trigger.notify_one();
+        Ok(())
+    }
+
+    /// Returns the state of every registered job, by name.
+    pub fn snapshot(&self) -> Vec<JobSnapshot> {
+        let mut snapshots: Vec<JobSnapshot> = self.read().values().map(|entry| entry.snapshot.clone()).collect();
+        snapshots.sort_by(|a, b| a.name.cmp(&b.name));
+        snapshots
+    }
+
+    fn update(&self, name: &str, f: impl FnOnce(&mut JobSnapshot)) {
+        if let Some(entry) = self.write().get_mut(name) {
+            f(&mut entry.snapshot);
+        }
+    }
+
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, HashMap<String, JobEntry>> {
+        self.jobs.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write(&self) -> std::sync::RwLockWriteGuard<'_, HashMap<String, JobEntry>> {
+        self.jobs.write().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(registry: &JobRegistry, name: &str) -> JobSnapshot {
+        registry.snapshot().into_iter().find(|job| job.name == name).unwrap()
+    }
+
+    #[tokio::test]
+    async fn runs_record_their_start_duration_and_outcome() {
+        let registry = JobRegistry::default();
+        let first_due = Utc::now() + chrono::Duration::seconds(30);
+        registry.register("stats_sync", Duration::from_secs(3600), first_due);
+        assert_eq!(job(&registry, "stats_sync").next_due, Some(first_due));
+        assert!(job(&registry, "stats_sync").last_outcome.is_none());
+
+        registry.run("stats_sync", true, async { Ok(()) }).await;
+        let snapshot = job(&registry, "stats_sync");
+        assert!(!snapshot.running);
+        assert!(matches!(snapshot.last_outcome, Some(JobOutcome::Ok)));
+        assert!(snapshot.last_duration_ms.is_some());
+        let started = snapshot.last_start.unwrap();
+        assert_eq!(snapshot.next_due, Some(started + chrono::Duration::seconds(3600)), "a scheduled run moves the next due time");
+
+        registry.run("stats_sync", false, async { Err("MySQL gone".to_string()) }).await;
+        let after = job(&registry, "stats_sync");
+        assert!(matches!(after.last_outcome, Some(JobOutcome::Error(ref e)) if e == "MySQL gone"));
+        assert_eq!(after.next_due, snapshot.next_due, "an on demand run keeps the next due time");
+    }
+
+    #[tokio::test]
+    async fn run_now_notifies_the_job_unless_it_is_running() {
+        let registry = Arc::new(JobRegistry::default());
+        let trigger = registry.register("integrity_report", Duration::from_secs(60), Utc::now());
+        assert_eq!(registry.run_now("nope"), Err(RunNowError::UnknownJob));
+
+        assert_eq!(registry.run_now("integrity_report"), Ok(()));
+        tokio::time::timeout(Duration::from_secs(1), trigger.notified()).await.unwrap();
+
+        let (release, released) = tokio::sync::oneshot::channel::<()>();
+        let running = tokio::spawn({
+            let registry = registry.clone();
+            async move { registry.run("integrity_report", false, async { released.await.map_err(|e| e.to_string()) }).await }
+        });
+        while !job(&registry, "integrity_report").running {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(registry.run_now("integrity_report"), Err(RunNowError::AlreadyRunning));
+        release.send(()).unwrap();
+        running.await.unwrap();
+        assert_eq!(registry.run_now("integrity_report"), Ok(()));
+    }
+
+    #[test]
+    fn outcomes_are_serialized_with_their_status() {
+        assert_eq!(serde_json::to_value(JobOutcome::Ok).unwrap(), serde_json::json!({ "status": "ok" }));
+        assert_eq!(
+            serde_json::to_value(JobOutcome::Error("boom".to_string())).unwrap(),
+            serde_json::json!({ "status": "error", "message": "boom" })
+        );
+    }
+}
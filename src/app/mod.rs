@@ -0,0 +1,393 @@
+pub mod jobs;
+pub mod tasks;
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use colored::Colorize;
+use log::{error, info, warn};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+use crate::config::Config;
+use crate::db::repository_default::Database;
+use crate::helper;
+use jobs::JobRegistry;
+
+/// Time given to all the tasks to stop once the shutdown is requested, the ones still running are aborted
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(15);
+
+pub type TaskFuture = Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send>>;
+type StartHook = Box<dyn FnOnce(AppContext) -> TaskFuture + Send>;
+type ShutdownHook = Box<dyn FnOnce() + Send>;
+type HealthReporter = Box<dyn Fn() -> TaskStatus + Send + Sync>;
+
+/// State of a registered task, as seen by the supervisor.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TaskStatus {
+    Starting,
+    Running,
+    /// Running, but not keeping up (ex: the watcher is far behind real time)
+    Degraded(String),
+    Stopped,
+    Failed(String),
+}
+
+/// Resources shared between every task of the application.
+#[derive(Clone)]
+pub struct AppContext {
+    pub config: Arc<Config>,
+    pub db: Option<Arc<Database>>,
+    pub jobs: Arc<JobRegistry>,
+    shutdown: watch::Receiver<bool>,
+    statuses: Arc<RwLock<HashMap<String, TaskStatus>>>,
+}
+
+impl AppContext {
+    /// Waits until the shutdown of the application is requested.
+    pub async fn shutdown_requested(&self) {
+        let mut rx = self.shutdown.clone();
+        while !*rx.borrow_and_update() {
+            if rx.changed().await.is_err() {
+                return;
+            }
+        }
+    }
+
+    /// Returns the status of every registered task, by task name.
+    pub fn task_statuses(&self) -> HashMap<String, TaskStatus> {
+        self.statuses.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    fn set_status(&self, name: &str, status: TaskStatus) {
+        self.statuses
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(name.to_string(), status);
+    }
+}
+
+#[cfg(test)]
+impl AppContext {
+    /// Context of the tests, without database, whose tasks have the given statuses.
+    pub(crate) fn for_tests(config: Config, statuses: &[(&str, TaskStatus)]) -> AppContext {
+        let ctx = AppBuilder { config, db: None, tasks: Vec::new() }.build().ctx;
+        for (name, status) in statuses {
+            ctx.set_status(name, status.clone());
+        }
+        ctx
+    }
+}
+
+/// A named unit of work of the application (log watcher, periodic events...).
+pub struct Task {
+    name: String,
+    start: StartHook,
+    on_shutdown: Option<ShutdownHook>,
+    health: Option<HealthReporter>,
+}
+
+impl Task {
+    /// Creates a task from its start hook. The returned future runs until the task ends.
+    pub fn new<F, Fut>(name: &str, start: F) -> Self
+    where
+        F: FnOnce(AppContext) -> Fut + Send + 'static,
+        Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        Self {
+            name: name.to_string(),
+            start: Box::new(move |ctx| Box::pin(start(ctx))),
+            on_shutdown: None,
+            health: None,
+        }
+    }
+
+    /// Hook called when the shutdown of the application is requested.
+    pub fn on_shutdown<F: FnOnce() + Send + 'static>(mut self, hook: F) -> Self {
+        self.on_shutdown = Some(Box::new(hook));
+        self
+    }
+
+    /// Reporter giving the health of the task while it runs, instead of the supervisor's status.
+    pub fn health<F: Fn() -> TaskStatus + Send + Sync + 'static>(mut self, reporter: F) -> Self {
+        self.health = Some(Box::new(reporter));
+        self
+    }
+}
+
+/// Builder of the application : wires the shared resources and registers the tasks.
+pub struct AppBuilder {
+    config: Config,
+    db: Option<Arc<Database>>,
+    tasks: Vec<Task>,
+}
+
+impl AppBuilder {
+    /// Registers a task. Tasks are started in registration order.
+    pub fn task(mut self, task: Task) -> Self {
+        self.tasks.push(task);
+        self
+    }
+
+    /// Registers a task only if `enabled` is true.
+    pub fn task_if(self, enabled: bool, task: Task) -> Self {
+        if enabled { self.task(task) } else { self }
+    }
+
+    pub fn build(self) -> App {
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        let ctx = AppContext {
+            config: Arc::new(self.config),
+            db: self.db,
+            jobs: Arc::new(JobRegistry::default()),
+            shutdown: shutdown_rx,
+            statuses: Arc::new(RwLock::new(HashMap::new())),
+        };
+        App { ctx, shutdown_tx, tasks: self.tasks, grace: SHUTDOWN_GRACE }
+    }
+}
+
+/// The application : a set of supervised tasks sharing an `AppContext`.
+pub struct App {
+    ctx: AppContext,
+    shutdown_tx: watch::Sender<bool>,
+    tasks: Vec<Task>,
+    /// Time given to the tasks to stop once the shutdown is requested
+    grace: Duration,
+}
+
+impl App {
+    /// Creates the builder of the application. The database pool is opened once here and shared.
+    pub fn builder(config: Config) -> AppBuilder {
+        let db = match Database::new(&config.database_url) {
+            Ok(db) => {
+                helper::open_database::share(&db);
+                Some(Arc::new(db))
+            }
+            Err(e) => {
+                error!("Could not create DB pool: {:?}", e);
+                None
+            }
+        };
+        AppBuilder { config, db, tasks: Vec::new() }
+    }
+
+    /// Returns a handle requesting the shutdown of the application when called.
+    pub fn shutdown_handle(&self) -> impl Fn() + Send + Sync + 'static {
+        let tx = self.shutdown_tx.clone();
+        move || { let _ = tx.send(true); }
+    }
+
+    /// Starts every task and supervises them until they all end, or until the shutdown is requested.
+    /// On shutdown, hooks are called in reverse registration order and tasks get a grace period to stop.
+    ///
+    /// # Returns
+    /// `false` if a task had to be aborted because it didn't stop within the grace period.
+    pub async fn run(self) -> bool {
+        let App { ctx, shutdown_tx, tasks, grace } = self;
+
+        let mut handles: Vec<(String, JoinHandle<()>)> = Vec::new();
+        let mut shutdown_hooks: Vec<(String, ShutdownHook)> = Vec::new();
+        let mut reporters: Vec<(String, HealthReporter)> = Vec::new();
+
+        for task in tasks {
+            let name = task.name.clone();
+            info!("Starting task {}", name.green().bold());
+            ctx.set_status(&name, TaskStatus::Starting);
+
+            if let Some(hook) = task.on_shutdown {
+                shutdown_hooks.push((name.clone(), hook));
+            }
+            if let Some(reporter) = task.health {
+                reporters.push((name.clone(), reporter));
+            }
+
+            let task_ctx = ctx.clone();
+            let future = (task.start)(ctx.clone());
+            ctx.set_status(&name, TaskStatus::Running);
+            let task_name = name.clone();
+            handles.push((name, tokio::spawn(async move {
+                match future.await {
+                    Ok(()) => {
+                        info!("Task {} ended", task_name.green().bold());
+                        task_ctx.set_status(&task_name, TaskStatus::Stopped);
+                    }
+                    Err(e) => {
+                        error!("Task {} failed: {}", task_name.red().bold(), e);
+                        task_ctx.set_status(&task_name, TaskStatus::Failed(e.to_string()));
+                    }
+                }
+            })));
+        }
+
+        // Health reporters refresh the statuses of the running tasks
+        let reporter_ctx = ctx.clone();
+        let reporter_handle = tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(5));
+            loop {
+                interval.tick().await;
+                for (name, reporter) in &reporters {
+                    let current = reporter_ctx.task_statuses().get(name).cloned();
+                    if matches!(current, Some(TaskStatus::Running) | Some(TaskStatus::Degraded(_))) {
+                        let status = reporter();
+                        if status != TaskStatus::Running {
+                            warn!("Task {} reports {:?}", name.yellow().bold(), status);
+                        }
+                        reporter_ctx.set_status(name, status);
+                    }
+                }
+            }
+        });
+
+        // Wait for every task to end, or for the shutdown
+        let all_done = async {
+            for (_, handle) in handles.iter_mut() {
+                let _ = handle.await;
+            }
+        };
+        // Biased so tasks ending because of the shutdown still get their hooks called
+        tokio::select! {
+            biased;
+            _ = ctx.shutdown_requested() => {}
+            _ = all_done => {
+                info!("Every task ended");
+                reporter_handle.abort();
+                return true;
+            }
+        }
+
+        info!("{}", "Shutdown requested, stopping tasks".yellow());
+        let _ = shutdown_tx.send(true);
+        for (name, hook) in shutdown_hooks.into_iter().rev() {
+            info!("Stopping task {}", name.yellow().bold());
+            hook();
+        }
+
+        // One deadline for all the tasks, so the shutdown never takes longer than the grace period
+        let deadline = tokio::time::Instant::now() + grace;
+        let mut clean = true;
+        for (name, handle) in handles {
+            if handle.is_finished() {
+                continue;
+            }
+            let abort = handle.abort_handle();
+            if tokio::time::timeout_at(deadline, handle).await.is_err() {
+                warn!("Task {} did not stop in time, aborting it", name.yellow().bold());
+                abort.abort();
+                clean = false;
+            }
+            ctx.set_status(&name, TaskStatus::Stopped);
+        }
+        reporter_handle.abort();
+        clean
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    fn test_config() -> Config {
+        let vars = [
+            ("LOG_LEVEL", "info"),
+            ("DATABASE_URL", "mysql://otternel@localhost/otternel"),
+            ("SERVERLOG_FOLDER", "."),
+            ("OTTERNEL_WEBHOOK_ACTIVATED", "false"),
+            ("OTTERNEL_WEBHOOK_URL", ""),
+            ("MINEOTTER_BOT_WEBHOOK_ACTIVATED", "false"),
+            ("MINEOTTER_BOT_WEBHOOK_URL", ""),
+            ("MULTILOUTRE_BOT_WEBHOOK_ACTIVATED", "false"),
+            ("MULTILOUTRE_BOT_WEBHOOK_URL", ""),
+            ("MCMYADMIN_WEBHOOK_ACTIVATED", "false"),
+            ("MCMYADMIN_WEBHOOK_URL", ""),
+            ("MCMYADMIN_SECONDARY_WEBHOOK_ACTIVATED", "false"),
+            ("MCMYADMIN_SECONDARY_WEBHOOK_URL", ""),
+        ];
+        envy::from_iter(vars.iter().map(|(k, v)| (k.to_string(), v.to_string()))).unwrap()
+    }
+
+    /// Builder without database, so the tests never open a pool
+    fn builder() -> AppBuilder {
+        AppBuilder { config: test_config(), db: None, tasks: Vec::new() }
+    }
+
+    type Events = Arc<Mutex<Vec<String>>>;
+
+    /// Task recording its start and its shutdown hook in `events`, and running until the shutdown.
+    fn recorded_task(name: &'static str, events: &Events) -> Task {
+        let started = events.clone();
+        let stopped = events.clone();
+        Task::new(name, move |ctx: AppContext| {
+            started.lock().unwrap().push(format!("start {name}"));
+            async move {
+                ctx.shutdown_requested().await;
+                Ok(())
+            }
+        })
+        .on_shutdown(move || stopped.lock().unwrap().push(format!("stop {name}")))
+    }
+
+    #[tokio::test]
+    async fn run_starts_tasks_in_order_and_calls_hooks_in_reverse() {
+        let events: Events = Arc::default();
+        let app = builder()
+            .task(recorded_task("first", &events))
+            .task_if(false, recorded_task("disabled", &events))
+            .task(recorded_task("second", &events))
+            .task(recorded_task("third", &events))
+            .build();
+
+        let shutdown = app.shutdown_handle();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            shutdown();
+        });
+        assert!(app.run().await);
+
+        assert_eq!(
+            *events.lock().unwrap(),
+            ["start first", "start second", "start third", "stop third", "stop second", "stop first"]
+        );
+    }
+
+    #[tokio::test]
+    async fn run_ends_when_every_task_ended_and_records_their_status() {
+        let app = builder()
+            .task(Task::new("ok", |_ctx: AppContext| async { Ok(()) }))
+            .task(Task::new("ko", |_ctx: AppContext| async { Err(anyhow::anyhow!("boom")) }))
+            .build();
+        let ctx = app.ctx.clone();
+
+        assert!(app.run().await);
+
+        let statuses = ctx.task_statuses();
+        assert_eq!(statuses["ok"], TaskStatus::Stopped);
+        assert_eq!(statuses["ko"], TaskStatus::Failed("boom".to_string()));
+    }
+
+    #[tokio::test]
+    async fn run_aborts_the_tasks_still_running_after_the_grace_period() {
+        let events: Events = Arc::default();
+        let mut app = builder()
+            .task(recorded_task("polite", &events))
+            .task(Task::new("stuck", |_ctx: AppContext| async {
+                std::future::pending::<()>().await;
+                Ok(())
+            }))
+            .build();
+        app.grace = Duration::from_millis(100);
+        let ctx = app.ctx.clone();
+
+        let shutdown = app.shutdown_handle();
+        shutdown();
+        let started = tokio::time::Instant::now();
+        assert!(!app.run().await);
+
+        assert!(started.elapsed() < SHUTDOWN_GRACE);
+        assert_eq!(*events.lock().unwrap(), ["start polite", "stop polite"]);
+        assert_eq!(ctx.task_statuses()["stuck"], TaskStatus::Stopped);
+    }
+}
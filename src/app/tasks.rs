@@ -0,0 +1,686 @@
+use std::sync::Arc;
+use std::time::Duration;
+use chrono::Datelike;
+use colored::Colorize;
+use log::{debug, error, info};
+use tokio::signal::unix::{signal, SignalKind};
+
+use crate::app::{AppContext, Task, TaskStatus};
+use crate::helper::active_servers::ServerTasks;
+use crate::helper::webhook_discord::DiscordEmbed;
+use crate::{api, helper, playerstats, serverlog};
+
+/// Interval between two searches of the worlds to back up
+const WORLD_BACKUP_CHECK_EVERY: Duration = Duration::from_secs(24 * 3600);
+
+/// Time left to the log watcher before the online players are reconciled with the servers
+const STARTUP_RECONCILIATION_DELAY: Duration = Duration::from_secs(30);
+
+/// Interval between two checks of the active server list by the per-server tasks
+const ACTIVE_SERVERS_FOLLOW_EVERY: Duration = Duration::from_secs(5);
+
+/// Errors listed in the embed sent at the end of a stats sync
+const SYNC_REPORT_MAX_ERRORS: usize = 10;
+
+/// Task watching the server logs folder and dispatching the triggers.
+pub fn log_watcher() -> Task {
+    // The watcher is blocking, it runs on its own thread and is stopped through a channel by the shutdown hook
+    let (stop_tx, stop_rx) = std::sync::mpsc::channel();
+    Task::new("log_watcher", move |ctx: AppContext| async move {
+        // Hidden players are loaded before the first line, so none of their activity is announced
+        if let Some(db) = ctx.db.clone() {
+            tokio::task::spawn_blocking(move || helper::player_privacy::load_hidden_players(&db)).await?;
+        }
+
+        let log_folder = ctx.config.serverlog_folder.clone();
+        let mut handle = tokio::task::spawn_blocking(move || serverlog::log_watcher::watch_serverlogs(&log_folder, stop_rx));
+
+        // SIGHUP reloads the triggers (database and triggers.toml) without restart
+        let mut hangup = signal(SignalKind::hangup())?;
+        let result = loop {
+            tokio::select! {
+                result = &mut handle => break result?,
+                _ = hangup.recv() => {
+                    info!("{}", "SIGHUP received, reloading the triggers".green());
+                    serverlog::log_watcher::request_triggers_reload();
+                }
+            }
+        };
+        result.map_err(|err| anyhow::anyhow!("Log watcher failed: {}", err))
+    })
+    .on_shutdown(move || {
+        let _ = stop_tx.send(());
+    })
+    .health(|| {
+        let drowning = serverlog::processing_lag::drowning_files();
+        if drowning.is_empty() {
+            TaskStatus::Running
+        } else {
+            TaskStatus::Degraded(format!("{} log files behind real time", drowning.len()))
+        }
+    })
+}
+
+/// Task sending the queued Discord webhooks, so the actions never wait for Discord.
+/// On shutdown, the webhooks already queued are sent before the task ends.
+pub fn webhook_queue() -> Task {
+    Task::new("webhook_queue", |_ctx: AppContext| async move {
+        let worker = helper::webhook_queue::start();
+        // The worker is blocking, it runs on its own thread until the shutdown hook stops the queue
+        tokio::task::spawn_blocking(move || worker.run()).await?;
+        Ok(())
+    })
+    // Registered first, so its hook runs last : the webhooks of the other tasks are queued before the queue stops
+    .on_shutdown(helper::webhook_queue::stop)
+}
+
+/// Task archiving the state files of Otternel (log positions, Mojang cache) every night at `STATE_BACKUP_HOUR`
+/// (Default 3, local time) to `STATE_BACKUP_DIR`, keeping the last `STATE_BACKUP_KEEP` archives (Default 7).
+pub fn state_backup() -> Task {
+    Task::new("state_backup", |ctx: AppContext| async move {
+        let Some(dir) = helper::state_backup::backup_dir() else {
+            return Ok(());
+        };
+        let keep = helper::state_backup::keep();
+        let hour = ctx.config.state_backup_hour;
+
+        info!(
+            "{}",
+            format!("State files archived every night at {}h to {} ({} kept)", hour, dir.display(), keep).green()
+        );
+
+        let period = Duration::from_secs(24 * 3600);
+        let now = chrono::Local::now();
+        let mut first_due = now.date_naive().and_hms_opt(hour, 0, 0).unwrap_or_default();
+        if first_due <= now.naive_local() {
+            first_due += chrono::Duration::days(1);
+        }
+        let until_first = (first_due - now.naive_local()).to_std().unwrap_or_default();
+        let mut interval = tokio::time::interval_at(tokio::time::Instant::now() + until_first, period);
+        let run_now = ctx.jobs.register("state_backup", period, chrono::Utc::now() + chrono::Duration::from_std(until_first)?);
+
+        loop {
+            let scheduled = tokio::select! {
+                _ = interval.tick() => true,
+                _ = run_now.notified() => false,
+                _ = ctx.shutdown_requested() => return Ok(()),
+            };
+            let dir = dir.clone();
+            ctx.jobs.run("state_backup", scheduled, async move {
+                tokio::task::spawn_blocking(move || helper::state_backup::create_archive(&dir, keep).map(|_| ()))
+                    .await
+                    .map_err(|e| e.to_string())?
+            }).await;
+        }
+    })
+}
+
+/// Task sending the PvP leaderboard of the Minecraft servers every `PVP_LEADERBOARD_EVERY_DAYS` days (default 7).
+pub fn pvp_leaderboard() -> Task {
+    Task::new("pvp_leaderboard", |ctx: AppContext| async move {
+        let every_days = ctx.config.pvp_leaderboard_every_days;
+        let period = Duration::from_secs(every_days * 24 * 3600);
+
+        info!("{}", format!("PvP leaderboard every {} days", every_days).green());
+
+        // First leaderboard after one period, not at each restart
+        let mut interval = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+        let first_due = chrono::Utc::now() + chrono::Duration::from_std(period)?;
+        let run_now = ctx.jobs.register("pvp_leaderboard", period, first_due);
+
+        loop {
+            let scheduled = tokio::select! {
+                _ = interval.tick() => true,
+                _ = run_now.notified() => false,
+                _ = ctx.shutdown_requested() => return Ok(()),
+            };
+            let db = ctx.db.clone();
+            ctx.jobs.run("pvp_leaderboard", scheduled, async move {
+                let Some(db) = db else {
+                    return Err("No database available".to_string());
+                };
+                tokio::task::spawn_blocking(move || helper::pvp_leaderboard::run_pvp_leaderboard(&db, every_days))
+                    .await
+                    .map_err(|e| e.to_string())?
+            }).await;
+        }
+    })
+}
+
+/// Task sending the top players of each stat every Sunday at `WEEKLY_LEADERBOARD_HOUR` (default 20h, local time).
+pub fn weekly_leaderboard() -> Task {
+    Task::new("weekly_leaderboard", |ctx: AppContext| async move {
+        let hour = ctx.config.weekly_leaderboard_hour;
+
+        info!("{}", format!("Weekly leaderboard every Sunday at {}h", hour).green());
+
+        let period = Duration::from_secs(7 * 24 * 3600);
+        let now = chrono::Local::now();
+        let days_to_sunday = (7 - now.weekday().num_days_from_sunday()) % 7;
+        let mut first_due = (now.date_naive() + chrono::Duration::days(days_to_sunday as i64))
+            .and_hms_opt(hour, 0, 0)
+            .unwrap_or_default();
+        if first_due <= now.naive_local() {
+            first_due += chrono::Duration::days(7);
+        }
+        let until_first = (first_due - now.naive_local()).to_std().unwrap_or_default();
+        let mut interval = tokio::time::interval_at(tokio::time::Instant::now() + until_first, period);
+        let run_now = ctx.jobs.register("weekly_leaderboard", period, chrono::Utc::now() + chrono::Duration::from_std(until_first)?);
+
+        loop {
+            let scheduled = tokio::select! {
+                _ = interval.tick() => true,
+                _ = run_now.notified() => false,
+                _ = ctx.shutdown_requested() => return Ok(()),
+            };
+            let db = ctx.db.clone();
+            ctx.jobs.run("weekly_leaderboard", scheduled, async move {
+                let Some(db) = db else {
+                    return Err("No database available".to_string());
+                };
+                tokio::task::spawn_blocking(move || helper::weekly_leaderboard::run_weekly_leaderboard(&db))
+                    .await
+                    .map_err(|e| e.to_string())?
+            }).await;
+        }
+    })
+}
+
+/// Task checking the activated webhooks at startup and on SIGHUP, so a wrong URL or a deleted webhook is
+/// known before the first event. The task is degraded while a webhook is invalid.
+pub fn webhook_check() -> Task {
+    Task::new("webhook_check", |ctx: AppContext| async move {
+        let mut hangup = signal(SignalKind::hangup())?;
+        loop {
+            let config = ctx.config.clone();
+            tokio::task::spawn_blocking(move || helper::webhook_check::run_check(&config)).await?;
+
+            tokio::select! {
+                _ = hangup.recv() => info!("{}", "SIGHUP received, checking the webhooks again".green()),
+                _ = ctx.shutdown_requested() => return Ok(()),
+            }
+        }
+    })
+    .health(|| {
+        let invalid = helper::webhook_check::invalid_webhooks();
+        if invalid.is_empty() {
+            TaskStatus::Running
+        } else {
+            let names: Vec<String> = invalid.into_iter().map(|p| p.identity).collect();
+            TaskStatus::Degraded(format!("invalid webhooks: {}", names.join(", ")))
+        }
+    })
+}
+
+/// Task running the periodic events (player stats fetch) every `PERIODIC_EVENTS_EVERY_SEC` seconds.
+pub fn periodic_events() -> Task {
+    Task::new("periodic_events", |ctx: AppContext| async move {
+        let every_sec = ctx.config.periodic_events_every_sec;
+
+        info!("{}", format!("Periodic event launching every {} seconds", every_sec).green());
+
+        // Create the periodic interval
+        let period = Duration::from_secs(every_sec);
+        let mut interval = tokio::time::interval(period);
+        let run_now = ctx.jobs.register("player_stats_sync", period, chrono::Utc::now());
+
+        loop {
+            let scheduled = tokio::select! {
+                _ = interval.tick() => true,
+                _ = run_now.notified() => false,
+                _ = ctx.shutdown_requested() => return Ok(()),
+            };
+            ctx.jobs.run("player_stats_sync", scheduled, periodic_playerstats_fetch()).await;
+
+            // The audited events past their retention are purged at each cycle
+            if helper::event_audit::enabled()
+                && let Some(db) = ctx.db.clone()
+                && let Err(e) = db.call(helper::event_audit::purge).await
+            {
+                error!("Failed to purge the audited events: {:?}", e);
+            }
+        }
+    })
+}
+
+/// Task sending the database integrity report every `INTEGRITY_REPORT_EVERY_DAYS` days (default 7).
+pub fn integrity_report() -> Task {
+    Task::new("integrity_report", |ctx: AppContext| async move {
+        let every_days = ctx.config.integrity_report_every_days;
+        let period = Duration::from_secs(every_days * 24 * 3600);
+
+        info!("{}", format!("Database integrity report every {} days", every_days).green());
+
+        // First report after one period, not at each restart
+        let mut interval = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+        let first_due = chrono::Utc::now() + chrono::Duration::from_std(period)?;
+        let run_now = ctx.jobs.register("integrity_report", period, first_due);
+
+        loop {
+            let scheduled = tokio::select! {
+                _ = interval.tick() => true,
+                _ = run_now.notified() => false,
+                _ = ctx.shutdown_requested() => return Ok(()),
+            };
+            let db = ctx.db.clone();
+            ctx.jobs.run("integrity_report", scheduled, async move {
+                let Some(db) = db else {
+                    error!("No database available for the integrity report");
+                    return Err("No database available".to_string());
+                };
+                tokio::task::spawn_blocking(move || helper::integrity_report::run_integrity_report(&db))
+                    .await
+                    .map_err(|e| e.to_string())?
+            }).await;
+        }
+    })
+}
+
+/// Task completing the provisional player profiles every `PROFILE_REPAIR_EVERY_MIN` minutes (default 60).
+pub fn profile_repair() -> Task {
+    Task::new("profile_repair", |ctx: AppContext| async move {
+        let period = Duration::from_secs(ctx.config.profile_repair_every_min * 60);
+
+        let mut interval = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+        let first_due = chrono::Utc::now() + chrono::Duration::from_std(period)?;
+        let run_now = ctx.jobs.register("profile_repair", period, first_due);
+
+        loop {
+            let scheduled = tokio::select! {
+                _ = interval.tick() => true,
+                _ = run_now.notified() => false,
+                _ = ctx.shutdown_requested() => return Ok(()),
+            };
+            let db = ctx.db.clone();
+            ctx.jobs.run("profile_repair", scheduled, async move {
+                let Some(db) = db else {
+                    return Err("No database available".to_string());
+                };
+                tokio::task::spawn_blocking(move || helper::profile_repair::run_profile_repair(&db))
+                    .await
+                    .map_err(|e| e.to_string())?
+            }).await;
+        }
+    })
+}
+
+/// Task reloading the active server list every `ACTIVE_SERVERS_REFRESH_SEC` seconds (default 60),
+/// and on SIGHUP, so servers added to `serveurs_actifs` are picked up without a restart.
+pub fn active_servers() -> Task {
+    Task::new("active_servers", |ctx: AppContext| async move {
+        let period = Duration::from_secs(ctx.config.active_servers_refresh_sec);
+
+        let mut interval = tokio::time::interval(period);
+        let run_now = ctx.jobs.register("active_servers_refresh", period, chrono::Utc::now());
+        let mut hangup = signal(SignalKind::hangup())?;
+
+        loop {
+            let scheduled = tokio::select! {
+                _ = interval.tick() => true,
+                _ = run_now.notified() => false,
+                _ = hangup.recv() => {
+                    info!("{}", "SIGHUP received, reloading the active server list".green());
+                    false
+                }
+                _ = ctx.shutdown_requested() => return Ok(()),
+            };
+            let db = ctx.db.clone();
+            ctx.jobs.run("active_servers_refresh", scheduled, async move {
+                let Some(db) = db else {
+                    return Err("No database available".to_string());
+                };
+                db.call(helper::active_servers::refresh)
+                    .await
+                    .map(|_| ())
+                    .map_err(|e| e.to_string())
+            }).await;
+        }
+    })
+}
+
+/// Task backing up, once a day, the worlds without any connection for `WORLD_BACKUP_AFTER_DAYS` days.
+pub fn world_backup() -> Task {
+    Task::new("world_backup", |ctx: AppContext| async move {
+        let Some(backup) = ctx.config.world_backup() else {
+            return Ok(());
+        };
+
+        info!(
+            "{}",
+            format!("Worlds without connection for {} days backed up to {}", backup.after_days, backup.dir).green()
+        );
+
+        let mut interval = tokio::time::interval(WORLD_BACKUP_CHECK_EVERY);
+        let run_now = ctx.jobs.register("world_backup", WORLD_BACKUP_CHECK_EVERY, chrono::Utc::now());
+
+        loop {
+            let scheduled = tokio::select! {
+                _ = interval.tick() => true,
+                _ = run_now.notified() => false,
+                _ = ctx.shutdown_requested() => return Ok(()),
+            };
+            ctx.jobs.run("world_backup", scheduled, helper::world_backup::run_world_backup(&backup)).await;
+        }
+    })
+}
+
+/// Task seeding the online players of every Minecraft server once, shortly after the startup.
+pub fn online_reconciliation() -> Task {
+    Task::new("online_reconciliation", |ctx: AppContext| async move {
+        // Let the log watcher catch up first, so the joins it reads aren't recorded twice
+        tokio::select! {
+            _ = tokio::time::sleep(STARTUP_RECONCILIATION_DELAY) => {}
+            _ = ctx.shutdown_requested() => return Ok(()),
+        }
+
+        helper::online_reconciliation::run_startup_reconciliation()
+            .await
+            .map_err(|e| anyhow::anyhow!("Startup reconciliation failed: {}", e))
+    })
+}
+
+/// Task pinging the active Minecraft servers (Server List Ping) every `STATUS_PROBE_EVERY_SEC` seconds (default 60),
+/// to catch the servers frozen while their process still runs.
+/// Each server has its own probe, started and stopped as the active server list changes.
+pub fn server_status_probe() -> Task {
+    Task::new("server_status_probe", |ctx: AppContext| async move {
+        let Some(period) = helper::server_list_ping::probe_every() else {
+            return Ok(());
+        };
+        let Some(db) = ctx.db.clone() else {
+            anyhow::bail!("The server status probe needs the database");
+        };
+
+        let mut probes: ServerTasks<tokio::task::JoinHandle<()>> = ServerTasks::default();
+        let mut applied = Arc::new(Vec::new());
+        let mut follow = tokio::time::interval(ACTIVE_SERVERS_FOLLOW_EVERY);
+        let run_now = ctx.jobs.register("server_status_probe", period, chrono::Utc::now());
+
+        loop {
+            tokio::select! {
+                _ = follow.tick() => {
+                    let current = helper::active_servers::snapshot();
+                    let changes = helper::active_servers::diff(&applied, &current);
+                    probes.apply(&changes, |server| {
+                        server.jeu.eq_ignore_ascii_case("minecraft").then(|| {
+                            tokio::spawn(helper::server_list_ping::probe_loop(db.clone(), server.active_id, period))
+                        })
+                    });
+                    if !changes.is_empty() {
+                        debug!("Active servers probed : {:?}", probes.running_ids());
+                    }
+                    applied = current;
+                }
+                _ = run_now.notified() => {
+                    ctx.jobs.run("server_status_probe", false, helper::server_list_ping::run_status_probe(db.clone())).await;
+                }
+                _ = ctx.shutdown_requested() => {
+                    probes.stop_all();
+                    return Ok(());
+                }
+            }
+        }
+    })
+}
+
+/// Task sending the status of Otternel every `STATUS_REPORT_EVERY_HOURS` hours (default 24).
+pub fn status_report() -> Task {
+    Task::new("status_report", |ctx: AppContext| async move {
+        let every_hours = ctx.config.status_report_every_hours;
+        let period = Duration::from_secs(every_hours * 3600);
+
+        info!("{}", format!("Status report every {} hours", every_hours).green());
+
+        // First report after one period, not at each restart
+        let mut interval = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {
+                    let statuses = ctx.task_statuses();
+                    tokio::task::spawn_blocking(move || helper::status_report::run_status_report(&statuses)).await?;
+                }
+                _ = ctx.shutdown_requested() => return Ok(()),
+            }
+        }
+    })
+}
+
+/// Task serving the HTTP API on `API_BIND_ADDR` (default 127.0.0.1:8080).
+pub fn api_server() -> Task {
+    Task::new("api_server", |ctx: AppContext| async move {
+        let addr = ctx.config.api_bind_addr.clone();
+        let listener = tokio::net::TcpListener::bind(&addr)
+            .await
+            .map_err(|e| anyhow::anyhow!("Could not bind the API on {}: {}", addr, e))?;
+
+        info!("{}", format!("API listening on {}", addr).green());
+
+        let shutdown_ctx = ctx.clone();
+        axum::serve(listener, api::router(ctx))
+            .with_graceful_shutdown(async move { shutdown_ctx.shutdown_requested().await })
+            .await?;
+        Ok(())
+    })
+}
+
+/// Task serving the healthcheck `GET /healthz`, the Prometheus `GET /metrics` and the online players
+/// `GET /servers/{id}/online` on `HEALTHCHECK_LISTEN_ADDR`.
+pub fn healthcheck_server() -> Task {
+    Task::new("healthcheck_server", |ctx: AppContext| async move {
+        let addr = ctx.config.healthcheck_addr().unwrap_or_default().to_string();
+        let listener = tokio::net::TcpListener::bind(&addr)
+            .await
+            .map_err(|e| anyhow::anyhow!("Could not bind the healthcheck on {}: {}", addr, e))?;
+
+        info!("{}", format!("Healthcheck listening on {}", addr).green());
+
+        let shutdown_ctx = ctx.clone();
+        axum::serve(listener, api::health::healthcheck_router(ctx))
+            .with_graceful_shutdown(async move { shutdown_ctx.shutdown_requested().await })
+            .await?;
+        Ok(())
+    })
+}
+
+/// Task serving the Discord -> game chat bridge on `CHAT_BRIDGE_LISTEN_ADDR`, apart from the API
+/// so it can be exposed to the Discord bot alone.
+pub fn chat_bridge() -> Task {
+    Task::new("chat_bridge", |ctx: AppContext| async move {
+        let addr = ctx.config.chat_bridge_addr().unwrap_or_default().to_string();
+        let listener = tokio::net::TcpListener::bind(&addr)
+            .await
+            .map_err(|e| anyhow::anyhow!("Could not bind the chat bridge on {}: {}", addr, e))?;
+
+        info!("{}", format!("Chat bridge listening on {}", addr).green());
+
+        let shutdown_ctx = ctx.clone();
+        axum::serve(listener, api::chat_bridge::router(ctx))
+            .with_graceful_shutdown(async move { shutdown_ctx.shutdown_requested().await })
+            .await?;
+        Ok(())
+    })
+}
+
+/// Task running the RCON commands of `rcon_schedules` at the times of their cron expression (local time).
+/// The schedules are reloaded every `RCON_SCHEDULES_REFRESH_SEC` seconds (Default 300) and when the active server
+/// list changes. The schedules of a server missing from the list are skipped.
+pub fn rcon_schedules() -> Task {
+    Task::new("rcon_schedules", |ctx: AppContext| async move {
+        let Some(db) = ctx.db.clone() else {
+            anyhow::bail!("RCON schedules need the database");
+        };
+        let refresh_every = Duration::from_secs(ctx.config.rcon_schedules_refresh_sec);
+        let rcon = helper::rcon_helper::RconHelper { db: (*db).clone() };
+
+        // Reloads and runs share the scheduler : an occurrence already run is never run again
+        let mut scheduler = helper::rcon_schedule::RconScheduler::default();
+        let mut refresh = tokio::time::interval(refresh_every);
+        let mut tick = tokio::time::interval(Duration::from_secs(10));
+        let mut generation = helper::active_servers::generation();
+        loop {
+            tokio::select! {
+                _ = refresh.tick() => {
+                    match db.call(|db| db.get_active_rcon_schedules()).await {
+                        Ok(schedules) => {
+                            scheduler.reload(schedules);
+                            debug!("{} RCON schedules loaded", scheduler.count());
+                        }
+                        Err(e) => error!("Failed to load the RCON schedules: {}", e),
+                    }
+                }
+                _ = tick.tick() => {
+                    // A server added or removed may have its own schedules
+                    if helper::active_servers::generation() != generation {
+                        generation = helper::active_servers::generation();
+                        refresh.reset_immediately();
+                    }
+                    let active = helper::active_servers::snapshot();
+                    for schedule in scheduler.take_due(chrono::Local::now().naive_local()) {
+                        if !helper::active_servers::includes(&active, |server| server.active_id == schedule.serveur_actif_id) {
+                            debug!("RCON schedule {} skipped, server {} isn't active", schedule.id, schedule.serveur_actif_id);
+                            continue;
+                        }
+                        helper::rcon_schedule::run_schedule(&rcon, &schedule).await;
+                    }
+                }
+                _ = ctx.shutdown_requested() => return Ok(()),
+            }
+        }
+    })
+}
+
+/// Task loading the server mutes at startup, then ending the expired ones every 30 seconds.
+pub fn server_mutes() -> Task {
+    Task::new("server_mutes", |ctx: AppContext| async move {
+        if let Some(db) = ctx.db.clone() {
+            tokio::task::spawn_blocking(move || helper::server_mute::load_mutes(&db)).await?;
+        }
+
+        let mut interval = tokio::time::interval(Duration::from_secs(30));
+
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {
+                    let db = ctx.db.clone();
+                    tokio::task::spawn_blocking(move || helper::server_mute::expire_mutes(db.as_deref())).await?;
+                }
+                _ = ctx.shutdown_requested() => return Ok(()),
+            }
+        }
+    })
+}
+
+/// Task replaying, every 30 seconds, the player connections whose write failed while the database was unreachable.
+pub fn connection_log_replay() -> Task {
+    Task::new("connection_log_replay", |ctx: AppContext| async move {
+        let Some(db) = ctx.db.clone() else {
+            return Ok(());
+        };
+        let mut interval = tokio::time::interval(Duration::from_secs(30));
+
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {
+                    let db = db.clone();
+                    tokio::task::spawn_blocking(move || helper::connection_log_retry::replay(&db)).await?;
+                }
+                _ = ctx.shutdown_requested() => return Ok(()),
+            }
+        }
+    })
+}
+
+/// Task announcing the accounts linked on the website, every `ACCOUNT_LINKS_CHECK_SEC` seconds (Default 60).
+pub fn account_links() -> Task {
+    Task::new("account_links", |ctx: AppContext| async move {
+        let Some(db) = ctx.db.clone() else {
+            anyhow::bail!("Account link announcements need the database");
+        };
+        let mut interval = tokio::time::interval(Duration::from_secs(ctx.config.account_links_check_sec));
+
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {
+                    if let Err(e) = helper::account_links::announce_new_links(&db).await {
+                        error!("Failed to check the linked accounts: {}", e);
+                    }
+                }
+                _ = ctx.shutdown_requested() => return Ok(()),
+            }
+        }
+    })
+}
+
+async fn periodic_playerstats_fetch() -> Result<(), String> {
+    // Send embed
+    if let Err(e) = DiscordEmbed::new("otternel")
+        .title("Enregistrement des stats de joueurs")
+        .description("Passage sur chaque serveur de la table `serveurs`.")
+        .color("126020")
+        .footer("Otternel Service")
+        .timestamp_now()
+        .send()
+    {
+        error!("{e}");
+    }
+
+    // Launch minecraft player stats
+    let result = playerstats::minecraft_players::sync_mc_stats_to_db().await.map_err(|e| {
+        error!("Erreur sync_mc_stats_to_db: {e:?}");
+        e.to_string()
+    });
+
+    // Minecraft player stats fetch ended, send the summary embed
+    send_sync_summary("Minecraft", &result);
+
+    // Launch palworld player stats, sent in their own summary when there are Palworld servers
+    let palworld_result = playerstats::palworld_players::sync_palworld_stats_to_db().await.map_err(|e| {
+        error!("Erreur sync_palworld_stats_to_db: {e:?}");
+        e.to_string()
+    });
+    let has_palworld_servers = match &palworld_result {
+        Ok(report) => report.serveurs_ok > 0 || !report.serveurs_en_erreur.is_empty(),
+        Err(_) => true,
+    };
+    if has_palworld_servers {
+        send_sync_summary("Palworld", &palworld_result);
+    }
+
+    result.and(palworld_result).map(|_| ())
+}
+
+/// Sends the summary embed of the stats sync of a game.
+fn send_sync_summary(game: &str, result: &Result<playerstats::minecraft_players::SyncReport, String>) {
+    let embed = DiscordEmbed::new("otternel").title(&format!("Enregistrement des stats de joueurs {} terminé", game));
+    let embed = match result {
+        Ok(report) => sync_report_summary(embed, report),
+        Err(e) => embed.description(&format!("Enregistrement interrompu : {}", e)).color("601010"),
+    };
+    if let Err(e) = embed.footer("Otternel Service").timestamp_now().send() {
+        error!("{e}");
+    }
+}
+
+/// Fills the embed sent at the end of a stats sync : one inline field per counter, green if every server
+/// was synced, red with one field per error (first `SYNC_REPORT_MAX_ERRORS` ones) otherwise.
+fn sync_report_summary(embed: DiscordEmbed, report: &playerstats::minecraft_players::SyncReport) -> DiscordEmbed {
+    let embed = embed
+        .add_field("Serveurs synchronisés", &report.serveurs_ok.to_string(), true)
+        .add_field("Serveurs en erreur", &report.serveurs_en_erreur.len().to_string(), true)
+        .add_field("Joueurs mis à jour", &report.joueurs_maj.to_string(), true)
+        .add_field("Durée", &format!("{} s", report.duree.as_secs()), true);
+    if report.serveurs_en_erreur.is_empty() {
+        return embed.color("126020");
+    }
+
+    let mut embed = embed.color("601010");
+    for (server, error) in report.serveurs_en_erreur.iter().take(SYNC_REPORT_MAX_ERRORS) {
+        embed = embed.add_field(server, error, false);
+    }
+    let hidden = report.serveurs_en_erreur.len().saturating_sub(SYNC_REPORT_MAX_ERRORS);
+    if hidden > 0 {
+        embed = embed.description(&format!("... et {} autres erreurs", hidden));
+    }
+    embed
+}
@@ -0,0 +1,104 @@
+use chrono::NaiveDateTime;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+/// Messages sent by the synthetic players
+const CHAT_MESSAGES: &[&str] = &[
+    "salut tout le monde",
+    "quelqu'un a du fer ?",
+    "je vais miner, à plus",
+    "gg",
+    "attention il y a un creeper devant la base",
+    "on se retrouve au spawn ?",
+    "qui veut faire le nether ce soir",
+    "j'ai trouvé des diamants !!",
+];
+
+/// Death causes, written like the vanilla death messages
+const DEATH_CAUSES: &[&str] = &[
+    "was slain by Zombie",
+    "was shot by Skeleton",
+    "was blown up by Creeper",
+    "fell from a high place",
+    "drowned",
+    "tried to swim in lava",
+    "hit the ground too hard",
+    "starved to death",
+];
+
+/// Lines that match no trigger, to keep a realistic share of noise
+const NOISE_LINES: &[&str] = &[
+    "[Server thread/INFO]: Saving the game (this may take a moment!)",
+    "[Server thread/INFO]: Saved the game",
+    "[Server thread/WARN]: Can't keep up! Is the server overloaded? Running 2034ms or 40 ticks behind",
+    "[Server thread/INFO]: [Rcon] Done",
+];
+
+/// Generates realistic Minecraft log lines (joins, leaves, chats, deaths and noise) with random players.
+///
+/// The generator keeps track of the connected players, so a player only leaves, chats or dies while connected.
+/// It is seeded, so a run can be replayed line for line.
+pub struct SyntheticLogGenerator {
+    rng: StdRng,
+    players: Vec<String>,
+    online: Vec<String>,
+}
+
+impl SyntheticLogGenerator {
+    /// Builds a generator picking among `player_count` players.
+    pub fn new(seed: u64, player_count: usize) -> Self {
+        let players = (1..=player_count.max(1)).map(|i| format!("Loutre_{i:03}")).collect();
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+            players,
+            online: Vec::new(),
+        }
+    }
+
+    /// Returns the next line, timestamped at `now` like `[14:43:14] [Server thread/INFO]: ...`.
+    pub fn next_line(&mut self, now: NaiveDateTime) -> String {
+        let body = self.next_body();
+        format!("[{}] {}", now.format("%H:%M:%S"), body)
+    }
+
+    fn next_body(&mut self) -> String {
+        let roll: u32 = self.rng.gen_range(0..100);
+
+        // Nobody online yet, or a few new players joining
+        if (self.online.is_empty() || (roll < 10 && self.online.len() < self.players.len()))
+            && let Some(player) = self.offline_player()
+        {
+            self.online.push(player.clone());
+            return format!("[Server thread/INFO]: {player} joined the game");
+        }
+
+        match roll {
+            10..=14 => {
+                let index = self.rng.gen_range(0..self.online.len());
+                let player = self.online.swap_remove(index);
+                format!("[Server thread/INFO]: {player} left the game")
+            }
+            15..=24 => {
+                let player = self.online_player();
+                let cause = DEATH_CAUSES.choose(&mut self.rng).copied().unwrap_or("died");
+                format!("[Server thread/INFO]: {player} {cause}")
+            }
+            25..=79 => {
+                let player = self.online_player();
+                let message = CHAT_MESSAGES.choose(&mut self.rng).copied().unwrap_or("gg");
+                format!("[Async Chat Thread - #0/INFO]: <{player}> {message}")
+            }
+            _ => NOISE_LINES.choose(&mut self.rng).copied().unwrap_or_default().to_string(),
+        }
+    }
+
+    fn offline_player(&mut self) -> Option<String> {
+        let offline: Vec<&String> = self.players.iter().filter(|p| !self.online.contains(p)).collect();
+        offline.choose(&mut self.rng).map(|p| p.to_string())
+    }
+
+    fn online_player(&mut self) -> String {
+        self.online.choose(&mut self.rng).cloned().unwrap_or_else(|| self.players[0].clone())
+    }
+}
@@ -0,0 +1,152 @@
+pub mod generator;
+pub mod report;
+
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use colored::Colorize;
+use log::info;
+
+use crate::helper;
+use crate::serverlog;
+use generator::SyntheticLogGenerator;
+use report::BenchReport;
+
+/// Time left to the watcher to start before writing, and to process the last lines after writing
+const SETTLE_TIME: Duration = Duration::from_secs(2);
+/// Lines are written in small batches, this often
+const WRITE_TICK: Duration = Duration::from_millis(100);
+
+/// Options of `otternel bench`.
+#[derive(Debug, Clone)]
+pub struct BenchOptions {
+    pub lines_per_sec: u64,
+    pub duration: Duration,
+    pub players: usize,
+    pub seed: u64,
+}
+
+impl Default for BenchOptions {
+    fn default() -> Self {
+        Self {
+            lines_per_sec: 100,
+            duration: Duration::from_secs(60),
+            players: 50,
+            seed: 42,
+        }
+    }
+}
+
+impl BenchOptions {
+    /// Parses the arguments following `bench` : `--lines-per-sec N`, `--duration S`, `--players N` and `--seed N`.
+    pub fn from_args(args: &[String]) -> Result<Self, String> {
+        let mut options = Self::default();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| -> Result<u64, String> {
+                args.next()
+                    .ok_or_else(|| format!("missing value for {name}"))?
+                    .parse()
+                    .map_err(|e| format!("invalid value for {name}: {e}"))
+            };
+            match arg.as_str() {
+                "--lines-per-sec" => options.lines_per_sec = value(arg)?,
+                "--duration" => options.duration = Duration::from_secs(value(arg)?),
+                "--players" => options.players = value(arg)? as usize,
+                "--seed" => options.seed = value(arg)?,
+                other => return Err(format!("unknown option: {other}")),
+            }
+        }
+        if options.lines_per_sec == 0 {
+            return Err("--lines-per-sec must be greater than 0".to_string());
+        }
+        Ok(options)
+    }
+}
+
+/// Runs the log pipeline against synthetic traffic and measures it.
+///
+/// The watcher watches a temporary folder where the generator writes `1/latest.log` at the requested rate.
+/// Actions run without database (`open_db_from_env` returns `None`) and webhooks are only logged (dry-run),
+/// so nothing leaves the machine.
+///
+/// # Returns
+/// The report of the run, or an error if the temporary folder couldn't be written.
+pub async fn run(options: BenchOptions) -> Result<BenchReport, String> {
+    let folder = std::env::temp_dir().join(format!("otternel-bench-{}", uuid::Uuid::new_v4()));
+    let log_path: PathBuf = folder.join("1").join("latest.log");
+    std::fs::create_dir_all(log_path.parent().unwrap_or(&folder)).map_err(|e| format!("cannot create {}: {e}", folder.display()))?;
+
+    helper::open_database::disable();
+    helper::dry_run::enable();
+
+    // The watcher runs on a plain thread until the bench stops it
+    let watched = folder.to_string_lossy().to_string();
+    let runtime = tokio::runtime::Handle::current();
+    let (stop_tx, stop_rx) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        let _guard = runtime.enter();
+        if let Err(e) = serverlog::log_watcher::watch_serverlogs(&watched, stop_rx) {
+            log::error!("Bench watcher failed: {}", e);
+        }
+    });
+    tokio::time::sleep(SETTLE_TIME).await;
+
+    info!(
+        "Writing {} lines/s for {}s in {}",
+        options.lines_per_sec.to_string().green().bold(),
+        options.duration.as_secs().to_string().green().bold(),
+        log_path.display()
+    );
+    let result = write_lines(&log_path, &options).await;
+    tokio::time::sleep(SETTLE_TIME).await;
+    let _ = stop_tx.send(());
+
+    let report = result.map(|(lines_written, duration)| BenchReport {
+        lines_written,
+        actions_dispatched: serverlog::actions::dispatched_count(),
+        duration,
+        max_lag_ms: serverlog::processing_lag::take_snapshot().values().map(|gauge| gauge.max_ms).max(),
+        memory_hwm_kb: report::memory_high_water_kb(),
+    });
+
+    let _ = std::fs::remove_dir_all(&folder);
+    report
+}
+
+/// Appends the generated lines to `path`, at `options.lines_per_sec`, for `options.duration`.
+///
+/// # Returns
+/// The number of lines written and the time spent writing them.
+async fn write_lines(path: &PathBuf, options: &BenchOptions) -> Result<(u64, Duration), String> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| format!("cannot open {}: {e}", path.display()))?;
+    let mut generator = SyntheticLogGenerator::new(options.seed, options.players);
+    let mut ticker = tokio::time::interval(WRITE_TICK);
+
+    let started = Instant::now();
+    let mut written: u64 = 0;
+    while started.elapsed() < options.duration {
+        ticker.tick().await;
+
+        // Catch up with the expected count, so a slow tick doesn't lower the rate
+        let expected = (started.elapsed().as_secs_f64() * options.lines_per_sec as f64) as u64;
+        let now = chrono::Local::now().naive_local();
+        let mut batch = String::new();
+        while written < expected {
+            batch.push_str(&generator.next_line(now));
+            batch.push('\n');
+            written += 1;
+        }
+        if !batch.is_empty() {
+            file.write_all(batch.as_bytes()).map_err(|e| format!("cannot write {}: {e}", path.display()))?;
+            file.flush().map_err(|e| format!("cannot write {}: {e}", path.display()))?;
+        }
+    }
+
+    Ok((written, started.elapsed()))
+}
@@ -0,0 +1,62 @@
+use std::fmt;
+use std::time::Duration;
+
+/// Results of a bench run.
+#[derive(Debug, Clone)]
+pub struct BenchReport {
+    /// Lines written in the watched folder
+    pub lines_written: u64,
+    /// Actions called by the watcher for these lines
+    pub actions_dispatched: u64,
+    /// Time spent writing the lines
+    pub duration: Duration,
+    /// Highest processing lag measured by the watcher, in milliseconds
+    pub max_lag_ms: Option<i64>,
+    /// Memory high-water mark of the process, in kB (Linux only)
+    pub memory_hwm_kb: Option<u64>,
+}
+
+impl BenchReport {
+    /// Lines written per second.
+    pub fn lines_per_sec(&self) -> f64 {
+        per_sec(self.lines_written, self.duration)
+    }
+
+    /// Actions dispatched per second.
+    pub fn actions_per_sec(&self) -> f64 {
+        per_sec(self.actions_dispatched, self.duration)
+    }
+}
+
+impl fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Duration           : {:.1}s", self.duration.as_secs_f64())?;
+        writeln!(f, "Lines written      : {} ({:.1}/s)", self.lines_written, self.lines_per_sec())?;
+        writeln!(f, "Actions dispatched : {} ({:.1}/s)", self.actions_dispatched, self.actions_per_sec())?;
+        match self.max_lag_ms {
+            Some(lag) => writeln!(f, "Max processing lag : {lag} ms")?,
+            None => writeln!(f, "Max processing lag : not measured")?,
+        }
+        match self.memory_hwm_kb {
+            Some(kb) => write!(f, "Memory high-water  : {:.1} MB", kb as f64 / 1024.0),
+            None => write!(f, "Memory high-water  : not available"),
+        }
+    }
+}
+
+/// Reads the memory high-water mark (`VmHWM`) of the process from `/proc/self/status`.
+///
+/// # Returns
+/// The high-water mark in kB, or `None` on systems without procfs.
+pub fn memory_high_water_kb() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmHWM:"))
+        .and_then(|value| value.trim().trim_end_matches("kB").trim().parse().ok())
+}
+
+fn per_sec(count: u64, duration: Duration) -> f64 {
+    let secs = duration.as_secs_f64();
+    if secs > 0.0 { count as f64 / secs } else { 0.0 }
+}
@@ -0,0 +1,461 @@
+use colored::Colorize;
+use log::{error, info, warn};
+use mysql::{params, prelude::Queryable, PooledConn};
+use std::collections::{HashMap, HashSet};
+
+use super::repository_default::Database;
+use crate::helper;
+
+/// A step of a migration.
+pub enum Step {
+    /// A `CREATE TABLE IF NOT EXISTS` : the tables created by the website are left as they are
+    Sql(&'static str),
+    /// Adds a column if the table doesn't have it (MySQL has no `ADD COLUMN IF NOT EXISTS`)
+    AddColumn { table: &'static str, column: &'static str, definition: &'static str },
+}
+
+/// A versioned change of the schema, applied once and recorded in `schema_version`.
+pub struct Migration {
+    pub version: u32,
+    pub name: &'static str,
+    pub steps: &'static [Step],
+}
+
+/// The migrations compiled into the binary, in order. A released migration is never modified : a change is a new version.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "colonnes_otternel",
+        steps: &[
+            Step::AddColumn { table: "joueurs", column: "profil_incomplet", definition: "BOOLEAN NOT NULL DEFAULT FALSE" },
+            Step::AddColumn { table: "joueurs", column: "visible", definition: "BOOLEAN NOT NULL DEFAULT TRUE" },
+            Step::AddColumn { table: "joueurs_connections_log", column: "type", definition: "ENUM('join', 'leave') NOT NULL DEFAULT 'join'" },
+            Step::AddColumn { table: "joueurs_connections_log", column: "duree_session", definition: "INT UNSIGNED NULL" },
+            Step::AddColumn { table: "joueurs_stats", column: "dern_enregistrment", definition: "DATETIME NULL" },
+            Step::AddColumn { table: "joueurs_stats", column: "niveau", definition: "INT NULL" },
+            Step::AddColumn { table: "joueurs_stats", column: "pals_captures", definition: "INT NULL" },
+            Step::AddColumn { table: "serveurs_actifs", column: "demarre_le", definition: "DATETIME NULL" },
+            Step::AddColumn { table: "serveurs_actifs", column: "arrete_le", definition: "DATETIME NULL" },
+        ],
+    },
+    Migration {
+        version: 2,
+        name: "tables_otternel",
+        steps: &[
+            Step::Sql(
+                r#"CREATE TABLE IF NOT EXISTS serveurs_mutes (
+                    serveur_actif_id BIGINT UNSIGNED NOT NULL PRIMARY KEY,
+                    fin DATETIME NOT NULL,
+                    raison VARCHAR(255) NULL
+                )"#,
+            ),
+            Step::Sql(
+                r#"CREATE TABLE IF NOT EXISTS rcon_schedules (
+                    id BIGINT UNSIGNED AUTO_INCREMENT PRIMARY KEY,
+                    serveur_actif_id BIGINT UNSIGNED NOT NULL,
+                    cron_expression VARCHAR(100) NOT NULL,
+                    commande VARCHAR(500) NOT NULL,
+                    actif TINYINT(1) NOT NULL DEFAULT 1
+                )"#,
+            ),
+            Step::Sql(
+                r#"CREATE TABLE IF NOT EXISTS serverlog_triggers (
+                    id BIGINT UNSIGNED AUTO_INCREMENT PRIMARY KEY,
+                    nom VARCHAR(100) NOT NULL,
+                    pattern VARCHAR(1000) NOT NULL,
+                    fonction VARCHAR(100) NOT NULL,
+                    serveur_actif_id BIGINT UNSIGNED NULL,
+                    actif TINYINT(1) NOT NULL DEFAULT 1
+                )"#,
+            ),
+            Step::Sql(
+                r#"CREATE TABLE IF NOT EXISTS joueurs_pvp (
+                    id BIGINT UNSIGNED AUTO_INCREMENT PRIMARY KEY,
+                    serveur_id BIGINT UNSIGNED NOT NULL,
+                    victime_id BIGINT UNSIGNED NOT NULL,
+                    tueur_id BIGINT UNSIGNED NOT NULL,
+                    arme VARCHAR(255) NULL,
+                    date DATETIME NOT NULL,
+                    KEY idx_joueurs_pvp_date (date)
+                )"#,
+            ),
+            Step::Sql(
+                r#"CREATE TABLE IF NOT EXISTS rapports_integrite (
+                    id BIGINT UNSIGNED AUTO_INCREMENT PRIMARY KEY,
+                    verification VARCHAR(100) NOT NULL,
+                    nb_anomalies BIGINT UNSIGNED NOT NULL,
+                    date DATETIME NOT NULL
+                )"#,
+            ),
+            Step::Sql(
+                r#"CREATE TABLE IF NOT EXISTS whitelist_refus (
+                    serveur_id BIGINT UNSIGNED NOT NULL,
+                    playername VARCHAR(32) NOT NULL,
+                    ip VARCHAR(45) NULL,
+                    tentatives INT UNSIGNED NOT NULL DEFAULT 1,
+                    derniere_tentative DATETIME NOT NULL,
+                    PRIMARY KEY (serveur_id, playername)
+                )"#,
+            ),
+            Step::Sql(
+                r#"CREATE TABLE IF NOT EXISTS moderation_log (
+                    id BIGINT UNSIGNED AUTO_INCREMENT PRIMARY KEY,
+                    serveur_id BIGINT UNSIGNED NOT NULL,
+                    playername VARCHAR(32) NOT NULL,
+                    type ENUM('kick', 'ban') NOT NULL,
+                    raison VARCHAR(255) NULL,
+                    auteur VARCHAR(32) NULL,
+                    date DATETIME NOT NULL,
+                    KEY idx_moderation_log_player (playername),
+                    KEY idx_moderation_log_serveur (serveur_id, date)
+                )"#,
+            ),
+        ],
+    },
+    Migration {
+        version: 3,
+        name: "etat_serveurs_actifs",
+        steps: &[Step::Sql(
+            r#"CREATE TABLE IF NOT EXISTS serveurs_actifs_etat (
+                serveur_id BIGINT UNSIGNED NOT NULL PRIMARY KEY,
+                nb_joueurs INT UNSIGNED NOT NULL DEFAULT 0,
+                joueurs TEXT NOT NULL,
+                maj_le DATETIME NOT NULL
+            )"#,
+        )],
+    },
+    Migration {
+        version: 4,
+        name: "server_list_ping",
+        steps: &[
+            Step::AddColumn { table: "serveurs_actifs", column: "ping_host", definition: "VARCHAR(255) NULL" },
+            Step::AddColumn { table: "serveurs_actifs", column: "ping_port", definition: "SMALLINT UNSIGNED NULL" },
+            Step::Sql(
+                r#"CREATE TABLE IF NOT EXISTS serveurs_ping_log (
+                    id BIGINT UNSIGNED AUTO_INCREMENT PRIMARY KEY,
+                    serveur_actif_id BIGINT UNSIGNED NOT NULL,
+                    date DATETIME NOT NULL,
+                    en_ligne BOOLEAN NOT NULL,
+                    latence_ms INT UNSIGNED NULL,
+                    version VARCHAR(100) NULL,
+                    nb_joueurs INT UNSIGNED NULL,
+                    nb_joueurs_max INT UNSIGNED NULL,
+                    motd VARCHAR(500) NULL,
+                    KEY idx_serveurs_ping_log_serveur (serveur_actif_id, date)
+                )"#,
+            ),
+        ],
+    },
+    Migration {
+        version: 5,
+        name: "audit_serverlog_events",
+        steps: &[Step::Sql(
+            r#"CREATE TABLE IF NOT EXISTS serverlog_events (
+                id BIGINT UNSIGNED AUTO_INCREMENT PRIMARY KEY,
+                date DATETIME NOT NULL,
+                serverlog_id INT UNSIGNED NOT NULL,
+                trigger_name VARCHAR(100) NOT NULL,
+                fonction VARCHAR(100) NOT NULL,
+                ligne VARCHAR(500) NOT NULL,
+                resultat ENUM('ok', 'erreur') NOT NULL,
+                duree_ms INT UNSIGNED NOT NULL,
+                KEY idx_serverlog_events_date (date)
+            )"#,
+        )],
+    },
+    Migration {
+        version: 6,
+        name: "server_discord_thread",
+        steps: &[Step::AddColumn { table: "serveurs", column: "discord_thread_id", definition: "VARCHAR(32) NULL" }],
+    },
+    Migration {
+        version: 7,
+        name: "date_liaison_joueurs",
+        steps: &[Step::AddColumn { table: "joueurs", column: "lie_le", definition: "DATETIME NULL" }],
+    },
+];
+
+/// Tables and columns read or written by the repositories, checked at startup.
+pub const REQUIRED_SCHEMA: &[(&str, &[&str])] = &[
+    ("serveurs", &["id", "nom", "jeu", "version", "modpack", "modpack_url", "nom_monde", "embed_color", "contenaire", "description", "actif", "global", "type", "image", "discord_thread_id"]),
+    ("serveurs_actifs", &["id", "serveurs_id", "rcon_host", "rcon_port", "rcon_password", "demarre_le", "arrete_le", "ping_host", "ping_port"]),
+    ("joueurs", &["id", "utilisateur_id", "jeu", "compte_id", "playername", "premiere_co", "derniere_co", "profil_incomplet", "visible", "lie_le"]),
+    ("joueurs_connections_log", &["serveur_id", "joueur_id", "date", "type", "duree_session"]),
+    ("joueurs_stats", &[
+        "serveur_id", "compte_id", "tmps_jeux", "nb_mort", "nb_kills", "nb_playerkill", "mob_killed", "nb_blocs_detr",
+        "nb_blocs_pose", "dist_total", "dist_pieds", "dist_elytres", "dist_vol", "item_crafted", "item_broken", "achievement",
+        "niveau", "pals_captures", "dern_enregistrment",
+    ]),
+    ("joueurs_pokemon", &["serveur_id", "joueur_uuid", "pkmn1", "pkmn2", "pkmn3", "pkmn4", "pkmn5", "pkmn6"]),
+    ("joueurs_pokemon_pc", &["serveur_id", "joueur_uuid", "boite", "slot", "pkmn", "pkmn_form", "pkmn_gender", "pkmn_nickname", "pkmn_level", "pkmn_shiny", "pkmn_do_uuid", "pkmn_uuid"]),
+    ("joueurs_pvp", &["serveur_id", "victime_id", "tueur_id", "arme", "date"]),
+    ("codes_liaison", &["joueur_id", "code_liaison", "cree_le", "expire_le"]),
+    ("serveurs_mutes", &["serveur_actif_id", "fin", "raison"]),
+    ("rcon_schedules", &["id", "serveur_actif_id", "cron_expression", "commande", "actif"]),
+    ("serverlog_triggers", &["id", "nom", "pattern", "fonction", "serveur_actif_id", "actif"]),
+    ("rapports_integrite", &["verification", "nb_anomalies", "date"]),
+    ("whitelist_refus", &["serveur_id", "playername", "ip", "tentatives", "derniere_tentative"]),
+    ("moderation_log", &["serveur_id", "playername", "type", "raison", "auteur", "date"]),
+    ("serveurs_actifs_etat", &["serveur_id", "nb_joueurs", "joueurs", "maj_le"]),
+    ("serveurs_ping_log", &["serveur_actif_id", "date", "en_ligne", "latence_ms", "version", "nb_joueurs", "nb_joueurs_max", "motd"]),
+    ("serverlog_events", &["date", "serverlog_id", "trigger_name", "fonction", "ligne", "resultat", "duree_ms"]),
+];
+
+impl Database {
+    // ===========================
+    // schema_version
+    // ===========================
+
+    /// Returns the columns of every table of the current database, by table.
+    fn get_schema_columns(&self) -> Result<HashMap<String, HashSet<String>>, mysql::Error> {
+        let mut conn = self.get_conn()?;
+        let rows: Vec<(String, String)> = conn.query(
+            "SELECT TABLE_NAME, COLUMN_NAME FROM information_schema.COLUMNS WHERE TABLE_SCHEMA = DATABASE()",
+        )?;
+        let mut columns: HashMap<String, HashSet<String>> = HashMap::new();
+        for (table, column) in rows {
+            columns.entry(table.to_lowercase()).or_default().insert(column.to_lowercase());
+        }
+        Ok(columns)
+    }
+
+    /// Returns the tables and columns of [`REQUIRED_SCHEMA`] missing from the database
+    /// (ex: "table moderation_log", "column joueurs_stats.dern_enregistrment").
+    pub fn get_missing_schema(&self) -> Result<Vec<String>, mysql::Error> {
+        Ok(missing_schema(&self.get_schema_columns()?, REQUIRED_SCHEMA))
+    }
+
+    /// Applies the migrations above the version recorded in `schema_version`, in order.
+    ///
+    /// # Returns
+    /// The number of migrations applied.
+    pub fn apply_migrations(&self) -> Result<usize, mysql::Error> {
+        let mut conn = self.get_conn()?;
+        conn.query_drop(
+            r#"CREATE TABLE IF NOT EXISTS schema_version (
+                version INT UNSIGNED NOT NULL PRIMARY KEY,
+                nom VARCHAR(100) NOT NULL,
+                applique_le DATETIME NOT NULL
+            )"#,
+        )?;
+        run_migrations(&mut conn, MIGRATIONS)
+    }
+}
+
+/// Returns the tables and columns of `required` missing from `existing` (columns by lowercase table name).
+fn missing_schema(existing: &HashMap<String, HashSet<String>>, required: &[(&str, &[&str])]) -> Vec<String> {
+    let mut missing = Vec::new();
+    for (table, columns) in required {
+        let Some(existing_columns) = existing.get(*table) else {
+            missing.push(format!("table {}", table));
+            continue;
+        };
+        missing.extend(
+            columns
+                .iter()
+                .filter(|column| !existing_columns.contains(**column))
+                .map(|column| format!("column {}.{}", table, column)),
+        );
+    }
+    missing
+}
+
+/// The database the migrations are applied to.
+trait SchemaTarget {
+    /// Highest version recorded in `schema_version`, 0 if none
+    fn current_version(&mut self) -> Result<u32, mysql::Error>;
+    fn run_sql(&mut self, sql: &str) -> Result<(), mysql::Error>;
+    fn has_column(&mut self, table: &str, column: &str) -> Result<bool, mysql::Error>;
+    /// Records in `schema_version` that a migration was applied
+    fn record(&mut self, migration: &Migration) -> Result<(), mysql::Error>;
+}
+
+impl SchemaTarget for PooledConn {
+    fn current_version(&mut self) -> Result<u32, mysql::Error> {
+        let current: Option<Option<u32>> = self.query_first("SELECT MAX(version) FROM schema_version")?;
+        Ok(current.flatten().unwrap_or(0))
+    }
+
+    fn run_sql(&mut self, sql: &str) -> Result<(), mysql::Error> {
+        self.query_drop(sql)
+    }
+
+    fn has_column(&mut self, table: &str, column: &str) -> Result<bool, mysql::Error> {
+        let count: Option<u64> = self.exec_first(
+            r#"SELECT COUNT(*) FROM information_schema.COLUMNS
+               WHERE TABLE_SCHEMA = DATABASE() AND TABLE_NAME = :table AND COLUMN_NAME = :column"#,
+            params! { "table" => table, "column" => column },
+        )?;
+        Ok(count.unwrap_or(0) > 0)
+    }
+
+    fn record(&mut self, migration: &Migration) -> Result<(), mysql::Error> {
+        self.exec_drop(
+            "INSERT INTO schema_version (version, nom, applique_le) VALUES (:version, :nom, UTC_TIMESTAMP())",
+            params! { "version" => migration.version, "nom" => migration.name },
+        )
+    }
+}
+
+/// Applies the migrations above the current version of `target`, in order, each one recorded once its steps ran.
+/// A failing step stops the run : its migration isn't recorded and is applied again by the next run.
+fn run_migrations(target: &mut impl SchemaTarget, migrations: &[Migration]) -> Result<usize, mysql::Error> {
+    let current = target.current_version()?;
+    let mut applied = 0;
+    for migration in migrations.iter().filter(|migration| migration.version > current) {
+        for step in migration.steps {
+            match step {
+                Step::Sql(sql) => target.run_sql(sql)?,
+                Step::AddColumn { table, column, definition } => {
+                    if !target.has_column(table, column)? {
+                        target.run_sql(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition))?;
+                    }
+                }
+            }
+        }
+        target.record(migration)?;
+        info!("Migration {} ({}) applied", migration.version.to_string().green().bold(), migration.name);
+        applied += 1;
+    }
+    Ok(applied)
+}
+
+/// Prepares the schema at startup : applies the migrations if `auto_migrate` (`DB_AUTO_MIGRATE`), then checks
+/// that every table and column used by the repositories exists. In dry-run, the migrations aren't applied.
+///
+/// # Returns
+/// `false` if something is missing : the caller refuses to start, the missing tables and columns being logged.
+/// Without database, `true` is returned and the watcher runs as usual.
+pub fn prepare_schema(db: &Database, auto_migrate: bool) -> bool {
+    if auto_migrate && !helper::dry_run::skip(|| "pending migrations applied".to_string()) {
+        match db.apply_migrations() {
+            Ok(0) => info!("Database schema up to date"),
+            Ok(applied) => info!("{} migrations applied", applied.to_string().green().bold()),
+            Err(e) => warn!("Could not apply the migrations: {}", e),
+        }
+    }
+    match db.get_missing_schema() {
+        Ok(missing) if missing.is_empty() => true,
+        Ok(missing) => {
+            error!(
+                "The database schema is missing: {}. Apply the migrations with {} or update the schema by hand",
+                missing.join(", ").yellow(),
+                "DB_AUTO_MIGRATE=true".green()
+            );
+            false
+        }
+        Err(e) => {
+            warn!("Could not check the database schema: {}", e);
+            true
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A database in memory : its columns, the versions recorded and the statements run
+    #[derive(Default)]
+    struct FakeSchema {
+        columns: HashSet<(String, String)>,
+        versions: Vec<u32>,
+        statements: Vec<String>,
+        /// A statement containing this text fails
+        failing: Option<&'static str>,
+    }
+
+    impl SchemaTarget for FakeSchema {
+        fn current_version(&mut self) -> Result<u32, mysql::Error> {
+            Ok(self.versions.iter().copied().max().unwrap_or(0))
+        }
+
+        fn run_sql(&mut self, sql: &str) -> Result<(), mysql::Error> {
+            if self.failing.is_some_and(|failing| sql.contains(failing)) {
+                return Err(mysql::Error::IoError(std::io::Error::other("step failed")));
+            }
+            if let ["ALTER", "TABLE", table, "ADD", "COLUMN", column, ..] = sql.split_whitespace().collect::<Vec<_>>()[..] {
+                self.columns.insert((table.to_string(), column.to_string()));
+            }
+            self.statements.push(sql.to_string());
+            Ok(())
+        }
+
+        fn has_column(&mut self, table: &str, column: &str) -> Result<bool, mysql::Error> {
+            Ok(self.columns.contains(&(table.to_string(), column.to_string())))
+        }
+
+        fn record(&mut self, migration: &Migration) -> Result<(), mysql::Error> {
+            self.versions.push(migration.version);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn versions_follow_each_other_and_names_are_unique() {
+        let versions: Vec<u32> = MIGRATIONS.iter().map(|migration| migration.version).collect();
+        assert_eq!(versions, (1..=MIGRATIONS.len() as u32).collect::<Vec<_>>());
+        let names: HashSet<&str> = MIGRATIONS.iter().map(|migration| migration.name).collect();
+        assert_eq!(names.len(), MIGRATIONS.len());
+    }
+
+    #[test]
+    fn migrations_are_applied_in_order_once() {
+        let mut schema = FakeSchema::default();
+        assert_eq!(run_migrations(&mut schema, MIGRATIONS).unwrap(), MIGRATIONS.len());
+        assert_eq!(schema.versions, (1..=MIGRATIONS.len() as u32).collect::<Vec<_>>());
+
+        let statements = schema.statements.len();
+        assert_eq!(run_migrations(&mut schema, MIGRATIONS).unwrap(), 0);
+        assert_eq!(schema.statements.len(), statements, "nothing is run again");
+    }
+
+    #[test]
+    fn only_the_migrations_above_the_current_version_are_applied() {
+        let mut schema = FakeSchema { versions: vec![1, 2], ..FakeSchema::default() };
+        run_migrations(&mut schema, MIGRATIONS).unwrap();
+        assert_eq!(schema.versions[2..], (3..=MIGRATIONS.len() as u32).collect::<Vec<_>>()[..]);
+        assert!(!schema.statements.iter().any(|sql| sql.contains("serveurs_mutes")), "version 2 isn't applied again");
+    }
+
+    #[test]
+    fn columns_already_there_are_not_added_again() {
+        let mut schema = FakeSchema::default();
+        schema.columns.insert(("joueurs".to_string(), "visible".to_string()));
+        run_migrations(&mut schema, &MIGRATIONS[..1]).unwrap();
+        assert!(!schema.statements.iter().any(|sql| sql.contains("ADD COLUMN visible")));
+        assert!(schema.statements.iter().any(|sql| sql.contains("ADD COLUMN profil_incomplet")));
+        assert_eq!(schema.versions, vec![1]);
+    }
+
+    #[test]
+    fn a_failing_step_stops_the_run_and_is_retried() {
+        let mut schema = FakeSchema { failing: Some("serveurs_ping_log"), ..FakeSchema::default() };
+        assert!(run_migrations(&mut schema, MIGRATIONS).is_err());
+        let failed = MIGRATIONS
+            .iter()
+            .find(|migration| migration.steps.iter().any(|step| matches!(step, Step::Sql(sql) if sql.contains("serveurs_ping_log"))))
+            .unwrap()
+            .version;
+        assert_eq!(schema.versions, (1..failed).collect::<Vec<_>>());
+
+        schema.failing = None;
+        assert_eq!(run_migrations(&mut schema, MIGRATIONS).unwrap(), MIGRATIONS.len() - failed as usize + 1);
+    }
+
+    #[test]
+    fn missing_tables_and_columns_are_listed() {
+        let mut schema = FakeSchema::default();
+        run_migrations(&mut schema, MIGRATIONS).unwrap();
+        let mut existing: HashMap<String, HashSet<String>> = HashMap::new();
+        for (table, column) in &schema.columns {
+            existing.entry(table.clone()).or_default().insert(column.clone());
+        }
+        let missing = missing_schema(&existing, &[("joueurs", &["profil_incomplet", "visible", "lie_le"]), ("serveurs_mutes", &["fin"])]);
+        assert_eq!(missing, vec!["table serveurs_mutes"], "tables created by SQL are only known to the database");
+        assert_eq!(
+            missing_schema(&existing, &[("joueurs", &["visible", "pseudo"])]),
+            vec!["column joueurs.pseudo"]
+        );
+    }
+}
@@ -3,6 +3,18 @@ pub mod repository_default;
 pub mod repository_servers;
 pub mod repository_player;
 pub mod repository_codes_liaison;
+pub mod repository_integrity;
+pub mod repository_mutes;
+pub mod repository_pvp;
+pub mod repository_rcon_schedules;
+pub mod repository_serverlog_triggers;
+pub mod repository_whitelist;
+pub mod repository_moderation;
+pub mod repository_leaderboard;
+pub mod repository_badges;
+pub mod repository_server_ping;
+pub mod repository_serverlog_events;
+pub mod migrations;
 
 // Expose Database type under `db::repository::Database`
 pub mod repository {
@@ -6,6 +6,86 @@ pub struct JoueurConnectionLog {
     pub serveur_id: u64,
     pub joueur_id: u64,
     pub date: NaiveDateTime,
+    pub r#type: ConnectionType,
+}
+
+/// Whether a row of `joueurs_connections_log` is a join or a leave.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConnectionType {
+    Join,
+    Leave,
+}
+
+impl ConnectionType {
+    /// Value stored in the `type` column
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConnectionType::Join => "join",
+            ConnectionType::Leave => "leave",
+        }
+    }
+}
+
+/// Counter of `joueurs_stats` incremented from the logs, between two syncs of the stats files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatColumn {
+    NbMort,
+    NbPlayerkill,
+}
+
+impl StatColumn {
+    /// Name of the column, never taken from user input
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StatColumn::NbMort => "nb_mort",
+            StatColumn::NbPlayerkill => "nb_playerkill",
+        }
+    }
+}
+
+/// Stat of `joueurs_stats` a leaderboard can be computed on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatKind {
+    TempsDeJeu,
+    Morts,
+    Kills,
+    BlocsMines,
+    Distance,
+}
+
+impl StatKind {
+    pub const ALL: [StatKind; 5] = [StatKind::TempsDeJeu, StatKind::Morts, StatKind::Kills, StatKind::BlocsMines, StatKind::Distance];
+
+    /// Name of the column, never taken from user input
+    pub fn column(&self) -> &'static str {
+        match self {
+            StatKind::TempsDeJeu => "tmps_jeux",
+            StatKind::Morts => "nb_mort",
+            StatKind::Kills => "nb_kills",
+            StatKind::BlocsMines => "nb_blocs_detr",
+            StatKind::Distance => "dist_total",
+        }
+    }
+
+    /// Title of the stat in the leaderboards
+    pub fn label(&self) -> &'static str {
+        match self {
+            StatKind::TempsDeJeu => "Temps de jeu",
+            StatKind::Morts => "Morts",
+            StatKind::Kills => "Monstres tués",
+            StatKind::BlocsMines => "Blocs minés",
+            StatKind::Distance => "Distance parcourue",
+        }
+    }
+}
+
+/// A player of a leaderboard, with their value of the stat (summed over the servers when no server is given).
+#[derive(Debug, Clone, Serialize)]
+pub struct TopJoueur {
+    pub playername: String,
+    pub compte_id: String,
+    pub valeur: i64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -25,9 +105,11 @@ pub struct Serveur {
     pub global: bool,
     pub r#type: Option<String>,
     pub image: Option<String>,
+    /// Thread of the webhook's channel where the events of the server are posted, if any
+    pub discord_thread_id: Option<String>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct ServeurActifGlobal {
     pub active_id: u64,
     pub nom: String,
@@ -47,6 +129,7 @@ pub struct RconParams {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[derive(Default)]
+#[allow(dead_code)]
 pub struct JoueurStats {
     pub id: u64,
     pub serveur_id: u64,
@@ -67,3 +150,91 @@ pub struct JoueurStats {
     pub achievement: Option<serde_json::Value>,
     pub dern_enregistr: NaiveDateTime,
 }
+
+/// Announcements of an active server muted until `fin` (UTC).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServeurMute {
+    pub serveur_actif_id: u64,
+    pub fin: NaiveDateTime,
+    pub raison: Option<String>,
+}
+
+/// RCON command run on an active server at the times of a cron expression (`rcon_schedules`).
+#[derive(Debug, Clone)]
+pub struct RconSchedule {
+    pub id: u64,
+    pub serveur_actif_id: u64,
+    /// `minute hour day-of-month month day-of-week`, in local time
+    pub cron_expression: String,
+    pub commande: String,
+}
+
+/// Trigger edited from the admin interface (`serverlog_triggers`), added to the ones of `triggers.toml`.
+#[derive(Debug, Clone)]
+pub struct ServerlogTrigger {
+    pub id: u64,
+    pub nom: String,
+    pub pattern: String,
+    pub fonction: String,
+    /// Active server concerned, `None` for every server
+    pub serveur_actif_id: Option<u64>,
+}
+
+/// Player account linked to a user of the website (`joueurs.utilisateur_id` set).
+#[derive(Debug, Clone, Serialize)]
+pub struct JoueurLie {
+    pub joueur_id: u64,
+    pub jeu: String,
+    pub playername: String,
+    /// When Otternel first saw the link (UTC)
+    pub lie_le: chrono::NaiveDateTime,
+}
+
+/// Active server nobody joined for a while, candidate for a world backup.
+#[derive(Debug, Clone)]
+pub struct ServeurInactif {
+    pub active_id: u64,
+    pub serveur_id: u64,
+    pub nom: String,
+    pub contenaire: Option<String>,
+    pub nom_monde: Option<String>,
+    /// Last connection, or the start of the server if nobody ever joined ("%Y-%m-%d %H:%i:%s")
+    pub derniere_activite: String,
+}
+
+/// A pokemon of a Cobblemon PC box, as stored in `joueurs_pokemon_pc`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JoueurPokemonPc {
+    /// Box number, from 0
+    pub boite: u32,
+    /// Slot in the box, from 0 to 29
+    pub slot: u32,
+    pub pkmn: String,
+    pub form: Option<String>,
+    pub gender: Option<String>,
+    pub nickname: Option<String>,
+    pub level: Option<i32>,
+    pub shiny: Option<bool>,
+    pub do_uuid: Option<String>,
+    pub pokemon_uuid: Option<String>,
+}
+
+/// Active Minecraft server with a Server List Ping address (`serveurs_actifs.ping_host` and `ping_port`).
+#[derive(Debug, Clone)]
+pub struct ServeurPingCible {
+    pub active_id: u64,
+    pub nom: String,
+    pub host: String,
+    pub port: u16,
+}
+
+/// Result of a Server List Ping, as stored in `serveurs_ping_log`. Only `en_ligne` is set when the server didn't answer.
+#[derive(Debug, Clone, Default)]
+pub struct ServeurPing {
+    pub en_ligne: bool,
+    pub latence_ms: Option<u32>,
+    pub version: Option<String>,
+    pub nb_joueurs: Option<u32>,
+    pub nb_joueurs_max: Option<u32>,
+    pub motd: Option<String>,
+}
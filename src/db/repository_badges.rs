@@ -0,0 +1,48 @@
+use mysql::{params, prelude::Queryable};
+
+use super::repository_default::Database;
+
+impl Database {
+    // ===========================
+    // badge_joueur
+    // ===========================
+
+    /// Returns the ids of the badges a player already has.
+    ///
+    /// # Arguments
+    /// * `joueur_id` - The ID of the player in the `joueurs` table.
+    pub fn list_badges_for_joueur(&self, joueur_id: u64) -> Result<Vec<u64>, mysql::Error> {
+        let mut conn = self.get_conn()?;
+        conn.exec(
+            "SELECT badge_id FROM badge_joueur WHERE joueur_id = :joueur_id",
+            params! { "joueur_id" => joueur_id },
+        )
+    }
+
+    /// Gives a badge to a player. A badge the player already has is left as it is.
+    ///
+    /// # Arguments
+    /// * `joueur_id` - The ID of the player in the `joueurs` table.
+    /// * `badge_id` - The ID of the badge.
+    ///
+    /// # Returns
+    /// `true` if the badge was given, `false` if the player already had it.
+    pub fn give_badge_to_joueur(&self, joueur_id: u64, badge_id: u64) -> Result<bool, mysql::Error> {
+        let mut conn = self.get_conn()?;
+        conn.exec_drop(
+            r#"INSERT IGNORE INTO badge_joueur (joueur_id, badge_id, date_obtention)
+               VALUES (:joueur_id, :badge_id, UTC_TIMESTAMP())"#,
+            params! {
+                "joueur_id" => joueur_id,
+                "badge_id" => badge_id,
+            },
+        )?;
+        Ok(conn.affected_rows() > 0)
+    }
+
+    /// Returns the name of a player from their id, `None` if the player doesn't exist.
+    pub fn get_playername_by_joueur_id(&self, joueur_id: u64) -> Result<Option<String>, mysql::Error> {
+        let mut conn = self.get_conn()?;
+        conn.exec_first("SELECT playername FROM joueurs WHERE id = :id", params! { "id" => joueur_id })
+    }
+}
@@ -1,4 +1,4 @@
-use log::{error, warn, info, debug};
+use log::debug;
 use mysql::{params, prelude::Queryable};
 
 use super::repository_default::Database;
@@ -45,7 +45,7 @@ impl Database {
         Ok(())
     }
 
-    /// Checks if an active linking code exists for a given player.
+    /// Fetches the linking code of a player still valid, the most recent one if several are.
     ///
     /// # Arguments
     ///
@@ -53,20 +53,18 @@ impl Database {
     ///
     /// # Returns
     ///
-    /// `Result<bool, mysql::Error>` - Returns `Ok(true)` if an active code exists,
-    /// `Ok(false)` otherwise, or an error in case of a database issue.
-    pub fn is_linking_code_active_for_player_id(&self, joueur_id: u64) -> Result<bool, mysql::Error> {
+    /// `Result<Option<String>, mysql::Error>` - Returns `Ok(Some(code))` if an active code exists,
+    /// `Ok(None)` otherwise, or an error in case of a database issue.
+    pub fn get_active_linking_code(&self, joueur_id: u64) -> Result<Option<String>, mysql::Error> {
         let mut conn = self.get_conn()?;
-        
-        let code_existe: Option<u8> = conn.exec_first(
-            r#"SELECT EXISTS(
-                SELECT 1
-                FROM codes_liaison
-                WHERE joueur_id = :joueur_id AND expire_le > NOW()
-            )"#,
-            params! { "joueur_id" => joueur_id },
-        )?;
 
-        Ok(code_existe.map_or(false, |val| val == 1))
+        conn.exec_first(
+            r#"SELECT code_liaison
+               FROM codes_liaison
+               WHERE joueur_id = :joueur_id AND expire_le > NOW()
+               ORDER BY expire_le DESC
+               LIMIT 1"#,
+            params! { "joueur_id" => joueur_id },
+        )
     }
 }
\ No newline at end of file
@@ -0,0 +1,129 @@
+use chrono::{Duration, NaiveDateTime, Utc};
+use mysql::{params, prelude::Queryable};
+
+use super::repository_default::Database;
+
+/// A session is an anomaly when it is still open after this long
+pub const MAX_SESSION_HOURS: i64 = 48;
+
+impl Database {
+    // ===========================
+    // integrity checks
+    // ===========================
+
+    /// Counts the `joueurs_stats` rows whose `compte_id` has no matching entry in `joueurs`.
+    pub fn count_orphan_player_stats(&self) -> Result<u64, mysql::Error> {
+        let mut conn = self.get_conn()?;
+        let count: Option<u64> = conn.query_first(
+            r#"SELECT COUNT(*)
+                FROM joueurs_stats js
+                LEFT JOIN joueurs j ON j.compte_id = js.compte_id
+                WHERE j.id IS NULL"#,
+        )?;
+        Ok(count.unwrap_or(0))
+    }
+
+    /// Counts the `joueurs_connections_log` rows referencing a server missing from `serveurs`.
+    pub fn count_connection_logs_with_missing_server(&self) -> Result<u64, mysql::Error> {
+        let mut conn = self.get_conn()?;
+        let count: Option<u64> = conn.query_first(
+            r#"SELECT COUNT(*)
+                FROM joueurs_connections_log jcl
+                LEFT JOIN serveurs s ON s.id = jcl.serveur_id
+                WHERE s.id IS NULL"#,
+        )?;
+        Ok(count.unwrap_or(0))
+    }
+
+    /// Counts the linking codes still active for players already linked to a user.
+    pub fn count_active_codes_for_linked_players(&self) -> Result<u64, mysql::Error> {
+        let mut conn = self.get_conn()?;
+        let count: Option<u64> = conn.query_first(
+            r#"SELECT COUNT(*)
+                FROM codes_liaison cl
+                INNER JOIN joueurs j ON j.id = cl.joueur_id
+                WHERE cl.expire_le > NOW() AND j.utilisateur_id IS NOT NULL"#,
+        )?;
+        Ok(count.unwrap_or(0))
+    }
+
+    /// Counts the sessions still open (a join without a later leave) for more than 48 hours,
+    /// only the last join of a player on a server being their session.
+    pub fn count_sessions_open_too_long(&self) -> Result<u64, mysql::Error> {
+        let mut conn = self.get_conn()?;
+        let open_sessions: Vec<(u64, u64, String)> = conn.query(
+            r#"SELECT j.serveur_id, j.joueur_id, DATE_FORMAT(MAX(j.date), '%Y-%m-%d %H:%i:%s')
+                FROM joueurs_connections_log j
+                WHERE j.type = 'join'
+                  AND NOT EXISTS (
+                    SELECT 1 FROM joueurs_connections_log l
+                    WHERE l.serveur_id = j.serveur_id AND l.joueur_id = j.joueur_id
+                      AND l.type = 'leave' AND l.date >= j.date
+                  )
+                GROUP BY j.serveur_id, j.joueur_id"#,
+        )?;
+        let starts: Vec<NaiveDateTime> = open_sessions
+            .iter()
+            .filter_map(|(_, _, start)| NaiveDateTime::parse_from_str(start, "%Y-%m-%d %H:%M:%S").ok())
+            .collect();
+        Ok(sessions_open_too_long(&starts, Utc::now().naive_utc()))
+    }
+
+    /// Counts the `serveurs_actifs` rows pointing at a server missing from `serveurs`.
+    pub fn count_active_servers_with_missing_server(&self) -> Result<u64, mysql::Error> {
+        let mut conn = self.get_conn()?;
+        let count: Option<u64> = conn.query_first(
+            r#"SELECT COUNT(*)
+                FROM serveurs_actifs sa
+                LEFT JOIN serveurs s ON s.id = sa.serveurs_id
+                WHERE s.id IS NULL"#,
+        )?;
+        Ok(count.unwrap_or(0))
+    }
+
+    /// Writes the result of an integrity check in the `rapports_integrite` table.
+    ///
+    /// # Arguments
+    /// * `verification` - Name of the check.
+    /// * `nb_anomalies` - Number of rows found by the check.
+    pub fn insert_integrity_report(&self, verification: &str, nb_anomalies: u64) -> Result<(), mysql::Error> {
+        let mut conn = self.get_conn()?;
+        conn.exec_drop(
+            r#"INSERT INTO rapports_integrite (verification, nb_anomalies, date)
+               VALUES (:verification, :nb_anomalies, NOW())"#,
+            params! {
+                "verification" => verification,
+                "nb_anomalies" => nb_anomalies,
+            },
+        )
+    }
+}
+
+/// Counts the sessions started more than `MAX_SESSION_HOURS` before `now` (dates in UTC, as in the database).
+fn sessions_open_too_long(starts: &[NaiveDateTime], now: NaiveDateTime) -> u64 {
+    let limit = now - Duration::hours(MAX_SESSION_HOURS);
+    starts.iter().filter(|start| **start < limit).count() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn at(day: u32, hour: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 3, day).unwrap().and_hms_opt(hour, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn only_sessions_open_for_more_than_48_hours_are_anomalies() {
+        let now = at(10, 12);
+        // Started an hour ago, exactly 48 hours ago, 48 hours and a second ago, and days ago (crash without leave)
+        let starts = [at(10, 11), at(8, 12), at(8, 12) - Duration::seconds(1), at(1, 0)];
+        assert_eq!(sessions_open_too_long(&starts, now), 2);
+    }
+
+    #[test]
+    fn no_open_session_is_no_anomaly() {
+        assert_eq!(sessions_open_too_long(&[], at(10, 12)), 0);
+    }
+}
@@ -0,0 +1,41 @@
+use mysql::{params, prelude::Queryable};
+use crate::db::models::{StatKind, TopJoueur};
+
+use super::repository_default::Database;
+
+impl Database {
+    // ===========================
+    // leaderboards (joueurs_stats)
+    // ===========================
+
+    /// Fetch the players with the highest value of a stat, hidden players excluded.
+    ///
+    /// # Arguments
+    /// * `serveur_id` - The ID of the server in the `serveurs` table, `None` to sum the stat over every server.
+    /// * `stat` - The stat ranked.
+    /// * `limit` - The number of players returned.
+    ///
+    /// # Returns
+    /// The players, highest value first.
+    pub fn get_top_players_by_stat(&self, serveur_id: Option<u64>, stat: StatKind, limit: u32) -> Result<Vec<TopJoueur>, mysql::Error> {
+        let mut conn = self.get_conn()?;
+        conn.exec_map(
+            format!(
+                r#"SELECT j.playername, js.compte_id, CAST(SUM(js.{column}) AS SIGNED) AS valeur
+                   FROM joueurs_stats js
+                   INNER JOIN joueurs j ON j.compte_id = js.compte_id
+                   WHERE (:serveur_id IS NULL OR js.serveur_id = :serveur_id) AND j.visible = TRUE
+                   GROUP BY js.compte_id, j.playername
+                   HAVING valeur > 0
+                   ORDER BY valeur DESC, j.playername
+                   LIMIT :limit"#,
+                column = stat.column()
+            ),
+            params! {
+                "serveur_id" => serveur_id,
+                "limit" => limit,
+            },
+            |(playername, compte_id, valeur)| TopJoueur { playername, compte_id, valeur },
+        )
+    }
+}
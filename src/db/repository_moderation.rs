@@ -0,0 +1,40 @@
+use mysql::{params, prelude::Queryable};
+
+use super::repository_default::Database;
+
+impl Database {
+    // ===========================
+    // moderation_log
+    // ===========================
+
+    /// Records a sanction (kick or ban) of a player, for the history of the site.
+    ///
+    /// # Arguments
+    /// * `serveur_id` - The ID of the server in the `serveurs` table.
+    /// * `playername` - The name of the sanctioned player.
+    /// * `sanction_type` - `kick` or `ban`.
+    /// * `raison` - The reason given to the command, if any.
+    /// * `auteur` - The player who ran the command, `None` from the console.
+    pub fn insert_moderation_log(
+        &self,
+        serveur_id: u64,
+        playername: &str,
+        sanction_type: &str,
+        raison: Option<&str>,
+        auteur: Option<&str>,
+    ) -> Result<(), mysql::Error> {
+        let mut conn = self.get_conn()?;
+        conn.exec_drop(
+            r#"INSERT INTO moderation_log (serveur_id, playername, type, raison, auteur, date)
+               VALUES (:serveur_id, :playername, :type, :raison, :auteur, UTC_TIMESTAMP())"#,
+            params! {
+                "serveur_id" => serveur_id,
+                "playername" => playername,
+                "type" => sanction_type,
+                "raison" => raison,
+                "auteur" => auteur,
+            },
+        )?;
+        Ok(())
+    }
+}
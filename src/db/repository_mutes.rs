@@ -0,0 +1,61 @@
+use chrono::NaiveDateTime;
+use mysql::{params, prelude::Queryable};
+use crate::db::models::ServeurMute;
+
+use super::repository_default::Database;
+
+impl Database {
+    // ===========================
+    // serveurs_mutes
+    // ===========================
+
+    /// Fetch the mutes not expired yet.
+    pub fn get_active_server_mutes(&self) -> Result<Vec<ServeurMute>, mysql::Error> {
+        let mut conn = self.get_conn()?;
+        let rows: Vec<(u64, String, Option<String>)> = conn.exec(
+            r#"SELECT serveur_actif_id, DATE_FORMAT(fin, '%Y-%m-%d %H:%i:%s'), raison
+               FROM serveurs_mutes
+               WHERE fin > UTC_TIMESTAMP()"#,
+            (),
+        )?;
+
+        // A date that can't be parsed is skipped rather than failing every mute
+        Ok(rows
+            .into_iter()
+            .filter_map(|(serveur_actif_id, fin, raison)| {
+                let fin = NaiveDateTime::parse_from_str(&fin, "%Y-%m-%d %H:%M:%S").ok()?;
+                Some(ServeurMute { serveur_actif_id, fin, raison })
+            })
+            .collect())
+    }
+
+    /// Insert the mute of an active server, or replace the existing one.
+    ///
+    /// # Arguments
+    /// * `mute` - The mute to store. `fin` is in UTC.
+    pub fn upsert_server_mute(&self, mute: &ServeurMute) -> Result<(), mysql::Error> {
+        let mut conn = self.get_conn()?;
+        conn.exec_drop(
+            r#"INSERT INTO serveurs_mutes (serveur_actif_id, fin, raison)
+               VALUES (:serveur_actif_id, :fin, :raison)
+               ON DUPLICATE KEY UPDATE fin = VALUES(fin), raison = VALUES(raison)"#,
+            params! {
+                "serveur_actif_id" => mute.serveur_actif_id,
+                "fin" => mute.fin.format("%Y-%m-%d %H:%M:%S").to_string(),
+                "raison" => &mute.raison,
+            },
+        )
+    }
+
+    /// Delete the mute of an active server.
+    ///
+    /// # Arguments
+    /// * `serveur_actif_id` - The ID of the active server in the `serveurs_actifs` table.
+    pub fn delete_server_mute(&self, serveur_actif_id: u64) -> Result<(), mysql::Error> {
+        let mut conn = self.get_conn()?;
+        conn.exec_drop(
+            "DELETE FROM serveurs_mutes WHERE serveur_actif_id = :id",
+            params! { "id" => serveur_actif_id },
+        )
+    }
+}
@@ -1,10 +1,14 @@
 use colored::Colorize;
 use log::debug;
 use mysql::{params, prelude::Queryable};
-use serde::Deserialize;
-use crate::db::models::{JoueurConnectionLog};
+use std::collections::HashSet;
+use crate::db::models::{ConnectionType, JoueurConnectionLog, JoueurLie, JoueurPokemonPc, StatColumn};
 use crate::helper;
-use log::{warn};
+use crate::helper::player_id_cache::{self, PlayerKey};
+use log::{info, warn};
+
+/// Playername stored when the Mojang API doesn't know an uuid (bedrock or crack player)
+pub const UNKNOWN_PLAYERNAME: &str = "JoueurBedrock";
 
 use super::repository_default::Database;
 
@@ -40,26 +44,96 @@ impl Database {
         Ok(count.unwrap_or(0) > 0)
     }
 
+    /// Date les liaisons apparues depuis le dernier passage : le site ne remplit que `utilisateur_id`,
+    /// `lie_le` est mis à `date` pour les comptes liés sans date, et vidé pour les comptes déliés (une nouvelle
+    /// liaison sera datée de nouveau).
+    ///
+    /// # Returns
+    /// Le nombre de nouvelles liaisons datées.
+    pub fn stamp_new_links(&self, date: chrono::NaiveDateTime) -> Result<u64, mysql::Error> {
+        let mut conn = self.get_conn()?;
+        conn.query_drop("UPDATE joueurs SET lie_le = NULL WHERE utilisateur_id IS NULL AND lie_le IS NOT NULL")?;
+        conn.exec_drop(
+            "UPDATE joueurs SET lie_le = :date WHERE utilisateur_id IS NOT NULL AND lie_le IS NULL",
+            params! { "date" => date.format("%Y-%m-%d %H:%M:%S").to_string() },
+        )?;
+        Ok(conn.affected_rows())
+    }
+
+    /// Récupère les comptes joueurs liés à un utilisateur après `since` (UTC), du plus ancien au plus récent.
+    /// Les liaisons sont datées par [`Database::stamp_new_links`].
+    pub fn get_recently_linked_players(&self, since: chrono::NaiveDateTime) -> Result<Vec<JoueurLie>, mysql::Error> {
+        let mut conn = self.get_conn()?;
+        let rows: Vec<(u64, String, String, String)> = conn.exec(
+            r#"SELECT id, jeu, playername, DATE_FORMAT(lie_le, '%Y-%m-%d %H:%i:%s')
+                FROM joueurs
+                WHERE utilisateur_id IS NOT NULL AND lie_le > :since
+                ORDER BY lie_le, id"#,
+            params! { "since" => since.format("%Y-%m-%d %H:%M:%S").to_string() },
+        )?;
+        Ok(rows
+            .into_iter()
+            .filter_map(|(joueur_id, jeu, playername, lie_le)| {
+                let lie_le = chrono::NaiveDateTime::parse_from_str(&lie_le, "%Y-%m-%d %H:%M:%S").ok()?;
+                Some(JoueurLie { joueur_id, jeu, playername, lie_le })
+            })
+            .collect())
+    }
+
     /// Met à jour la date de dernière connexion d'un joueur via son ID interne.
+    /// La date ne recule jamais : un événement plus ancien (rattrapage de log) ne l'écrase pas.
     ///
     /// # Arguments
     /// * `joueur_id` - L'ID unique du joueur dans la base de données (table `joueurs`).
-    pub fn update_last_connection(&self, joueur_id: u64) -> Result<(), mysql::Error> {
+    /// * `date` - La date de la connexion (UTC).
+    pub fn touch_player_last_connection(&self, joueur_id: u64, date: chrono::NaiveDateTime) -> Result<(), mysql::Error> {
         let mut conn = self.get_conn()?;
+        conn.touch_last_connection(joueur_id, date)
+    }
 
-        // On récupère la date actuelle UTC formatée comme dans tes autres méthodes
-        let now = chrono::Utc::now().naive_utc();
-        let date_str = now.format("%Y-%m-%d %H:%M:%S").to_string();
+    /// Récupère les joueurs ayant masqué leur activité (`visible = FALSE`).
+    ///
+    /// # Returns
+    /// Les couples `(jeu, playername)` des joueurs masqués.
+    pub fn get_hidden_players(&self) -> Result<Vec<(String, String)>, mysql::Error> {
+        let mut conn = self.get_conn()?;
+        conn.exec(
+            "SELECT jeu, playername FROM joueurs WHERE visible = FALSE",
+            (),
+        )
+    }
 
+    /// Met à jour la visibilité d'un joueur : un joueur masqué a toujours ses stats enregistrées,
+    /// mais son activité n'est plus annoncée.
+    ///
+    /// # Arguments
+    /// * `game` - Le jeu du compte (ex: "minecraft").
+    /// * `playername` - Le pseudo du joueur.
+    /// * `visible` - `false` pour masquer le joueur.
+    ///
+    /// # Returns
+    /// - `Ok(true)` si le joueur existe.
+    /// - `Ok(false)` si aucun joueur ne correspond.
+    pub fn set_player_visibility(&self, game: &str, playername: &str, visible: bool) -> Result<bool, mysql::Error> {
+        let mut conn = self.get_conn()?;
         conn.exec_drop(
-            r"UPDATE joueurs SET derniere_co = :date WHERE id = :id",
+            "UPDATE joueurs SET visible = :visible WHERE jeu = :jeu AND playername = :playername",
             params! {
-                "date" => date_str,
-                "id" => joueur_id,
+                "visible" => visible,
+                "jeu" => game,
+                "playername" => playername,
             },
         )?;
+        if conn.affected_rows() > 0 {
+            return Ok(true);
+        }
 
-        Ok(())
+        // Aucune ligne modifiée : le joueur n'existe pas, ou avait déjà cette visibilité
+        let exists: Option<u64> = conn.exec_first(
+            "SELECT id FROM joueurs WHERE jeu = :jeu AND playername = :playername LIMIT 1",
+            params! { "jeu" => game, "playername" => playername },
+        )?;
+        Ok(exists.is_some())
     }
 
     // ===========================
@@ -81,6 +155,7 @@ impl Database {
     ///     serveur_id: 42,
     ///     joueur_id: 123,
     ///     date: chrono::NaiveDateTime::from_timestamp(chrono::Utc::now().timestamp(), 0),
+    ///     r#type: ConnectionType::Join,
     /// };
     /// db.insert_joueur_connection_log(&log)?;
     /// ```
@@ -95,88 +170,210 @@ impl Database {
 
         conn.exec_drop(
             r#"
-            INSERT INTO joueurs_connections_log (serveur_id, joueur_id, date)
-            VALUES (:serveur_id, :joueur_id, :date)
+            INSERT INTO joueurs_connections_log (serveur_id, joueur_id, date, type)
+            VALUES (:serveur_id, :joueur_id, :date, :type)
             "#,
             params! {
                 "serveur_id" => log.serveur_id,
                 "joueur_id" => log.joueur_id,
-                "date" => date_str
+                "date" => date_str,
+                "type" => log.r#type.as_str()
             },
         )?;
 
         Ok(())
     }
 
-    pub fn add_and_get_minecraft_player_id(&self, username: &str) -> Result<u64, Box<dyn std::error::Error>> {
+    /// Closes the open session of a player on a server : records their leave, with the duration since
+    /// their last join not followed by a leave.
+    ///
+    /// # Arguments
+    /// * `serveur_id` - The ID of the server in the `serveurs` table.
+    /// * `joueur_id` - The ID of the player in the `joueurs` table.
+    /// * `leave_date` - When the player left (UTC).
+    ///
+    /// # Returns
+    /// * `Ok(Some(seconds))` - The duration of the session that was closed.
+    /// * `Ok(None)` - No session was open (join missed) : the leave is recorded without duration.
+    /// * `Err(mysql::Error)` if a MySQL error occurs.
+    pub fn close_open_session(
+        &self,
+        serveur_id: u64,
+        joueur_id: u64,
+        leave_date: chrono::NaiveDateTime,
+    ) -> Result<Option<u64>, mysql::Error> {
         let mut conn = self.get_conn()?;
 
-        // Check if player exists
-        if let Some(row) = conn.exec_first::<(u64,), _, _>(
-            "SELECT id FROM joueurs WHERE playername = :playername",
-            params! { "playername" => username }
-        )? {
-            return Ok(row.0);
-        }
+        let join_date: Option<String> = conn.exec_first(
+            r#"SELECT DATE_FORMAT(j.date, '%Y-%m-%d %H:%i:%s')
+                FROM joueurs_connections_log j
+                WHERE j.serveur_id = :serveur_id AND j.joueur_id = :joueur_id AND j.type = 'join'
+                  AND NOT EXISTS (
+                    SELECT 1 FROM joueurs_connections_log l
+                    WHERE l.serveur_id = j.serveur_id AND l.joueur_id = j.joueur_id
+                      AND l.type = 'leave' AND l.date >= j.date
+                  )
+                ORDER BY j.date DESC
+                LIMIT 1"#,
+            params! { "serveur_id" => serveur_id, "joueur_id" => joueur_id },
+        )?;
+        let duration = join_date
+            .and_then(|date| chrono::NaiveDateTime::parse_from_str(&date, "%Y-%m-%d %H:%M:%S").ok())
+            .map(|join_date| (leave_date - join_date).num_seconds().max(0) as u64);
 
-        // Get current datetime
-        let now = chrono::Utc::now().naive_utc();
-        let date_str = now.format("%Y-%m-%d %H:%M:%S").to_string();
+        insert_leave(&mut conn, serveur_id, joueur_id, leave_date, duration)?;
+        Ok(duration)
+    }
+
+    /// Closes every session still open on a server, when it stops or restarts.
+    ///
+    /// # Arguments
+    /// * `serveur_id` - The ID of the server in the `serveurs` table.
+    /// * `leave_date` - When the sessions end (UTC).
+    /// * `duration_known` - `false` when the real end is unknown (the server crashed and restarted) :
+    ///   the sessions are closed without duration, so they don't count in the session time.
+    ///
+    /// # Returns
+    /// The number of sessions closed.
+    pub fn close_open_sessions_of_server(
+        &self,
+        serveur_id: u64,
+        leave_date: chrono::NaiveDateTime,
+        duration_known: bool,
+    ) -> Result<usize, mysql::Error> {
+        let mut conn = self.get_conn()?;
+
+        let open_sessions: Vec<(u64, String)> = conn.exec(
+            r#"SELECT j.joueur_id, DATE_FORMAT(MAX(j.date), '%Y-%m-%d %H:%i:%s')
+                FROM joueurs_connections_log j
+                WHERE j.serveur_id = :serveur_id AND j.type = 'join'
+                  AND NOT EXISTS (
+                    SELECT 1 FROM joueurs_connections_log l
+                    WHERE l.serveur_id = j.serveur_id AND l.joueur_id = j.joueur_id
+                      AND l.type = 'leave' AND l.date >= j.date
+                  )
+                GROUP BY j.joueur_id"#,
+            params! { "serveur_id" => serveur_id },
+        )?;
 
-        // Fetch UUID from Mojang API
-        #[derive(Deserialize)]
-        struct MojangResponse {
-            id: String,
-            name: String,
+        for (joueur_id, join_date) in &open_sessions {
+            let duration = chrono::NaiveDateTime::parse_from_str(join_date, "%Y-%m-%d %H:%M:%S")
+                .ok()
+                .filter(|_| duration_known)
+                .map(|join_date| (leave_date - join_date).num_seconds().max(0) as u64);
+            insert_leave(&mut conn, serveur_id, *joueur_id, leave_date, duration)?;
         }
 
-        let url = format!("https://api.mojang.com/users/profiles/minecraft/{}", username);
-        let resp: MojangResponse = ureq::get(&url)
-            .call()?
-            .into_json()?;
-
-        // Checking player uuid (account_id)
-        let player_account_id = match helper::minecraft_account_formatter::check_and_format_minecraft_uuid(&resp.id) {
-            Ok(formatted_uuid) => formatted_uuid,
-            Err(e) => {
-                warn!(
-                    "Invalid minecraft UUID : {} ; error: {}",
-                    &resp.id.yellow().bold(),
-                    e
-                );
-                return Err(Box::new(e));
-            }
-        };
+        Ok(open_sessions.len())
+    }
 
-        // Insert new player into DB
-        conn.exec_drop(
-            r#"
-        INSERT INTO joueurs (utilisateur_id, jeu, compte_id, playername, premiere_co, derniere_co)
-        VALUES (:utilisateur_id, :jeu, :compte_id, :playername, :premiere_co, :derniere_co)
-        "#,
-            params! {
-            "utilisateur_id" => Option::<u64>::None,
-            "jeu" => "Minecraft",
-            "compte_id" => player_account_id,
-            "playername" => &resp.name,
-            "premiere_co" => &date_str,
-            "derniere_co" => &date_str,
-        },
+    /// Total time played by a player on a server, summed over their closed sessions.
+    ///
+    /// # Arguments
+    /// * `joueur_id` - The ID of the player in the `joueurs` table.
+    /// * `serveur_id` - The ID of the server in the `serveurs` table.
+    ///
+    /// # Returns
+    /// The total duration in seconds, 0 if the player has no closed session on this server.
+    pub fn get_total_session_time(&self, joueur_id: u64, serveur_id: u64) -> Result<u64, mysql::Error> {
+        let mut conn = self.get_conn()?;
+
+        let total: Option<Option<u64>> = conn.exec_first(
+            r#"SELECT CAST(SUM(duree_session) AS UNSIGNED)
+                FROM joueurs_connections_log
+                WHERE joueur_id = :joueur_id AND serveur_id = :serveur_id AND type = 'leave'"#,
+            params! { "joueur_id" => joueur_id, "serveur_id" => serveur_id },
         )?;
+        Ok(total.flatten().unwrap_or(0))
+    }
 
-        // Fetch the id of the newly inserted player
-        let new_id = conn.exec_first::<(u64,), _, _>(
-            "SELECT id FROM joueurs WHERE playername = :playername",
-            params! { "playername" => username }
-        )?.ok_or("Failed to retrieve new player id")?;
+    /// Returns the id of a Minecraft player from their name, adding the player if they aren't in the database.
+    /// Ids are cached in memory (see `helper::player_id_cache`), so a repeated join doesn't query MySQL.
+    pub fn add_and_get_minecraft_player_id(&self, username: &str) -> Result<u64, Box<dyn std::error::Error>> {
+        // Never store or look up a name with colour codes, team prefixes or spaces
+        let username = helper::minecraft_account_formatter::normalize_playername(username)?;
+        player_id_cache::get_or_lookup(PlayerKey::name("minecraft", &username), || {
+            self.lookup_or_add_minecraft_player(&username)
+        })
+    }
 
-        Ok(new_id.0)
+    fn lookup_or_add_minecraft_player(&self, username: &str) -> Result<u64, Box<dyn std::error::Error>> {
+        let mut conn = self.get_conn()?;
+        let now = chrono::Utc::now().naive_utc();
+        lookup_or_add_minecraft_player(&mut conn, username, now, helper::mojang_api::fetch_profile_by_playername)
     }
 
+    /// Récupère les joueurs Minecraft créés avec un profil provisoire.
+    ///
+    /// # Returns
+    /// Les couples `(id, playername)` des joueurs dont le profil est incomplet.
+    pub fn get_incomplete_minecraft_players(&self) -> Result<Vec<(u64, String)>, mysql::Error> {
+        let mut conn = self.get_conn()?;
+        conn.exec(
+            "SELECT id, playername FROM joueurs WHERE jeu = 'Minecraft' AND profil_incomplet = TRUE",
+            (),
+        )
+    }
+
+    /// Complète le profil provisoire d'un joueur Minecraft avec son uuid et son pseudo Mojang.
+    ///
+    /// Si une ligne complète existe déjà pour cet uuid (le joueur a été recréé correctement entre temps),
+    /// les connexions et codes de liaison de la ligne provisoire lui sont rattachés et la ligne provisoire
+    /// est supprimée. Tout est fait dans une transaction.
+    ///
+    /// # Arguments
+    /// * `provisional_id` - L'ID de la ligne provisoire.
+    /// * `player_uuid` - L'uuid Mojang du joueur, avec tirets.
+    /// * `playername` - Le pseudo renvoyé par Mojang.
+    ///
+    /// # Returns
+    /// L'ID du joueur à conserver : `provisional_id`, ou l'ID de la ligne existante en cas de fusion.
+    pub fn complete_minecraft_player(
+        &self,
+        provisional_id: u64,
+        player_uuid: &str,
+        playername: &str,
+    ) -> Result<u64, mysql::Error> {
+        let mut conn = self.get_conn()?;
+        let mut tx = conn.start_transaction(mysql::TxOpts::default())?;
+        let kept_id = complete_profile(&mut tx, provisional_id, player_uuid, playername)?;
+        tx.commit()?;
+        // The provisional name and id must not be served from the cache anymore
+        player_id_cache::invalidate_players(&[provisional_id, kept_id]);
+        Ok(kept_id)
+    }
+
+    /// Returns the id of a player from its account id, adding the player if it isn't in the database.
+    ///
+    /// # Arguments
+    /// * `game` - The game of the account (ex: "minecraft", "palworld").
+    /// * `player_uuid` - The account id of the player (Minecraft uuid, Steam id...).
+    /// * `playername` - The name of the player when it is already known. If `None`, it is fetched
+    ///   from the Mojang API, so it must be given for any game other than Minecraft.
+    ///
+    /// # Errors
+    /// A Minecraft account id that isn't an uuid (ex: the name of a stray file) is rejected,
+    /// rather than stored as a player.
     pub fn add_player_if_not_exist(
         &self,
         game: &str,
         player_uuid: String,
+        playername: Option<&str>,
+    ) -> Result<u64, Box<dyn std::error::Error>> {
+        if game == "minecraft" && !helper::minecraft_account_formatter::is_minecraft_uuid(&player_uuid) {
+            return Err(format!("'{}' is not a Minecraft uuid", player_uuid).into());
+        }
+
+        player_id_cache::get_or_lookup(PlayerKey::uuid(game, &player_uuid), || {
+            self.lookup_or_add_player(game, player_uuid, playername)
+        })
+    }
+
+    fn lookup_or_add_player(
+        &self,
+        game: &str,
+        player_uuid: String,
+        playername: Option<&str>,
     ) -> Result<u64, Box<dyn std::error::Error>> {
         let mut conn = self.get_conn()?;
 
@@ -193,34 +390,11 @@ impl Database {
         let now = chrono::Utc::now().naive_utc();
         let date_str = now.format("%Y-%m-%d %H:%M:%S").to_string();
 
-        // Fetch UUID from Mojang API
-        #[derive(Deserialize)]
-        struct MojangResponse {
-            id: String,
-            name: String,
-        }
-
-        let url = format!("https://api.minetools.eu/uuid/{}", player_uuid);
-        let resp: MojangResponse = match ureq::get(&url).call() {
-            Ok(r) => match r.into_json() {
-                Ok(json) => json,
-                Err(_) => MojangResponse {
-                    id: player_uuid.clone(),
-                    name: "JoueurBedrock".into(),
-                },
-            },
-            Err(ureq::Error::Status(code, _response)) => {
-                // Probably bedrock or crack player
-                if code == 204 || code == 404 {
-                    MojangResponse {
-                        id: player_uuid.clone(),
-                        name: "JoueurBedrock".into(),
-                    }
-                } else {
-                    return Err(Box::new(ureq::Error::Status(code, _response)));
-                }
-            }
-            Err(e) => return Err(Box::new(e)),
+        // Fetch playername from Mojang API if not given. Unknown uuid is probably a bedrock or crack player
+        let playername = match playername {
+            Some(playername) => playername.to_string(),
+            None => helper::mojang_api::fetch_playername_by_uuid(&player_uuid)?
+                .unwrap_or_else(|| UNKNOWN_PLAYERNAME.to_string()),
         };
 
         conn.exec_drop(
@@ -231,8 +405,8 @@ impl Database {
             params! {
             "utilisateur_id" => Option::<u64>::None,
             "jeu" => game,
-            "compte_id" => &resp.id,
-            "playername" => &resp.name,
+            "compte_id" => &player_uuid,
+            "playername" => &playername,
             "premiere_co" => &date_str,
             "derniere_co" => &date_str,
         },
@@ -246,10 +420,100 @@ impl Database {
         },
         )?.ok_or("Failed to retrieve new player id")?;
 
-        debug!("Added new player to database : {} with uuid : {}", &resp.id.to_string().green().bold(), player_uuid.green().bold());
+        debug!("Added new player to database : {} with uuid : {}", playername.green().bold(), player_uuid.green().bold());
         Ok(new_id.0)
     }
 
+    /// Fetch the account id of a player from its playername, for a game.
+    ///
+    /// # Arguments
+    /// * `game` - The game of the account (ex: "palworld").
+    /// * `playername` - The name of the player.
+    pub fn get_compte_id_by_playername(
+        &self,
+        game: &str,
+        playername: &str,
+    ) -> Result<Option<String>, mysql::Error> {
+        let mut conn = self.get_conn()?;
+        conn.exec_first(
+            "SELECT compte_id FROM joueurs WHERE jeu = :jeu AND playername = :playername ORDER BY derniere_co DESC LIMIT 1",
+            params! {
+                "jeu" => game,
+                "playername" => playername,
+            },
+        )
+    }
+
+    /// Returns, among the given account ids, the ones already present in the `joueurs` table for a game.
+    ///
+    /// # Arguments
+    /// * `game` - The game of the accounts (ex: "minecraft").
+    /// * `compte_ids` - The account ids to look for.
+    pub fn get_existing_compte_ids(
+        &self,
+        game: &str,
+        compte_ids: &[String],
+    ) -> Result<HashSet<String>, mysql::Error> {
+        if compte_ids.is_empty() {
+            return Ok(HashSet::new());
+        }
+        let mut conn = self.get_conn()?;
+
+        let placeholders = vec!["?"; compte_ids.len()].join(", ");
+        let query = format!(
+            "SELECT compte_id FROM joueurs WHERE jeu = ? AND compte_id IN ({})",
+            placeholders
+        );
+        let mut values: Vec<mysql::Value> = vec![game.into()];
+        values.extend(compte_ids.iter().map(|id| id.as_str().into()));
+
+        let existing: Vec<String> = conn.exec(query, values)?;
+        Ok(existing.into_iter().collect())
+    }
+
+    /// Inserts several players at once, used when a sync discovers a large amount of unknown accounts.
+    /// Accounts already present are left untouched.
+    ///
+    /// # Arguments
+    /// * `game` - The game of the accounts (ex: "minecraft").
+    /// * `players` - Tuples of `(compte_id, playername)`.
+    ///
+    /// # Returns
+    /// The number of players given to the batch insert.
+    pub fn add_players_batch(
+        &self,
+        game: &str,
+        players: &[(String, String)],
+    ) -> Result<usize, mysql::Error> {
+        if players.is_empty() {
+            return Ok(0);
+        }
+        let mut conn = self.get_conn()?;
+
+        let now = chrono::Utc::now().naive_utc();
+        let date_str = now.format("%Y-%m-%d %H:%M:%S").to_string();
+
+        conn.exec_batch(
+            r#"
+        INSERT IGNORE INTO joueurs (utilisateur_id, jeu, compte_id, playername, premiere_co, derniere_co)
+        VALUES (:utilisateur_id, :jeu, :compte_id, :playername, :premiere_co, :derniere_co)
+        "#,
+            players.iter().map(|(compte_id, playername)| params! {
+                "utilisateur_id" => Option::<u64>::None,
+                "jeu" => game,
+                "compte_id" => compte_id,
+                "playername" => playername,
+                "premiere_co" => &date_str,
+                "derniere_co" => &date_str,
+            }),
+        )?;
+
+        Ok(players.len())
+    }
+
+    /// Writes the stats of a player read from their stats file, creating the stats row if needed.
+    /// The values are absolute : they replace the counters incremented from the logs (see [`Database::increment_stat`]).
+    #[allow(clippy::too_many_arguments)]
     pub fn add_or_update_playerstats(
         &self,
         serveur_id: u64,
@@ -323,6 +587,157 @@ impl Database {
         Ok(())
     }
 
+    /// Returns the stats of a player on a server, as written by `add_or_update_playerstats`.
+    /// Used to tell what an upsert changed.
+    ///
+    /// # Arguments
+    /// * `serveur_id` - The ID of the server in the `serveurs` table.
+    /// * `compte_id` - The account id of the player.
+    ///
+    /// # Returns
+    /// The stats by column name (JSON columns parsed), `None` if the player has no stats on this server yet.
+    pub fn get_playerstats_values(
+        &self,
+        serveur_id: u64,
+        compte_id: &str,
+    ) -> Result<Option<serde_json::Map<String, serde_json::Value>>, mysql::Error> {
+        const INT_COLUMNS: [&str; 10] = [
+            "tmps_jeux", "nb_mort", "nb_kills", "nb_playerkill", "nb_blocs_detr",
+            "nb_blocs_pose", "dist_total", "dist_pieds", "dist_elytres", "dist_vol",
+        ];
+        const JSON_COLUMNS: [&str; 4] = ["mob_killed", "item_crafted", "item_broken", "achievement"];
+
+        let mut conn = self.get_conn()?;
+        let row: Option<mysql::Row> = conn.exec_first(
+            format!(
+                "SELECT {}, {} FROM joueurs_stats WHERE serveur_id = :serveur_id AND compte_id = :compte_id",
+                INT_COLUMNS.join(", "),
+                JSON_COLUMNS.join(", ")
+            ),
+            params! {
+                "serveur_id" => serveur_id,
+                "compte_id" => compte_id,
+            },
+        )?;
+
+        Ok(row.map(|row| {
+            let mut values = serde_json::Map::new();
+            for column in INT_COLUMNS {
+                let value: Option<i64> = row.get_opt(column).and_then(|v| v.ok()).flatten();
+                values.insert(column.to_string(), value.into());
+            }
+            for column in JSON_COLUMNS {
+                let value: Option<String> = row.get_opt(column).and_then(|v| v.ok()).flatten();
+                let value = value
+                    .and_then(|v| serde_json::from_str(&v).ok())
+                    .unwrap_or(serde_json::Value::Null);
+                values.insert(column.to_string(), value);
+            }
+            values
+        }))
+    }
+
+    /// Adds one death to the stats of a player on a server, creating the stats row if needed.
+    ///
+    /// # Arguments
+    /// * `serveur_id` - The ID of the server in the `serveurs` table.
+    /// * `compte_id` - The account id of the player.
+    pub fn increment_player_death(&self, serveur_id: u64, compte_id: &str) -> Result<(), mysql::Error> {
+        self.increment_stat(serveur_id, compte_id, StatColumn::NbMort, 1)
+    }
+
+    /// Adds `delta` to a counter of the stats of a player on a server, in one statement, creating the stats
+    /// row if needed. Used by the triggers so the site is up to date between two syncs of the stats files.
+    ///
+    /// # Priority with the syncs
+    /// The stats files are the reference : `add_or_update_playerstats` writes their absolute values, so the
+    /// next sync replaces what was incremented instead of adding to it, and an event is never counted twice.
+    /// Until the server saves its stats file, a sync may lower the counter back to the saved value; it catches
+    /// up at the following save. Games without stats files (ex: Palworld) are only counted from the logs.
+    ///
+    /// # Arguments
+    /// * `serveur_id` - The ID of the server in the `serveurs` table.
+    /// * `compte_id` - The account id of the player.
+    /// * `column` - The counter to increment.
+    /// * `delta` - The value added (negative to decrement, the counter doesn't go below 0).
+    pub fn increment_stat(&self, serveur_id: u64, compte_id: &str, column: StatColumn, delta: i64) -> Result<(), mysql::Error> {
+        let mut conn = self.get_conn()?;
+
+        // A new row starts with the delta in its counter, the other ones at 0
+        let (nb_mort, nb_playerkill) = match column {
+            StatColumn::NbMort => (delta.max(0), 0),
+            StatColumn::NbPlayerkill => (0, delta.max(0)),
+        };
+        conn.exec_drop(
+            format!(
+                r#"
+                INSERT INTO joueurs_stats (
+                    serveur_id, compte_id, tmps_jeux, nb_mort, nb_kills, nb_playerkill,
+                    nb_blocs_detr, nb_blocs_pose, dist_total, dist_pieds, dist_elytres, dist_vol, dern_enregistrment
+                ) VALUES (
+                    :serveur_id, :compte_id, 0, :nb_mort, 0, :nb_playerkill, 0, 0, 0, 0, 0, 0, NOW()
+                )
+                ON DUPLICATE KEY UPDATE
+                    {column} = GREATEST(CAST({column} AS SIGNED) + :delta, 0),
+                    dern_enregistrment = NOW()
+                "#,
+                column = column.as_str()
+            ),
+            params! {
+                "serveur_id" => serveur_id,
+                "compte_id" => compte_id,
+                "nb_mort" => nb_mort,
+                "nb_playerkill" => nb_playerkill,
+                "delta" => delta,
+            },
+        )
+    }
+
+    /// Writes the stats of a Palworld player read from the save files, creating the stats row if needed.
+    /// The other columns (ex: `nb_mort`, counted from the logs) are left as they are.
+    ///
+    /// # Arguments
+    /// * `serveur_id` - The ID of the server in the `serveurs` table.
+    /// * `compte_id` - The account id of the player.
+    /// * `tmps_jeux` - Play time, in ticks like Minecraft (20 per second).
+    /// * `niveau` - Level of the player.
+    /// * `pals_captures` - Number of pals captured, `None` to keep the stored one.
+    pub fn upsert_palworld_playerstats(
+        &self,
+        serveur_id: u64,
+        compte_id: &str,
+        tmps_jeux: i64,
+        niveau: i64,
+        pals_captures: Option<i64>,
+    ) -> Result<(), mysql::Error> {
+        let mut conn = self.get_conn()?;
+
+        conn.exec_drop(
+            r#"
+            INSERT INTO joueurs_stats (
+                serveur_id, compte_id, tmps_jeux, nb_mort, nb_kills, nb_playerkill,
+                nb_blocs_detr, nb_blocs_pose, dist_total, dist_pieds, dist_elytres, dist_vol,
+                niveau, pals_captures, dern_enregistrment
+            ) VALUES (
+                :serveur_id, :compte_id, :tmps_jeux, 0, 0, 0, 0, 0, 0, 0, 0, 0, :niveau, :pals_captures, NOW()
+            )
+            ON DUPLICATE KEY UPDATE
+                tmps_jeux = VALUES(tmps_jeux),
+                niveau = VALUES(niveau),
+                pals_captures = COALESCE(VALUES(pals_captures), pals_captures),
+                dern_enregistrment = NOW()
+            "#,
+            params! {
+                "serveur_id" => serveur_id,
+                "compte_id" => compte_id,
+                "tmps_jeux" => tmps_jeux,
+                "niveau" => niveau,
+                "pals_captures" => pals_captures,
+            },
+        )
+    }
+
+    #[allow(clippy::type_complexity)]
     pub fn insert_joueur_pokemon(
         &self,
         serveur_id: u64,
@@ -406,4 +821,456 @@ impl Database {
         Ok(())
     }
 
+    /// Replaces the PC boxes of a Cobblemon player : their previous rows are deleted, then every pokemon
+    /// is inserted with a single multi-row INSERT, in one transaction.
+    ///
+    /// # Arguments
+    /// * `serveur_id` - The ID of the server in the `serveurs` table.
+    /// * `joueur_uuid` - The Minecraft uuid of the player.
+    /// * `pokemons` - The pokemon of the boxes, empty slots left out.
+    pub fn insert_joueur_pokemon_pc(
+        &self,
+        serveur_id: u64,
+        joueur_uuid: &str,
+        pokemons: &[JoueurPokemonPc],
+    ) -> Result<(), mysql::Error> {
+        let mut conn = self.get_conn()?;
+        let mut tx = conn.start_transaction(mysql::TxOpts::default())?;
+
+        tx.exec_drop(
+            "DELETE FROM joueurs_pokemon_pc WHERE serveur_id = :serveur_id AND joueur_uuid = :joueur_uuid",
+            params! { "serveur_id" => serveur_id, "joueur_uuid" => joueur_uuid },
+        )?;
+
+        if !pokemons.is_empty() {
+            let rows = vec!["(?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"; pokemons.len()].join(", ");
+            let mut values: Vec<mysql::Value> = Vec::with_capacity(pokemons.len() * 12);
+            for pokemon in pokemons {
+                values.extend([
+                    serveur_id.into(),
+                    joueur_uuid.into(),
+                    pokemon.boite.into(),
+                    pokemon.slot.into(),
+                    pokemon.pkmn.as_str().into(),
+                    pokemon.form.as_deref().into(),
+                    pokemon.gender.as_deref().into(),
+                    pokemon.nickname.as_deref().into(),
+                    pokemon.level.into(),
+                    pokemon.shiny.into(),
+                    pokemon.do_uuid.as_deref().into(),
+                    pokemon.pokemon_uuid.as_deref().into(),
+                ]);
+            }
+            tx.exec_drop(
+                format!(
+                    r#"INSERT INTO joueurs_pokemon_pc (
+                        serveur_id, joueur_uuid, boite, slot, pkmn, pkmn_form, pkmn_gender,
+                        pkmn_nickname, pkmn_level, pkmn_shiny, pkmn_do_uuid, pkmn_uuid
+                    ) VALUES {}"#,
+                    rows
+                ),
+                mysql::Params::Positional(values),
+            )?;
+        }
+
+        tx.commit()
+    }
+
+}
+
+/// Inserts a leave in `joueurs_connections_log`, with the duration of the session it closes if known.
+fn insert_leave(
+    conn: &mut mysql::PooledConn,
+    serveur_id: u64,
+    joueur_id: u64,
+    leave_date: chrono::NaiveDateTime,
+    duration: Option<u64>,
+) -> Result<(), mysql::Error> {
+    conn.exec_drop(
+        r#"INSERT INTO joueurs_connections_log (serveur_id, joueur_id, date, type, duree_session)
+            VALUES (:serveur_id, :joueur_id, :date, :type, :duree_session)"#,
+        params! {
+            "serveur_id" => serveur_id,
+            "joueur_id" => joueur_id,
+            "date" => leave_date.format("%Y-%m-%d %H:%M:%S").to_string(),
+            "type" => ConnectionType::Leave.as_str(),
+            "duree_session" => duration
+        },
+    )
+}
+
+/// The players table the Minecraft players are looked up and added in.
+trait MinecraftPlayers {
+    fn player_named(&mut self, playername: &str) -> Result<Option<u64>, mysql::Error>;
+    fn player_with_uuid(&mut self, player_uuid: &str) -> Result<Option<u64>, mysql::Error>;
+    fn rename_player(&mut self, id: u64, playername: &str) -> Result<(), mysql::Error>;
+    /// Adds a player connected at `date`, flagged `profil_incomplet` if `provisional`, and returns their ID
+    fn insert_player(&mut self, compte_id: &str, playername: &str, provisional: bool, date: chrono::NaiveDateTime)
+        -> Result<u64, mysql::Error>;
+    /// Moves `derniere_co` to `date`, never backwards
+    fn touch_last_connection(&mut self, id: u64, date: chrono::NaiveDateTime) -> Result<(), mysql::Error>;
+}
+
+impl MinecraftPlayers for mysql::PooledConn {
+    fn player_named(&mut self, playername: &str) -> Result<Option<u64>, mysql::Error> {
+        self.exec_first("SELECT id FROM joueurs WHERE playername = :playername", params! { "playername" => playername })
+    }
+
+    fn player_with_uuid(&mut self, player_uuid: &str) -> Result<Option<u64>, mysql::Error> {
+        self.exec_first(
+            "SELECT id FROM joueurs WHERE jeu = 'Minecraft' AND compte_id = :compte_id",
+            params! { "compte_id" => player_uuid },
+        )
+    }
+
+    fn rename_player(&mut self, id: u64, playername: &str) -> Result<(), mysql::Error> {
+        self.exec_drop(
+            "UPDATE joueurs SET playername = :playername WHERE id = :id",
+            params! { "playername" => playername, "id" => id },
+        )
+    }
+
+    fn insert_player(&mut self, compte_id: &str, playername: &str, provisional: bool, date: chrono::NaiveDateTime)
+        -> Result<u64, mysql::Error> {
+        let date_str = date.format("%Y-%m-%d %H:%M:%S").to_string();
+        self.exec_drop(
+            r#"
+        INSERT INTO joueurs (utilisateur_id, jeu, compte_id, playername, premiere_co, derniere_co, profil_incomplet)
+        VALUES (:utilisateur_id, :jeu, :compte_id, :playername, :premiere_co, :derniere_co, :profil_incomplet)
+        "#,
+            params! {
+            "utilisateur_id" => Option::<u64>::None,
+            "jeu" => "Minecraft",
+            "compte_id" => compte_id,
+            "playername" => playername,
+            "premiere_co" => &date_str,
+            "derniere_co" => &date_str,
+            "profil_incomplet" => provisional,
+        },
+        )?;
+        Ok(self.last_insert_id())
+    }
+
+    fn touch_last_connection(&mut self, id: u64, date: chrono::NaiveDateTime) -> Result<(), mysql::Error> {
+        self.exec_drop(
+            r"UPDATE joueurs SET derniere_co = GREATEST(COALESCE(derniere_co, :date), :date) WHERE id = :id",
+            params! {
+                "date" => date.format("%Y-%m-%d %H:%M:%S").to_string(),
+                "id" => id,
+            },
+        )
+    }
+}
+
+/// Returns the id of a Minecraft player from their name, adding them as first connected `now` if they aren't in
+/// `players`.
+///
+/// Mojang (`fetch_profile`) is only asked for an unknown name : a known uuid under a new name is a renamed
+/// account, whose row is renamed. If Mojang is rate limiting or unreachable, the player is created with a
+/// provisional profile so the event isn't lost : the repair job fills the real uuid later.
+fn lookup_or_add_minecraft_player(
+    players: &mut impl MinecraftPlayers,
+    username: &str,
+    now: chrono::NaiveDateTime,
+    fetch_profile: impl FnOnce(&str) -> Result<helper::mojang_api::MojangProfile, Box<dyn std::error::Error>>,
+) -> Result<u64, Box<dyn std::error::Error>> {
+    // Check if player exists
+    if let Some(id) = players.player_named(username)? {
+        return Ok(id);
+    }
+
+    let resp = match fetch_profile(username) {
+        Ok(resp) => resp,
+        Err(e) => {
+            warn!("Mojang lookup of {} failed ({}), creating a provisional profile", username.yellow().bold(), e);
+            return Ok(players.insert_player(username, username, true, now)?);
+        }
+    };
+
+    // Checking player uuid (account_id)
+    let player_account_id = match helper::minecraft_account_formatter::check_and_format_minecraft_uuid(&resp.id) {
+        Ok(formatted_uuid) => formatted_uuid,
+        Err(e) => {
+            warn!(
+                "Invalid minecraft UUID : {} ; error: {}",
+                &resp.id.yellow().bold(),
+                e
+            );
+            return Err(Box::new(e));
+        }
+    };
+
+    // A known uuid under a new name : the player renamed their Mojang account, the row is renamed
+    if let Some(id) = players.player_with_uuid(&player_account_id)? {
+        players.rename_player(id, &resp.name)?;
+        player_id_cache::invalidate_players(&[id]);
+        info!("Player {} renamed to {}", player_account_id, resp.name.green().bold());
+        return Ok(id);
+    }
+
+    Ok(players.insert_player(&player_account_id, &resp.name, false, now)?)
+}
+
+/// The players table a provisional profile is completed in.
+trait ProfileTarget {
+    /// Player other than `except_id` already recorded with this Minecraft uuid
+    fn player_with_uuid(&mut self, player_uuid: &str, except_id: u64) -> Result<Option<u64>, mysql::Error>;
+    /// Moves the connections and the linking codes of `from` to `into`, which keeps the earliest first connection
+    /// and the latest last connection of both
+    fn merge_player(&mut self, from: u64, into: u64) -> Result<(), mysql::Error>;
+    fn delete_player(&mut self, id: u64) -> Result<(), mysql::Error>;
+    /// Sets the uuid and the name of a player, and clears `profil_incomplet`
+    fn complete_player(&mut self, id: u64, player_uuid: &str, playername: &str) -> Result<(), mysql::Error>;
+}
+
+impl ProfileTarget for mysql::Transaction<'_> {
+    fn player_with_uuid(&mut self, player_uuid: &str, except_id: u64) -> Result<Option<u64>, mysql::Error> {
+        self.exec_first(
+            "SELECT id FROM joueurs WHERE jeu = 'Minecraft' AND compte_id = :compte_id AND id <> :id",
+            params! { "compte_id" => player_uuid, "id" => except_id },
+        )
+    }
+
+    fn merge_player(&mut self, from: u64, into: u64) -> Result<(), mysql::Error> {
+        self.exec_drop(
+            "UPDATE joueurs_connections_log SET joueur_id = :existing WHERE joueur_id = :provisional",
+            params! { "existing" => into, "provisional" => from },
+        )?;
+        self.exec_drop(
+            "UPDATE codes_liaison SET joueur_id = :existing WHERE joueur_id = :provisional",
+            params! { "existing" => into, "provisional" => from },
+        )?;
+        self.exec_drop(
+            r#"UPDATE joueurs j
+               INNER JOIN joueurs p ON p.id = :provisional
+               SET j.derniere_co = GREATEST(j.derniere_co, p.derniere_co),
+                   j.premiere_co = LEAST(j.premiere_co, p.premiere_co)
+               WHERE j.id = :existing"#,
+            params! { "existing" => into, "provisional" => from },
+        )
+    }
+
+    fn delete_player(&mut self, id: u64) -> Result<(), mysql::Error> {
+        self.exec_drop("DELETE FROM joueurs WHERE id = :provisional", params! { "provisional" => id })
+    }
+
+    fn complete_player(&mut self, id: u64, player_uuid: &str, playername: &str) -> Result<(), mysql::Error> {
+        self.exec_drop(
+            r#"UPDATE joueurs
+               SET compte_id = :compte_id, playername = :playername, profil_incomplet = FALSE
+               WHERE id = :id"#,
+            params! { "compte_id" => player_uuid, "playername" => playername, "id" => id },
+        )
+    }
+}
+
+/// Completes a provisional profile, or merges it into the complete row already recorded for its uuid.
+///
+/// # Returns
+/// The ID of the player kept : `provisional_id`, or the ID of the existing row when merged.
+fn complete_profile(
+    target: &mut impl ProfileTarget,
+    provisional_id: u64,
+    player_uuid: &str,
+    playername: &str,
+) -> Result<u64, mysql::Error> {
+    match target.player_with_uuid(player_uuid, provisional_id)? {
+        Some(existing_id) => {
+            target.merge_player(provisional_id, existing_id)?;
+            target.delete_player(provisional_id)?;
+            Ok(existing_id)
+        }
+        None => {
+            target.complete_player(provisional_id, player_uuid, playername)?;
+            Ok(provisional_id)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    /// A player row : uuid, name, first and last connection, incomplete profile
+    #[derive(Debug, Clone, PartialEq)]
+    struct Row {
+        uuid: String,
+        name: String,
+        first: u32,
+        last: u32,
+        incomplete: bool,
+    }
+
+    #[derive(Default)]
+    struct FakePlayers {
+        rows: HashMap<u64, Row>,
+        /// joueur_id of each connection log
+        connections: Vec<u64>,
+        /// joueur_id of each linking code
+        codes: Vec<u64>,
+    }
+
+    impl FakePlayers {
+        fn with(mut self, id: u64, uuid: &str, first: u32, last: u32, incomplete: bool) -> Self {
+            self.rows.insert(id, Row { uuid: uuid.to_string(), name: uuid.to_string(), first, last, incomplete });
+            self
+        }
+    }
+
+    impl ProfileTarget for FakePlayers {
+        fn player_with_uuid(&mut self, player_uuid: &str, except_id: u64) -> Result<Option<u64>, mysql::Error> {
+            Ok(self.rows.iter().find(|(id, row)| **id != except_id && row.uuid == player_uuid).map(|(id, _)| *id))
+        }
+
+        fn merge_player(&mut self, from: u64, into: u64) -> Result<(), mysql::Error> {
+            for joueur_id in self.connections.iter_mut().chain(self.codes.iter_mut()).filter(|id| **id == from) {
+                *joueur_id = into;
+            }
+            let provisional = self.rows[&from].clone();
+            let existing = self.rows.get_mut(&into).unwrap();
+            existing.first = existing.first.min(provisional.first);
+            existing.last = existing.last.max(provisional.last);
+            Ok(())
+        }
+
+        fn delete_player(&mut self, id: u64) -> Result<(), mysql::Error> {
+            self.rows.remove(&id);
+            Ok(())
+        }
+
+        fn complete_player(&mut self, id: u64, player_uuid: &str, playername: &str) -> Result<(), mysql::Error> {
+            let row = self.rows.get_mut(&id).unwrap();
+            row.uuid = player_uuid.to_string();
+            row.name = playername.to_string();
+            row.incomplete = false;
+            Ok(())
+        }
+    }
+
+    const UUID: &str = "069a79f4-44e9-4726-a5be-fca90e38aaf5";
+
+    #[test]
+    fn a_provisional_profile_without_collision_is_completed() {
+        let mut players = FakePlayers::default().with(7, "Loutre", 10, 20, true);
+        assert_eq!(complete_profile(&mut players, 7, UUID, "Loutre").unwrap(), 7);
+        assert_eq!(
+            players.rows[&7],
+            Row { uuid: UUID.to_string(), name: "Loutre".to_string(), first: 10, last: 20, incomplete: false }
+        );
+    }
+
+    #[test]
+    fn a_provisional_profile_colliding_with_a_complete_row_is_merged_into_it() {
+        let mut players = FakePlayers::default().with(3, UUID, 5, 15, false).with(7, "Loutre", 2, 30, true);
+        players.connections = vec![3, 7, 7, 4];
+        players.codes = vec![7];
+
+        assert_eq!(complete_profile(&mut players, 7, UUID, "Loutre").unwrap(), 3);
+        assert!(!players.rows.contains_key(&7), "the provisional row is deleted");
+        assert_eq!(players.connections, vec![3, 3, 3, 4]);
+        assert_eq!(players.codes, vec![3]);
+        let kept = &players.rows[&3];
+        assert_eq!((kept.first, kept.last), (2, 30));
+        assert_eq!(kept.uuid, UUID);
+        assert!(!kept.incomplete);
+    }
+
+    #[test]
+    fn completing_a_profile_twice_does_not_merge_it_with_itself() {
+        let mut players = FakePlayers::default().with(7, UUID, 10, 20, false);
+        assert_eq!(complete_profile(&mut players, 7, UUID, "Loutre").unwrap(), 7);
+        assert_eq!(players.rows.len(), 1);
+    }
+
+    /// Players table of the join tests : (compte_id, playername, derniere_co, profil_incomplet) by id
+    #[derive(Default)]
+    struct FakeRoster {
+        rows: Vec<(String, String, chrono::NaiveDateTime, bool)>,
+    }
+
+    impl MinecraftPlayers for FakeRoster {
+        fn player_named(&mut self, playername: &str) -> Result<Option<u64>, mysql::Error> {
+            Ok(self.rows.iter().position(|row| row.1.eq_ignore_ascii_case(playername)).map(|i| i as u64 + 1))
+        }
+
+        fn player_with_uuid(&mut self, player_uuid: &str) -> Result<Option<u64>, mysql::Error> {
+            Ok(self.rows.iter().position(|row| row.0 == player_uuid).map(|i| i as u64 + 1))
+        }
+
+        fn rename_player(&mut self, id: u64, playername: &str) -> Result<(), mysql::Error> {
+            self.rows[id as usize - 1].1 = playername.to_string();
+            Ok(())
+        }
+
+        fn insert_player(&mut self, compte_id: &str, playername: &str, provisional: bool, date: chrono::NaiveDateTime)
+            -> Result<u64, mysql::Error> {
+            self.rows.push((compte_id.to_string(), playername.to_string(), date, provisional));
+            Ok(self.rows.len() as u64)
+        }
+
+        fn touch_last_connection(&mut self, id: u64, date: chrono::NaiveDateTime) -> Result<(), mysql::Error> {
+            let last = &mut self.rows[id as usize - 1].2;
+            *last = (*last).max(date);
+            Ok(())
+        }
+    }
+
+    fn mojang(name: &'static str) -> impl FnOnce(&str) -> Result<helper::mojang_api::MojangProfile, Box<dyn std::error::Error>> {
+        move |_| Ok(helper::mojang_api::MojangProfile { id: UUID.replace('-', ""), name: name.to_string() })
+    }
+
+    fn no_mojang(_: &str) -> Result<helper::mojang_api::MojangProfile, Box<dyn std::error::Error>> {
+        panic!("Mojang must not be asked for a known player")
+    }
+
+    /// A join as handled by the actions : the player is looked up or added, then their last connection touched
+    fn join(roster: &mut FakeRoster, playername: &str, date: chrono::NaiveDateTime) -> u64 {
+        let id = lookup_or_add_minecraft_player(roster, playername, date, mojang("Loutre")).unwrap();
+        roster.touch_last_connection(id, date).unwrap();
+        id
+    }
+
+    fn at(hour: u32) -> chrono::NaiveDateTime {
+        chrono::NaiveDate::from_ymd_opt(2026, 10, 16).unwrap().and_hms_opt(hour, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn two_joins_update_the_last_connection_without_a_duplicate_player() {
+        let mut roster = FakeRoster::default();
+        let first = join(&mut roster, "Loutre", at(10));
+        let second = lookup_or_add_minecraft_player(&mut roster, "Loutre", at(12), no_mojang).unwrap();
+        roster.touch_last_connection(second, at(12)).unwrap();
+
+        assert_eq!(first, second);
+        assert_eq!(roster.rows.len(), 1);
+        assert_eq!(roster.rows[0], (UUID.to_string(), "Loutre".to_string(), at(12), false));
+
+        // A join read late (log catch-up) doesn't move the last connection backwards
+        roster.touch_last_connection(first, at(11)).unwrap();
+        assert_eq!(roster.rows[0].2, at(12));
+    }
+
+    #[test]
+    fn a_renamed_account_keeps_its_row() {
+        let mut roster = FakeRoster::default();
+        let id = join(&mut roster, "Loutre", at(10));
+        let renamed = lookup_or_add_minecraft_player(&mut roster, "LoutreDeMer", at(11), mojang("LoutreDeMer")).unwrap();
+        assert_eq!(renamed, id);
+        assert_eq!(roster.rows.len(), 1);
+        assert_eq!(roster.rows[0].1, "LoutreDeMer");
+    }
+
+    #[test]
+    fn a_failed_mojang_lookup_creates_a_provisional_player() {
+        let mut roster = FakeRoster::default();
+        let id = lookup_or_add_minecraft_player(&mut roster, "Loutre", at(10), |_| Err("429 Too Many Requests".into())).unwrap();
+        assert_eq!(roster.rows[id as usize - 1].0, "Loutre", "the name is the provisional compte_id");
+        assert!(roster.rows[id as usize - 1].3);
+        assert_eq!(lookup_or_add_minecraft_player(&mut roster, "Loutre", at(12), no_mojang).unwrap(), id);
+
+        let invalid = |_: &str| Ok(helper::mojang_api::MojangProfile { id: "nope".to_string(), name: "Steve".to_string() });
+        assert!(lookup_or_add_minecraft_player(&mut roster, "Steve", at(10), invalid).is_err());
+        assert_eq!(roster.rows.len(), 1);
+    }
 }
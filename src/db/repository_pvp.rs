@@ -0,0 +1,216 @@
+use chrono::NaiveDateTime;
+use mysql::{params, prelude::Queryable};
+
+use super::repository_default::Database;
+
+impl Database {
+    // ===========================
+    // joueurs_pvp
+    // ===========================
+
+    /// Fetch the id of a player from its playername, without adding them if they aren't in the database.
+    ///
+    /// # Arguments
+    /// * `game` - The game of the account (ex: "minecraft").
+    /// * `playername` - The name of the player.
+    pub fn get_player_id_by_playername(&self, game: &str, playername: &str) -> Result<Option<u64>, mysql::Error> {
+        self.get_conn()?.player_id(game, playername)
+    }
+
+    /// Insert a player killed by another player, if both are known players of the game.
+    /// The killer of a death message may be a mob, which isn't in `joueurs`.
+    ///
+    /// # Arguments
+    /// * `serveur_id` - The ID of the server in the `serveurs` table.
+    /// * `game` - The game of the accounts (ex: "minecraft").
+    /// * `victim` - The name of the killed player.
+    /// * `killer` - The name of the killer.
+    /// * `weapon` - The weapon named in the death message, if any.
+    /// * `date` - Date of the death.
+    ///
+    /// # Returns
+    /// `false` if the victim or the killer isn't a known player, and nothing was inserted.
+    pub fn record_pvp_kill(
+        &self,
+        serveur_id: u64,
+        game: &str,
+        victim: &str,
+        killer: &str,
+        weapon: Option<&str>,
+        date: NaiveDateTime,
+    ) -> Result<bool, mysql::Error> {
+        let mut conn = self.get_conn()?;
+        record_kill(&mut conn, serveur_id, game, victim, killer, weapon, date)
+    }
+
+    /// Fetch the players with the most kills since a date, all servers together.
+    ///
+    /// # Returns
+    /// Tuples of `(playername, kills)`, most kills first.
+    pub fn get_pvp_top_killers(&self, since: NaiveDateTime, limit: u32) -> Result<Vec<(String, u64)>, mysql::Error> {
+        let mut conn = self.get_conn()?;
+        conn.exec(
+            r#"SELECT j.playername, COUNT(*) AS kills
+               FROM joueurs_pvp p
+               INNER JOIN joueurs j ON j.id = p.tueur_id
+               WHERE p.date >= :since
+               GROUP BY p.tueur_id, j.playername
+               ORDER BY kills DESC, j.playername
+               LIMIT :limit"#,
+            params! {
+                "since" => since.format("%Y-%m-%d %H:%M:%S").to_string(),
+                "limit" => limit,
+            },
+        )
+    }
+
+    /// Fetch the (killer, victim) pairs with the most kills since a date, all servers together.
+    ///
+    /// # Returns
+    /// Tuples of `(killer playername, victim playername, kills)`, most kills first.
+    pub fn get_pvp_nemesis_pairs(
+        &self,
+        since: NaiveDateTime,
+        limit: u32,
+    ) -> Result<Vec<(String, String, u64)>, mysql::Error> {
+        let mut conn = self.get_conn()?;
+        conn.exec(
+            r#"SELECT t.playername, v.playername, COUNT(*) AS kills
+               FROM joueurs_pvp p
+               INNER JOIN joueurs t ON t.id = p.tueur_id
+               INNER JOIN joueurs v ON v.id = p.victime_id
+               WHERE p.date >= :since
+               GROUP BY p.tueur_id, p.victime_id, t.playername, v.playername
+               ORDER BY kills DESC, t.playername, v.playername
+               LIMIT :limit"#,
+            params! {
+                "since" => since.format("%Y-%m-%d %H:%M:%S").to_string(),
+                "limit" => limit,
+            },
+        )
+    }
+}
+
+/// The tables a PvP kill is recorded in.
+trait PvpKills {
+    /// Id of the most recently connected player of `game` named `playername`
+    fn player_id(&mut self, game: &str, playername: &str) -> Result<Option<u64>, mysql::Error>;
+    fn insert_kill(&mut self, serveur_id: u64, victime_id: u64, tueur_id: u64, arme: Option<&str>, date: NaiveDateTime)
+        -> Result<(), mysql::Error>;
+}
+
+impl PvpKills for mysql::PooledConn {
+    fn player_id(&mut self, game: &str, playername: &str) -> Result<Option<u64>, mysql::Error> {
+        self.exec_first(
+            "SELECT id FROM joueurs WHERE jeu = :jeu AND playername = :playername ORDER BY derniere_co DESC LIMIT 1",
+            params! {
+                "jeu" => game,
+                "playername" => playername,
+            },
+        )
+    }
+
+    fn insert_kill(&mut self, serveur_id: u64, victime_id: u64, tueur_id: u64, arme: Option<&str>, date: NaiveDateTime)
+        -> Result<(), mysql::Error> {
+        self.exec_drop(
+            r#"INSERT INTO joueurs_pvp (serveur_id, victime_id, tueur_id, arme, date)
+               VALUES (:serveur_id, :victime_id, :tueur_id, :arme, :date)"#,
+            params! {
+                "serveur_id" => serveur_id,
+                "victime_id" => victime_id,
+                "tueur_id" => tueur_id,
+                "arme" => arme,
+                "date" => date.format("%Y-%m-%d %H:%M:%S").to_string(),
+            },
+        )
+    }
+}
+
+/// See [`Database::record_pvp_kill`].
+fn record_kill(
+    target: &mut impl PvpKills,
+    serveur_id: u64,
+    game: &str,
+    victim: &str,
+    killer: &str,
+    weapon: Option<&str>,
+    date: NaiveDateTime,
+) -> Result<bool, mysql::Error> {
+    let Some(victime_id) = target.player_id(game, victim)? else {
+        return Ok(false);
+    };
+    let Some(tueur_id) = target.player_id(game, killer)? else {
+        return Ok(false);
+    };
+    target.insert_kill(serveur_id, victime_id, tueur_id, weapon, date)?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    /// `joueurs` as (id, jeu, playername), and the rows inserted in `joueurs_pvp`
+    #[derive(Default)]
+    struct FakeKills {
+        players: Vec<(u64, &'static str, &'static str)>,
+        kills: Vec<(u64, u64, u64, Option<String>, NaiveDateTime)>,
+        failing: bool,
+    }
+
+    impl PvpKills for FakeKills {
+        fn player_id(&mut self, game: &str, playername: &str) -> Result<Option<u64>, mysql::Error> {
+            if self.failing {
+                return Err(mysql::Error::IoError(std::io::Error::other("lost")));
+            }
+            // The fake keeps the players most recently connected first
+            Ok(self.players.iter().find(|(_, g, name)| *g == game && *name == playername).map(|(id, _, _)| *id))
+        }
+
+        fn insert_kill(&mut self, serveur_id: u64, victime_id: u64, tueur_id: u64, arme: Option<&str>, date: NaiveDateTime)
+            -> Result<(), mysql::Error> {
+            self.kills.push((serveur_id, victime_id, tueur_id, arme.map(str::to_string), date));
+            Ok(())
+        }
+    }
+
+    fn roster() -> FakeKills {
+        FakeKills {
+            players: vec![(1, "minecraft", "Loutre"), (2, "minecraft", "Castor"), (3, "palworld", "Zombie")],
+            ..FakeKills::default()
+        }
+    }
+
+    fn date() -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 10, 16).unwrap().and_hms_opt(21, 30, 0).unwrap()
+    }
+
+    #[test]
+    fn a_kill_between_known_players_is_inserted() {
+        let mut table = roster();
+        assert!(record_kill(&mut table, 4, "minecraft", "Castor", "Loutre", Some("Sword of Doom"), date()).unwrap());
+        assert!(record_kill(&mut table, 4, "minecraft", "Loutre", "Castor", None, date()).unwrap());
+        assert_eq!(table.kills, vec![
+            (4, 2, 1, Some("Sword of Doom".to_string()), date()),
+            (4, 1, 2, None, date()),
+        ]);
+    }
+
+    #[test]
+    fn unknown_players_are_not_recorded() {
+        let mut table = roster();
+        // A mob, a player of another game, an unknown victim
+        assert!(!record_kill(&mut table, 4, "minecraft", "Castor", "Skeleton", None, date()).unwrap());
+        assert!(!record_kill(&mut table, 4, "minecraft", "Castor", "Zombie", None, date()).unwrap());
+        assert!(!record_kill(&mut table, 4, "minecraft", "Inconnu", "Loutre", None, date()).unwrap());
+        assert!(table.kills.is_empty());
+    }
+
+    #[test]
+    fn lookup_errors_are_returned() {
+        let mut table = FakeKills { failing: true, ..roster() };
+        assert!(record_kill(&mut table, 4, "minecraft", "Castor", "Loutre", None, date()).is_err());
+        assert!(table.kills.is_empty());
+    }
+}
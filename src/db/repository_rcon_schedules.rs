@@ -0,0 +1,27 @@
+use mysql::prelude::Queryable;
+use crate::db::models::RconSchedule;
+
+use super::repository_default::Database;
+
+impl Database {
+    // ===========================
+    // rcon_schedules
+    // ===========================
+
+    /// Fetch the activated RCON schedules.
+    pub fn get_active_rcon_schedules(&self) -> Result<Vec<RconSchedule>, mysql::Error> {
+        let mut conn = self.get_conn()?;
+        conn.exec_map(
+            r#"SELECT id, serveur_actif_id, cron_expression, commande
+               FROM rcon_schedules
+               WHERE actif = 1"#,
+            (),
+            |(id, serveur_actif_id, cron_expression, commande)| RconSchedule {
+                id,
+                serveur_actif_id,
+                cron_expression,
+                commande,
+            },
+        )
+    }
+}
@@ -0,0 +1,53 @@
+use mysql::{params, prelude::Queryable};
+
+use crate::db::models::{ServeurPing, ServeurPingCible};
+
+use super::repository_default::Database;
+
+impl Database {
+    // ===========================
+    // serveurs_ping_log
+    // ===========================
+
+    /// Fetch the active Minecraft servers having a Server List Ping address.
+    pub fn get_minecraft_servers_to_ping(&self) -> Result<Vec<ServeurPingCible>, mysql::Error> {
+        let mut conn = self.get_conn()?;
+        conn.exec_map(
+            r#"SELECT sa.id AS active_id, s.nom, sa.ping_host, sa.ping_port
+               FROM serveurs s
+               INNER JOIN serveurs_actifs sa ON sa.serveurs_id = s.id
+               WHERE s.actif = true AND LOWER(s.jeu) = 'minecraft'
+                 AND sa.ping_host IS NOT NULL AND sa.ping_host <> '' AND sa.ping_port IS NOT NULL
+               ORDER BY sa.id"#,
+            (),
+            |mut row: mysql::Row| ServeurPingCible {
+                active_id: row.take("active_id").unwrap(),
+                nom: row.take("nom").unwrap(),
+                host: row.take("ping_host").unwrap(),
+                port: row.take("ping_port").unwrap(),
+            },
+        )
+    }
+
+    /// Records the result of a Server List Ping.
+    ///
+    /// # Arguments
+    /// * `active_id` - The ID of the active server in the `serveurs_actifs` table.
+    /// * `ping` - The answer of the server, or `en_ligne = false` if it didn't answer.
+    pub fn insert_server_ping(&self, active_id: u64, ping: &ServeurPing) -> Result<(), mysql::Error> {
+        let mut conn = self.get_conn()?;
+        conn.exec_drop(
+            r#"INSERT INTO serveurs_ping_log (serveur_actif_id, date, en_ligne, latence_ms, version, nb_joueurs, nb_joueurs_max, motd)
+               VALUES (:serveur_actif_id, UTC_TIMESTAMP(), :en_ligne, :latence_ms, :version, :nb_joueurs, :nb_joueurs_max, :motd)"#,
+            params! {
+                "serveur_actif_id" => active_id,
+                "en_ligne" => ping.en_ligne,
+                "latence_ms" => ping.latence_ms,
+                "version" => ping.version.as_deref(),
+                "nb_joueurs" => ping.nb_joueurs,
+                "nb_joueurs_max" => ping.nb_joueurs_max,
+                "motd" => ping.motd.as_deref(),
+            },
+        )
+    }
+}
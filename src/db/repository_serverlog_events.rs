@@ -0,0 +1,55 @@
+use mysql::{params, prelude::Queryable};
+
+use super::repository_default::Database;
+
+impl Database {
+    // ===========================
+    // serverlog_events
+    // ===========================
+
+    /// Records an action dispatched by a trigger, for the audit (`EVENT_AUDIT_ENABLED`).
+    ///
+    /// # Arguments
+    /// * `serverlog_id` - The ID of the active server in the `serveurs_actifs` table.
+    /// * `trigger_name` - The name of the trigger that matched.
+    /// * `fonction` - The action function called.
+    /// * `ligne` - The line given to the action, already cut.
+    /// * `resultat` - `ok` or `erreur`.
+    /// * `duree_ms` - The time the action took.
+    pub fn insert_serverlog_event(
+        &self,
+        serverlog_id: u32,
+        trigger_name: &str,
+        fonction: &str,
+        ligne: &str,
+        resultat: &str,
+        duree_ms: u64,
+    ) -> Result<(), mysql::Error> {
+        let mut conn = self.get_conn()?;
+        conn.exec_drop(
+            r#"INSERT INTO serverlog_events (date, serverlog_id, trigger_name, fonction, ligne, resultat, duree_ms)
+               VALUES (UTC_TIMESTAMP(), :serverlog_id, :trigger_name, :fonction, :ligne, :resultat, :duree_ms)"#,
+            params! {
+                "serverlog_id" => serverlog_id,
+                "trigger_name" => trigger_name,
+                "fonction" => fonction,
+                "ligne" => ligne,
+                "resultat" => resultat,
+                "duree_ms" => duree_ms,
+            },
+        )
+    }
+
+    /// Deletes the audited events older than `days` days.
+    ///
+    /// # Returns
+    /// The number of events deleted.
+    pub fn delete_serverlog_events_older_than(&self, days: u32) -> Result<u64, mysql::Error> {
+        let mut conn = self.get_conn()?;
+        conn.exec_drop(
+            "DELETE FROM serverlog_events WHERE date < UTC_TIMESTAMP() - INTERVAL :days DAY",
+            params! { "days" => days },
+        )?;
+        Ok(conn.affected_rows())
+    }
+}
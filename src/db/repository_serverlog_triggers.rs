@@ -0,0 +1,29 @@
+use mysql::prelude::Queryable;
+use crate::db::models::ServerlogTrigger;
+
+use super::repository_default::Database;
+
+impl Database {
+    // ===========================
+    // serverlog_triggers
+    // ===========================
+
+    /// Fetch the activated triggers, in the order they were created.
+    pub fn get_active_triggers(&self) -> Result<Vec<ServerlogTrigger>, mysql::Error> {
+        let mut conn = self.get_conn()?;
+        conn.exec_map(
+            r#"SELECT id, nom, pattern, fonction, serveur_actif_id
+               FROM serverlog_triggers
+               WHERE actif = 1
+               ORDER BY id"#,
+            (),
+            |(id, nom, pattern, fonction, serveur_actif_id)| ServerlogTrigger {
+                id,
+                nom,
+                pattern,
+                fonction,
+                serveur_actif_id,
+            },
+        )
+    }
+}
@@ -2,6 +2,7 @@ use mysql::{params, prelude::Queryable};
 use crate::db::models::{Serveur};
 use crate::db::models::{ServeurActifGlobal};
 use crate::db::models::{RconParams};
+use crate::db::models::{ServeurInactif};
 
 use super::repository_default::Database;
 
@@ -18,7 +19,7 @@ impl Database {
         let mut conn = self.get_conn()?;
         let result: Vec<Serveur> = conn.exec_map(
             r#"SELECT id, nom, jeu, version, modpack, modpack_url, nom_monde, embed_color,
-              contenaire, description, actif, global, type, image
+              contenaire, description, actif, global, type, image, discord_thread_id
        FROM serveurs
        WHERE jeu = :jeu"#,
             params! { "jeu" => game },
@@ -38,6 +39,7 @@ impl Database {
                     global: row.take("global").unwrap(),
                     r#type: row.take("type"),
                     image: row.take("image"),
+                    discord_thread_id: row.take("discord_thread_id"),
                 }
             },
         )?;
@@ -78,6 +80,55 @@ impl Database {
         Ok(result)
     }
 
+    /// Fetch every active server, global or not, ordered by active server id.
+    pub fn get_all_active_servers(&self) -> Result<Vec<ServeurActifGlobal>, mysql::Error> {
+        let mut conn = self.get_conn()?;
+        let result: Vec<ServeurActifGlobal> = conn.exec_map(
+            r#"SELECT sa.id as active_id, s.nom, s.jeu, s.version, s.embed_color, s.contenaire, s.type
+                FROM serveurs s
+                INNER JOIN serveurs_actifs sa ON sa.serveurs_id = s.id
+                WHERE s.actif = true
+                ORDER BY sa.id"#,
+            (),
+            |mut row: mysql::Row| ServeurActifGlobal {
+                active_id: row.take("active_id").unwrap(),
+                nom: row.take("nom").unwrap(),
+                jeu: row.take("jeu").unwrap(),
+                version: row.take("version").unwrap(),
+                embed_color: row.take("embed_color"),
+                contenaire: row.take("contenaire"),
+                r#type: row.take("type"),
+            },
+        )?;
+        Ok(result)
+    }
+
+    /// Fetch every active server of a game, global or not.
+    ///
+    /// # Arguments
+    ///
+    /// * `game` - The game of the servers (ex: "minecraft"), compared case-insensitively.
+    pub fn get_all_active_servers_by_game(&self, game: &str) -> Result<Vec<ServeurActifGlobal>, mysql::Error> {
+        let mut conn = self.get_conn()?;
+        let result: Vec<ServeurActifGlobal> = conn.exec_map(
+            r#"SELECT sa.id as active_id, s.nom, s.jeu, s.version, s.embed_color, s.contenaire, s.type
+                FROM serveurs s
+                INNER JOIN serveurs_actifs sa ON sa.serveurs_id = s.id
+                WHERE s.actif = true AND LOWER(s.jeu) = LOWER(:jeu)"#,
+            params! { "jeu" => game },
+            |mut row: mysql::Row| ServeurActifGlobal {
+                active_id: row.take("active_id").unwrap(),
+                nom: row.take("nom").unwrap(),
+                jeu: row.take("jeu").unwrap(),
+                version: row.take("version").unwrap(),
+                embed_color: row.take("embed_color"),
+                contenaire: row.take("contenaire"),
+                r#type: row.take("type"),
+            },
+        )?;
+        Ok(result)
+    }
+
     /// Fetch a `Serveur` using an active server (serveurs_actifs) id.
     /// Fetch a `Serveur` using an active server ID from the `serveurs_actifs` table.
     ///
@@ -111,7 +162,7 @@ impl Database {
 
         let result: Vec<Serveur> = conn.exec_map(
             r#"SELECT id, nom, jeu, version, modpack, modpack_url, nom_monde, embed_color,
-                contenaire, description, actif, global, type, image
+                contenaire, description, actif, global, type, image, discord_thread_id
             FROM serveurs
             WHERE id = :id"#,
             params! { "id" => serveurs_id },
@@ -131,6 +182,7 @@ impl Database {
                     global: row.take("global").unwrap(),
                     r#type: row.take("type"),
                     image: row.take("image"),
+                    discord_thread_id: row.take("discord_thread_id"),
                 }
             },
         )?;
@@ -138,6 +190,126 @@ impl Database {
         Ok(result.into_iter().next())
     }
 
+    /// Fetch the active servers without any connection in `joueurs_connections_log` for `days` days.
+    ///
+    /// A server nobody ever joined is idle once it has been started for `days` days.
+    /// Servers never started and without any connection are left out.
+    ///
+    /// # Arguments
+    ///
+    /// * `days` - Number of days without any connection.
+    pub fn get_idle_active_servers(&self, days: u64) -> Result<Vec<ServeurInactif>, mysql::Error> {
+        let mut conn = self.get_conn()?;
+        conn.exec_map(
+            r#"SELECT sa.id AS active_id, s.id AS serveur_id, s.nom, s.contenaire, s.nom_monde,
+                    DATE_FORMAT(COALESCE(MAX(l.date), sa.demarre_le), '%Y-%m-%d %H:%i:%s') AS derniere_activite
+                FROM serveurs s
+                INNER JOIN serveurs_actifs sa ON sa.serveurs_id = s.id
+                LEFT JOIN joueurs_connections_log l ON l.serveur_id = s.id
+                WHERE s.actif = true
+                GROUP BY sa.id, s.id, s.nom, s.contenaire, s.nom_monde, sa.demarre_le
+                HAVING COALESCE(MAX(l.date), sa.demarre_le) < NOW() - INTERVAL :days DAY"#,
+            params! { "days" => days },
+            |mut row: mysql::Row| ServeurInactif {
+                active_id: row.take("active_id").unwrap(),
+                serveur_id: row.take("serveur_id").unwrap(),
+                nom: row.take("nom").unwrap(),
+                contenaire: row.take("contenaire").unwrap_or(None),
+                nom_monde: row.take("nom_monde").unwrap_or(None),
+                derniere_activite: row.take("derniere_activite").unwrap_or_default(),
+            },
+        )
+    }
+
+    /// Marks a server as inactive (`serveurs.actif = false`).
+    ///
+    /// # Arguments
+    ///
+    /// * `serveur_id` - The ID of the server in the `serveurs` table.
+    pub fn set_server_inactive(&self, serveur_id: u64) -> Result<(), mysql::Error> {
+        let mut conn = self.get_conn()?;
+
+        conn.exec_drop(
+            "UPDATE serveurs SET actif = false WHERE id = :id",
+            params! { "id" => serveur_id },
+        )
+    }
+
+    /// Fetch the active server ID (`serveurs_actifs.id`) of a server from its name.
+    ///
+    /// The comparison is case-insensitive, so a log folder named `purpur-survie` matches
+    /// a server named `Purpur-Survie`.
+    ///
+    /// # Arguments
+    ///
+    /// * `nom` - The name of the server, as stored in the `serveurs` table.
+    ///
+    /// # Returns
+    ///
+    /// `Result<Option<u64>, mysql::Error>` - Returns the active server ID if found, otherwise `None`.
+    pub fn get_active_server_id_by_name(
+        &self,
+        nom: &str,
+    ) -> Result<Option<u64>, mysql::Error> {
+        let mut conn = self.get_conn()?;
+
+        conn.exec_first(
+            r#"SELECT sa.id
+                FROM serveurs_actifs sa
+                INNER JOIN serveurs s ON sa.serveurs_id = s.id
+                WHERE LOWER(s.nom) = LOWER(:nom)
+                LIMIT 1"#,
+            params! { "nom" => nom },
+        )
+    }
+
+    /// Records that an active server just started : sets `demarre_le` to now and clears `arrete_le`.
+    ///
+    /// # Arguments
+    ///
+    /// * `active_id` - The ID of the active server in the `serveurs_actifs` table.
+    pub fn update_active_server_started(&self, active_id: u64) -> Result<(), mysql::Error> {
+        let mut conn = self.get_conn()?;
+
+        conn.exec_drop(
+            "UPDATE serveurs_actifs SET demarre_le = NOW(), arrete_le = NULL WHERE id = :id",
+            params! { "id" => active_id },
+        )
+    }
+
+    /// Records that an active server just stopped : sets `arrete_le` to now.
+    ///
+    /// # Arguments
+    ///
+    /// * `active_id` - The ID of the active server in the `serveurs_actifs` table.
+    pub fn update_active_server_stopped(&self, active_id: u64) -> Result<(), mysql::Error> {
+        let mut conn = self.get_conn()?;
+
+        conn.exec_drop(
+            "UPDATE serveurs_actifs SET arrete_le = NOW() WHERE id = :id",
+            params! { "id" => active_id },
+        )
+    }
+
+    /// Records the players currently online on a server in `serveurs_actifs_etat`, read by the website
+    /// instead of querying each server.
+    ///
+    /// # Arguments
+    ///
+    /// * `serveur_id` - The ID of the server in the `serveurs` table.
+    /// * `count` - The number of players online.
+    /// * `noms_json` - The names of the players online, as a JSON array.
+    pub fn update_online_players(&self, serveur_id: u64, count: u32, noms_json: &str) -> Result<(), mysql::Error> {
+        let mut conn = self.get_conn()?;
+
+        conn.exec_drop(
+            r#"INSERT INTO serveurs_actifs_etat (serveur_id, nb_joueurs, joueurs, maj_le)
+               VALUES (:serveur_id, :nb_joueurs, :joueurs, UTC_TIMESTAMP())
+               ON DUPLICATE KEY UPDATE nb_joueurs = VALUES(nb_joueurs), joueurs = VALUES(joueurs), maj_le = VALUES(maj_le)"#,
+            params! { "serveur_id" => serveur_id, "nb_joueurs" => count, "joueurs" => noms_json },
+        )
+    }
+
     /// Fetches RCON parameters for an active server by its ID.
     ///
     /// # Arguments
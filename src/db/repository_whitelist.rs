@@ -0,0 +1,41 @@
+use mysql::{params, prelude::Queryable};
+
+use super::repository_default::Database;
+
+impl Database {
+    // ===========================
+    // whitelist_refus
+    // ===========================
+
+    /// Counts a connection refused by the whitelist of a server, and returns the number of refusals of this
+    /// player on this server so far.
+    ///
+    /// # Arguments
+    /// * `serveur_id` - The ID of the server in the `serveurs` table.
+    /// * `playername` - The name given by the player.
+    /// * `ip` - The IP of the player, if the log line gives it (the last one is kept).
+    pub fn increment_whitelist_refusal(&self, serveur_id: u64, playername: &str, ip: Option<&str>) -> Result<u64, mysql::Error> {
+        let mut conn = self.get_conn()?;
+        conn.exec_drop(
+            r#"INSERT INTO whitelist_refus (serveur_id, playername, ip, tentatives, derniere_tentative)
+               VALUES (:serveur_id, :playername, :ip, 1, UTC_TIMESTAMP())
+               ON DUPLICATE KEY UPDATE
+                   tentatives = tentatives + 1,
+                   ip = COALESCE(VALUES(ip), ip),
+                   derniere_tentative = VALUES(derniere_tentative)"#,
+            params! {
+                "serveur_id" => serveur_id,
+                "playername" => playername,
+                "ip" => ip,
+            },
+        )?;
+        let tentatives: Option<u64> = conn.exec_first(
+            "SELECT tentatives FROM whitelist_refus WHERE serveur_id = :serveur_id AND playername = :playername",
+            params! {
+                "serveur_id" => serveur_id,
+                "playername" => playername,
+            },
+        )?;
+        Ok(tentatives.unwrap_or(1))
+    }
+}
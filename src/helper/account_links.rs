@@ -0,0 +1,73 @@
+use colored::Colorize;
+use log::{error, info, warn};
+use chrono::{NaiveDateTime, Utc};
+use std::sync::{LazyLock, Mutex};
+
+use crate::db::models::JoueurLie;
+use crate::db::repository_default::Database;
+use crate::helper::rcon_helper::RconHelper;
+use crate::helper::webhook_discord::{self, DiscordEmbed};
+use crate::serverlog::online_tracker;
+
+/// Date of the previous pass (UTC), `None` before the first one
+static LAST_PASS: LazyLock<Mutex<Option<NaiveDateTime>>> = LazyLock::new(|| Mutex::new(None));
+
+/// Returns the players linked since the previous call. The new links are dated first (see
+/// [`Database::stamp_new_links`]). The first call only dates the current links : the links made while
+/// Otternel was down aren't announced.
+pub fn recently_linked_players(db: &Database) -> Result<Vec<JoueurLie>, mysql::Error> {
+    let now = Utc::now().naive_utc();
+    let stamped = db.stamp_new_links(now)?;
+
+    let mut last_pass = LAST_PASS.lock().unwrap_or_else(|e| e.into_inner());
+    let new_links = match *last_pass {
+        Some(since) => db.get_recently_linked_players(since)?,
+        None => {
+            info!("{} new linked player accounts dated", stamped.to_string().green().bold());
+            Vec::new()
+        }
+    };
+    *last_pass = Some(now);
+    Ok(new_links)
+}
+
+/// Announces the new links : an embed on the webhook of the game of the player, and a confirmation
+/// in game on the servers where the player is online (Minecraft only).
+pub async fn announce_new_links(db: &Database) -> Result<(), String> {
+    let lookup_db = db.clone();
+    let new_links = tokio::task::spawn_blocking(move || recently_linked_players(&lookup_db))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())?;
+
+    let rcon = RconHelper { db: db.clone() };
+    for player in new_links {
+        info!("Player {} linked their account", player.playername.green().bold());
+
+        let identity = webhook_discord::get_webhook_identity_by_server_id(player.jeu.clone());
+        if let Err(e) = DiscordEmbed::new(&identity)
+            .title("Compte lié")
+            .description(&format!("{} a lié son compte Discord", player.playername))
+            .color("126020")
+            .timestamp_now()
+            .send()
+        {
+            error!("{e}");
+        }
+
+        if !player.jeu.eq_ignore_ascii_case("minecraft") {
+            continue;
+        }
+        let component = serde_json::json!([
+            {"text": "[Antre des Loutres]", "color": "gold"},
+            {"text": " Ton compte est maintenant lié à ton compte Discord !"},
+        ]);
+        let command = format!("tellraw {} {}", player.playername, component);
+        for serverlog_id in online_tracker::servers_of(&player.playername) {
+            if let Err(e) = rcon.execute_command(serverlog_id as u64, &command).await {
+                warn!("Could not confirm the link to {} on server {}: {}", player.playername, serverlog_id, e);
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,280 @@
+use arc_swap::ArcSwap;
+use colored::Colorize;
+use log::{debug, info};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, LazyLock};
+
+use crate::db::models::ServeurActifGlobal;
+use crate::db::repository_default::Database;
+
+/// Active servers as of the last refresh. Readers take the `Arc`, so a refresh never waits for them.
+static SERVERS: LazyLock<ArcSwap<Vec<ServeurActifGlobal>>> = LazyLock::new(|| ArcSwap::from_pointee(Vec::new()));
+
+/// Incremented each time the list changes, so the consumers know their derived state is stale.
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// What changed between two lists of active servers, by active server id.
+#[derive(Debug, Default, PartialEq)]
+pub struct ActiveServersDiff {
+    pub added: Vec<ServeurActifGlobal>,
+    pub removed: Vec<ServeurActifGlobal>,
+    /// New version of the servers still active whose row changed (ex: renamed, new container)
+    pub changed: Vec<ServeurActifGlobal>,
+}
+
+impl ActiveServersDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Returns the active servers as of the last refresh.
+pub fn snapshot() -> Arc<Vec<ServeurActifGlobal>> {
+    SERVERS.load_full()
+}
+
+/// Tells whether a server of the list matches `is_server`. An empty list (not loaded yet) matches every server,
+/// so its consumers keep working until the first refresh.
+pub fn includes(servers: &[ServeurActifGlobal], is_server: impl Fn(&ServeurActifGlobal) -> bool) -> bool {
+    servers.is_empty() || servers.iter().any(is_server)
+}
+
+/// Returns the generation of the list, incremented at each change.
+pub fn generation() -> u64 {
+    GENERATION.load(Ordering::Relaxed)
+}
+
+/// Compares two lists of active servers by active server id.
+pub fn diff(old: &[ServeurActifGlobal], new: &[ServeurActifGlobal]) -> ActiveServersDiff {
+    let find = |list: &[ServeurActifGlobal], id: u64| list.iter().find(|s| s.active_id == id).cloned();
+
+    let mut result = ActiveServersDiff::default();
+    for server in new {
+        match find(old, server.active_id) {
+            None => result.added.push(server.clone()),
+            Some(previous) if previous != *server => result.changed.push(server.clone()),
+            Some(_) => {}
+        }
+    }
+    result.removed = old
+        .iter()
+        .filter(|s| find(new, s.active_id).is_none())
+        .cloned()
+        .collect();
+    result
+}
+
+/// Reloads the active servers from the database and replaces the shared list if it changed.
+/// Additions, removals and changes are logged.
+///
+/// # Returns
+/// What changed since the previous refresh (everything is "added" on the first one).
+pub fn refresh(db: &Database) -> Result<ActiveServersDiff, mysql::Error> {
+    let servers = db.get_all_active_servers()?;
+    Ok(replace(servers))
+}
+
+/// Replaces the shared list if it changed, logging what changed.
+fn replace(servers: Vec<ServeurActifGlobal>) -> ActiveServersDiff {
+    let changes = diff(&snapshot(), &servers);
+    if changes.is_empty() {
+        debug!("Active server list unchanged ({} servers)", servers.len());
+        return changes;
+    }
+
+    for server in &changes.added {
+        info!("Active server added : {} ({}, id {})", server.nom.green().bold(), server.jeu, server.active_id);
+    }
+    for server in &changes.removed {
+        info!("Active server removed : {} ({}, id {})", server.nom.yellow().bold(), server.jeu, server.active_id);
+    }
+    for server in &changes.changed {
+        info!("Active server updated : {} ({}, id {})", server.nom.green(), server.jeu, server.active_id);
+    }
+
+    SERVERS.store(Arc::new(servers));
+    GENERATION.fetch_add(1, Ordering::Relaxed);
+    changes
+}
+
+/// A task run for one active server, stopped when the server leaves the list.
+pub trait ServerTask {
+    fn stop(self);
+}
+
+impl ServerTask for tokio::task::JoinHandle<()> {
+    fn stop(self) {
+        self.abort();
+    }
+}
+
+/// The tasks run for each active server, following the changes of the list.
+pub struct ServerTasks<T: ServerTask> {
+    running: HashMap<u64, T>,
+}
+
+impl<T: ServerTask> Default for ServerTasks<T> {
+    fn default() -> Self {
+        Self { running: HashMap::new() }
+    }
+}
+
+impl<T: ServerTask> ServerTasks<T> {
+    /// Applies the changes of the list : the task of a removed server is stopped, the task of a changed server
+    /// is restarted with its new row, and a task is started for each added server.
+    /// `start` returns `None` for the servers not concerned (ex: another game).
+    pub fn apply(&mut self, changes: &ActiveServersDiff, mut start: impl FnMut(&ServeurActifGlobal) -> Option<T>) {
+        for server in changes.removed.iter().chain(&changes.changed) {
+            if let Some(task) = self.running.remove(&server.active_id) {
+                task.stop();
+            }
+        }
+        for server in changes.changed.iter().chain(&changes.added) {
+            if let Some(task) = start(server) {
+                self.running.insert(server.active_id, task);
+            }
+        }
+    }
+
+    /// Returns the active server ids having a running task, sorted.
+    pub fn running_ids(&self) -> Vec<u64> {
+        let mut ids: Vec<u64> = self.running.keys().copied().collect();
+        ids.sort_unstable();
+        ids
+    }
+
+    /// Stops every task, on shutdown.
+    pub fn stop_all(&mut self) {
+        for (_, task) in self.running.drain() {
+            task.stop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    fn server(active_id: u64, nom: &str, jeu: &str) -> ServeurActifGlobal {
+        ServeurActifGlobal {
+            active_id,
+            nom: nom.to_string(),
+            jeu: jeu.to_string(),
+            version: "1.21".to_string(),
+            embed_color: None,
+            contenaire: None,
+            r#type: None,
+        }
+    }
+
+    type Events = Arc<Mutex<Vec<String>>>;
+
+    /// Fake task handle recording when it is stopped
+    struct FakeTask {
+        name: String,
+        events: Events,
+    }
+
+    impl ServerTask for FakeTask {
+        fn stop(self) {
+            self.events.lock().unwrap().push(format!("stop {}", self.name));
+        }
+    }
+
+    /// Starts a fake task for the Minecraft servers only
+    fn apply(tasks: &mut ServerTasks<FakeTask>, changes: &ActiveServersDiff, events: &Events) {
+        tasks.apply(changes, |server| {
+            if server.jeu != "minecraft" {
+                return None;
+            }
+            events.lock().unwrap().push(format!("start {}", server.nom));
+            Some(FakeTask { name: server.nom.clone(), events: events.clone() })
+        });
+    }
+
+    #[test]
+    fn diff_finds_added_removed_and_changed_servers() {
+        let old = [server(1, "survie", "minecraft"), server(2, "crea", "minecraft"), server(3, "pal", "palworld")];
+        let new = [server(1, "survie", "minecraft"), server(2, "creatif", "minecraft"), server(4, "modde", "minecraft")];
+
+        let changes = diff(&old, &new);
+        assert_eq!(changes.added, [server(4, "modde", "minecraft")]);
+        assert_eq!(changes.removed, [server(3, "pal", "palworld")]);
+        assert_eq!(changes.changed, [server(2, "creatif", "minecraft")]);
+        assert!(diff(&new, &new).is_empty());
+    }
+
+    #[test]
+    fn an_empty_list_includes_every_server() {
+        let list = [server(1, "survie", "minecraft"), server(2, "pal", "palworld")];
+        assert!(includes(&list, |s| s.active_id == 2));
+        assert!(!includes(&list, |s| s.active_id == 3));
+        assert!(includes(&[], |s| s.active_id == 3), "the list isn't loaded yet");
+    }
+
+    #[test]
+    fn first_list_starts_a_task_per_concerned_server() {
+        let events = Events::default();
+        let mut tasks = ServerTasks::default();
+        let list = [server(1, "survie", "minecraft"), server(2, "pal", "palworld"), server(3, "crea", "minecraft")];
+
+        apply(&mut tasks, &diff(&[], &list), &events);
+
+        assert_eq!(tasks.running_ids(), [1, 3]);
+        assert_eq!(*events.lock().unwrap(), ["start survie", "start crea"]);
+    }
+
+    #[test]
+    fn changes_stop_restart_and_start_only_the_servers_concerned() {
+        let events = Events::default();
+        let mut tasks = ServerTasks::default();
+        let before = [server(1, "survie", "minecraft"), server(2, "crea", "minecraft"), server(3, "skyblock", "minecraft")];
+        apply(&mut tasks, &diff(&[], &before), &events);
+        events.lock().unwrap().clear();
+
+        let after = [server(1, "survie", "minecraft"), server(2, "creatif", "minecraft"), server(4, "modde", "minecraft")];
+        apply(&mut tasks, &diff(&before, &after), &events);
+
+        assert_eq!(tasks.running_ids(), [1, 2, 4]);
+        assert_eq!(
+            *events.lock().unwrap(),
+            ["stop skyblock", "stop crea", "start creatif", "start modde"],
+            "the unchanged server keeps its task"
+        );
+
+        // An unchanged list touches no task
+        events.lock().unwrap().clear();
+        apply(&mut tasks, &diff(&after, &after), &events);
+        assert!(events.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn stop_all_stops_every_task() {
+        let events = Events::default();
+        let mut tasks = ServerTasks::default();
+        apply(&mut tasks, &diff(&[], &[server(1, "survie", "minecraft"), server(2, "crea", "minecraft")]), &events);
+        events.lock().unwrap().clear();
+
+        tasks.stop_all();
+
+        assert!(tasks.running_ids().is_empty());
+        let mut stopped = events.lock().unwrap().clone();
+        stopped.sort();
+        assert_eq!(stopped, ["stop crea", "stop survie"]);
+    }
+
+    #[test]
+    fn replaced_list_is_seen_by_new_readers_only() {
+        let held = snapshot();
+        let before = generation();
+        let list = vec![server(900_001, "replace-test", "minecraft")];
+
+        let changes = replace(list.clone());
+        assert!(changes.added.contains(&list[0]));
+        assert!(generation() > before);
+        assert!(snapshot().contains(&list[0]));
+        assert!(!held.contains(&list[0]), "a reader keeps the list it took");
+    }
+}
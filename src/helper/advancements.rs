@@ -0,0 +1,56 @@
+use colored::Colorize;
+use log::{info, warn};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+use crate::config::Config;
+
+/// Mapping compiled into the binary : vanilla advancement id -> French title and description
+const DEFAULT_ADVANCEMENTS: &str = include_str!("advancements.json");
+
+/// An advancement of the mapping.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Advancement {
+    pub title: String,
+    pub description: Option<String>,
+    /// URL of an icon shown in the embed
+    pub icon: Option<String>,
+}
+
+/// The mapping of `ADVANCEMENTS_MAP_FILE`, or the built-in one if it isn't set or can't be read
+static ADVANCEMENTS: LazyLock<HashMap<String, Advancement>> = LazyLock::new(load);
+
+fn load() -> HashMap<String, Advancement> {
+    if let Some(path) = Config::current().advancements_map_file.as_deref().filter(|path| !path.trim().is_empty()) {
+        match std::fs::read_to_string(path).map_err(|e| e.to_string()).and_then(|content| parse(&content)) {
+            Ok(advancements) => {
+                info!("{} advancements loaded from {}", advancements.len().to_string().green().bold(), path);
+                return advancements;
+            }
+            Err(e) => warn!("Could not load {}, the built-in advancements are used: {}", path, e),
+        }
+    }
+    parse(DEFAULT_ADVANCEMENTS).unwrap_or_else(|e| {
+        warn!("Invalid built-in advancements: {}", e);
+        HashMap::new()
+    })
+}
+
+/// Parses a mapping file, the ids being normalized as by [`lookup`].
+fn parse(content: &str) -> Result<HashMap<String, Advancement>, String> {
+    let advancements: HashMap<String, Advancement> = serde_json::from_str(content).map_err(|e| e.to_string())?;
+    Ok(advancements.into_iter().map(|(id, advancement)| (normalize_id(&id), advancement)).collect())
+}
+
+/// Lowercase id, with the `minecraft:` namespace when it has none ("story/mine_diamond" -> "minecraft:story/mine_diamond").
+fn normalize_id(id: &str) -> String {
+    let id = id.trim().trim_start_matches('[').trim_end_matches(']').to_lowercase();
+    if id.contains(':') { id } else { format!("minecraft:{}", id) }
+}
+
+/// Returns the advancement of an id written in a log (`minecraft:story/mine_diamond`), `None` if it isn't in the
+/// mapping (the title of a vanilla server, a custom datapack) : the original text is then kept.
+pub fn lookup(id: &str) -> Option<&'static Advancement> {
+    ADVANCEMENTS.get(&normalize_id(id))
+}
@@ -1,7 +1,7 @@
 use log::{error, warn, info, debug};
+use crate::config::Config;
 use crate::db::repository_default::Database;
 use crate::helper::rcon_helper::RconHelper;
-use crate::{helper};
 use rand::{thread_rng, Rng};
 
 // Charset without ambiguous characters (no I, O, 1, 0)
@@ -15,7 +15,7 @@ const CHUNK_SIZE: usize = 3;
 /// ## Returns
 /// A `String` representing the generated linking code in the format "XXX-XXX-XXX".
 ///
-pub fn create_linking_code() -> String {
+pub fn generate_linking_code() -> String {
     let mut rng = thread_rng();
     let mut result = String::with_capacity(CODE_LENGTH + 2); // 9 chars + 2 tirets
 
@@ -47,55 +47,163 @@ pub fn create_linking_code() -> String {
 /// 
 pub fn handle_unlinked_player_join(db: &Database, player_id: u64, playername: &str, serverlog_id: u32) -> Result<(), Box<dyn std::error::Error>> {
     // Fist we check if the linking code feature is enabled
-    let linking_code_enabled = std::env::var("LINKING_CODE_ENABLED").unwrap_or("false".to_string());
-    if linking_code_enabled.to_lowercase() != "true" {
+    let linking_code_enabled = crate::config::Config::shared().is_ok_and(|cfg| cfg.linking_code_enabled);
+    if !linking_code_enabled {
         warn!("LINKING_CODE_ENABLED is false or malformed : skipping code generation for player '{}'.", playername);
         return Ok(());
     }
 
-    // Checks if the generation of a code is necessary
-    let is_linked = db.is_account_linked_to_user(player_id)?;
-    if is_linked {
-        info!("Player '{}' is linked, no code generation needed.", playername);
-        return Ok(());
-    }
-
-    let is_code_active = db.is_linking_code_active_for_player_id(player_id)?;
-    if is_code_active {
-        info!("Player '{}' already has a active code, no new one generated.", playername);
+    // Expiration time of the code (`LINKING_CODE_EXPIRATION_MIN`, default 10 minutes)
+    let code_duration_minutes = Config::current().linking_code_expiration_min;
+    let Some(code) = code_to_send(db, player_id, playername, code_duration_minutes, generate_linking_code)? else {
         return Ok(());
-    }
-
-    // Get expiration time from env, default to 10 minutes if not set or malformed
-    let code_duration_minutes: u32 = std::env::var("LINKING_CODE_EXPIRATION_MIN") 
-        .unwrap_or("10".to_string())
-        .parse()
-        .unwrap_or(10);
-
-    // Player is not linked and has no active code, proceed to generate one
-    let new_code = helper::code_generator::create_linking_code();
-    info!("Player '{}' is not linked. Generating code: {}", playername, new_code);
-
-    db.save_linking_code(player_id, &new_code, code_duration_minutes)?;
-    debug!("Successfully saved linking code for player '{}'.", playername);
+    };
 
     // TODO : DEPENDING ON THE GAME OF THE PLAYER ID, CHANGE THE LOGIC
     // For now, we assume it's always Minecraft and we send the code via RCON
     let rcon_helper = RconHelper::new()?;
-    let command_to_run = format!(
-        r#"/tellraw {player} ["", {{"text":"[Antre des Loutres]","color":"gold"}}, {{"text":" Voici un code pour lié ton compte Minecraft à ton compte Discord : "}}, {{"text":"{code}","color":"gold"}}, {{"text":". Tu peux l'utiliser sur la page de ton profil "}}, {{"text":"(https://antredesloutres.fr)","italic":true,"underlined":true,"color":"dark_aqua","clickEvent":{{"action":"open_url","value":"https://antredesloutres.fr/joueurs/minecraft/{player_lc}/"}}}}, {{"text":"."}}]"#,
-        player = playername,
-        code = new_code,
-        player_lc = playername.to_lowercase()
-    ); 
+    let command_to_run = linking_code_command(playername, &code);
 
     // Execute the RCON command asynchronously with Tokio
     tokio::spawn(async move {
         debug!("Sending RCON command to server ID {}", serverlog_id);
         if let Err(e) = rcon_helper.execute_command(serverlog_id as u64, &command_to_run).await {
-            error!("Failed to execute RCON command to inform player linking code: {}", e);
+            error!("Failed to send the linking code to the player in game (sent again on their next join): {}", e);
         }
     });
     
     Ok(())
-}
\ No newline at end of file
+}
+
+/// The linking codes of the players.
+trait LinkingCodes {
+    fn is_linked(&self, player_id: u64) -> Result<bool, mysql::Error>;
+    fn active_code(&self, player_id: u64) -> Result<Option<String>, mysql::Error>;
+    fn save_code(&self, player_id: u64, code: &str, duration_minutes: u32) -> Result<(), mysql::Error>;
+}
+
+impl LinkingCodes for Database {
+    fn is_linked(&self, player_id: u64) -> Result<bool, mysql::Error> {
+        self.is_account_linked_to_user(player_id)
+    }
+
+    fn active_code(&self, player_id: u64) -> Result<Option<String>, mysql::Error> {
+        self.get_active_linking_code(player_id)
+    }
+
+    fn save_code(&self, player_id: u64, code: &str, duration_minutes: u32) -> Result<(), mysql::Error> {
+        self.save_linking_code(player_id, code, duration_minutes)
+    }
+}
+
+/// Returns the code to send to a player joining : none if they are linked, their active code if they have one
+/// (the previous send may have failed), never a second code. Otherwise a code from `generate`, saved for `duration_minutes`.
+fn code_to_send(
+    codes: &impl LinkingCodes,
+    player_id: u64,
+    playername: &str,
+    duration_minutes: u32,
+    generate: impl FnOnce() -> String,
+) -> Result<Option<String>, mysql::Error> {
+    if codes.is_linked(player_id)? {
+        info!("Player '{}' is linked, no code generation needed.", playername);
+        return Ok(None);
+    }
+
+    if let Some(code) = codes.active_code(player_id)? {
+        info!("Player '{}' already has an active code, sending it again instead of a new one.", playername);
+        return Ok(Some(code));
+    }
+
+    // Player is not linked and has no active code, proceed to generate one
+    let new_code = generate();
+    info!("Player '{}' is not linked. Generating code: {}", playername, new_code);
+    codes.save_code(player_id, &new_code, duration_minutes)?;
+    debug!("Successfully saved linking code for player '{}'.", playername);
+    Ok(Some(new_code))
+}
+
+/// The `tellraw` command giving a player their linking code, with a link to their profile.
+fn linking_code_command(playername: &str, code: &str) -> String {
+    format!(
+        r#"tellraw {player} ["", {{"text":"[Antre des Loutres]","color":"gold"}}, {{"text":" Voici un code pour lié ton compte Minecraft à ton compte Discord : "}}, {{"text":"{code}","color":"gold"}}, {{"text":". Tu peux l'utiliser sur la page de ton profil "}}, {{"text":"(https://antredesloutres.fr)","italic":true,"underlined":true,"color":"dark_aqua","clickEvent":{{"action":"open_url","value":"https://antredesloutres.fr/joueurs/minecraft/{player_lc}/"}}}}, {{"text":"."}}]"#,
+        player = playername,
+        code = code,
+        player_lc = playername.to_lowercase()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    /// Linked players, and the codes saved as (player, code, valid for minutes), the last one active
+    #[derive(Default)]
+    struct FakeCodes {
+        linked: Vec<u64>,
+        saved: RefCell<Vec<(u64, String, u32)>>,
+    }
+
+    impl LinkingCodes for FakeCodes {
+        fn is_linked(&self, player_id: u64) -> Result<bool, mysql::Error> {
+            Ok(self.linked.contains(&player_id))
+        }
+
+        fn active_code(&self, player_id: u64) -> Result<Option<String>, mysql::Error> {
+            Ok(self.saved.borrow().iter().rev().find(|(id, _, _)| *id == player_id).map(|(_, code, _)| code.clone()))
+        }
+
+        fn save_code(&self, player_id: u64, code: &str, duration_minutes: u32) -> Result<(), mysql::Error> {
+            self.saved.borrow_mut().push((player_id, code.to_string(), duration_minutes));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn a_player_with_an_active_code_gets_it_again_and_no_second_one() {
+        let codes = FakeCodes::default();
+        let first = code_to_send(&codes, 7, "Loutre", 10, || "ABC-DEF-GHJ".to_string()).unwrap();
+        assert_eq!(first.as_deref(), Some("ABC-DEF-GHJ"));
+
+        // Joins again while the code is active
+        let second = code_to_send(&codes, 7, "Loutre", 10, || panic!("a second code was generated")).unwrap();
+        assert_eq!(second, first);
+        assert_eq!(*codes.saved.borrow(), vec![(7, "ABC-DEF-GHJ".to_string(), 10)]);
+
+        // Another player gets their own
+        let other = code_to_send(&codes, 8, "Castor", 15, || "KLM-NPQ-RST".to_string()).unwrap();
+        assert_eq!(other.as_deref(), Some("KLM-NPQ-RST"));
+        assert_eq!(codes.saved.borrow().len(), 2);
+    }
+
+    #[test]
+    fn linked_players_get_no_code() {
+        let codes = FakeCodes { linked: vec![7], ..Default::default() };
+        assert_eq!(code_to_send(&codes, 7, "Loutre", 10, || panic!("generated")).unwrap(), None);
+        assert!(codes.saved.borrow().is_empty());
+    }
+
+    #[test]
+    fn codes_are_three_chunks_of_unambiguous_characters() {
+        for _ in 0..200 {
+            let code = generate_linking_code();
+            let chunks: Vec<&str> = code.split('-').collect();
+            assert_eq!(chunks.len(), 3, "{code}");
+            assert!(chunks.iter().all(|chunk| chunk.len() == CHUNK_SIZE), "{code}");
+            assert!(code.bytes().filter(|b| *b != b'-').all(|b| CHARSET.contains(&b)), "{code}");
+            assert!(!code.contains(['I', 'O', '0', '1']), "{code}");
+        }
+    }
+
+    #[test]
+    fn the_code_is_sent_with_a_link_to_the_profile() {
+        let command = linking_code_command("Loutre_42", "ABC-DEF-GHJ");
+        assert!(command.starts_with("tellraw Loutre_42 ["), "{command}");
+        assert!(command.contains(r#"{"text":"ABC-DEF-GHJ","color":"gold"}"#), "{command}");
+        assert!(command.contains("https://antredesloutres.fr/joueurs/minecraft/loutre_42/"), "{command}");
+        // The part after the player is valid JSON
+        let json = command.strip_prefix("tellraw Loutre_42 ").unwrap();
+        assert!(serde_json::from_str::<serde_json::Value>(json).is_ok(), "{json}");
+    }
+}
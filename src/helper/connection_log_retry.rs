@@ -0,0 +1,72 @@
+use colored::Colorize;
+use log::{info, warn};
+use std::sync::{LazyLock, Mutex};
+
+use crate::db::models::{ConnectionType, JoueurConnectionLog};
+use crate::db::repository_default::{self, Database};
+
+/// Most connections kept while the database is down, the oldest being dropped beyond
+const MAX_PENDING: usize = 10_000;
+
+/// Joins and leaves whose write failed on a transient error, in order
+static PENDING: LazyLock<Mutex<Vec<JoueurConnectionLog>>> = LazyLock::new(|| Mutex::new(Vec::new()));
+
+/// Records a join, or closes the session of the player on a leave, retried on transient errors (see
+/// [`Database::retry`]). When the retries are exhausted, the connection is queued and replayed by [`replay`].
+///
+/// # Returns
+/// The duration of the session closed by a leave, if one was open and the write succeeded.
+pub fn record(db: &Database, log: JoueurConnectionLog) -> Option<u64> {
+    match write(db, &log) {
+        Ok(duration) => duration,
+        Err(e) if repository_default::is_transient_error(&e) => {
+            warn!("Connection of player {} queued, the database is unreachable: {}", log.joueur_id, e);
+            let mut pending = PENDING.lock().unwrap_or_else(|e| e.into_inner());
+            if pending.len() >= MAX_PENDING {
+                pending.remove(0);
+            }
+            pending.push(log);
+            None
+        }
+        Err(e) => {
+            warn!("Failed to record the connection of player {}: {:?}", log.joueur_id, e);
+            None
+        }
+    }
+}
+
+fn write(db: &Database, log: &JoueurConnectionLog) -> Result<Option<u64>, mysql::Error> {
+    match log.r#type {
+        ConnectionType::Join => db.retry("recording a join", |db| db.insert_joueur_connection_log(log)).map(|_| None),
+        ConnectionType::Leave => db.retry("closing a session", |db| db.close_open_session(log.serveur_id, log.joueur_id, log.date)),
+    }
+}
+
+/// Replays the queued connections, in order, with their original date. Stops at the first transient error,
+/// the remaining connections being kept for the next call.
+pub fn replay(db: &Database) {
+    let pending: Vec<JoueurConnectionLog> = std::mem::take(&mut *PENDING.lock().unwrap_or_else(|e| e.into_inner()));
+    if pending.is_empty() {
+        return;
+    }
+
+    let total = pending.len();
+    let mut remaining = pending.into_iter();
+    let mut replayed = 0;
+    while let Some(log) = remaining.next() {
+        match write(db, &log) {
+            Ok(_) => replayed += 1,
+            Err(e) if repository_default::is_transient_error(&e) => {
+                // Put back in front of the connections queued meanwhile, to keep the order
+                let mut pending = PENDING.lock().unwrap_or_else(|e| e.into_inner());
+                let queued_meanwhile = std::mem::take(&mut *pending);
+                pending.push(log);
+                pending.extend(&mut remaining);
+                pending.extend(queued_meanwhile);
+                break;
+            }
+            Err(e) => warn!("Queued connection of player {} dropped: {:?}", log.joueur_id, e),
+        }
+    }
+    info!("{} of {} queued player connections replayed", replayed.to_string().green().bold(), total);
+}
@@ -0,0 +1,49 @@
+use log::{debug, info, warn};
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::db::repository_default::Database;
+use crate::helper;
+
+/// Longest part of a line kept in `serverlog_events`
+const MAX_LINE_CHARS: usize = 500;
+
+/// Tells whether the dispatched actions are recorded in `serverlog_events` (`EVENT_AUDIT_ENABLED`, Not set = false).
+pub fn enabled() -> bool {
+    Config::current().event_audit_enabled
+}
+
+/// Days an audited event is kept (`EVENT_AUDIT_RETENTION_DAYS`, default 30).
+pub fn retention_days() -> u32 {
+    Config::current().event_audit_retention_days
+}
+
+/// Records an action dispatched by a trigger, once it ran. A failed insertion is only logged.
+///
+/// # Arguments
+/// * `outcome` - Ok, or the reason the action failed (unknown function, panic).
+pub fn record(trigger_name: &str, function: &str, serverlog_id: u32, line: &str, outcome: &Result<(), String>, duration: Duration) {
+    if !enabled() || helper::dry_run::skip(|| format!("dispatch of {} audited", function)) {
+        return;
+    }
+    let Some(db) = helper::open_database::open_db_from_env() else {
+        return;
+    };
+    let line: String = line.chars().take(MAX_LINE_CHARS).collect();
+    let resultat = if outcome.is_ok() { "ok" } else { "erreur" };
+    if let Err(e) = db.insert_serverlog_event(serverlog_id, trigger_name, function, &line, resultat, duration.as_millis() as u64) {
+        warn!("Failed to audit the dispatch of {}: {:?}", function, e);
+    }
+}
+
+/// Deletes the audited events older than `EVENT_AUDIT_RETENTION_DAYS`.
+pub fn purge(db: &Database) -> Result<u64, mysql::Error> {
+    let days = retention_days();
+    let deleted = db.delete_serverlog_events_older_than(days)?;
+    if deleted > 0 {
+        info!("{} audited events older than {} days deleted", deleted, days);
+    } else {
+        debug!("No audited event older than {} days", days);
+    }
+    Ok(deleted)
+}
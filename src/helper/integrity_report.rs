@@ -0,0 +1,74 @@
+use colored::Colorize;
+use log::{error, info, warn};
+
+use crate::config::Config;
+use crate::db::repository_default::Database;
+use crate::helper::webhook_discord::DiscordEmbed;
+
+type IntegrityCheck = fn(&Database) -> Result<u64, mysql::Error>;
+
+/// Integrity checks of the database, with the label shown in the report
+const CHECKS: &[(&str, &str, IntegrityCheck)] = &[
+    ("orphan_player_stats", "Stats sans joueur", Database::count_orphan_player_stats),
+    ("connection_logs_missing_server", "Connexions vers un serveur inexistant", Database::count_connection_logs_with_missing_server),
+    ("active_codes_linked_players", "Codes actifs de joueurs déjà liés", Database::count_active_codes_for_linked_players),
+    ("sessions_open_too_long", "Sessions ouvertes depuis plus de 48 h", Database::count_sessions_open_too_long),
+    ("active_servers_missing_server", "Serveurs actifs vers un serveur inexistant", Database::count_active_servers_with_missing_server),
+];
+
+/// Runs every integrity check and sends the counts in an admin embed.
+/// When `INTEGRITY_REPORT_TABLE_ENABLED` is true, each result is also written in `rapports_integrite`.
+///
+/// # Returns
+/// An error if at least one check couldn't run. Anomalies found by the checks aren't an error.
+pub fn run_integrity_report(db: &Database) -> Result<(), String> {
+    let write_table = Config::current().integrity_report_table_enabled;
+
+    let mut lines = Vec::new();
+    let mut total_anomalies = 0u64;
+    let mut failed_checks = 0usize;
+
+    for (name, label, check) in CHECKS {
+        match check(db) {
+            Ok(count) => {
+                info!("Integrity check {} : {} anomalies", name.green().bold(), count.to_string().green().bold());
+                total_anomalies += count;
+                lines.push(format!("{} : **{}**", label, count));
+                if write_table
+                    && let Err(e) = db.insert_integrity_report(name, count)
+                {
+                    warn!("Failed to write integrity report of {}: {}", name, e);
+                }
+            }
+            Err(e) => {
+                error!("Integrity check {} failed: {}", name.red().bold(), e);
+                failed_checks += 1;
+                lines.push(format!("{} : erreur ({})", label, e));
+            }
+        }
+    }
+
+    let embed_color = if failed_checks > 0 {
+        "601010" // Red
+    } else if total_anomalies > 0 {
+        "c08020" // Orange
+    } else {
+        "126020" // Green
+    };
+
+    if let Err(e) = DiscordEmbed::new("otternel")
+        .title("Rapport d'intégrité de la base de données")
+        .description(&lines.join("\n"))
+        .color(embed_color)
+        .footer("Otternel Service")
+        .timestamp_now()
+        .send()
+    {
+        error!("{e}");
+    }
+
+    if failed_checks > 0 {
+        return Err(format!("{} integrity checks failed", failed_checks));
+    }
+    Ok(())
+}
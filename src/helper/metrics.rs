@@ -0,0 +1,225 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+
+use crate::helper::{player_id_cache, webhook_discord};
+use crate::serverlog::{processing_lag, self_guard};
+
+/// Lines of log read, by file
+static LOG_LINES: LazyLock<Mutex<BTreeMap<String, u64>>> = LazyLock::new(|| Mutex::new(BTreeMap::new()));
+/// Triggers matched, by action function
+static TRIGGER_MATCHES: LazyLock<Mutex<BTreeMap<String, u64>>> = LazyLock::new(|| Mutex::new(BTreeMap::new()));
+static WEBHOOKS_SENT: AtomicU64 = AtomicU64::new(0);
+static WEBHOOKS_FAILED: AtomicU64 = AtomicU64::new(0);
+/// Duration of the last `sync_mc_stats_to_db`, in milliseconds (0 before the first one)
+static LAST_SYNC_DURATION_MS: AtomicU64 = AtomicU64::new(0);
+static PLAYERS_UPSERTED: AtomicU64 = AtomicU64::new(0);
+static MYSQL_ERRORS: AtomicU64 = AtomicU64::new(0);
+
+/// Counts the complete lines read from a log file.
+pub fn record_log_lines(file: &str, lines: u64) {
+    if lines == 0 {
+        return;
+    }
+    *LOG_LINES.lock().unwrap_or_else(|e| e.into_inner()).entry(file.to_string()).or_default() += lines;
+}
+
+/// Counts a trigger matched, by the function it calls.
+pub fn record_trigger_match(function: &str) {
+    *TRIGGER_MATCHES.lock().unwrap_or_else(|e| e.into_inner()).entry(function.to_string()).or_default() += 1;
+}
+
+/// Counts a webhook delivered to Discord, or given up on.
+pub fn record_webhook(sent: bool) {
+    let counter = if sent { &WEBHOOKS_SENT } else { &WEBHOOKS_FAILED };
+    counter.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Records the end of a Minecraft stats sync.
+pub fn record_stats_sync(duration: Duration, players_upserted: usize) {
+    LAST_SYNC_DURATION_MS.store(duration.as_millis() as u64, Ordering::Relaxed);
+    PLAYERS_UPSERTED.fetch_add(players_upserted as u64, Ordering::Relaxed);
+}
+
+/// Counts a MySQL error (connection not acquired, failed write).
+pub fn record_mysql_error() {
+    MYSQL_ERRORS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Renders every metric in the Prometheus text format (version 0.0.4).
+pub fn render() -> String {
+    let mut out = String::new();
+
+    write_header(&mut out, "otternel_log_lines_total", "counter", "Lines of log read, by file");
+    for (file, count) in LOG_LINES.lock().unwrap_or_else(|e| e.into_inner()).iter() {
+        let _ = writeln!(out, "otternel_log_lines_total{{file=\"{}\"}} {}", escape_label(file), count);
+    }
+
+    let mut lags: Vec<_> = processing_lag::gauges().into_iter().collect();
+    lags.sort_by(|a, b| a.0.cmp(&b.0));
+    write_header(&mut out, "otternel_processing_lag_seconds", "gauge", "Lag behind real time of the last line processed, by file");
+    for (file, gauge) in &lags {
+        let file = escape_label(&file.display().to_string());
+        let _ = writeln!(out, "otternel_processing_lag_seconds{{file=\"{}\"}} {}", file, gauge.latest_ms as f64 / 1000.0);
+    }
+    write_header(&mut out, "otternel_processing_lag_max_seconds", "gauge", "Highest lag behind real time since the last status report, by file");
+    for (file, gauge) in &lags {
+        let file = escape_label(&file.display().to_string());
+        let _ = writeln!(out, "otternel_processing_lag_max_seconds{{file=\"{}\"}} {}", file, gauge.max_ms as f64 / 1000.0);
+    }
+
+    write_header(&mut out, "otternel_trigger_matches_total", "counter", "Triggers matched, by action function");
+    for (function, count) in TRIGGER_MATCHES.lock().unwrap_or_else(|e| e.into_inner()).iter() {
+        let _ = writeln!(out, "otternel_trigger_matches_total{{function=\"{}\"}} {}", escape_label(function), count);
+    }
+
+    write_header(&mut out, "otternel_self_lines_skipped_total", "counter", "Log lines written by Otternel through RCON, kept from the triggers");
+    let _ = writeln!(out, "otternel_self_lines_skipped_total {}", self_guard::skipped_count());
+
+    write_header(&mut out, "otternel_discord_webhooks_total", "counter", "Discord webhooks sent or failed (after the retries)");
+    let _ = writeln!(out, "otternel_discord_webhooks_total{{outcome=\"sent\"}} {}", WEBHOOKS_SENT.load(Ordering::Relaxed));
+    let _ = writeln!(out, "otternel_discord_webhooks_total{{outcome=\"failed\"}} {}", WEBHOOKS_FAILED.load(Ordering::Relaxed));
+
+    let latencies = webhook_discord::webhook_latencies();
+    write_header(&mut out, "otternel_webhook_latency_seconds", "gauge", "p50 and p95 of the webhook requests over the last 24 hours, by identity");
+    for latency in &latencies {
+        let identity = escape_label(&latency.identity);
+        let _ = writeln!(out, "otternel_webhook_latency_seconds{{identity=\"{}\",quantile=\"0.5\"}} {}", identity, latency.p50.as_secs_f64());
+        let _ = writeln!(out, "otternel_webhook_latency_seconds{{identity=\"{}\",quantile=\"0.95\"}} {}", identity, latency.p95.as_secs_f64());
+    }
+    write_header(&mut out, "otternel_webhook_latency_samples", "gauge", "Webhook requests measured over the last 24 hours, by identity");
+    for latency in &latencies {
+        let _ = writeln!(out, "otternel_webhook_latency_samples{{identity=\"{}\"}} {}", escape_label(&latency.identity), latency.count);
+    }
+
+    let cache = player_id_cache::stats();
+    write_header(&mut out, "otternel_player_id_cache_lookups_total", "counter", "Player id lookups answered by the cache (hit) or by the database (miss)");
+    let _ = writeln!(out, "otternel_player_id_cache_lookups_total{{result=\"hit\"}} {}", cache.hits);
+    let _ = writeln!(out, "otternel_player_id_cache_lookups_total{{result=\"miss\"}} {}", cache.misses);
+    write_header(&mut out, "otternel_player_id_cache_evictions_total", "counter", "Player ids evicted from the full cache");
+    let _ = writeln!(out, "otternel_player_id_cache_evictions_total {}", cache.evictions);
+    write_header(&mut out, "otternel_player_id_cache_invalidations_total", "counter", "Player ids forgotten after a merge or a rename");
+    let _ = writeln!(out, "otternel_player_id_cache_invalidations_total {}", cache.invalidations);
+    write_header(&mut out, "otternel_player_id_cache_size", "gauge", "Player ids in the cache");
+    let _ = writeln!(out, "otternel_player_id_cache_size {}", cache.size);
+
+    write_header(&mut out, "otternel_stats_sync_last_duration_seconds", "gauge", "Duration of the last Minecraft stats sync");
+    let _ = writeln!(
+        out,
+        "otternel_stats_sync_last_duration_seconds {}",
+        LAST_SYNC_DURATION_MS.load(Ordering::Relaxed) as f64 / 1000.0
+    );
+
+    write_header(&mut out, "otternel_players_upserted_total", "counter", "Player stats written by the stats syncs");
+    let _ = writeln!(out, "otternel_players_upserted_total {}", PLAYERS_UPSERTED.load(Ordering::Relaxed));
+
+    write_header(&mut out, "otternel_mysql_errors_total", "counter", "MySQL errors (connections not acquired, failed writes)");
+    let _ = writeln!(out, "otternel_mysql_errors_total {}", MYSQL_ERRORS.load(Ordering::Relaxed));
+
+    out
+}
+
+fn write_header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+/// Escapes a label value : backslash, double quote and line break.
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use std::path::PathBuf;
+
+    /// Every sample follows the `# HELP` and `# TYPE` of its metric, and is a `name{labels} value` line
+    #[test]
+    fn render_follows_the_prometheus_text_format() {
+        record_log_lines("/srv/1/latest.log", 3);
+        record_trigger_match("on_render_test");
+        record_webhook(true);
+        record_stats_sync(Duration::from_millis(1250), 2);
+        record_mysql_error();
+
+        let out = render();
+        assert!(out.ends_with('\n'));
+        let mut typed: Option<(String, String)> = None;
+        let mut helped = Vec::new();
+        for line in out.lines() {
+            if let Some(rest) = line.strip_prefix("# HELP ") {
+                let (name, help) = rest.split_once(' ').unwrap();
+                assert!(!help.is_empty(), "{line}");
+                helped.push(name.to_string());
+            } else if let Some(rest) = line.strip_prefix("# TYPE ") {
+                let (name, kind) = rest.split_once(' ').unwrap();
+                assert!(["counter", "gauge"].contains(&kind), "{line}");
+                assert_eq!(helped.last().map(String::as_str), Some(name), "TYPE without HELP: {line}");
+                typed = Some((name.to_string(), kind.to_string()));
+            } else {
+                let (series, value) = line.rsplit_once(' ').unwrap();
+                value.parse::<f64>().unwrap_or_else(|_| panic!("invalid value: {line}"));
+                let name = series.split('{').next().unwrap();
+                assert!(name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'), "{line}");
+                let (current, kind) = typed.as_ref().expect("sample before any TYPE");
+                assert_eq!(name, current, "sample outside of its metric: {line}");
+                if kind == "counter" {
+                    assert!(name.ends_with("_total"), "{line}");
+                }
+                if let Some(labels) = series.strip_prefix(name) {
+                    assert!(labels.is_empty() || (labels.starts_with('{') && labels.ends_with("\"}")), "{line}");
+                }
+            }
+        }
+        let mut unique = helped.clone();
+        unique.dedup();
+        assert_eq!(unique, helped, "metric declared twice");
+
+        assert!(out.contains("otternel_trigger_matches_total{function=\"on_render_test\"} 1\n"));
+        assert!(out.contains("otternel_stats_sync_last_duration_seconds 1.25\n"));
+    }
+
+    #[test]
+    fn label_values_are_escaped() {
+        assert_eq!(escape_label("C:\\logs\\\"a\"\nb"), "C:\\\\logs\\\\\\\"a\\\"\\nb");
+    }
+
+    #[test]
+    fn processing_lag_of_each_file_is_exported() {
+        let path = PathBuf::from(format!("/tmp/otternel-metrics-{}/latest.log", uuid::Uuid::new_v4()));
+        let line = NaiveDate::from_ymd_opt(2026, 1, 1).unwrap().and_hms_opt(12, 0, 0).unwrap();
+        processing_lag::record(&path, line, line + chrono::Duration::milliseconds(1500));
+        processing_lag::record(&path, line, line + chrono::Duration::milliseconds(500));
+
+        let out = render();
+        let file = path.display();
+        assert!(out.contains(&format!("otternel_processing_lag_seconds{{file=\"{file}\"}} 0.5\n")), "{out}");
+        assert!(out.contains(&format!("otternel_processing_lag_max_seconds{{file=\"{file}\"}} 1.5\n")), "{out}");
+        processing_lag::forget(&path);
+    }
+
+    #[test]
+    fn self_guard_skips_are_exported() {
+        self_guard::record_skipped();
+        let out = render();
+        let line = out.lines().find(|line| line.starts_with("otternel_self_lines_skipped_total ")).unwrap();
+        assert!(line.rsplit(' ').next().unwrap().parse::<u64>().unwrap() >= 1);
+    }
+
+    #[test]
+    fn webhook_latency_quantiles_of_each_identity_are_exported() {
+        let identity = format!("metrics-{}", uuid::Uuid::new_v4());
+        for ms in 1..=100 {
+            webhook_discord::record_latency(&identity, Duration::from_millis(ms * 10));
+        }
+
+        let out = render();
+        assert!(out.contains(&format!("otternel_webhook_latency_seconds{{identity=\"{identity}\",quantile=\"0.5\"}} 0.5\n")), "{out}");
+        assert!(out.contains(&format!("otternel_webhook_latency_seconds{{identity=\"{identity}\",quantile=\"0.95\"}} 0.95\n")), "{out}");
+        assert!(out.contains(&format!("otternel_webhook_latency_samples{{identity=\"{identity}\"}} 100\n")), "{out}");
+    }
+}
@@ -1,5 +1,108 @@
 use colored::*;
 use log::{info, warn};
+use thiserror::Error;
+
+use crate::config::Config;
+
+/// Why a playername was rejected by [`normalize_playername`].
+#[derive(Debug, Error, PartialEq)]
+pub enum NameError {
+    #[error("Playername is empty")]
+    Empty,
+
+    #[error("Playername '{0}' must be 3 to 16 characters long")]
+    InvalidLength(String),
+
+    #[error("Playername '{0}' contains characters other than letters, digits and '_'")]
+    InvalidCharacters(String),
+}
+
+/// Normalizes a playername read from a log line before any database write or URL building.
+///
+/// Steps:
+/// 1. strips the `§` colour codes and the surrounding whitespace,
+/// 2. strips the team prefixes set in `PLAYERNAME_TEAM_PREFIXES` (comma separated, ex: `[Admin] ,[Modo] `),
+/// 3. validates the Java rule : 3 to 16 characters among `[A-Za-z0-9_]`.
+///
+/// Bedrock players (Geyser/Floodgate) start with `BEDROCK_PLAYER_PREFIX` (default `.`) and may contain
+/// spaces, so after the prefix they only need 1 to 16 characters among `[A-Za-z0-9_ ]`.
+///
+/// # Returns
+/// The normalized playername, or the reason it was rejected.
+pub fn normalize_playername(raw: &str) -> Result<String, NameError> {
+    let cfg = Config::current();
+    normalize_playername_with(raw, &cfg.playername_team_prefixes, &cfg.bedrock_player_prefix)
+}
+
+/// Same as [`normalize_playername`], with the team prefixes and the Bedrock prefix given explicitly.
+pub fn normalize_playername_with(raw: &str, team_prefixes: &str, bedrock_prefix: &str) -> Result<String, NameError> {
+    // Colour codes are "§" followed by one character
+    let mut name = String::with_capacity(raw.len());
+    let mut chars = raw.chars();
+    while let Some(c) = chars.next() {
+        if c == '§' {
+            chars.next();
+        } else {
+            name.push(c);
+        }
+    }
+    let mut name = name.trim();
+
+    // Team prefixes can be stacked ("[Admin] [VIP] name")
+    let prefixes: Vec<&str> = team_prefixes
+        .split(',')
+        .map(str::trim_start)
+        .filter(|p| !p.trim().is_empty())
+        .collect();
+    // A name left with only a prefix lost its trailing space to the trim : nothing remains after it
+    while let Some(rest) = prefixes.iter().find_map(|prefix| {
+        strip_prefix_ignore_case(name, prefix).or_else(|| name.eq_ignore_ascii_case(prefix.trim_end()).then_some(""))
+    }) {
+        name = rest.trim();
+    }
+
+    if name.is_empty() {
+        return Err(NameError::Empty);
+    }
+
+    // Bedrock players, prefixed by Floodgate
+    if let Some(bedrock_name) = name.strip_prefix(bedrock_prefix).filter(|_| !bedrock_prefix.is_empty()) {
+        let len = bedrock_name.chars().count();
+        if !(1..=16).contains(&len) {
+            return Err(NameError::InvalidLength(name.to_string()));
+        }
+        if !bedrock_name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == ' ') {
+            return Err(NameError::InvalidCharacters(name.to_string()));
+        }
+        return Ok(name.to_string());
+    }
+
+    if !(3..=16).contains(&name.chars().count()) {
+        return Err(NameError::InvalidLength(name.to_string()));
+    }
+    if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err(NameError::InvalidCharacters(name.to_string()));
+    }
+    Ok(name.to_string())
+}
+
+fn strip_prefix_ignore_case<'a>(name: &'a str, prefix: &str) -> Option<&'a str> {
+    let head = name.get(..prefix.len())?;
+    head.eq_ignore_ascii_case(prefix).then(|| &name[prefix.len()..])
+}
+
+/// Returns true if `value` is a Minecraft uuid, with or without dashes. Unlike
+/// [`check_and_format_minecraft_uuid`], nothing is logged : used to sort out stray files (ex: `README.json`).
+pub fn is_minecraft_uuid(value: &str) -> bool {
+    match value.len() {
+        32 => value.chars().all(|c| c.is_ascii_hexdigit()),
+        36 => value
+            .chars()
+            .enumerate()
+            .all(|(i, c)| if [8, 13, 18, 23].contains(&i) { c == '-' } else { c.is_ascii_hexdigit() }),
+        _ => false,
+    }
+}
 
 pub(crate) fn check_and_format_minecraft_uuid(player_uuid: &str) -> Result<String, fern::InitError> {
     info!(
@@ -85,3 +188,93 @@ pub(crate) fn check_and_format_minecraft_uuid(player_uuid: &str) -> Result<Strin
         "Invalid Minecraft UUID",
     )))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PREFIXES: &str = "[Admin] ,[VIP] , [Modo] ";
+
+    fn normalize(raw: &str) -> Result<String, NameError> {
+        normalize_playername_with(raw, PREFIXES, ".")
+    }
+
+    #[test]
+    fn messy_names_are_cleaned() {
+        let cases = [
+            ("Loutre", "Loutre"),
+            ("  Loutre \t", "Loutre"),
+            ("Loutre\r\n", "Loutre"),
+            ("§aLoutre§r", "Loutre"),
+            ("§l§6Lou§ktre", "Loutre"),
+            ("Loutre§", "Loutre"),
+            ("[Admin] Loutre", "Loutre"),
+            ("[admin] Loutre", "Loutre"),
+            ("§c[Admin] §rLoutre", "Loutre"),
+            ("[Admin] [VIP] Loutre", "Loutre"),
+            ("[VIP]   Loutre", "Loutre"),
+            ("[Modo] Loutre", "Loutre"),
+            ("Lou", "Lou"),
+            ("Loutre_Otter_123", "Loutre_Otter_123"),
+        ];
+        for (raw, expected) in cases {
+            assert_eq!(normalize(raw), Ok(expected.to_string()), "{raw:?}");
+        }
+    }
+
+    #[test]
+    fn invalid_names_are_rejected_with_their_reason() {
+        let cases = [
+            ("", NameError::Empty),
+            ("   ", NameError::Empty),
+            ("§a§r", NameError::Empty),
+            ("[Admin] [VIP] ", NameError::Empty),
+            ("§c[Admin]", NameError::Empty),
+            ("Lo", NameError::InvalidLength("Lo".to_string())),
+            ("§aLo§r", NameError::InvalidLength("Lo".to_string())),
+            ("Loutre_Otter_1234", NameError::InvalidLength("Loutre_Otter_1234".to_string())),
+            ("Lou tre", NameError::InvalidCharacters("Lou tre".to_string())),
+            ("Loütre", NameError::InvalidCharacters("Loütre".to_string())),
+            ("Loutre!", NameError::InvalidCharacters("Loutre!".to_string())),
+            ("Lou\u{200b}tre", NameError::InvalidCharacters("Lou\u{200b}tre".to_string())),
+            ("[Owner] Loutre", NameError::InvalidCharacters("[Owner] Loutre".to_string())),
+            ("[Admin]Loutre", NameError::InvalidCharacters("[Admin]Loutre".to_string())),
+        ];
+        for (raw, expected) in cases {
+            assert_eq!(normalize(raw), Err(expected), "{raw:?}");
+        }
+    }
+
+    #[test]
+    fn bedrock_names_may_contain_spaces() {
+        assert_eq!(normalize(".Loutre Otter"), Ok(".Loutre Otter".to_string()));
+        assert_eq!(normalize("[VIP] §a.Lo"), Ok(".Lo".to_string()));
+        assert_eq!(normalize(".L"), Ok(".L".to_string()));
+        assert_eq!(normalize("."), Err(NameError::InvalidLength(".".to_string())));
+        assert_eq!(normalize(".Loutre_Otter_1234"), Err(NameError::InvalidLength(".Loutre_Otter_1234".to_string())));
+        assert_eq!(normalize(".Loutre-Otter"), Err(NameError::InvalidCharacters(".Loutre-Otter".to_string())));
+        assert_eq!(normalize_playername_with("*Loutre Otter", "", "*"), Ok("*Loutre Otter".to_string()));
+        // Without a Bedrock prefix, the Java rule applies to every name
+        assert_eq!(normalize_playername_with(".Loutre", "", ""), Err(NameError::InvalidCharacters(".Loutre".to_string())));
+    }
+
+    #[test]
+    fn prefixes_cut_inside_a_character_do_not_panic() {
+        assert!(normalize("[Admiéé] Loutre").is_err());
+        assert!(normalize("é").is_err());
+        assert_eq!(normalize_playername_with("[Modérateur] Loutre", "[Modérateur] ", "."), Ok("Loutre".to_string()));
+    }
+
+    #[test]
+    fn minecraft_uuids_are_recognized_with_or_without_dashes() {
+        assert!(is_minecraft_uuid("069a79f4-44e9-4726-a5be-fca90e38aaf5"));
+        assert!(is_minecraft_uuid("069a79f444e94726a5befca90e38aaf5"));
+        assert!(!is_minecraft_uuid("069a79f4-44e9-4726-a5be-fca90e38aaf"));
+        assert!(!is_minecraft_uuid("README"));
+        assert_eq!(
+            check_and_format_minecraft_uuid("069a79f444e94726a5befca90e38aaf5").unwrap(),
+            "069a79f4-44e9-4726-a5be-fca90e38aaf5"
+        );
+        assert!(check_and_format_minecraft_uuid("069a79f4x44e9-4726-a5be-fca90e38aaf5").is_err());
+    }
+}
@@ -1,6 +1,34 @@
 pub mod webhook_discord;
+pub mod webhook_queue;
 pub mod open_database;
 pub mod code_generator;
+pub mod account_links;
 pub mod rcon_helper;
+pub mod rcon_schedule;
 pub mod minecraft_account_formatter;
+pub mod mojang_api;
+pub mod steam_api;
+pub mod integrity_report;
+pub mod server_mute;
+pub mod player_privacy;
+pub mod online_reconciliation;
+pub mod profile_repair;
+pub mod rolling_histogram;
+pub mod status_report;
 pub(crate) mod logger_tool;
+pub mod world_backup;
+pub mod player_id_cache;
+pub mod mojang_cache;
+pub mod stats_changefeed;
+pub mod active_servers;
+pub mod http_client;
+pub mod webhook_check;
+pub mod pvp_leaderboard;
+pub mod state_backup;
+pub mod metrics;
+pub mod dry_run;
+pub mod advancements;
+pub mod connection_log_retry;
+pub mod weekly_leaderboard;
+pub mod server_list_ping;
+pub mod event_audit;
@@ -0,0 +1,86 @@
+use log::warn;
+use serde::Deserialize;
+use std::time::Duration;
+
+use crate::helper::http_client::HttpClient;
+use crate::helper::mojang_cache;
+
+/// Longest wait for the Mojang API, so a slow answer can't hold a join event
+const MOJANG_TIMEOUT: Duration = Duration::from_secs(5);
+/// Attempts of a request rate limited by the API (429) before giving up
+const MAX_ATTEMPTS: u64 = 3;
+/// Longest wait between two attempts, whatever the `Retry-After` header asks
+const MAX_RETRY_WAIT: Duration = Duration::from_secs(10);
+
+#[derive(Deserialize)]
+pub struct MojangProfile {
+    pub id: String,
+    pub name: String,
+}
+
+/// Fetches the Minecraft profile (uuid and playername) of a player from its playername.
+/// The local Mojang cache is checked first, the API is only called on a miss.
+///
+/// # Errors
+/// Returns the `ureq` error if the request fails (429 when still rate limited after the retries) or times out,
+/// or if the response is not a valid profile.
+pub fn fetch_profile_by_playername(playername: &str) -> Result<MojangProfile, Box<dyn std::error::Error>> {
+    if let Some((id, name)) = mojang_cache::profile_by_name(playername) {
+        return Ok(MojangProfile { id, name });
+    }
+
+    let url = format!("https://api.mojang.com/users/profiles/minecraft/{}", playername);
+    let profile: MojangProfile = call_with_retry(&url)?.into_json()?;
+    mojang_cache::store_profile(&profile.id, &profile.name);
+    Ok(profile)
+}
+
+/// Fetches the playername of a Minecraft account from its uuid.
+/// The local Mojang cache is checked first, the API is only called on a miss.
+///
+/// # Returns
+/// - `Ok(Some(name))` if the account exists.
+/// - `Ok(None)` if the API doesn't know the uuid (204 or 404), probably a bedrock or crack player.
+/// - `Err(ureq::Error)` for any other failure, including a 429 still returned after the retries.
+pub fn fetch_playername_by_uuid(player_uuid: &str) -> Result<Option<String>, Box<ureq::Error>> {
+    if let Some(name) = mojang_cache::name_by_uuid(player_uuid) {
+        return Ok(name);
+    }
+
+    let url = format!("https://api.minetools.eu/uuid/{}", player_uuid);
+    let name = match call_with_retry(&url) {
+        Ok(r) => r.into_json::<MojangProfile>().ok().map(|p| p.name),
+        Err(e) if matches!(*e, ureq::Error::Status(204 | 404, _)) => None,
+        Err(e) => return Err(e),
+    };
+    mojang_cache::store_name(player_uuid, name.as_deref());
+    Ok(name)
+}
+
+/// Calls the API, waiting and trying again when it answers 429 (rate limited).
+/// The wait is the `Retry-After` header if present, one more second per attempt otherwise.
+/// Errors of the proxy (if one is configured) are logged as such, to tell them apart from the ones of the API.
+fn call_with_retry(url: &str) -> Result<ureq::Response, Box<ureq::Error>> {
+    let client = HttpClient::for_url(url);
+    let mut attempt = 1;
+    loop {
+        match client.get(url).timeout(MOJANG_TIMEOUT).call() {
+            Err(ureq::Error::Status(429, response)) if attempt < MAX_ATTEMPTS => {
+                let wait = response
+                    .header("Retry-After")
+                    .and_then(|value| value.trim().parse().ok())
+                    .map(Duration::from_secs)
+                    .unwrap_or(Duration::from_secs(attempt))
+                    .min(MAX_RETRY_WAIT);
+                warn!("Rate limited by {}, retrying in {}s (attempt {}/{})", url, wait.as_secs(), attempt + 1, MAX_ATTEMPTS);
+                std::thread::sleep(wait);
+                attempt += 1;
+            }
+            Err(e) if client.is_proxy_error(&e) => {
+                warn!("Calling {} failed, {}", url, client.describe_error(&e));
+                return Err(Box::new(e));
+            }
+            result => return result.map_err(Box::new),
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{LazyLock, Mutex};
+
+use crate::config::Config;
+
+
+/// Resolutions uuid <-> playername already made, loaded from disk on first use.
+static CACHE: LazyLock<Mutex<MojangCache>> = LazyLock::new(|| Mutex::new(MojangCache::load(&cache_path())));
+
+static HITS: AtomicU64 = AtomicU64::new(0);
+static MISSES: AtomicU64 = AtomicU64::new(0);
+
+/// Content of the cache file.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct MojangCache {
+    /// Profiles by lowercase playername
+    by_name: HashMap<String, CachedProfile>,
+    /// Playernames by uuid, `None` when the API doesn't know the uuid (bedrock or crack player)
+    by_uuid: HashMap<String, CachedName>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedProfile {
+    id: String,
+    name: String,
+    /// Unix timestamp of the resolution
+    fetched_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedName {
+    name: Option<String>,
+    fetched_at: i64,
+}
+
+impl MojangCache {
+    fn load(path: &str) -> Self {
+        match std::fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                warn!("Invalid Mojang cache {}, starting empty: {}", path, e);
+                MojangCache::default()
+            }),
+            Err(_) => MojangCache::default(),
+        }
+    }
+
+    /// Writes the cache next to its final path, then renames it, so a crash never leaves half a file.
+    fn save(&self, path: &str) {
+        let tmp = format!("{}.tmp", path);
+        let result = serde_json::to_string(self)
+            .map_err(std::io::Error::other)
+            .and_then(|json| std::fs::write(&tmp, json))
+            .and_then(|_| std::fs::rename(&tmp, path));
+        if let Err(e) = result {
+            warn!("Failed to write the Mojang cache {}: {}", path, e);
+        }
+    }
+}
+
+/// Returns the cached profile `(uuid, playername)` of a playername, if fresh.
+pub fn profile_by_name(playername: &str) -> Option<(String, String)> {
+    let cache = CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    let found = cache
+        .by_name
+        .get(&playername.to_lowercase())
+        .filter(|p| is_fresh(p.fetched_at))
+        .map(|p| (p.id.clone(), p.name.clone()));
+    count(found.is_some(), playername);
+    found
+}
+
+/// Returns the cached playername of a uuid, if fresh : `Some(None)` means the uuid is known to have no Mojang account.
+pub fn name_by_uuid(player_uuid: &str) -> Option<Option<String>> {
+    let cache = CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    let found = cache
+        .by_uuid
+        .get(player_uuid)
+        .filter(|n| is_fresh(n.fetched_at))
+        .map(|n| n.name.clone());
+    count(found.is_some(), player_uuid);
+    found
+}
+
+/// Caches a profile fetched from Mojang, both ways.
+pub fn store_profile(player_uuid: &str, playername: &str) {
+    let now = chrono::Utc::now().timestamp();
+    let mut cache = CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    cache.by_name.insert(
+        playername.to_lowercase(),
+        CachedProfile { id: player_uuid.to_string(), name: playername.to_string(), fetched_at: now },
+    );
+    cache.by_uuid.insert(player_uuid.to_string(), CachedName { name: Some(playername.to_string()), fetched_at: now });
+    cache.save(&cache_path());
+}
+
+/// Caches the playername of a uuid, or that the uuid has no Mojang account (`None`).
+pub fn store_name(player_uuid: &str, playername: Option<&str>) {
+    let now = chrono::Utc::now().timestamp();
+    let mut cache = CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    cache.by_uuid.insert(
+        player_uuid.to_string(),
+        CachedName { name: playername.map(str::to_string), fetched_at: now },
+    );
+    if let Some(playername) = playername {
+        cache.by_name.insert(
+            playername.to_lowercase(),
+            CachedProfile { id: player_uuid.to_string(), name: playername.to_string(), fetched_at: now },
+        );
+    }
+    cache.save(&cache_path());
+}
+
+fn count(hit: bool, key: &str) {
+    let (hits, misses) = if hit {
+        (HITS.fetch_add(1, Ordering::Relaxed) + 1, MISSES.load(Ordering::Relaxed))
+    } else {
+        (HITS.load(Ordering::Relaxed), MISSES.fetch_add(1, Ordering::Relaxed) + 1)
+    };
+    debug!(
+        "Mojang cache {} for {} ({} hits, {} misses)",
+        if hit { "hit" } else { "miss" },
+        key,
+        hits,
+        misses
+    );
+}
+
+fn is_fresh(fetched_at: i64) -> bool {
+    let ttl_days = Config::current().mojang_cache_ttl_days;
+    chrono::Utc::now().timestamp() - fetched_at < ttl_days * 24 * 3600
+}
+
+/// Returns the path of the cache file (`MOJANG_CACHE_PATH`, Default `mojang_cache.json`).
+pub fn cache_path() -> String {
+    Config::current().mojang_cache_path.clone()
+}
@@ -0,0 +1,117 @@
+use colored::Colorize;
+use log::{debug, info, warn};
+
+use crate::db::models::{ConnectionType, JoueurConnectionLog};
+use crate::db::repository_default::Database;
+use crate::helper;
+use crate::helper::rcon_helper::RconHelper;
+use crate::serverlog::online_tracker::{self, OnlineDiff};
+
+/// Seeds the online tracker with the players actually online on every active Minecraft server.
+///
+/// After a restart, the players who joined or left while Otternel was down are invisible until they relog.
+/// For each server, the RCON `list` command gives the players online : the ones not tracked get a connection
+/// recorded (source `startup_seed`), the ones tracked but absent get their disconnection recorded.
+///
+/// # Returns
+/// Ok(()) once every reachable server is reconciled; Err(String) if the servers couldn't be listed.
+pub async fn run_startup_reconciliation() -> Result<(), String> {
+    let rcon = RconHelper::new().map_err(|e| e.to_string())?;
+    let servers = rcon
+        .db
+        .call(|db| db.get_all_active_servers_by_game("minecraft"))
+        .await
+        .map_err(|e| e.to_string())?;
+
+    for server in servers {
+        let response = match rcon.execute_command(server.active_id, "list").await {
+            Ok(response) => response,
+            Err(e) => {
+                warn!("Could not list the players online on {}: {}", server.nom.yellow(), e);
+                continue;
+            }
+        };
+        let Some(actual) = online_tracker::parse_list_response(&response) else {
+            warn!("Unexpected answer to list on {}: {}", server.nom.yellow(), response);
+            continue;
+        };
+
+        let diff = online_tracker::reconcile(server.active_id as u32, actual);
+        if diff.is_empty() {
+            debug!("Online players of {} already up to date", server.nom);
+            continue;
+        }
+        info!(
+            "Startup reconciliation of {} : {} appeared, {} vanished",
+            server.nom.green().bold(),
+            diff.appeared.len().to_string().green().bold(),
+            diff.vanished.len().to_string().green().bold()
+        );
+        let active_id = server.active_id;
+        rcon.db.call(move |db| apply_diff(db, active_id, &diff)).await;
+    }
+
+    Ok(())
+}
+
+/// Records in database the connections and disconnections found by a reconciliation.
+pub fn apply_diff(db: &Database, active_id: u64, diff: &OnlineDiff) {
+    let serveur_id = match db.get_server_by_active_server_id(active_id) {
+        Ok(Some(server)) => server.id,
+        Ok(None) => {
+            warn!("No server found for active server id {}", active_id);
+            return;
+        }
+        Err(e) => {
+            warn!("Error fetching server for active server id {}: {}", active_id, e);
+            return;
+        }
+    };
+
+    let now = chrono::Utc::now().naive_utc();
+    for (playername, r#type) in connection_changes(diff) {
+        let joueur_id = match db.add_and_get_minecraft_player_id(playername) {
+            Ok(id) => id,
+            Err(e) => {
+                warn!("Player {}'s ID couldn't be fetched or added to the database: {}", playername, e);
+                continue;
+            }
+        };
+
+        helper::connection_log_retry::record(db, JoueurConnectionLog { serveur_id, joueur_id, date: now, r#type });
+        if let Err(e) = db.touch_player_last_connection(joueur_id, now) {
+            warn!("Failed to update last player connection: {:?}", e);
+        }
+        let change = if r#type == ConnectionType::Join { "Connection" } else { "Disconnection" };
+        debug!("{} of {} recorded (source startup_seed)", change, playername);
+    }
+    online_tracker::persist(db, active_id as u32, serveur_id);
+}
+
+/// Connections to record for a reconciliation : a join for each player that appeared, then a leave for each one that
+/// vanished.
+fn connection_changes(diff: &OnlineDiff) -> impl Iterator<Item = (&String, ConnectionType)> {
+    diff.appeared
+        .iter()
+        .map(|name| (name, ConnectionType::Join))
+        .chain(diff.vanished.iter().map(|name| (name, ConnectionType::Leave)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn appeared_players_join_and_vanished_players_leave() {
+        let diff = OnlineDiff {
+            appeared: vec!["Alex".to_string(), "Steve".to_string()],
+            vanished: vec!["Loutre".to_string()],
+        };
+        let changes: Vec<(&str, ConnectionType)> = connection_changes(&diff).map(|(name, t)| (name.as_str(), t)).collect();
+        assert_eq!(
+            changes,
+            vec![("Alex", ConnectionType::Join), ("Steve", ConnectionType::Join), ("Loutre", ConnectionType::Leave)]
+        );
+        assert_eq!(connection_changes(&OnlineDiff::default()).count(), 0);
+    }
+}
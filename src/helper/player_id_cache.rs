@@ -0,0 +1,245 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+/// Most player ids kept in memory, the least recently used one is evicted beyond
+const CAPACITY: usize = 1000;
+/// A cached id is looked up again in database after this delay
+const TTL: Duration = Duration::from_secs(600);
+
+/// Player ids by name and by uuid, so the actions don't query MySQL on every join.
+static CACHE: LazyLock<Mutex<PlayerIdCache>> = LazyLock::new(|| Mutex::new(PlayerIdCache::new(CAPACITY, TTL)));
+
+static HITS: AtomicU64 = AtomicU64::new(0);
+static MISSES: AtomicU64 = AtomicU64::new(0);
+static EVICTIONS: AtomicU64 = AtomicU64::new(0);
+static INVALIDATIONS: AtomicU64 = AtomicU64::new(0);
+
+/// What a player id is looked up by.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PlayerKey {
+    /// Lowercase playername of a game
+    Name { game: String, playername: String },
+    /// Account id (uuid, steam id...) of a game
+    Uuid { game: String, compte_id: String },
+}
+
+impl PlayerKey {
+    pub fn name(game: &str, playername: &str) -> Self {
+        PlayerKey::Name { game: game.to_lowercase(), playername: playername.to_lowercase() }
+    }
+
+    pub fn uuid(game: &str, compte_id: &str) -> Self {
+        PlayerKey::Uuid { game: game.to_lowercase(), compte_id: compte_id.to_string() }
+    }
+}
+
+/// Counters of the player id cache, since the start.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PlayerIdCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    pub invalidations: u64,
+    pub size: usize,
+}
+
+/// Bounded LRU of player ids, with a time to live.
+struct PlayerIdCache {
+    capacity: usize,
+    ttl: Duration,
+    entries: HashMap<PlayerKey, CachedId>,
+}
+
+struct CachedId {
+    joueur_id: u64,
+    inserted_at: Instant,
+    last_used: Instant,
+}
+
+impl PlayerIdCache {
+    fn new(capacity: usize, ttl: Duration) -> Self {
+        Self { capacity, ttl, entries: HashMap::new() }
+    }
+
+    fn get(&mut self, key: &PlayerKey, now: Instant) -> Option<u64> {
+        let entry = self.entries.get_mut(key)?;
+        if now.duration_since(entry.inserted_at) >= self.ttl {
+            self.entries.remove(key);
+            return None;
+        }
+        entry.last_used = now;
+        Some(entry.joueur_id)
+    }
+
+    /// Inserts an id, evicting the least recently used one if full.
+    ///
+    /// # Returns
+    /// Whether an entry was evicted.
+    fn insert(&mut self, key: PlayerKey, joueur_id: u64, now: Instant) -> bool {
+        let mut evicted = false;
+        if !self.entries.contains_key(&key) && self.entries.len() >= self.capacity
+            && let Some(oldest) = self.entries.iter().min_by_key(|(_, e)| e.last_used).map(|(k, _)| k.clone())
+        {
+            self.entries.remove(&oldest);
+            evicted = true;
+        }
+        self.entries.insert(key, CachedId { joueur_id, inserted_at: now, last_used: now });
+        evicted
+    }
+
+    /// Removes every key pointing to one of `joueur_ids`.
+    fn invalidate(&mut self, joueur_ids: &[u64]) -> usize {
+        let before = self.entries.len();
+        self.entries.retain(|_, e| !joueur_ids.contains(&e.joueur_id));
+        before - self.entries.len()
+    }
+}
+
+/// Returns the cached id of a player, if it is fresh.
+pub fn get(key: &PlayerKey) -> Option<u64> {
+    let found = CACHE.lock().unwrap_or_else(|e| e.into_inner()).get(key, Instant::now());
+    match found {
+        Some(_) => HITS.fetch_add(1, Ordering::Relaxed),
+        None => MISSES.fetch_add(1, Ordering::Relaxed),
+    };
+    found
+}
+
+/// Returns the cached id of a player, or looks it up with `lookup` (in database) and caches it.
+pub fn get_or_lookup<E>(key: PlayerKey, lookup: impl FnOnce() -> Result<u64, E>) -> Result<u64, E> {
+    if let Some(id) = get(&key) {
+        return Ok(id);
+    }
+    let id = lookup()?;
+    insert(key, id);
+    Ok(id)
+}
+
+/// Caches the id of a player.
+pub fn insert(key: PlayerKey, joueur_id: u64) {
+    if CACHE.lock().unwrap_or_else(|e| e.into_inner()).insert(key, joueur_id, Instant::now()) {
+        EVICTIONS.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Forgets every name and uuid of these players, after a merge or a rename.
+pub fn invalidate_players(joueur_ids: &[u64]) {
+    let removed = CACHE.lock().unwrap_or_else(|e| e.into_inner()).invalidate(joueur_ids);
+    INVALIDATIONS.fetch_add(removed as u64, Ordering::Relaxed);
+}
+
+/// Returns the counters of the cache.
+pub fn stats() -> PlayerIdCacheStats {
+    PlayerIdCacheStats {
+        hits: HITS.load(Ordering::Relaxed),
+        misses: MISSES.load(Ordering::Relaxed),
+        evictions: EVICTIONS.load(Ordering::Relaxed),
+        invalidations: INVALIDATIONS.load(Ordering::Relaxed),
+        size: CACHE.lock().unwrap_or_else(|e| e.into_inner()).entries.len(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Each test uses its own names, the cache being shared by the tests
+    fn unique(name: &str) -> String {
+        format!("{}-{}", name, uuid::Uuid::new_v4().simple())
+    }
+
+    #[test]
+    fn repeated_join_looks_the_player_up_once() {
+        let name = unique("joiner");
+        let mut lookups = 0;
+        for _ in 0..3 {
+            let id = get_or_lookup(PlayerKey::name("minecraft", &name), || {
+                lookups += 1;
+                Ok::<u64, ()>(42)
+            });
+            assert_eq!(id, Ok(42));
+        }
+        assert_eq!(lookups, 1);
+    }
+
+    #[test]
+    fn failed_lookup_is_not_cached() {
+        let name = unique("failing");
+        let key = PlayerKey::name("minecraft", &name);
+        assert_eq!(get_or_lookup(key.clone(), || Err("MySQL down")), Err("MySQL down"));
+        assert_eq!(get_or_lookup(key, || Ok::<u64, &str>(7)), Ok(7));
+    }
+
+    #[test]
+    fn names_are_case_insensitive() {
+        let name = unique("Steve");
+        insert(PlayerKey::name("Minecraft", &name), 3);
+        assert_eq!(get(&PlayerKey::name("minecraft", &name.to_uppercase())), Some(3));
+    }
+
+    #[test]
+    fn rename_invalidates_every_key_of_the_player() {
+        let (old_name, uuid) = (unique("old"), unique("uuid"));
+        let joueur_id = uuid::Uuid::new_v4().as_u64_pair().0;
+        insert(PlayerKey::name("minecraft", &old_name), joueur_id);
+        insert(PlayerKey::uuid("minecraft", &uuid), joueur_id);
+        let invalidations = stats().invalidations;
+
+        invalidate_players(&[joueur_id]);
+
+        assert_eq!(get(&PlayerKey::name("minecraft", &old_name)), None);
+        assert_eq!(get(&PlayerKey::uuid("minecraft", &uuid)), None);
+        assert!(stats().invalidations >= invalidations + 2);
+
+        let mut lookups = 0;
+        let id = get_or_lookup(PlayerKey::name("minecraft", &old_name), || {
+            lookups += 1;
+            Ok::<u64, ()>(joueur_id)
+        });
+        assert_eq!((id, lookups), (Ok(joueur_id), 1));
+    }
+
+    #[test]
+    fn hits_and_misses_are_counted() {
+        let name = unique("counted");
+        let before = stats();
+        let key = PlayerKey::name("minecraft", &name);
+        assert_eq!(get(&key), None);
+        insert(key.clone(), 5);
+        assert_eq!(get(&key), Some(5));
+        assert_eq!(get(&key), Some(5));
+
+        let after = stats();
+        assert!(after.misses > before.misses);
+        assert!(after.hits >= before.hits + 2);
+    }
+
+    #[test]
+    fn entry_expires_after_the_ttl() {
+        let mut cache = PlayerIdCache::new(10, Duration::from_secs(60));
+        let now = Instant::now();
+        let key = PlayerKey::name("minecraft", "Alex");
+        cache.insert(key.clone(), 1, now);
+
+        assert_eq!(cache.get(&key, now + Duration::from_secs(59)), Some(1));
+        assert_eq!(cache.get(&key, now + Duration::from_secs(60)), None);
+        assert!(cache.entries.is_empty());
+    }
+
+    #[test]
+    fn least_recently_used_entry_is_evicted_when_full() {
+        let mut cache = PlayerIdCache::new(2, Duration::from_secs(60));
+        let now = Instant::now();
+        let (a, b, c) = (PlayerKey::name("minecraft", "a"), PlayerKey::name("minecraft", "b"), PlayerKey::name("minecraft", "c"));
+        assert!(!cache.insert(a.clone(), 1, now));
+        assert!(!cache.insert(b.clone(), 2, now + Duration::from_secs(1)));
+        // a is used again, b becomes the least recently used
+        cache.get(&a, now + Duration::from_secs(2));
+
+        assert!(cache.insert(c.clone(), 3, now + Duration::from_secs(3)));
+        let later = now + Duration::from_secs(4);
+        assert_eq!((cache.get(&a, later), cache.get(&b, later), cache.get(&c, later)), (Some(1), None, Some(3)));
+    }
+}
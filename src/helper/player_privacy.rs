@@ -0,0 +1,99 @@
+use colored::Colorize;
+use log::{error, info};
+use std::collections::HashSet;
+use std::sync::{LazyLock, RwLock};
+
+use crate::db::repository_default::Database;
+
+/// Name shown instead of a hidden player's name
+pub const ANONYMOUS_PLAYERNAME: &str = "Un joueur";
+
+/// Hidden players, by (game, lowercase playername). Kept in memory so the actions never query the database per line.
+static HIDDEN: LazyLock<RwLock<HashSet<(String, String)>>> = LazyLock::new(|| RwLock::new(HashSet::new()));
+
+/// Returns true if the player asked for their activity not to be announced.
+pub fn is_hidden(game: &str, playername: &str) -> bool {
+    HIDDEN
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .contains(&key(game, playername))
+}
+
+/// Returns the name to show for a player : their name, or "Un joueur" if they are hidden.
+pub fn display_name(game: &str, playername: &str) -> String {
+    if is_hidden(game, playername) {
+        ANONYMOUS_PLAYERNAME.to_string()
+    } else {
+        playername.to_string()
+    }
+}
+
+/// Loads the hidden players from the database.
+pub fn load_hidden_players(db: &Database) {
+    match db.get_hidden_players() {
+        Ok(players) => {
+            info!("{} hidden players loaded", players.len().to_string().green().bold());
+            let mut hidden = HIDDEN.write().unwrap_or_else(|e| e.into_inner());
+            hidden.clear();
+            hidden.extend(players.iter().map(|(game, playername)| key(game, playername)));
+        }
+        Err(e) => error!("Failed to load hidden players: {}", e),
+    }
+}
+
+/// Shows or hides the activity of a player, in database and in memory.
+///
+/// # Returns
+/// - `Ok(true)` if the player exists and was updated.
+/// - `Ok(false)` if no player matches.
+/// - `Err(String)` if the database couldn't be updated.
+pub fn set_visible(db: &Database, game: &str, playername: &str, visible: bool) -> Result<bool, String> {
+    let found = db
+        .set_player_visibility(game, playername, visible)
+        .map_err(|e| e.to_string())?;
+    if !found {
+        return Ok(false);
+    }
+
+    remember(game, playername, visible);
+    info!(
+        "Player {} ({}) is now {}",
+        playername.green().bold(),
+        game,
+        if visible { "visible" } else { "hidden" }
+    );
+    Ok(true)
+}
+
+/// Records in memory whether a player is visible, the database being already up to date.
+pub(crate) fn remember(game: &str, playername: &str, visible: bool) {
+    let mut hidden = HIDDEN.write().unwrap_or_else(|e| e.into_inner());
+    if visible {
+        hidden.remove(&key(game, playername));
+    } else {
+        hidden.insert(key(game, playername));
+    }
+}
+
+fn key(game: &str, playername: &str) -> (String, String) {
+    (game.trim().to_lowercase(), playername.trim().to_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hidden_players_are_anonymized() {
+        remember("minecraft", "PrivacyLoutre", false);
+        assert!(is_hidden("minecraft", "PrivacyLoutre"));
+        assert!(is_hidden(" Minecraft ", "privacyloutre "));
+        assert!(!is_hidden("palworld", "PrivacyLoutre"));
+        assert_eq!(display_name("minecraft", "PrivacyLoutre"), ANONYMOUS_PLAYERNAME);
+        assert_eq!(display_name("palworld", "PrivacyLoutre"), "PrivacyLoutre");
+
+        remember("minecraft", "privacyLOUTRE", true);
+        assert!(!is_hidden("minecraft", "PrivacyLoutre"));
+        assert_eq!(display_name("minecraft", "PrivacyLoutre"), "PrivacyLoutre");
+    }
+}
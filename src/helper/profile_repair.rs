@@ -0,0 +1,160 @@
+use colored::Colorize;
+use log::{debug, info, warn};
+use std::error::Error;
+
+use crate::db::repository_default::Database;
+use crate::helper::minecraft_account_formatter::check_and_format_minecraft_uuid;
+use crate::helper::mojang_api::{self, MojangProfile};
+
+/// Retries the Mojang lookup of the Minecraft players created with a provisional profile
+/// (Mojang was rate limiting or unreachable when they joined), and fills their real uuid.
+///
+/// # Returns
+/// Ok(()) if the provisional profiles could be listed, even if some lookups failed again; Err(String) otherwise.
+pub fn run_profile_repair(db: &Database) -> Result<(), String> {
+    let players = db.get_incomplete_minecraft_players().map_err(|e| e.to_string())?;
+    if players.is_empty() {
+        return Ok(());
+    }
+
+    let repaired = repair_profiles(&players, mojang_api::fetch_profile_by_playername, |provisional_id, player_uuid, playername| {
+        db.complete_minecraft_player(provisional_id, player_uuid, playername)
+    });
+    info!(
+        "{} of {} provisional player profiles repaired",
+        repaired.to_string().green().bold(),
+        players.len().to_string().green().bold()
+    );
+    Ok(())
+}
+
+/// Looks up the profile of each provisional player with `lookup`, and completes it with `complete`.
+/// A player whose lookup fails is left for the next run; a rate limit (429) stops the run.
+///
+/// # Returns
+/// The number of profiles completed or merged.
+fn repair_profiles(
+    players: &[(u64, String)],
+    mut lookup: impl FnMut(&str) -> Result<MojangProfile, Box<dyn Error>>,
+    mut complete: impl FnMut(u64, &str, &str) -> Result<u64, mysql::Error>,
+) -> usize {
+    let mut repaired = 0;
+    for (provisional_id, playername) in players {
+        let profile = match lookup(playername) {
+            Ok(profile) => profile,
+            Err(e) => {
+                // Still rate limited : the next run will continue
+                if is_rate_limited(e.as_ref()) {
+                    warn!("Mojang is still rate limiting, profile repair paused");
+                    break;
+                }
+                debug!("Mojang lookup of {} failed again: {}", playername, e);
+                continue;
+            }
+        };
+
+        let player_uuid = match check_and_format_minecraft_uuid(&profile.id) {
+            Ok(uuid) => uuid,
+            Err(e) => {
+                warn!("Invalid Mojang uuid for {}: {}", playername, e);
+                continue;
+            }
+        };
+
+        match complete(*provisional_id, &player_uuid, &profile.name) {
+            Ok(kept_id) if kept_id != *provisional_id => {
+                info!("Provisional profile of {} merged into player {}", playername.green().bold(), kept_id);
+                repaired += 1;
+            }
+            Ok(_) => {
+                debug!("Profile of {} completed with uuid {}", playername, player_uuid);
+                repaired += 1;
+            }
+            Err(e) => warn!("Failed to complete the profile of {}: {}", playername, e),
+        }
+    }
+    repaired
+}
+
+/// Returns true if a lookup failed because Mojang answered 429. The `ureq` error may be boxed once more.
+fn is_rate_limited(e: &(dyn Error + 'static)) -> bool {
+    let status = e
+        .downcast_ref::<ureq::Error>()
+        .or_else(|| e.downcast_ref::<Box<ureq::Error>>().map(|e| e.as_ref()));
+    matches!(status, Some(ureq::Error::Status(429, _)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    const UUID: &str = "069a79f444e94726a5befca90e38aaf5";
+    const DASHED_UUID: &str = "069a79f4-44e9-4726-a5be-fca90e38aaf5";
+
+    fn players(names: &[&str]) -> Vec<(u64, String)> {
+        names.iter().enumerate().map(|(i, name)| (i as u64 + 1, name.to_string())).collect()
+    }
+
+    fn profile(name: &str) -> Result<MojangProfile, Box<dyn Error>> {
+        Ok(MojangProfile { id: UUID.to_string(), name: name.to_string() })
+    }
+
+    fn status(code: u16) -> Box<dyn Error> {
+        let response = ureq::Response::new(code, "status", "").unwrap();
+        Box::new(Box::new(ureq::Error::Status(code, response)))
+    }
+
+    #[test]
+    fn profiles_are_completed_or_merged() {
+        let completed = RefCell::new(Vec::new());
+        let repaired = repair_profiles(&players(&["Loutre", "Steve"]), profile, |id, uuid, name| {
+            completed.borrow_mut().push((id, uuid.to_string(), name.to_string()));
+            // Steve already has a complete row : the provisional one is merged into it
+            Ok(if name == "Steve" { 42 } else { id })
+        });
+        assert_eq!(repaired, 2);
+        assert_eq!(
+            completed.into_inner(),
+            vec![(1, DASHED_UUID.to_string(), "Loutre".to_string()), (2, DASHED_UUID.to_string(), "Steve".to_string())]
+        );
+    }
+
+    #[test]
+    fn a_rate_limit_pauses_the_repair() {
+        let looked_up = RefCell::new(Vec::new());
+        let lookup = |name: &str| {
+            looked_up.borrow_mut().push(name.to_string());
+            if name == "Steve" { Err(status(429)) } else { profile(name) }
+        };
+        let repaired = repair_profiles(&players(&["Loutre", "Steve", "Alex"]), lookup, |id, _, _| Ok(id));
+        assert_eq!(repaired, 1);
+        assert_eq!(looked_up.into_inner(), vec!["Loutre", "Steve"]);
+    }
+
+    #[test]
+    fn other_failures_are_left_for_the_next_run() {
+        let lookup = |name: &str| match name {
+            "Steve" => Err(status(500)),
+            "Alex" => Ok(MojangProfile { id: "not-an-uuid".to_string(), name: name.to_string() }),
+            _ => profile(name),
+        };
+        let completed = RefCell::new(Vec::new());
+        let repaired = repair_profiles(&players(&["Steve", "Alex", "Bob", "Loutre"]), lookup, |id, _, name| {
+            completed.borrow_mut().push(name.to_string());
+            if name == "Bob" { Err(mysql::Error::IoError(std::io::Error::other("lost"))) } else { Ok(id) }
+        });
+        assert_eq!(repaired, 1);
+        assert_eq!(completed.into_inner(), vec!["Bob", "Loutre"]);
+    }
+
+    #[test]
+    fn rate_limits_are_recognized_boxed_or_not() {
+        assert!(is_rate_limited(status(429).as_ref()));
+        let unboxed: Box<dyn Error> = Box::new(ureq::Error::Status(429, ureq::Response::new(429, "status", "").unwrap()));
+        assert!(is_rate_limited(unboxed.as_ref()));
+        assert!(!is_rate_limited(status(404).as_ref()));
+        let timeout: Box<dyn Error> = Box::new(std::io::Error::other("timeout"));
+        assert!(!is_rate_limited(timeout.as_ref()));
+    }
+}
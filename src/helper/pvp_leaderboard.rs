@@ -0,0 +1,68 @@
+use log::error;
+
+use crate::db::repository_default::Database;
+use crate::helper::webhook_discord::DiscordEmbed;
+
+/// Players and pairs listed in each part of the leaderboard
+const LEADERBOARD_SIZE: u32 = 5;
+
+/// Sends the PvP leaderboard of the last `days` days in an embed : the top killers, and the nemesis pairs
+/// (the killers who killed the same victim the most).
+///
+/// # Returns
+/// An error if the leaderboard couldn't be read from `joueurs_pvp`.
+pub fn run_pvp_leaderboard(db: &Database, days: u64) -> Result<(), String> {
+    let since = chrono::Utc::now().naive_utc() - chrono::Duration::days(days as i64);
+    let killers = db.get_pvp_top_killers(since, LEADERBOARD_SIZE).map_err(|e| e.to_string())?;
+    let pairs = db.get_pvp_nemesis_pairs(since, LEADERBOARD_SIZE).map_err(|e| e.to_string())?;
+
+    if let Err(e) = DiscordEmbed::new("mineotter")
+        .title(&format!("Classement PvP des {} derniers jours", days))
+        .description(&leaderboard_description(days, &killers, &pairs))
+        .color("bb2020")
+        .footer("Otternel Service")
+        .timestamp_now()
+        .send()
+    {
+        error!("{e}");
+    }
+    Ok(())
+}
+
+/// Text of the leaderboard : the ranked killers, then the nemesis pairs.
+fn leaderboard_description(days: u64, killers: &[(String, u64)], pairs: &[(String, String, u64)]) -> String {
+    let mut lines = Vec::new();
+    if killers.is_empty() {
+        lines.push(format!("Aucun joueur tué par un autre joueur ces {} derniers jours.", days));
+    } else {
+        lines.push("**Meilleurs tueurs**".to_string());
+        for (rank, (playername, kills)) in killers.iter().enumerate() {
+            lines.push(format!("{}. {} : {} kills", rank + 1, playername, kills));
+        }
+        lines.push("**Némésis**".to_string());
+        for (killer, victim, kills) in pairs {
+            lines.push(format!("{} a tué {} {} fois", killer, victim, kills));
+        }
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn leaderboard_lists_the_killers_then_the_pairs() {
+        let killers = vec![("Loutre".to_string(), 5), ("Castor".to_string(), 2)];
+        let pairs = vec![("Loutre".to_string(), "Castor".to_string(), 4), ("Castor".to_string(), "Loutre".to_string(), 2)];
+        assert_eq!(
+            leaderboard_description(7, &killers, &pairs),
+            "**Meilleurs tueurs**\n1. Loutre : 5 kills\n2. Castor : 2 kills\n**Némésis**\nLoutre a tué Castor 4 fois\nCastor a tué Loutre 2 fois"
+        );
+    }
+
+    #[test]
+    fn quiet_weeks_say_so() {
+        assert_eq!(leaderboard_description(7, &[], &[]), "Aucun joueur tué par un autre joueur ces 7 derniers jours.");
+    }
+}
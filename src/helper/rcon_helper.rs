@@ -1,7 +1,13 @@
+use crate::config::Config;
 use crate::db::repository_default::Database;
+use log::debug;
 use rcon::Connection;
-use std::net::ToSocketAddrs;
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::Duration;
+use super::dry_run;
 use super::open_database::open_db_from_env;
+use crate::serverlog::self_guard;
 
 use thiserror::Error;
 
@@ -23,7 +29,30 @@ pub enum RconHelperError {
     DbInitError,
 
     #[error("Failed to resolve address: {0}")]
-    DnsResolutionFailed(String),
+    AddressResolution(String),
+
+    #[error("RCON timeout on the active server {0}")]
+    Timeout(u64),
+}
+
+/// Open RCON connection of an active server, shared by the helpers (one command at a time on each)
+type ConnectionSlot = Arc<tokio::sync::Mutex<Option<Connection>>>;
+
+/// RCON connections kept open, by active server id
+static CONNECTIONS: LazyLock<Mutex<HashMap<u64, ConnectionSlot>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+fn connection_slot(active_server_id: u64) -> ConnectionSlot {
+    CONNECTIONS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .entry(active_server_id)
+        .or_default()
+        .clone()
+}
+
+/// Timeout of the connection and of each command (`RCON_TIMEOUT_SECS`, Default 5).
+fn rcon_timeout() -> Duration {
+    Duration::from_secs(Config::current().rcon_timeout_secs.max(1))
 }
 
 pub struct RconHelper {
@@ -43,6 +72,12 @@ impl RconHelper {
     /// * `active_server_id` - The ID from the `serveurs_actifs` table.
     /// * `command` - The command string to execute (no leading slash).
     ///
+    /// The connection of the server is kept open for the next commands. If it was closed meanwhile,
+    /// the command is sent again on a new connection. A command timing out is not sent again
+    /// (the server may have run it), its connection is closed.
+    ///
+    /// In dry-run (`OTTERNEL_DRY_RUN`), the command is only logged and an empty response is returned.
+    ///
     /// # Returns
     /// The string response from the server.
     pub async fn execute_command(
@@ -50,25 +85,57 @@ impl RconHelper {
         active_server_id: u64,
         command: &str,
     ) -> Result<String, RconHelperError> {
+        if dry_run::skip(|| format!("rcon server {}: {}", active_server_id, command)) {
+            return Ok(String::new());
+        }
+
+        let timeout = rcon_timeout();
+        // Tag the command so the lines it writes in the log don't trigger Otternel again
+        let command = self_guard::tag_command(command);
+
+        let slot = connection_slot(active_server_id);
+        let mut cached = slot.lock().await;
+        if let Some(conn) = cached.as_mut() {
+            match tokio::time::timeout(timeout, conn.cmd(&command)).await {
+                Ok(Ok(response)) => return Ok(response),
+                Ok(Err(e)) => debug!("RCON connection of server {} lost ({}), reconnecting", active_server_id, e),
+                Err(_) => {
+                    *cached = None;
+                    return Err(RconHelperError::Timeout(active_server_id));
+                }
+            }
+            *cached = None;
+        }
+
+        let mut conn = self.connect(active_server_id, timeout).await?;
+        let response = tokio::time::timeout(timeout, conn.cmd(&command))
+            .await
+            .map_err(|_| RconHelperError::Timeout(active_server_id))??;
+        *cached = Some(conn);
+        Ok(response)
+    }
+
+    /// Opens a new RCON connection to an active server, within `timeout`.
+    async fn connect(&self, active_server_id: u64, timeout: Duration) -> Result<Connection, RconHelperError> {
         let rcon_params = self
             .db
-            .get_rcon_params_by_id(active_server_id)?
+            .call(move |db| db.get_rcon_params_by_id(active_server_id))
+            .await?
             .ok_or(RconHelperError::ServerNotFound(active_server_id))?;
 
         let addr = format!("{}:{}", rcon_params.host, rcon_params.port);
-
-        let resolved_addr = addr
-            .to_socket_addrs()
-            .map_err(|e| RconHelperError::RconError(rcon::Error::from(e)))?
+        let resolved_addr = tokio::time::timeout(timeout, tokio::net::lookup_host(addr.clone()))
+            .await
+            .map_err(|_| RconHelperError::AddressResolution(format!("{} (timeout)", addr)))?
+            .map_err(|e| RconHelperError::AddressResolution(format!("{} ({})", addr, e)))?
             .next()
-            .ok_or_else(|| RconHelperError::DnsResolutionFailed(addr.clone()))?;
+            .ok_or_else(|| RconHelperError::AddressResolution(addr.clone()))?;
 
-        let mut conn = Connection::builder()
-            .connect(resolved_addr, &rcon_params.password)
-            .await?;
-
-        let response = conn.cmd(command).await?;
-        Ok(response)
+        let conn = tokio::time::timeout(timeout, Connection::builder().connect(resolved_addr, &rcon_params.password))
+            .await
+            .map_err(|_| RconHelperError::Timeout(active_server_id))??;
+        debug!("RCON connection opened to server {} ({})", active_server_id, addr);
+        Ok(conn)
     }
 
     /// Sends a command to every active & global server.
@@ -79,11 +146,12 @@ impl RconHelper {
     /// # Returns
     /// A `Vec` of tuples `(active_id, Result<String, RconHelperError>)` — one entry per server,
     /// allowing partial failures without interrupting the whole broadcast.
+    #[allow(dead_code)]
     pub async fn broadcast_command_to_active_global_servers(
         &self,
         command: &str,
     ) -> Vec<(u64, Result<String, RconHelperError>)> {
-        let servers = match self.db.get_all_active_global_servers() {
+        let servers = match self.db.call(|db| db.get_all_active_global_servers()).await {
             Ok(s) => s,
             Err(e) => return vec![(0, Err(RconHelperError::DatabaseError(e)))],
         };
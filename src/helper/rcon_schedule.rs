@@ -0,0 +1,264 @@
+use chrono::{Datelike, NaiveDateTime, Timelike};
+use colored::Colorize;
+use log::{error, info, warn};
+use std::collections::HashMap;
+
+use crate::db::models::RconSchedule;
+use crate::helper::rcon_helper::RconHelper;
+use crate::helper::webhook_discord::DiscordEmbed;
+
+/// A 5 fields cron expression : `minute hour day-of-month month day-of-week`.
+/// Each field is `*`, a value, a range `a-b`, a step `*/n` or `a-b/n`, or a list of these separated by commas.
+/// The day of week goes from 0 (sunday) to 6, 7 being sunday too.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CronExpression {
+    minutes: Vec<bool>,
+    hours: Vec<bool>,
+    days: Vec<bool>,
+    months: Vec<bool>,
+    weekdays: Vec<bool>,
+    /// Whether the day-of-month and day-of-week fields aren't `*` (a day then matches either of them, as in cron)
+    days_restricted: bool,
+    weekdays_restricted: bool,
+}
+
+impl CronExpression {
+    /// Parses a cron expression.
+    ///
+    /// # Errors
+    /// Returns a message saying which field is invalid.
+    pub fn parse(expression: &str) -> Result<Self, String> {
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(format!("expected 5 fields, got {}", fields.len()));
+        };
+        let mut weekdays = parse_field(weekday, 0, 7).map_err(|e| format!("day of week: {}", e))?;
+        if weekdays[7] {
+            weekdays[0] = true;
+        }
+        Ok(Self {
+            minutes: parse_field(minute, 0, 59).map_err(|e| format!("minute: {}", e))?,
+            hours: parse_field(hour, 0, 23).map_err(|e| format!("hour: {}", e))?,
+            days: parse_field(day, 1, 31).map_err(|e| format!("day of month: {}", e))?,
+            months: parse_field(month, 1, 12).map_err(|e| format!("month: {}", e))?,
+            weekdays,
+            days_restricted: day != "*",
+            weekdays_restricted: weekday != "*",
+        })
+    }
+
+    /// Tells whether the minute of `datetime` is an occurrence of the expression.
+    pub fn matches(&self, datetime: &NaiveDateTime) -> bool {
+        let day = self.days[datetime.day() as usize];
+        let weekday = self.weekdays[datetime.weekday().num_days_from_sunday() as usize];
+        let day_matches = match (self.days_restricted, self.weekdays_restricted) {
+            (true, true) => day || weekday,
+            _ => day && weekday,
+        };
+        self.minutes[datetime.minute() as usize]
+            && self.hours[datetime.hour() as usize]
+            && self.months[datetime.month() as usize]
+            && day_matches
+    }
+}
+
+/// Parses a cron field to the values it allows, indexed by value (`max + 1` entries).
+fn parse_field(field: &str, min: u32, max: u32) -> Result<Vec<bool>, String> {
+    let mut allowed = vec![false; max as usize + 1];
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().ok().filter(|step| *step > 0).ok_or(format!("invalid step in {}", part))?),
+            None => (part, 1),
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((start, end)) => (parse_value(start, min, max)?, parse_value(end, min, max)?),
+                // `5/15` goes from 5 to the end, as in cron
+                None if step > 1 => (parse_value(range, min, max)?, max),
+                None => {
+                    let value = parse_value(range, min, max)?;
+                    (value, value)
+                }
+            },
+        };
+        if start > end {
+            return Err(format!("empty range {}", part));
+        }
+        for value in (start..=end).step_by(step as usize) {
+            allowed[value as usize] = true;
+        }
+    }
+    Ok(allowed)
+}
+
+fn parse_value(value: &str, min: u32, max: u32) -> Result<u32, String> {
+    value
+        .parse::<u32>()
+        .ok()
+        .filter(|value| (min..=max).contains(value))
+        .ok_or(format!("{} is not between {} and {}", value, min, max))
+}
+
+/// RCON schedules with their parsed expression, and the last occurrence run of each.
+#[derive(Default)]
+pub struct RconScheduler {
+    schedules: Vec<(RconSchedule, CronExpression)>,
+    /// Last minute run by schedule id, so an occurrence is never run twice (several ticks in the same minute,
+    /// reload of the schedules in between)
+    last_run: HashMap<u64, NaiveDateTime>,
+}
+
+impl RconScheduler {
+    /// Replaces the schedules (ex: by the ones of `Database::get_active_rcon_schedules`).
+    /// A schedule with an invalid expression is skipped. The occurrences already run are kept.
+    pub fn reload(&mut self, schedules: Vec<RconSchedule>) {
+        self.schedules = schedules
+            .into_iter()
+            .filter_map(|schedule| match CronExpression::parse(&schedule.cron_expression) {
+                Ok(expression) => Some((schedule, expression)),
+                Err(e) => {
+                    warn!("RCON schedule {} skipped, invalid cron '{}': {}", schedule.id, schedule.cron_expression, e);
+                    None
+                }
+            })
+            .collect();
+        let ids: Vec<u64> = self.schedules.iter().map(|(schedule, _)| schedule.id).collect();
+        self.last_run.retain(|id, _| ids.contains(id));
+    }
+
+    /// Number of schedules loaded
+    pub fn count(&self) -> usize {
+        self.schedules.len()
+    }
+
+    /// Returns the schedules due at `now` (local time) not run yet for this minute, and marks them as run.
+    pub fn take_due(&mut self, now: NaiveDateTime) -> Vec<RconSchedule> {
+        let Some(minute) = now.with_second(0).and_then(|now| now.with_nanosecond(0)) else {
+            return Vec::new();
+        };
+        let mut due = Vec::new();
+        for (schedule, expression) in &self.schedules {
+            if expression.matches(&minute) && self.last_run.get(&schedule.id) != Some(&minute) {
+                self.last_run.insert(schedule.id, minute);
+                due.push(schedule.clone());
+            }
+        }
+        due
+    }
+}
+
+/// Runs a scheduled command, and announces a failure in the admin channel.
+pub async fn run_schedule(rcon: &RconHelper, schedule: &RconSchedule) {
+    match rcon.execute_command(schedule.serveur_actif_id, &schedule.commande).await {
+        Ok(_) => info!(
+            "Scheduled RCON command '{}' run on server {}",
+            schedule.commande.green(),
+            schedule.serveur_actif_id
+        ),
+        Err(e) => {
+            error!("Scheduled RCON command '{}' failed on server {}: {}", schedule.commande, schedule.serveur_actif_id, e);
+            let description = format!(
+                "La commande planifiée `{}` ({}) n'a pas pu être exécutée sur le serveur actif {} : {}",
+                schedule.commande, schedule.cron_expression, schedule.serveur_actif_id, e
+            );
+            if let Err(e) = DiscordEmbed::new("otternel")
+                .title("Commande RCON planifiée en échec")
+                .description(&description)
+                .color("601010")
+                .timestamp_now()
+                .send()
+            {
+                error!("{e}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    /// `2025-01-<day> hour:minute`, the 5th of january 2025 being a sunday
+    fn at(day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2025, 1, day).unwrap().and_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn expressions_match_their_occurrences() {
+        let cases = [
+            ("* * * * *", at(6, 13, 37), true),
+            ("30 4 * * *", at(6, 4, 30), true),
+            ("30 4 * * *", at(6, 4, 31), false),
+            // Steps
+            ("*/15 * * * *", at(6, 10, 45), true),
+            ("*/15 * * * *", at(6, 10, 50), false),
+            ("5/20 * * * *", at(6, 10, 45), true),
+            ("5/20 * * * *", at(6, 10, 40), false),
+            ("0 8-18/2 * * *", at(6, 14, 0), true),
+            ("0 8-18/2 * * *", at(6, 15, 0), false),
+            // Ranges and lists
+            ("0 9-17 * * *", at(6, 17, 0), true),
+            ("0 9-17 * * *", at(6, 18, 0), false),
+            ("0,30 12 * * *", at(6, 12, 30), true),
+            ("0,30 12 * * *", at(6, 12, 15), false),
+            // Day of week : 0 and 7 are sunday, 1 monday
+            ("0 12 * * 0", at(5, 12, 0), true),
+            ("0 12 * * 7", at(5, 12, 0), true),
+            ("0 12 * * 1", at(6, 12, 0), true),
+            ("0 12 * * 1", at(7, 12, 0), false),
+            ("0 12 * * 1-5", at(10, 12, 0), true),
+            ("0 12 * * 1-5", at(11, 12, 0), false),
+            // Day of month and month
+            ("0 0 1 1 *", at(1, 0, 0), true),
+            ("0 0 1 2 *", at(1, 0, 0), false),
+            // Both day fields set : either of them matches, as in cron
+            ("0 0 15 * 1", at(6, 0, 0), true),
+            ("0 0 15 * 1", at(15, 0, 0), true),
+            ("0 0 15 * 1", at(14, 0, 0), false),
+        ];
+        for (expression, datetime, expected) in cases {
+            let parsed = CronExpression::parse(expression).unwrap();
+            assert_eq!(parsed.matches(&datetime), expected, "'{}' at {}", expression, datetime);
+        }
+    }
+
+    #[test]
+    fn invalid_expressions_name_their_field() {
+        let cases = [
+            ("* * * *", "expected 5 fields, got 4"),
+            ("60 * * * *", "minute: 60 is not between 0 and 59"),
+            ("* 24 * * *", "hour: 24 is not between 0 and 23"),
+            ("* * 0 * *", "day of month: 0 is not between 1 and 31"),
+            ("* * * 13 *", "month: 13 is not between 1 and 12"),
+            ("* * * * 8", "day of week: 8 is not between 0 and 7"),
+            ("*/0 * * * *", "minute: invalid step in */0"),
+            ("* 18-8 * * *", "hour: empty range 18-8"),
+            ("a * * * *", "minute: a is not between 0 and 59"),
+        ];
+        for (expression, error) in cases {
+            assert_eq!(CronExpression::parse(expression), Err(error.to_string()), "'{}'", expression);
+        }
+    }
+
+    fn schedule(id: u64, cron_expression: &str) -> RconSchedule {
+        RconSchedule { id, serveur_actif_id: 1, cron_expression: cron_expression.to_string(), commande: "save-all".to_string() }
+    }
+
+    #[test]
+    fn an_occurrence_is_taken_once_per_minute() {
+        let mut scheduler = RconScheduler::default();
+        scheduler.reload(vec![schedule(1, "*/5 * * * *"), schedule(2, "not a cron")]);
+        assert_eq!(scheduler.count(), 1, "the invalid schedule is skipped");
+
+        assert_eq!(scheduler.take_due(at(6, 10, 5)).len(), 1);
+        assert!(scheduler.take_due(at(6, 10, 5) + chrono::Duration::seconds(30)).is_empty());
+        assert!(scheduler.take_due(at(6, 10, 6)).is_empty());
+
+        // A reload keeps the occurrences already run
+        scheduler.reload(vec![schedule(1, "*/5 * * * *")]);
+        assert!(scheduler.take_due(at(6, 10, 5)).is_empty());
+        assert_eq!(scheduler.take_due(at(6, 10, 10)).len(), 1);
+    }
+}
@@ -0,0 +1,141 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Durations recorded over a rolling window, to compute percentiles (p50, p95...) of recent values.
+///
+/// Samples older than the window are dropped, and at most `max_samples` are kept so a burst
+/// can't grow the memory without bound.
+#[derive(Debug, Clone)]
+pub struct RollingHistogram {
+    window: Duration,
+    max_samples: usize,
+    samples: VecDeque<(Instant, Duration)>,
+}
+
+impl RollingHistogram {
+    pub fn new(window: Duration, max_samples: usize) -> Self {
+        Self {
+            window,
+            max_samples: max_samples.max(1),
+            samples: VecDeque::new(),
+        }
+    }
+
+    /// Records a value now.
+    pub fn record(&mut self, value: Duration) {
+        self.record_at(value, Instant::now());
+    }
+
+    /// Records a value at a given instant.
+    pub fn record_at(&mut self, value: Duration, now: Instant) {
+        self.prune(now);
+        if self.samples.len() >= self.max_samples {
+            self.samples.pop_front();
+        }
+        self.samples.push_back((now, value));
+    }
+
+    /// Number of samples in the window.
+    pub fn count(&mut self) -> usize {
+        self.prune(Instant::now());
+        self.samples.len()
+    }
+
+    /// Returns the `p` percentile (0 to 100) of the values in the window, or `None` if it is empty.
+    pub fn percentile(&mut self, p: f64) -> Option<Duration> {
+        self.percentile_at(p, Instant::now())
+    }
+
+    /// Same as [`RollingHistogram::percentile`], with the current instant given explicitly.
+    pub fn percentile_at(&mut self, p: f64, now: Instant) -> Option<Duration> {
+        self.prune(now);
+        if self.samples.is_empty() {
+            return None;
+        }
+
+        let mut values: Vec<Duration> = self.samples.iter().map(|(_, value)| *value).collect();
+        values.sort_unstable();
+
+        // Nearest-rank percentile
+        let p = p.clamp(0.0, 100.0);
+        let rank = ((p / 100.0) * values.len() as f64).ceil() as usize;
+        Some(values[rank.saturating_sub(1).min(values.len() - 1)])
+    }
+
+    /// Drops the samples older than the window.
+    fn prune(&mut self, now: Instant) {
+        while let Some((at, _)) = self.samples.front() {
+            if now.saturating_duration_since(*at) > self.window {
+                self.samples.pop_front();
+            } else {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(value: u64) -> Duration {
+        Duration::from_millis(value)
+    }
+
+    #[test]
+    fn empty_histogram_has_no_percentile() {
+        let mut histogram = RollingHistogram::new(Duration::from_secs(60), 10);
+        assert_eq!(histogram.percentile(50.0), None);
+        assert_eq!(histogram.count(), 0);
+    }
+
+    #[test]
+    fn percentiles_use_the_nearest_rank() {
+        let mut histogram = RollingHistogram::new(Duration::from_secs(60), 1000);
+        let now = Instant::now();
+        // Recorded out of order, the percentiles sort the values
+        for value in (1..=20).rev() {
+            histogram.record_at(ms(value * 10), now);
+        }
+
+        assert_eq!(histogram.percentile_at(50.0, now), Some(ms(100)));
+        assert_eq!(histogram.percentile_at(95.0, now), Some(ms(190)));
+        assert_eq!(histogram.percentile_at(100.0, now), Some(ms(200)));
+        assert_eq!(histogram.percentile_at(0.0, now), Some(ms(10)));
+        // Out of range percentiles are clamped
+        assert_eq!(histogram.percentile_at(150.0, now), Some(ms(200)));
+    }
+
+    #[test]
+    fn single_sample_is_every_percentile() {
+        let mut histogram = RollingHistogram::new(Duration::from_secs(60), 10);
+        let now = Instant::now();
+        histogram.record_at(ms(42), now);
+        assert_eq!(histogram.percentile_at(50.0, now), Some(ms(42)));
+        assert_eq!(histogram.percentile_at(95.0, now), Some(ms(42)));
+    }
+
+    #[test]
+    fn samples_older_than_the_window_are_dropped() {
+        let mut histogram = RollingHistogram::new(Duration::from_secs(60), 100);
+        let start = Instant::now();
+        histogram.record_at(ms(1000), start);
+        histogram.record_at(ms(10), start + Duration::from_secs(30));
+
+        assert_eq!(histogram.percentile_at(95.0, start + Duration::from_secs(60)), Some(ms(1000)));
+        // 61 seconds later, the slow sample left the window
+        assert_eq!(histogram.percentile_at(95.0, start + Duration::from_secs(61)), Some(ms(10)));
+        assert_eq!(histogram.percentile_at(50.0, start + Duration::from_secs(200)), None);
+    }
+
+    #[test]
+    fn oldest_samples_are_dropped_beyond_the_max() {
+        let mut histogram = RollingHistogram::new(Duration::from_secs(60), 3);
+        let now = Instant::now();
+        for value in [500, 1, 2, 3] {
+            histogram.record_at(ms(value), now);
+        }
+        assert_eq!(histogram.samples.len(), 3);
+        assert_eq!(histogram.percentile_at(100.0, now), Some(ms(3)));
+    }
+}
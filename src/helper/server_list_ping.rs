@@ -0,0 +1,245 @@
+use colored::Colorize;
+use log::{debug, error, info, warn};
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use crate::config::Config;
+use crate::db::models::{ServeurPing, ServeurPingCible};
+use crate::db::repository_default::Database;
+use crate::helper;
+use crate::helper::webhook_discord::DiscordEmbed;
+
+/// Time given to a server to answer the whole ping
+const PING_TIMEOUT: Duration = Duration::from_secs(5);
+/// A server is reported once it failed this many pings in a row
+const ALERT_AFTER_FAILURES: u32 = 3;
+/// Largest status answer read (the JSON holds the favicon, a few dozen KiB)
+const MAX_PACKET_LEN: usize = 1024 * 1024;
+
+/// Pings failed in a row, by active server id
+static FAILURES: LazyLock<Mutex<HashMap<u64, u32>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Interval of the `server_status_probe` task, `STATUS_PROBE_EVERY_SEC` (default 60, 0 to disable).
+pub fn probe_every() -> Option<Duration> {
+    let every_sec = Config::current().status_probe_every_sec;
+    (every_sec > 0).then(|| Duration::from_secs(every_sec))
+}
+
+/// Pings every active Minecraft server having a `ping_host` and a `ping_port`, and records the results in
+/// `serveurs_ping_log` (run on demand through the jobs API, each server is otherwise pinged by its own task).
+///
+/// # Returns
+/// Ok(()) once every server is pinged; Err(String) if the servers couldn't be listed.
+pub async fn run_status_probe(db: Arc<Database>) -> Result<(), String> {
+    let servers = db
+        .call(|db| db.get_minecraft_servers_to_ping())
+        .await
+        .map_err(|e| e.to_string())?;
+
+    for server in servers {
+        probe(&db, server).await;
+    }
+    Ok(())
+}
+
+/// Task pinging one active server every `period`, started and stopped with the server (see `ServerTasks`).
+/// A server without `ping_host` or `ping_port` is skipped at each round, so setting them needs no restart.
+pub async fn probe_loop(db: Arc<Database>, active_id: u64, period: Duration) {
+    let mut interval = tokio::time::interval(period);
+    loop {
+        interval.tick().await;
+        let target = db
+            .call(move |db| db.get_minecraft_servers_to_ping())
+            .await
+            .map(|servers| servers.into_iter().find(|server| server.active_id == active_id));
+        match target {
+            Ok(Some(server)) => probe(&db, server).await,
+            Ok(None) => debug!("Active server {} has no ping address, not pinged", active_id),
+            Err(e) => warn!("Failed to load the ping address of the active server {}: {}", active_id, e),
+        }
+    }
+}
+
+/// Pings a server and records the result. A server failing `ALERT_AFTER_FAILURES` pings in a row is reported
+/// on the `otternel` webhook, and again when it answers back.
+async fn probe(db: &Arc<Database>, server: ServeurPingCible) {
+    let ping = match ping(&server.host, server.port).await {
+        Ok(ping) => {
+            debug!(
+                "{} answered in {} ms ({} players)",
+                server.nom,
+                ping.latence_ms.unwrap_or_default(),
+                ping.nb_joueurs.unwrap_or_default()
+            );
+            ping
+        }
+        Err(e) => {
+            warn!("Server List Ping of {} ({}:{}) failed: {}", server.nom.yellow(), server.host, server.port, e);
+            ServeurPing::default()
+        }
+    };
+    track_failures(&server, ping.en_ligne);
+
+    if helper::dry_run::skip(|| format!("ping of {} recorded", server.nom)) {
+        return;
+    }
+    let active_id = server.active_id;
+    if let Err(e) = db.call(move |db| db.insert_server_ping(active_id, &ping)).await {
+        warn!("Failed to record the ping of {}: {:?}", server.nom, e);
+    }
+}
+
+/// Counts the failed pings of a server, and reports it when it reaches `ALERT_AFTER_FAILURES` or answers back after.
+fn track_failures(server: &ServeurPingCible, answered: bool) {
+    let failures = {
+        let mut failures = FAILURES.lock().unwrap_or_else(|e| e.into_inner());
+        if answered {
+            failures.remove(&server.active_id).unwrap_or(0)
+        } else {
+            let count = failures.entry(server.active_id).or_default();
+            *count += 1;
+            *count
+        }
+    };
+
+    let embed = if !answered && failures == ALERT_AFTER_FAILURES {
+        error!("{} didn't answer {} pings in a row", server.nom.red().bold(), failures);
+        DiscordEmbed::new("otternel")
+            .title(&format!("{} ne répond plus", server.nom))
+            .description(&format!(
+                "{} est actif mais n'a pas répondu à {} pings de suite ({}:{}). Le serveur est peut-être figé.",
+                server.nom, failures, server.host, server.port
+            ))
+            .color("c02020")
+    } else if answered && failures >= ALERT_AFTER_FAILURES {
+        info!("{} answers again after {} failed pings", server.nom.green().bold(), failures);
+        DiscordEmbed::new("otternel")
+            .title(&format!("{} répond de nouveau", server.nom))
+            .description(&format!("{} répond de nouveau après {} pings sans réponse.", server.nom, failures))
+            .color("126020")
+    } else {
+        return;
+    };
+
+    if helper::dry_run::skip(|| format!("ping alert of {} sent", server.nom)) {
+        return;
+    }
+    if let Err(e) = embed.footer("Otternel Service").timestamp_now().send() {
+        error!("{e}");
+    }
+}
+
+/// Server List Ping of a Minecraft server : handshake, then status request.
+/// The latency is the time between the status request and its answer.
+pub async fn ping(host: &str, port: u16) -> Result<ServeurPing, String> {
+    tokio::time::timeout(PING_TIMEOUT, exchange(host, port))
+        .await
+        .map_err(|_| format!("no answer after {}s", PING_TIMEOUT.as_secs()))?
+}
+
+async fn exchange(host: &str, port: u16) -> Result<ServeurPing, String> {
+    let mut stream = TcpStream::connect((host, port)).await.map_err(|e| e.to_string())?;
+
+    // Handshake : protocol version (-1 when unknown), address, port, next state 1 (status)
+    let mut handshake = Vec::new();
+    write_varint(&mut handshake, 0x00);
+    write_varint(&mut handshake, -1);
+    write_varint(&mut handshake, host.len() as i32);
+    handshake.extend_from_slice(host.as_bytes());
+    handshake.extend_from_slice(&port.to_be_bytes());
+    write_varint(&mut handshake, 1);
+    send_packet(&mut stream, &handshake).await?;
+
+    let started = Instant::now();
+    send_packet(&mut stream, &[0x00]).await?;
+
+    let length = read_varint(&mut stream).await? as usize;
+    if length == 0 || length > MAX_PACKET_LEN {
+        return Err(format!("invalid packet length {length}"));
+    }
+    let mut packet = vec![0; length];
+    stream.read_exact(&mut packet).await.map_err(|e| e.to_string())?;
+    let latence_ms = started.elapsed().as_millis() as u32;
+
+    let mut cursor = packet.as_slice();
+    if read_varint(&mut cursor).await? != 0x00 {
+        return Err("unexpected packet instead of the status".to_string());
+    }
+    let json_len = read_varint(&mut cursor).await? as usize;
+    let json = cursor.get(..json_len).ok_or("truncated status")?;
+    let status: serde_json::Value = serde_json::from_slice(json).map_err(|e| format!("invalid status: {e}"))?;
+
+    let as_u32 = |value: &serde_json::Value| value.as_u64().map(|v| v as u32);
+    Ok(ServeurPing {
+        en_ligne: true,
+        latence_ms: Some(latence_ms),
+        version: status["version"]["name"].as_str().map(str::to_string),
+        nb_joueurs: as_u32(&status["players"]["online"]),
+        nb_joueurs_max: as_u32(&status["players"]["max"]),
+        motd: Some(strip_formatting(&motd_text(&status["description"]))).filter(|motd| !motd.is_empty()),
+    })
+}
+
+/// Sends a packet prefixed by its length.
+async fn send_packet(stream: &mut TcpStream, data: &[u8]) -> Result<(), String> {
+    let mut packet = Vec::with_capacity(data.len() + 5);
+    write_varint(&mut packet, data.len() as i32);
+    packet.extend_from_slice(data);
+    stream.write_all(&packet).await.map_err(|e| e.to_string())
+}
+
+fn write_varint(out: &mut Vec<u8>, value: i32) {
+    let mut value = value as u32;
+    loop {
+        if value & !0x7f == 0 {
+            out.push(value as u8);
+            return;
+        }
+        out.push((value & 0x7f) as u8 | 0x80);
+        value >>= 7;
+    }
+}
+
+async fn read_varint<R: AsyncReadExt + Unpin>(reader: &mut R) -> Result<i32, String> {
+    let mut value: u32 = 0;
+    for position in 0..5 {
+        let byte = reader.read_u8().await.map_err(|e| e.to_string())?;
+        value |= ((byte & 0x7f) as u32) << (7 * position);
+        if byte & 0x80 == 0 {
+            return Ok(value as i32);
+        }
+    }
+    Err("varint too long".to_string())
+}
+
+/// Text of a MOTD, given as a string or as a chat component (`text` and its `extra`).
+fn motd_text(description: &serde_json::Value) -> String {
+    match description {
+        serde_json::Value::String(text) => text.clone(),
+        serde_json::Value::Object(component) => {
+            let mut text = component.get("text").and_then(|t| t.as_str()).unwrap_or_default().to_string();
+            for extra in component.get("extra").and_then(|e| e.as_array()).into_iter().flatten() {
+                text.push_str(&motd_text(extra));
+            }
+            text
+        }
+        _ => String::new(),
+    }
+}
+
+/// Removes the `§` formatting codes of a MOTD and joins its lines.
+fn strip_formatting(motd: &str) -> String {
+    let mut text = String::with_capacity(motd.len());
+    let mut chars = motd.chars();
+    while let Some(c) = chars.next() {
+        if c == '§' {
+            chars.next();
+        } else {
+            text.push(c);
+        }
+    }
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
@@ -0,0 +1,152 @@
+use chrono::{NaiveDateTime, Utc};
+use colored::Colorize;
+use log::{error, info, warn};
+use std::collections::HashMap;
+use std::sync::{LazyLock, RwLock};
+
+use crate::db::models::ServeurMute;
+use crate::db::repository_default::Database;
+use crate::helper::webhook_discord::DiscordEmbed;
+
+/// Active mutes, by serverlog_id (id of the `serveurs_actifs` table)
+static MUTES: LazyLock<RwLock<HashMap<u64, ServeurMute>>> = LazyLock::new(|| RwLock::new(HashMap::new()));
+
+/// Returns true if the Discord announcements of a server are muted right now.
+/// Database writes of the server events are never muted, only the embeds.
+pub fn is_muted(serverlog_id: u32) -> bool {
+    is_muted_at(serverlog_id, Utc::now().naive_utc())
+}
+
+/// Same as [`is_muted`], with the current UTC datetime given explicitly.
+pub fn is_muted_at(serverlog_id: u32, now: NaiveDateTime) -> bool {
+    MUTES
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(&(serverlog_id as u64))
+        .is_some_and(|mute| mute.fin > now)
+}
+
+/// Returns the mutes not expired yet.
+pub fn active_mutes() -> Vec<ServeurMute> {
+    let now = Utc::now().naive_utc();
+    let mut mutes: Vec<ServeurMute> = MUTES
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .values()
+        .filter(|mute| mute.fin > now)
+        .cloned()
+        .collect();
+    mutes.sort_by_key(|mute| mute.serveur_actif_id);
+    mutes
+}
+
+/// Loads the mutes stored in database, so they survive a restart.
+pub fn load_mutes(db: &Database) {
+    match db.get_active_server_mutes() {
+        Ok(mutes) => {
+            info!("{} active server mutes loaded", mutes.len().to_string().green().bold());
+            let mut map = MUTES.write().unwrap_or_else(|e| e.into_inner());
+            for mute in mutes {
+                map.insert(mute.serveur_actif_id, mute);
+            }
+        }
+        Err(e) => error!("Failed to load server mutes: {}", e),
+    }
+}
+
+/// Mutes the announcements of a server for `duration`, replacing any existing mute of this server.
+/// The mute is stored in memory and in database, and announced in the admin channel.
+pub fn mute(db: Option<&Database>, serverlog_id: u64, duration: chrono::Duration, reason: Option<String>) -> ServeurMute {
+    let mute = ServeurMute {
+        serveur_actif_id: serverlog_id,
+        fin: Utc::now().naive_utc() + duration,
+        raison: reason,
+    };
+
+    MUTES.write().unwrap_or_else(|e| e.into_inner()).insert(serverlog_id, mute.clone());
+    if let Some(db) = db
+        && let Err(e) = db.upsert_server_mute(&mute)
+    {
+        warn!("Failed to store the mute of server {}: {}", serverlog_id, e);
+    }
+
+    announce(db, &mute, true);
+    mute
+}
+
+/// Removes the mute of a server before its end.
+///
+/// # Returns
+/// The removed mute, or `None` if the server wasn't muted.
+pub fn unmute(db: Option<&Database>, serverlog_id: u64) -> Option<ServeurMute> {
+    let mute = MUTES.write().unwrap_or_else(|e| e.into_inner()).remove(&serverlog_id)?;
+    if let Some(db) = db
+        && let Err(e) = db.delete_server_mute(serverlog_id)
+    {
+        warn!("Failed to delete the mute of server {}: {}", serverlog_id, e);
+    }
+
+    announce(db, &mute, false);
+    Some(mute)
+}
+
+/// Removes the expired mutes and announces their end.
+///
+/// # Returns
+/// The number of expired mutes.
+pub fn expire_mutes(db: Option<&Database>) -> usize {
+    let expired = take_expired(Utc::now().naive_utc());
+    for mute in &expired {
+        if let Some(db) = db
+            && let Err(e) = db.delete_server_mute(mute.serveur_actif_id)
+        {
+            warn!("Failed to delete the mute of server {}: {}", mute.serveur_actif_id, e);
+        }
+        announce(db, mute, false);
+    }
+    expired.len()
+}
+
+/// Removes from memory the mutes ended at `now`.
+fn take_expired(now: NaiveDateTime) -> Vec<ServeurMute> {
+    let mut map = MUTES.write().unwrap_or_else(|e| e.into_inner());
+    let expired_ids: Vec<u64> = map
+        .values()
+        .filter(|mute| mute.fin <= now)
+        .map(|mute| mute.serveur_actif_id)
+        .collect();
+    expired_ids.iter().filter_map(|id| map.remove(id)).collect()
+}
+
+/// Sends the start or the end of a mute in the admin channel.
+fn announce(db: Option<&Database>, mute: &ServeurMute, started: bool) {
+    let server_name = db
+        .and_then(|db| db.get_server_by_active_server_id(mute.serveur_actif_id).ok().flatten())
+        .map(|server| server.nom)
+        .unwrap_or_else(|| format!("Serveur {}", mute.serveur_actif_id));
+
+    let supertext = if started {
+        let mut text = format!(
+            "Les annonces de {} sont coupées jusqu'au {} UTC.",
+            server_name,
+            mute.fin.format("%d/%m/%Y %H:%M")
+        );
+        if let Some(reason) = &mute.raison {
+            text.push_str(&format!("\nRaison : {}", reason));
+        }
+        text
+    } else {
+        format!("Les annonces de {} sont de retour.", server_name)
+    };
+
+    if let Err(e) = DiscordEmbed::new("otternel")
+        .title(if started { "Serveur mis en sourdine" } else { "Fin de la sourdine" })
+        .description(&supertext)
+        .color(if started { "c08020" } else { "126020" })
+        .footer("Otternel Service")
+        .timestamp_now()
+        .send()
+    {
+        error!("{e}");
+    }
+}
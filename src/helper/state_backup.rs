@@ -0,0 +1,354 @@
+use colored::Colorize;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use log::{info, warn};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use crate::config::Config;
+use crate::helper::mojang_cache;
+use crate::serverlog::offset_store;
+
+/// Default path of the lock file written while the daemon runs
+const DEFAULT_LOCK_FILE: &str = "otternel.lock";
+/// Prefix of the state archives, followed by their date
+const ARCHIVE_PREFIX: &str = "otternel-state-";
+
+/// A local file keeping state between two restarts, as stored in the archives.
+struct StateFile {
+    /// Name of the file in the archive
+    name: &'static str,
+    /// Where the running configuration keeps it
+    path: PathBuf,
+    /// Checks the content before it is restored
+    validate: fn(&[u8]) -> Result<(), String>,
+}
+
+/// Returns the state files of the current configuration, from their stores :
+/// - `offsets.json` or `offsets.bin` : read positions of the log files (`SERVERLOG_STATE_FILE`, unless disabled),
+/// - `mojang_cache.json` : uuid <-> playername resolutions (`MOJANG_CACHE_PATH`).
+fn state_files() -> Vec<StateFile> {
+    let mut files = Vec::new();
+    if let Some(path) = offset_store::configured_path() {
+        files.push(if offset_store::is_json(&path) {
+            StateFile {
+                name: "offsets.json",
+                path,
+                validate: |bytes| offset_store::decode_json(bytes).map(|_| ()).map_err(|e| e.to_string()),
+            }
+        } else {
+            StateFile {
+                name: "offsets.bin",
+                path,
+                validate: |bytes| offset_store::decode(bytes).map(|_| ()).map_err(|e| e.to_string()),
+            }
+        });
+    }
+    files.push(StateFile {
+        name: "mojang_cache.json",
+        path: PathBuf::from(mojang_cache::cache_path()),
+        validate: |bytes| serde_json::from_slice::<serde_json::Value>(bytes).map(|_| ()).map_err(|e| e.to_string()),
+    });
+    files
+}
+
+/// Returns the folder of the nightly state archives (`STATE_BACKUP_DIR`), `None` when the backup is disabled.
+pub fn backup_dir() -> Option<PathBuf> {
+    Config::current()
+        .state_backup_dir
+        .as_deref()
+        .map(|dir| dir.trim().to_string())
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Returns the number of archives kept (`STATE_BACKUP_KEEP`, Default 7).
+pub fn keep() -> usize {
+    Config::current().state_backup_keep.max(1)
+}
+
+/// Archives the existing state files to `<dir>/otternel-state-<date>.tar.gz`, then removes the oldest
+/// archives over `keep`.
+///
+/// # Returns
+/// The path of the new archive.
+pub fn create_archive(dir: &Path, keep: usize) -> Result<PathBuf, String> {
+    archive_files(&state_files(), dir, keep)
+}
+
+fn archive_files(states: &[StateFile], dir: &Path, keep: usize) -> Result<PathBuf, String> {
+    fs::create_dir_all(dir).map_err(|e| format!("Could not create {}: {}", dir.display(), e))?;
+
+    let archive = dir.join(format!("{}{}.tar.gz", ARCHIVE_PREFIX, chrono::Local::now().format("%Y%m%d-%H%M%S")));
+    let file = File::create(&archive).map_err(|e| format!("Could not create {}: {}", archive.display(), e))?;
+    let mut builder = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+
+    let mut archived = 0;
+    for state in states {
+        // The stores write through a temporary file and a rename : the file read here is always whole
+        let bytes = match fs::read(&state.path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(format!("Could not read {}: {}", state.path.display(), e)),
+        };
+        let mut header = tar::Header::new_gnu();
+        header.set_size(bytes.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(chrono::Utc::now().timestamp().max(0) as u64);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, state.name, bytes.as_slice())
+            .map_err(|e| format!("Could not archive {}: {}", state.name, e))?;
+        archived += 1;
+    }
+    builder
+        .into_inner()
+        .and_then(|encoder| encoder.finish())
+        .map_err(|e| format!("Could not write {}: {}", archive.display(), e))?;
+
+    info!("{} state files archived to {}", archived, archive.display().to_string().green());
+    prune_archives(dir, keep);
+    Ok(archive)
+}
+
+/// Removes the oldest state archives of `dir`, keeping the `keep` newest (their names sort by date).
+fn prune_archives(dir: &Path, keep: usize) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let mut archives: Vec<PathBuf> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with(ARCHIVE_PREFIX) && name.ends_with(".tar.gz"))
+        })
+        .collect();
+    archives.sort();
+
+    let extra = archives.len().saturating_sub(keep);
+    for old in archives.into_iter().take(extra) {
+        if let Err(e) = fs::remove_file(&old) {
+            warn!("Could not remove the old state archive {}: {}", old.display(), e);
+        }
+    }
+}
+
+/// Restores the state files of an archive to the paths of the current configuration.
+/// Refused while the daemon runs (see [`DaemonLock`]). Every file is checked before any is written :
+/// an unknown entry or a corrupted file cancels the whole restore.
+///
+/// # Returns
+/// The paths written.
+pub fn restore_archive(archive: &Path) -> Result<Vec<PathBuf>, String> {
+    if let Some(pid) = running_daemon() {
+        return Err(format!("Otternel is running (pid {}), stop it before restoring its state", pid));
+    }
+    restore_files(state_files(), archive)
+}
+
+fn restore_files(states: Vec<StateFile>, archive: &Path) -> Result<Vec<PathBuf>, String> {
+    let file = File::open(archive).map_err(|e| format!("Could not open {}: {}", archive.display(), e))?;
+    let mut contents: HashMap<String, Vec<u8>> = HashMap::new();
+    let mut tar = tar::Archive::new(GzDecoder::new(file));
+    for entry in tar.entries().map_err(|e| format!("Invalid archive: {}", e))? {
+        let mut entry = entry.map_err(|e| format!("Invalid archive: {}", e))?;
+        let name = entry.path().map_err(|e| format!("Invalid archive: {}", e))?.to_string_lossy().into_owned();
+        let mut bytes = Vec::new();
+        entry.read_to_end(&mut bytes).map_err(|e| format!("Could not read {}: {}", name, e))?;
+        contents.insert(name, bytes);
+    }
+
+    if let Some(unknown) = contents.keys().find(|name| !states.iter().any(|s| s.name == name.as_str())) {
+        return Err(format!("Unknown file {} in the archive (or its store isn't configured)", unknown));
+    }
+    for state in &states {
+        if let Some(bytes) = contents.get(state.name) {
+            (state.validate)(bytes).map_err(|e| format!("{} is corrupted: {}", state.name, e))?;
+        }
+    }
+
+    let mut restored = Vec::new();
+    for state in states {
+        let Some(bytes) = contents.remove(state.name) else {
+            continue;
+        };
+        write_atomically(&state.path, &bytes).map_err(|e| format!("Could not write {}: {}", state.path.display(), e))?;
+        restored.push(state.path);
+    }
+    Ok(restored)
+}
+
+/// Writes next to `path`, then renames, so an interrupted restore never leaves half a file.
+fn write_atomically(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".restore");
+    fs::write(&tmp, bytes)?;
+    fs::rename(&tmp, path)
+}
+
+/// Lock file holding the pid of the running daemon (`OTTERNEL_LOCK_FILE`, Default `otternel.lock`),
+/// removed when dropped.
+pub struct DaemonLock {
+    path: PathBuf,
+}
+
+impl DaemonLock {
+    /// Writes the lock file of this process. A lock left by a crashed daemon is replaced.
+    pub fn acquire() -> std::io::Result<Self> {
+        let path = lock_path();
+        if let Some(pid) = running_daemon() {
+            warn!("Lock file {} names another running Otternel (pid {})", path.display(), pid);
+        }
+        fs::write(&path, std::process::id().to_string())?;
+        Ok(Self { path })
+    }
+}
+
+impl Drop for DaemonLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+fn lock_path() -> PathBuf {
+    Config::current()
+        .otternel_lock_file
+        .clone()
+        .filter(|path| !path.trim().is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_LOCK_FILE))
+}
+
+/// Returns the pid of the lock file if that process is still alive (a stale lock is ignored).
+fn running_daemon() -> Option<u32> {
+    let pid: u32 = fs::read_to_string(lock_path()).ok()?.trim().parse().ok()?;
+    (pid != std::process::id() && Path::new(&format!("/proc/{}", pid)).exists()).then_some(pid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("otternel-state-backup-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// State files of a test : positions and Mojang cache under `dir`
+    fn states(dir: &Path) -> Vec<StateFile> {
+        vec![
+            StateFile {
+                name: "offsets.json",
+                path: dir.join("state/offsets.json"),
+                validate: |bytes| offset_store::decode_json(bytes).map(|_| ()).map_err(|e| e.to_string()),
+            },
+            StateFile {
+                name: "mojang_cache.json",
+                path: dir.join("state/mojang_cache.json"),
+                validate: |bytes| serde_json::from_slice::<serde_json::Value>(bytes).map(|_| ()).map_err(|e| e.to_string()),
+            },
+        ]
+    }
+
+    fn archive_entries(archive: &Path) -> Vec<(String, Vec<u8>)> {
+        let mut tar = tar::Archive::new(GzDecoder::new(File::open(archive).unwrap()));
+        let mut entries: Vec<(String, Vec<u8>)> = tar
+            .entries()
+            .unwrap()
+            .map(|entry| {
+                let mut entry = entry.unwrap();
+                let mut bytes = Vec::new();
+                entry.read_to_end(&mut bytes).unwrap();
+                (entry.path().unwrap().to_string_lossy().into_owned(), bytes)
+            })
+            .collect();
+        entries.sort();
+        entries
+    }
+
+    const OFFSETS: &[u8] = br#"{"files":[{"path":"/srv/12/latest.log","offset":1024,"len":2048,"inode":7}]}"#;
+    const MOJANG: &[u8] = br#"{"Steve":{"uuid":"8667ba71b85a4004af54457a9734eed7"}}"#;
+
+    #[test]
+    fn archive_holds_every_state_file_and_restores_them() {
+        let dir = temp_dir();
+        let files = states(&dir);
+        fs::create_dir_all(dir.join("state")).unwrap();
+        fs::write(&files[0].path, OFFSETS).unwrap();
+        fs::write(&files[1].path, MOJANG).unwrap();
+
+        let archive = archive_files(&files, &dir.join("backups"), 7).unwrap();
+        assert_eq!(
+            archive_entries(&archive),
+            [("mojang_cache.json".to_string(), MOJANG.to_vec()), ("offsets.json".to_string(), OFFSETS.to_vec())]
+        );
+
+        // A bad deploy wipes the state, the restore writes it back
+        fs::remove_dir_all(dir.join("state")).unwrap();
+        let restored = restore_files(states(&dir), &archive).unwrap();
+        assert_eq!(restored.len(), 2);
+        assert_eq!(fs::read(&files[0].path).unwrap(), OFFSETS);
+        assert_eq!(fs::read(&files[1].path).unwrap(), MOJANG);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn missing_state_file_is_left_out_of_the_archive() {
+        let dir = temp_dir();
+        let files = states(&dir);
+        fs::create_dir_all(dir.join("state")).unwrap();
+        fs::write(&files[1].path, MOJANG).unwrap();
+
+        let archive = archive_files(&files, &dir.join("backups"), 7).unwrap();
+        assert_eq!(archive_entries(&archive), [("mojang_cache.json".to_string(), MOJANG.to_vec())]);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn corrupted_file_cancels_the_whole_restore() {
+        let dir = temp_dir();
+        let files = states(&dir);
+        fs::create_dir_all(dir.join("state")).unwrap();
+        fs::write(&files[0].path, b"{ not json").unwrap();
+        fs::write(&files[1].path, MOJANG).unwrap();
+        let archive = archive_files(&files, &dir.join("backups"), 7).unwrap();
+
+        fs::remove_dir_all(dir.join("state")).unwrap();
+        let error = restore_files(states(&dir), &archive).unwrap_err();
+        assert!(error.contains("offsets.json is corrupted"), "{error}");
+        assert!(!files[1].path.exists(), "no file is written when one is corrupted");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn only_the_newest_archives_are_kept() {
+        let dir = temp_dir();
+        for date in ["20260101-030000", "20260102-030000", "20260103-030000"] {
+            fs::write(dir.join(format!("{ARCHIVE_PREFIX}{date}.tar.gz")), b"").unwrap();
+        }
+        fs::write(dir.join("notes.txt"), b"kept").unwrap();
+
+        prune_archives(&dir, 2);
+
+        let mut left: Vec<String> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        left.sort();
+        assert_eq!(left, ["notes.txt", "otternel-state-20260102-030000.tar.gz", "otternel-state-20260103-030000.tar.gz"]);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
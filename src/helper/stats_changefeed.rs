@@ -0,0 +1,332 @@
+use colored::Colorize;
+use log::{debug, info, warn};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::helper::http_client::HttpClient;
+/// Longest wait for the HTTP sink
+const HTTP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Sink chosen by `STATS_CHANGEFEED` ("file" or "http"), `None` when the changefeed is disabled.
+static SINK: LazyLock<Option<Mutex<Box<dyn ChangefeedSink>>>> = LazyLock::new(|| sink_from_env().map(Mutex::new));
+
+static PUBLISHED: AtomicU64 = AtomicU64::new(0);
+static FAILED: AtomicU64 = AtomicU64::new(0);
+
+/// One stat whose value changed.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StatChange {
+    pub field: String,
+    pub old: Value,
+    pub new: Value,
+}
+
+/// Event published after a stats upsert, as one JSON line.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StatsChangeEvent {
+    pub serveur_id: u64,
+    pub compte_id: String,
+    pub changes: Vec<StatChange>,
+    /// RFC 3339, UTC
+    pub timestamp: String,
+}
+
+impl StatsChangeEvent {
+    /// Builds the event of an upsert, from the stats before (`None` for a new row) and after.
+    ///
+    /// # Returns
+    /// `None` if no stat changed.
+    pub fn new(serveur_id: u64, compte_id: &str, old: Option<&Map<String, Value>>, new: &Map<String, Value>) -> Option<Self> {
+        let changes = diff(old, new);
+        if changes.is_empty() {
+            return None;
+        }
+        Some(Self {
+            serveur_id,
+            compte_id: compte_id.to_string(),
+            changes,
+            timestamp: chrono::Utc::now().to_rfc3339(),
+        })
+    }
+
+    /// Serializes the event as one NDJSON line, without the trailing new line.
+    pub fn to_line(&self) -> Result<String, String> {
+        serde_json::to_string(self).map_err(|e| e.to_string())
+    }
+}
+
+/// Compares the stats before and after an upsert. A stat missing before is compared with `null`.
+/// The changes are sorted by field name.
+pub fn diff(old: Option<&Map<String, Value>>, new: &Map<String, Value>) -> Vec<StatChange> {
+    let mut changes: Vec<StatChange> = new
+        .iter()
+        .filter_map(|(field, new_value)| {
+            let old_value = old.and_then(|old| old.get(field)).cloned().unwrap_or(Value::Null);
+            (old_value != *new_value).then(|| StatChange {
+                field: field.clone(),
+                old: old_value,
+                new: new_value.clone(),
+            })
+        })
+        .collect();
+    changes.sort_by(|a, b| a.field.cmp(&b.field));
+    changes
+}
+
+/// Destination of the changefeed lines.
+pub trait ChangefeedSink: Send {
+    /// Publishes one NDJSON line (without its new line).
+    fn publish(&mut self, line: &str) -> Result<(), String>;
+}
+
+/// Appends the lines to a file, renamed to `<path>.1` once it exceeds `max_bytes`.
+pub struct FileSink {
+    path: String,
+    max_bytes: u64,
+}
+
+impl FileSink {
+    pub fn new(path: &str, max_bytes: u64) -> Self {
+        Self { path: path.to_string(), max_bytes }
+    }
+}
+
+impl ChangefeedSink for FileSink {
+    fn publish(&mut self, line: &str) -> Result<(), String> {
+        let size = std::fs::metadata(&self.path).map(|m| m.len()).unwrap_or(0);
+        if size > 0 && size + line.len() as u64 + 1 > self.max_bytes {
+            std::fs::rename(&self.path, format!("{}.1", self.path)).map_err(|e| e.to_string())?;
+            info!("Stats changefeed {} rotated", self.path.green());
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(|e| e.to_string())?;
+        writeln!(file, "{}", line).map_err(|e| e.to_string())
+    }
+}
+
+/// POSTs each line to a URL, as `application/x-ndjson`.
+pub struct HttpSink {
+    url: String,
+}
+
+impl HttpSink {
+    pub fn new(url: &str) -> Self {
+        Self { url: url.to_string() }
+    }
+}
+
+impl ChangefeedSink for HttpSink {
+    fn publish(&mut self, line: &str) -> Result<(), String> {
+        let client = HttpClient::for_url(&self.url);
+        client
+            .post(&self.url)
+            .timeout(HTTP_TIMEOUT)
+            .set("Content-Type", "application/x-ndjson")
+            .send_string(&format!("{}\n", line))
+            .map(|_| ())
+            .map_err(|e| client.describe_error(&e))
+    }
+}
+
+/// Returns true if a sink is configured, so the callers only read the old stats when needed.
+pub fn enabled() -> bool {
+    SINK.is_some()
+}
+
+/// Publishes an event to the configured sink. A failure is logged and counted, never returned :
+/// the changefeed must not fail the sync.
+pub fn publish(event: &StatsChangeEvent) {
+    if let Some(sink) = SINK.as_ref() {
+        publish_to(sink.lock().unwrap_or_else(|e| e.into_inner()).as_mut(), event);
+    }
+}
+
+/// Publishes an event to `sink`, counting it as published or failed.
+fn publish_to(sink: &mut dyn ChangefeedSink, event: &StatsChangeEvent) {
+    match event.to_line().and_then(|line| sink.publish(&line)) {
+        Ok(()) => {
+            PUBLISHED.fetch_add(1, Ordering::Relaxed);
+            debug!("Stats change of {} published ({} fields)", event.compte_id, event.changes.len());
+        }
+        Err(e) => {
+            FAILED.fetch_add(1, Ordering::Relaxed);
+            warn!("Failed to publish the stats change of {}: {}", event.compte_id, e);
+        }
+    }
+}
+
+/// Returns the number of events published and failed since the start.
+pub fn counts() -> (u64, u64) {
+    (PUBLISHED.load(Ordering::Relaxed), FAILED.load(Ordering::Relaxed))
+}
+
+/// Builds the sink from the environment :
+/// - `STATS_CHANGEFEED` : "file" or "http" (Not set = disabled),
+/// - `STATS_CHANGEFEED_PATH` and `STATS_CHANGEFEED_MAX_MB` (default 50) for "file",
+/// - `STATS_CHANGEFEED_URL` for "http".
+fn sink_from_env() -> Option<Box<dyn ChangefeedSink>> {
+    let cfg = Config::current();
+    let kind = cfg.stats_changefeed.trim().to_lowercase();
+    match kind.as_str() {
+        "" => None,
+        "file" => {
+            let path = &cfg.stats_changefeed_path;
+            let max_mb = cfg.stats_changefeed_max_mb;
+            info!("Stats changefeed written to {}", path.green().bold());
+            Some(Box::new(FileSink::new(path, max_mb * 1024 * 1024)))
+        }
+        "http" => match cfg.stats_changefeed_url.as_deref() {
+            Some(url) if !url.trim().is_empty() => {
+                info!("Stats changefeed posted to {}", url.green().bold());
+                Some(Box::new(HttpSink::new(url.trim())))
+            }
+            _ => {
+                warn!("STATS_CHANGEFEED=http but STATS_CHANGEFEED_URL is not set, changefeed disabled");
+                None
+            }
+        },
+        other => {
+            warn!("Unknown STATS_CHANGEFEED '{}', changefeed disabled", other);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn stats(value: Value) -> Map<String, Value> {
+        value.as_object().unwrap().clone()
+    }
+
+    fn event() -> StatsChangeEvent {
+        StatsChangeEvent {
+            serveur_id: 2,
+            compte_id: "069a79f4-44e9-4726-a5be-fca90e38aaf5".to_string(),
+            changes: vec![StatChange { field: "nb_mort".to_string(), old: json!(3), new: json!(4) }],
+            timestamp: "2026-10-16T12:00:00+00:00".to_string(),
+        }
+    }
+
+    fn temp_path() -> String {
+        std::env::temp_dir().join(format!("otternel-changefeed-{}.ndjson", uuid::Uuid::new_v4())).to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn only_the_changed_stats_are_kept_sorted_by_field() {
+        let old = stats(json!({ "tmps_jeux": 100, "nb_mort": 3, "mob_killed": { "zombie": 2 }, "dist_vol": null }));
+        let new = stats(json!({ "tmps_jeux": 160, "nb_mort": 3, "mob_killed": { "zombie": 3 }, "dist_vol": null }));
+        assert_eq!(
+            diff(Some(&old), &new),
+            vec![
+                StatChange { field: "mob_killed".to_string(), old: json!({ "zombie": 2 }), new: json!({ "zombie": 3 }) },
+                StatChange { field: "tmps_jeux".to_string(), old: json!(100), new: json!(160) },
+            ]
+        );
+    }
+
+    #[test]
+    fn a_new_row_is_compared_with_null() {
+        let new = stats(json!({ "nb_mort": 0, "dist_vol": null }));
+        assert_eq!(diff(None, &new), vec![StatChange { field: "nb_mort".to_string(), old: Value::Null, new: json!(0) }]);
+        assert!(StatsChangeEvent::new(2, "x", Some(&new), &new).is_none(), "no event without a change");
+        let event = StatsChangeEvent::new(2, "x", None, &new).unwrap();
+        assert!(chrono::DateTime::parse_from_rfc3339(&event.timestamp).is_ok());
+    }
+
+    #[test]
+    fn events_are_one_json_line() {
+        let line = event().to_line().unwrap();
+        assert!(!line.contains('\n'));
+        assert_eq!(
+            serde_json::from_str::<Value>(&line).unwrap(),
+            json!({
+                "serveur_id": 2,
+                "compte_id": "069a79f4-44e9-4726-a5be-fca90e38aaf5",
+                "changes": [{ "field": "nb_mort", "old": 3, "new": 4 }],
+                "timestamp": "2026-10-16T12:00:00+00:00",
+            })
+        );
+    }
+
+    #[test]
+    fn the_file_sink_appends_lines_and_rotates() {
+        let path = temp_path();
+        let line = event().to_line().unwrap();
+        let mut sink = FileSink::new(&path, (line.len() as u64 + 1) * 2);
+        for _ in 0..3 {
+            sink.publish(&line).unwrap();
+        }
+        let rotated = std::fs::read_to_string(format!("{}.1", path)).unwrap();
+        assert_eq!(rotated, format!("{line}\n{line}\n"), "the file is rotated before exceeding its size");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), format!("{line}\n"));
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(format!("{}.1", path)).unwrap();
+    }
+
+    #[tokio::test]
+    async fn the_http_sink_posts_ndjson() {
+        use axum::http::{HeaderMap, StatusCode};
+        use axum::routing::post;
+        use std::sync::Arc;
+
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let app = axum::Router::new()
+            .route(
+                "/feed",
+                post({
+                    let received = received.clone();
+                    move |headers: HeaderMap, body: String| async move {
+                        let content_type = headers[axum::http::header::CONTENT_TYPE].to_str().unwrap().to_string();
+                        received.lock().unwrap().push((content_type, body));
+                        StatusCode::NO_CONTENT
+                    }
+                }),
+            )
+            .route("/down", post(|| async { StatusCode::SERVICE_UNAVAILABLE }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let line = event().to_line().unwrap();
+        let results = tokio::task::spawn_blocking({
+            let (base, line) = (base.clone(), line.clone());
+            move || (HttpSink::new(&format!("{base}/feed")).publish(&line), HttpSink::new(&format!("{base}/down")).publish(&line))
+        })
+        .await
+        .unwrap();
+        assert_eq!(results.0, Ok(()));
+        assert!(results.1.is_err());
+        assert_eq!(*received.lock().unwrap(), vec![("application/x-ndjson".to_string(), format!("{line}\n"))]);
+    }
+
+    struct FailingSink;
+
+    impl ChangefeedSink for FailingSink {
+        fn publish(&mut self, _: &str) -> Result<(), String> {
+            Err("sink down".to_string())
+        }
+    }
+
+    #[test]
+    fn failures_are_only_counted() {
+        let (published, failed) = counts();
+        publish_to(&mut FailingSink, &event());
+        let path = temp_path();
+        publish_to(&mut FileSink::new(&path, 1024), &event());
+        assert_eq!(counts(), (published + 1, failed + 1));
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,91 @@
+use std::collections::HashMap;
+use log::error;
+
+use crate::app::TaskStatus;
+use crate::helper::{player_id_cache, stats_changefeed};
+use crate::helper::webhook_discord::{webhook_latencies, DiscordEmbed};
+use crate::serverlog::{processing_lag, self_guard, trigger_tuning};
+
+/// Sends the status of Otternel in an admin embed : tasks, processing lag, self guard, trigger tuning and webhook latencies.
+/// The max processing lag of each file and the unknown action counts are reset for the next report.
+pub fn run_status_report(statuses: &HashMap<String, TaskStatus>) {
+    let mut lines = Vec::new();
+
+    // Tasks
+    let mut tasks: Vec<_> = statuses.iter().collect();
+    tasks.sort_by(|a, b| a.0.cmp(b.0));
+    lines.push("**Tâches**".to_string());
+    for (name, status) in tasks {
+        let status = match status {
+            TaskStatus::Starting => "démarrage".to_string(),
+            TaskStatus::Running => "ok".to_string(),
+            TaskStatus::Degraded(reason) => format!("dégradée ({})", reason),
+            TaskStatus::Stopped => "arrêtée".to_string(),
+            TaskStatus::Failed(reason) => format!("en échec ({})", reason),
+        };
+        lines.push(format!("{} : {}", name, status));
+    }
+
+    // Processing lag, max since the last report
+    let mut lags: Vec<_> = processing_lag::take_snapshot().into_iter().collect();
+    if !lags.is_empty() {
+        lags.sort_by(|a, b| a.0.cmp(&b.0));
+        lines.push("**Retard de traitement (max)**".to_string());
+        for (path, gauge) in lags {
+            lines.push(format!("{} : {} s", path.display(), gauge.max_ms / 1000));
+        }
+    }
+
+    lines.push(format!("Lignes d'Otternel ignorées : {}", self_guard::skipped_count()));
+
+    // Trigger tuning
+    if let Some(sampled) = trigger_tuning::sampled_count() {
+        lines.push(format!("Lignes sans trigger échantillonnées : {}", sampled));
+    }
+    let unknown_actions = trigger_tuning::take_unknown_actions();
+    if !unknown_actions.is_empty() {
+        lines.push("**Actions inconnues (depuis le dernier rapport)**".to_string());
+        for (name, count) in unknown_actions {
+            lines.push(format!("{} : {} appels", name, count));
+        }
+    }
+
+    // Player id cache
+    let cache = player_id_cache::stats();
+    lines.push(format!(
+        "Cache des joueurs : {} en mémoire, {} succès, {} échecs, {} évictions, {} invalidations",
+        cache.size, cache.hits, cache.misses, cache.evictions, cache.invalidations
+    ));
+
+    // Stats changefeed
+    if stats_changefeed::enabled() {
+        let (published, failed) = stats_changefeed::counts();
+        lines.push(format!("Changefeed des stats : {} publiés, {} échecs", published, failed));
+    }
+
+    // Webhook latencies
+    let latencies = webhook_latencies();
+    if !latencies.is_empty() {
+        lines.push("**Latence des webhooks (24h)**".to_string());
+        for latency in latencies {
+            lines.push(format!(
+                "{} : p50 {} ms, p95 {} ms ({} envois)",
+                latency.identity,
+                latency.p50.as_millis(),
+                latency.p95.as_millis(),
+                latency.count
+            ));
+        }
+    }
+
+    if let Err(e) = DiscordEmbed::new("otternel")
+        .title("Statut d'Otternel")
+        .description(&lines.join("\n"))
+        .color("20bbbb")
+        .footer("Otternel Service")
+        .timestamp_now()
+        .send()
+    {
+        error!("{e}");
+    }
+}
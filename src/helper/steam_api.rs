@@ -0,0 +1,143 @@
+use log::debug;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+use crate::helper::http_client::HttpClient;
+
+/// A cached avatar is fetched again after this delay
+const AVATAR_TTL: Duration = Duration::from_secs(3600);
+/// After a failed request, the Steam API isn't called again for the account before this delay
+const FAILURE_TTL: Duration = Duration::from_secs(300);
+
+/// Moment a cached avatar expires, and the avatar (`None` for an account without one)
+type CachedAvatar = (Instant, Option<String>);
+
+/// Avatars by SteamID64. Palworld chat lines look the avatar up on every message : the Steam API is only called
+/// once per account.
+static AVATARS: LazyLock<Mutex<HashMap<String, CachedAvatar>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Deserialize)]
+struct PlayerSummariesResponse {
+    response: PlayerSummaries,
+}
+
+#[derive(Deserialize)]
+struct PlayerSummaries {
+    players: Vec<PlayerSummary>,
+}
+
+#[derive(Deserialize)]
+struct PlayerSummary {
+    avatarmedium: Option<String>,
+}
+
+/// Fetches the avatar URL of a Steam account from its SteamID64.
+/// Needs a Steam Web API key in `STEAM_API_KEY`.
+///
+/// # Returns
+/// - `Ok(Some(url))` if the account has an avatar.
+/// - `Ok(None)` if `STEAM_API_KEY` isn't set or the account is unknown.
+/// - `Err(ureq::Error)` if the request fails.
+pub fn fetch_avatar_url(steam_id: &str) -> Result<Option<String>, Box<ureq::Error>> {
+    let api_key = Config::current().steam_api_key.trim();
+    if api_key.is_empty() {
+        return Ok(None);
+    }
+
+    let url = "https://api.steampowered.com/ISteamUser/GetPlayerSummaries/v2/";
+    let response: PlayerSummariesResponse = HttpClient::for_url(url)
+        .get(url)
+        .query("key", api_key)
+        .query("steamids", steam_id)
+        .call()?
+        .into_json()
+        .map_err(ureq::Error::from)?;
+
+    Ok(response.response.players.into_iter().next().and_then(|p| p.avatarmedium))
+}
+
+/// Returns the avatar URL of a Steam account, from the cache or else the Steam Web API (see [`fetch_avatar_url`]).
+/// A failed request is logged in debug and gives no avatar, it is tried again after a few minutes.
+pub fn cached_avatar_url(steam_id: &str) -> Option<String> {
+    cached_avatar_url_with(steam_id, Instant::now(), fetch_avatar_url)
+}
+
+fn cached_avatar_url_with(
+    steam_id: &str,
+    now: Instant,
+    fetch: impl FnOnce(&str) -> Result<Option<String>, Box<ureq::Error>>,
+) -> Option<String> {
+    if let Some((expires_at, avatar)) = AVATARS.lock().unwrap_or_else(|e| e.into_inner()).get(steam_id)
+        && now < *expires_at
+    {
+        return avatar.clone();
+    }
+
+    // The request is made without holding the lock
+    let (avatar, ttl) = match fetch(steam_id) {
+        Ok(avatar) => (avatar, AVATAR_TTL),
+        Err(e) => {
+            debug!("Could not fetch the Steam avatar of {}: {}", steam_id, e);
+            (None, FAILURE_TTL)
+        }
+    };
+    let mut avatars = AVATARS.lock().unwrap_or_else(|e| e.into_inner());
+    avatars.retain(|_, (expires_at, _)| now < *expires_at);
+    avatars.insert(steam_id.to_string(), (now + ttl, avatar.clone()));
+    avatar
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    #[test]
+    fn an_avatar_is_fetched_once_per_account_until_it_expires() {
+        let calls = Cell::new(0);
+        let fetch = |steam_id: &str| {
+            calls.set(calls.get() + 1);
+            Ok(Some(format!("https://avatars.steamstatic.com/{steam_id}_medium.jpg")))
+        };
+        let start = Instant::now();
+        let expected = Some("https://avatars.steamstatic.com/76561198000000101_medium.jpg".to_string());
+        assert_eq!(cached_avatar_url_with("76561198000000101", start, fetch), expected);
+        for second in [1, 60, 3599] {
+            assert_eq!(cached_avatar_url_with("76561198000000101", start + Duration::from_secs(second), fetch), expected);
+        }
+        assert_eq!(calls.get(), 1);
+
+        // Another account has its own entry, an expired one is fetched again
+        cached_avatar_url_with("76561198000000102", start, fetch);
+        assert_eq!(calls.get(), 2);
+        cached_avatar_url_with("76561198000000101", start + AVATAR_TTL, fetch);
+        assert_eq!(calls.get(), 3);
+    }
+
+    #[test]
+    fn accounts_without_avatar_and_failures_are_cached_too() {
+        let calls = Cell::new(0);
+        let start = Instant::now();
+        let none = |_: &str| {
+            calls.set(calls.get() + 1);
+            Ok(None)
+        };
+        assert_eq!(cached_avatar_url_with("76561198000000201", start, none), None);
+        assert_eq!(cached_avatar_url_with("76561198000000201", start + Duration::from_secs(600), none), None);
+        assert_eq!(calls.get(), 1);
+
+        let failing = |_: &str| {
+            calls.set(calls.get() + 1);
+            Err(Box::new(ureq::Error::from(std::io::Error::other("steam down"))))
+        };
+        assert_eq!(cached_avatar_url_with("76561198000000202", start, failing), None);
+        assert_eq!(cached_avatar_url_with("76561198000000202", start + Duration::from_secs(60), failing), None);
+        assert_eq!(calls.get(), 2);
+        // Tried again once the failure expired
+        assert_eq!(cached_avatar_url_with("76561198000000202", start + FAILURE_TTL, failing), None);
+        assert_eq!(calls.get(), 3);
+    }
+}
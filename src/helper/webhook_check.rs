@@ -0,0 +1,145 @@
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+use colored::Colorize;
+use log::{info, warn};
+
+use crate::config::{Config, WebhookIdentity};
+use crate::helper::http_client::HttpClient;
+
+/// Longest wait for Discord when checking a webhook
+const CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Invalid webhooks found by the last check
+static INVALID_WEBHOOKS: LazyLock<Mutex<Vec<WebhookProblem>>> = LazyLock::new(|| Mutex::new(Vec::new()));
+
+/// An activated webhook identity that can't be used.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WebhookProblem {
+    pub identity: String,
+    pub reason: String,
+}
+
+/// Returns true unless `WEBHOOK_CHECK_ENABLED` is "false" (ex: tests without network access).
+pub fn enabled() -> bool {
+    Config::current().webhook_check_enabled
+}
+
+/// Checks every activated webhook identity and keeps the invalid ones for [`invalid_webhooks`].
+/// The invalid ones are logged in a single warning.
+///
+/// Blocking : one HTTP request per identity.
+pub fn run_check(cfg: &Config) -> Vec<WebhookProblem> {
+    let problems = check_webhooks(cfg);
+    if problems.is_empty() {
+        info!("{}", "Every activated webhook is valid".green());
+    } else {
+        let list: Vec<String> = problems.iter().map(|p| format!("{} ({})", p.identity, p.reason)).collect();
+        warn!("{} invalid webhooks : {}", problems.len().to_string().yellow().bold(), list.join(", "));
+    }
+    *INVALID_WEBHOOKS.lock().unwrap_or_else(|e| e.into_inner()) = problems.clone();
+    problems
+}
+
+/// Returns the invalid webhooks found by the last [`run_check`].
+pub fn invalid_webhooks() -> Vec<WebhookProblem> {
+    INVALID_WEBHOOKS.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Checks every activated webhook identity of the configuration.
+pub fn check_webhooks(cfg: &Config) -> Vec<WebhookProblem> {
+    cfg.webhook_identities()
+        .into_iter()
+        .filter(|webhook| webhook.activated)
+        .filter_map(|webhook| {
+            check_webhook(&webhook).err().map(|reason| WebhookProblem {
+                identity: webhook.name,
+                reason,
+            })
+        })
+        .collect()
+}
+
+/// Checks a webhook with a GET on its URL : Discord answers with the webhook, without posting any message.
+///
+/// # Errors
+/// Returns why the webhook can't be used : empty URL, webhook deleted or token invalid (401/404),
+/// other status, or network (or proxy) error.
+pub fn check_webhook(webhook: &WebhookIdentity) -> Result<(), String> {
+    let url = webhook.url.trim();
+    if url.is_empty() {
+        return Err("URL vide".to_string());
+    }
+
+    let client = HttpClient::with_proxy(url, webhook.proxy.as_deref());
+    match client.get(url).timeout(CHECK_TIMEOUT).call() {
+        Ok(_) => Ok(()),
+        Err(ureq::Error::Status(code @ (401 | 404), _)) => Err(format!("webhook supprimé ou token invalide ({code})")),
+        Err(ureq::Error::Status(code, _)) => Err(format!("réponse inattendue ({code})")),
+        Err(e) => Err(client.describe_error(&e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::Router;
+    use axum::http::StatusCode;
+    use axum::routing::get;
+
+    fn webhook(url: &str) -> WebhookIdentity {
+        WebhookIdentity {
+            name: "otternel".to_string(),
+            url: url.to_string(),
+            activated: true,
+            game: None,
+            proxy: Some("none".to_string()),
+            thread_id: None,
+        }
+    }
+
+    /// A local server answering like Discord : the webhook, or a 404 once it is deleted
+    async fn mock_discord() -> String {
+        let app = Router::new()
+            .route("/api/webhooks/1/valid", get(|| async { r#"{"id":"1","name":"Otternel"}"# }))
+            .route("/api/webhooks/2/deleted", get(|| async { (StatusCode::NOT_FOUND, r#"{"message":"Unknown Webhook"}"#) }))
+            .route("/api/webhooks/3/broken", get(|| async { StatusCode::INTERNAL_SERVER_ERROR }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{}", address)
+    }
+
+    async fn check(url: String) -> Result<(), String> {
+        tokio::task::spawn_blocking(move || check_webhook(&webhook(&url))).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn a_valid_webhook_passes() {
+        let base = mock_discord().await;
+        assert_eq!(check(format!("{}/api/webhooks/1/valid", base)).await, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn a_deleted_webhook_is_reported() {
+        let base = mock_discord().await;
+        assert_eq!(
+            check(format!("{}/api/webhooks/2/deleted", base)).await,
+            Err("webhook supprimé ou token invalide (404)".to_string())
+        );
+        assert_eq!(check(format!("{}/api/webhooks/3/broken", base)).await, Err("réponse inattendue (500)".to_string()));
+    }
+
+    #[tokio::test]
+    async fn a_network_error_is_reported() {
+        // A port nobody listens on anymore
+        let address = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let error = check(format!("http://{}/api/webhooks/1/valid", address)).await.unwrap_err();
+        assert!(!error.is_empty());
+        assert!(!error.starts_with("proxy error"), "{}", error);
+    }
+
+    #[test]
+    fn an_empty_url_is_reported_without_request() {
+        assert_eq!(check_webhook(&webhook("  ")), Err("URL vide".to_string()));
+    }
+}
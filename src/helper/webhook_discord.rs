@@ -1,3 +1,310 @@
+use log::debug;
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::config::WebhookIdentity;
+use crate::helper::dry_run;
+use crate::helper::http_client::HttpClient;
+use crate::helper::metrics;
+use crate::helper::rolling_histogram::RollingHistogram;
+use crate::helper::webhook_queue::{self, DiscordMessage};
+
+/// Maximum length of a message content accepted by Discord
+pub(crate) const DISCORD_CONTENT_MAX_CHARS: usize = 2000;
+/// Maximum length of a webhook username override accepted by Discord
+const DISCORD_USERNAME_MAX_CHARS: usize = 80;
+/// Limits of an embed accepted by Discord : beyond them, the whole webhook is rejected (400)
+const DISCORD_TITLE_MAX_CHARS: usize = 256;
+const DISCORD_DESCRIPTION_MAX_CHARS: usize = 4096;
+const DISCORD_MAX_FIELDS: usize = 25;
+const DISCORD_FIELD_NAME_MAX_CHARS: usize = 256;
+const DISCORD_FIELD_VALUE_MAX_CHARS: usize = 1024;
+/// Total of the title, description, fields and footer of an embed
+const DISCORD_EMBED_MAX_CHARS: usize = 6000;
+/// Retries of a webhook on a rate limit (429) or a server error (5xx)
+const DISCORD_MAX_RETRIES: u32 = 3;
+/// First delay before retrying a server error, doubled at each retry
+const DISCORD_BACKOFF_BASE: Duration = Duration::from_secs(1);
+/// Longest `Retry-After` waited for, so a huge value can't block the sender
+const DISCORD_MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+/// Rolling window of the webhook latencies
+const LATENCY_WINDOW: Duration = Duration::from_secs(24 * 3600);
+const LATENCY_MAX_SAMPLES: usize = 5000;
+
+/// Duration of the webhook HTTP requests, by webhook identity
+static WEBHOOK_LATENCIES: LazyLock<Mutex<HashMap<String, RollingHistogram>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// A Discord embed sent through a webhook, built field by field.
+///
+/// Blank values are ignored, so a field that isn't set never appears in the JSON payload.
+/// Without any embed field, only the content is sent (a plain message, ex: as a player with `username` and `avatar_url`).
+///
+/// # Example
+/// ```rust
+/// DiscordEmbed::new("otternel")
+///     .title("Serveur en ligne")
+///     .description("Le serveur a démarré")
+///     .color("126020")
+///     .footer("Otternel Service")
+///     .timestamp_now()
+///     .send()?;
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct DiscordEmbed {
+    identity: String,
+    content: Option<String>,
+    title: Option<String>,
+    url: Option<String>,
+    description: Option<String>,
+    color: Option<u32>,
+    thumbnail_url: Option<String>,
+    image_url: Option<String>,
+    footer_text: Option<String>,
+    footer_icon_url: Option<String>,
+    timestamp: Option<String>,
+    username: Option<String>,
+    avatar_url: Option<String>,
+    fields: Vec<EmbedField>,
+    thread_id: Option<String>,
+}
+
+/// A field of an embed : a small block with its own name, side by side with the other inline fields.
+#[derive(Debug, Clone)]
+struct EmbedField {
+    name: String,
+    value: String,
+    inline: bool,
+}
+
+impl DiscordEmbed {
+    /// Creates an empty embed for a webhook identity ("otternel", "mineotter", "multiloutre"...).
+    pub fn new(webhook_identity: &str) -> Self {
+        Self {
+            identity: webhook_identity.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Message content sent above the embed, truncated to 2000 characters.
+    pub fn content(mut self, content: &str) -> Self {
+        self.content = non_blank(content).map(truncate_content);
+        self
+    }
+
+    /// Title of the embed, truncated to 256 characters.
+    pub fn title(mut self, title: &str) -> Self {
+        self.title = non_blank(title).map(|t| truncate_chars(t, DISCORD_TITLE_MAX_CHARS));
+        self
+    }
+
+    /// Link opened when clicking the title.
+    pub fn url(mut self, url: &str) -> Self {
+        self.url = Some(url.trim()).filter(|u| is_http_url(u)).map(str::to_string);
+        self
+    }
+
+    /// Text of the embed, truncated to 4096 characters.
+    pub fn description(mut self, description: &str) -> Self {
+        self.description = non_blank(description).map(|d| truncate_chars(d, DISCORD_DESCRIPTION_MAX_CHARS));
+        self
+    }
+
+    /// Adds a field, its name truncated to 256 characters and its value to 1024. A field with a blank name or value
+    /// is ignored, as is any field past the 25th. Fields not fitting in the 6000 characters of an embed are left
+    /// out when it is sent, the last one kept being truncated.
+    pub fn add_field(mut self, name: &str, value: &str, inline: bool) -> Self {
+        let (Some(name), Some(value)) = (non_blank(name), non_blank(value)) else {
+            return self;
+        };
+        if self.fields.len() >= DISCORD_MAX_FIELDS {
+            debug!("Field '{}' ignored, an embed has {} fields at most", name, DISCORD_MAX_FIELDS);
+            return self;
+        }
+        self.fields.push(EmbedField {
+            name: truncate_chars(name, DISCORD_FIELD_NAME_MAX_CHARS),
+            value: truncate_chars(value, DISCORD_FIELD_VALUE_MAX_CHARS),
+            inline,
+        });
+        self
+    }
+
+    /// Color of the embed, as "#RRGGBB", "0xRRGGBB", "RRGGBB" or decimal. "0" or an invalid color means no color.
+    pub fn color(mut self, color: impl AsRef<str>) -> Self {
+        self.color = Some(color.as_ref())
+            .filter(|s| *s != "0" && !s.is_empty())
+            .and_then(parse_discord_color);
+        self
+    }
+
+    /// Small image at the top right of the embed. Ignored if not an http(s) URL.
+    pub fn thumbnail(mut self, url: &str) -> Self {
+        self.thumbnail_url = Some(url.trim()).filter(|u| is_http_url(u)).map(str::to_string);
+        self
+    }
+
+    /// Main image, displayed full width below the description. Ignored if not an http(s) URL.
+    pub fn image(mut self, url: &str) -> Self {
+        self.image_url = Some(url.trim()).filter(|u| is_http_url(u)).map(str::to_string);
+        self
+    }
+
+    pub fn footer(mut self, text: &str) -> Self {
+        self.footer_text = non_blank(text).map(str::to_string);
+        self
+    }
+
+    /// Icon of the footer. Ignored if not an http(s) URL.
+    pub fn footer_icon(mut self, url: &str) -> Self {
+        self.footer_icon_url = Some(url.trim()).filter(|u| is_http_url(u)).map(str::to_string);
+        self
+    }
+
+    /// ISO 8601 datetime (e.g. RFC3339) shown as the embed timestamp.
+    pub fn timestamp(mut self, timestamp_iso8601: &str) -> Self {
+        self.timestamp = non_blank(timestamp_iso8601).map(str::to_string);
+        self
+    }
+
+    pub fn timestamp_now(self) -> Self {
+        self.timestamp(&chrono::Utc::now().to_rfc3339())
+    }
+
+    /// Overrides the webhook's username for this message, truncated to 80 characters (Discord limit).
+    pub fn username(mut self, username: &str) -> Self {
+        self.username = non_blank(username).map(|u| u.chars().take(DISCORD_USERNAME_MAX_CHARS).collect());
+        self
+    }
+
+    /// Overrides the webhook's avatar for this message. Ignored if not an http(s) URL.
+    pub fn avatar_url(mut self, url: &str) -> Self {
+        self.avatar_url = Some(url.trim()).filter(|u| is_http_url(u)).map(str::to_string);
+        self
+    }
+
+    /// Thread of the webhook's channel to post in, instead of the one of the identity (`WEBHOOK_<NAME>_THREAD_ID`).
+    /// `None` or an id that isn't made of digits keeps the thread of the identity.
+    pub fn thread(mut self, thread_id: Option<&str>) -> Self {
+        self.thread_id = thread_id.and_then(valid_thread_id).map(str::to_string);
+        self
+    }
+
+    /// Builds the JSON payload posted to the webhook.
+    pub fn payload(&self) -> serde_json::Value {
+        let mut embed = serde_json::json!({});
+
+        if let Some(title) = &self.title {
+            embed["title"] = serde_json::json!(title);
+        }
+        // The description and the fields share what the title and the footer leave of the 6000 characters
+        let char_count = |text: &Option<String>| text.as_deref().map_or(0, |t| t.chars().count());
+        let mut budget = DISCORD_EMBED_MAX_CHARS.saturating_sub(char_count(&self.title) + char_count(&self.footer_text));
+        if let Some(description) = &self.description {
+            let description = truncate_chars(description, budget);
+            budget -= description.chars().count();
+            embed["description"] = serde_json::json!(description);
+        }
+        let mut fields = Vec::new();
+        for field in &self.fields {
+            let name_len = field.name.chars().count();
+            let value_len = field.value.chars().count();
+            if name_len + value_len > budget {
+                // The value of the first field not fitting is cut to the space left, if there is enough of it
+                if budget > name_len + 1 {
+                    let value = truncate_chars(&field.value, budget - name_len);
+                    fields.push(serde_json::json!({ "name": field.name, "value": value, "inline": field.inline }));
+                }
+                debug!("{} fields of the embed cut to fit the embed limit", self.fields.len() - fields.len() + 1);
+                break;
+            }
+            budget -= name_len + value_len;
+            fields.push(serde_json::json!({ "name": field.name, "value": field.value, "inline": field.inline }));
+        }
+        if !fields.is_empty() {
+            embed["fields"] = serde_json::json!(fields);
+        }
+        if let Some(url) = &self.url {
+            embed["url"] = serde_json::json!(url);
+        }
+        if let Some(color) = self.color {
+            embed["color"] = serde_json::json!(color as i64);
+        }
+        if let Some(url) = &self.thumbnail_url {
+            embed["thumbnail"] = serde_json::json!({ "url": url });
+        }
+        if let Some(url) = &self.image_url {
+            embed["image"] = serde_json::json!({ "url": url });
+        }
+        if self.footer_text.is_some() || self.footer_icon_url.is_some() {
+            let mut footer = serde_json::json!({ "text": self.footer_text.as_deref().unwrap_or_default() });
+            if let Some(url) = &self.footer_icon_url {
+                footer["icon_url"] = serde_json::json!(url);
+            }
+            embed["footer"] = footer;
+        }
+        if let Some(timestamp) = &self.timestamp {
+            embed["timestamp"] = serde_json::json!(timestamp);
+        }
+
+        // Mentions are never parsed, so a relayed message can't ping anyone
+        let mut payload = serde_json::json!({ "allowed_mentions": { "parse": [] } });
+        // Discord rejects an embed without any field : a content-only message is sent without it
+        if embed.as_object().is_some_and(|fields| !fields.is_empty()) {
+            payload["embeds"] = serde_json::json!([embed]);
+        }
+        if let Some(content) = &self.content {
+            payload["content"] = serde_json::json!(content);
+        }
+        if let Some(username) = &self.username {
+            payload["username"] = serde_json::json!(username);
+        }
+        if let Some(url) = &self.avatar_url {
+            payload["avatar_url"] = serde_json::json!(url);
+        }
+        payload
+    }
+
+    /// Short text of the embed for the logs : its title and description, or its content.
+    fn summary(&self) -> String {
+        let text: Vec<&str> = [&self.title, &self.description, &self.content]
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+            .collect();
+        text.join(" - ")
+    }
+
+    /// Sends the embed through the webhook queue, without waiting for Discord.
+    /// If the queue isn't running, the embed is sent directly.
+    ///
+    /// # Returns
+    /// Ok(()) if the webhook is queued, sent or disabled; Err(String) if an error occurs.
+    ///
+    /// # Errors
+    /// Returns an error if configuration fails to load, identity is unknown, or the direct HTTP request fails.
+    pub fn send(self) -> Result<(), String> {
+        // In dry-run, the embed is built and logged but never sent nor queued
+        if dry_run::skip(|| format!("embed {}: '{}'", self.identity, self.summary())) {
+            return Ok(());
+        }
+
+        // Get the webhook configuration
+        let webhook = get_webhook_config(&self.identity)?;
+        if !webhook.activated || webhook.url.is_empty() {
+            return Ok(());
+        }
+
+        // Do not send empty messages
+        let payload = self.payload();
+        if payload.get("embeds").is_none() && payload.get("content").is_none() {
+            return Ok(());
+        }
+
+        let thread_id = self.thread_id.as_deref().or(webhook.thread_id.as_deref());
+        queue_or_send(&webhook.name, &thread_url(&webhook.url, thread_id), payload)
+    }
+}
+
 /// Sends a Discord embed via a webhook for a specific identity.
 ///
 /// # Parameters
@@ -15,10 +322,8 @@
 ///
 /// # Returns
 /// Ok(()) if the webhook is sent or disabled; Err(String) if an error occurs while sending.
-///
-/// # Errors
-/// Returns an error if configuration fails to load, identity is unknown, or the HTTP request fails.
-///
+#[deprecated(note = "use the `DiscordEmbed` builder")]
+#[allow(dead_code, clippy::too_many_arguments)]
 pub fn send_discord_embed(
     webhook_identity: &str,
     content: &str,
@@ -32,183 +337,322 @@ pub fn send_discord_embed(
     footer_text: &str,
     timestamp_iso8601: Option<String>,
 ) -> Result<(), String> {
+    DiscordEmbed::new(webhook_identity)
+        .content(content)
+        .title(title)
+        .url(title_hyperlink)
+        .description(supertext)
+        .color(color_rgb.unwrap_or_default())
+        .thumbnail(thumbnail_url)
+        .image(image_url)
+        .footer_icon(footer_image_url)
+        .footer(footer_text)
+        .timestamp(timestamp_iso8601.as_deref().unwrap_or_default())
+        .send()
+}
+
+/// Sends a simple Discord message (without embed) via a webhook for a specific identity.
+///
+/// # Parameters
+/// - webhook_identity: Which webhook configuration to use.
+/// - content: The message content to send, truncated to 2000 characters (Discord limit).
+/// - username: Optional override of the webhook's username for this message.
+/// - avatar_url: Optional override of the webhook's avatar for this message.
+/// - thread_id: Optional thread to post in, instead of the one of the identity (`WEBHOOK_<NAME>_THREAD_ID`).
+///
+/// Mentions in the content are never parsed, so relayed messages can't ping anyone.
+///
+/// # Returns
+/// Ok(()) if the webhook is queued, sent or disabled; Err(String) if an error occurs.
+pub fn send_discord_message(
+    webhook_identity: &str,
+    content: &str,
+    username: Option<&str>,
+    avatar_url: Option<&str>,
+    thread_id: Option<&str>,
+) -> Result<(), String> {
+    // In dry-run, the message is logged but never sent nor queued
+    if dry_run::skip(|| format!("message {}: '{}'", webhook_identity, content)) {
+        return Ok(());
+    }
+
     // Get the webhook configuration
-    let (_identity, activated, url) = get_webhook_config(webhook_identity)?;
-    if !activated.eq_ignore_ascii_case("true") || url.is_empty() {
+    let webhook = get_webhook_config(webhook_identity)?;
+    if !webhook.activated || webhook.url.is_empty() {
+        return Ok(());
+    }
+
+    // Do not send empty messages
+    if content.trim().is_empty() {
         return Ok(());
     }
 
-    // Build the embed without color first
-    let mut embed = serde_json::json!({
-        "title": title,
-        "description": supertext,
+    let payload = message_payload(content, username, avatar_url);
+    let thread_id = thread_id.and_then(valid_thread_id).or(webhook.thread_id.as_deref());
+    queue_or_send(&webhook.name, &thread_url(&webhook.url, thread_id), payload)
+}
+
+/// Builds the payload of a plain message (see [`send_discord_message`]) : the content without embed, and the overrides
+/// that aren't blank.
+fn message_payload(content: &str, username: Option<&str>, avatar_url: Option<&str>) -> serde_json::Value {
+    let mut payload = serde_json::json!({
+        "content": truncate_content(content),
+        "allowed_mentions": { "parse": [] }
     });
 
-    // Set a hyperlink on the title if provided (Discord uses "url" on the embed)
-    if !title_hyperlink.trim().is_empty() {
-        embed["url"] = serde_json::json!(title_hyperlink);
+    if let Some(username) = username.and_then(non_blank) {
+        payload["username"] = serde_json::json!(username.chars().take(DISCORD_USERNAME_MAX_CHARS).collect::<String>());
     }
-
-    // Sanitize and set color if provided
-    if let Some(c) = color_rgb
-        .as_deref()
-        .filter(|s| *s != "0" && !s.is_empty())
-        .and_then(parse_discord_color)
-    {
-        embed["color"] = serde_json::json!(c as i64);
+    if let Some(avatar_url) = avatar_url.filter(|s| !s.trim().is_empty()) {
+        payload["avatar_url"] = serde_json::json!(avatar_url);
     }
+    payload
+}
 
-    // Add the thumbnail if there is one
-    if !thumbnail_url.trim().is_empty() {
-        embed["thumbnail"] = serde_json::json!({ "url": thumbnail_url });
-    }
+/// Sends a plain message as if a Minecraft player wrote it : the webhook takes the player's name
+/// and head (from mc-heads.net) as author for this message, in the thread given or the one of the identity.
+///
+/// # Returns
+/// Ok(()) if the webhook is queued, sent or disabled; Err(String) if an error occurs.
+pub fn send_discord_as_player(
+    webhook_identity: &str,
+    playername: &str,
+    message: &str,
+    thread_id: Option<&str>,
+) -> Result<(), String> {
+    DiscordEmbed::new(webhook_identity)
+        .thread(thread_id)
+        .content(message)
+        .username(playername)
+        .avatar_url(&minecraft_avatar_url(playername))
+        .send()
+}
 
-    // Add a main image if provided
-    if !image_url.trim().is_empty() {
-        embed["image"] = serde_json::json!({ "url": image_url });
-    }
+/// URL of the head of a Minecraft player, used as avatar.
+pub fn minecraft_avatar_url(playername: &str) -> String {
+    format!("https://mc-heads.net/avatar/{}", playername.trim().to_lowercase())
+}
 
-    // Add footer if provided
-    if !footer_text.trim().is_empty() || !footer_image_url.trim().is_empty() {
-        let mut footer = serde_json::json!({ "text": footer_text });
-        if !footer_image_url.trim().is_empty() {
-            footer["icon_url"] = serde_json::json!(footer_image_url);
-        }
-        embed["footer"] = footer;
+/// Pushes a payload to the webhook queue, or sends it directly if the queue isn't running.
+fn queue_or_send(identity: &str, url: &str, payload: serde_json::Value) -> Result<(), String> {
+    let message = DiscordMessage {
+        identity: identity.to_string(),
+        url: url.to_string(),
+        payload,
+    };
+    match webhook_queue::enqueue(message) {
+        Ok(()) => Ok(()),
+        Err(message) => send_discord_content(&message.identity, &message.url, message.payload),
     }
+}
 
-    // Add timestamp if provided
-    if let Some(ts) = timestamp_iso8601.as_ref().filter(|s| !s.trim().is_empty()) {
-        embed["timestamp"] = serde_json::json!(ts);
-    }
+/// Picks the thumbnail of an embed, so every action follows the same precedence:
+/// 1. the thumbnail set by the action (ex: a player avatar),
+/// 2. the image of the server (`serveurs.image`), for server-level events,
+/// 3. no thumbnail.
+///
+/// URLs that aren't http(s) are ignored, as Discord rejects the whole embed for them.
+///
+/// # Returns
+/// The URL to give to `send_discord_embed`, or an empty string for no thumbnail.
+pub fn resolve_embed_thumbnail(explicit_url: &str, server_image: Option<&str>) -> String {
+    [Some(explicit_url), server_image]
+        .into_iter()
+        .flatten()
+        .map(str::trim)
+        .find(|url| is_http_url(url))
+        .unwrap_or_default()
+        .to_string()
+}
 
-    // Build the payload
-    let mut payload = serde_json::json!({
-        "embeds": [embed],
-        // "allowed_mentions": { "parse": [] } // If uncommented, mentions will be parsed
-    });
+/// Returns the value, or `None` if it is blank.
+fn non_blank(value: &str) -> Option<&str> {
+    Some(value).filter(|v| !v.trim().is_empty())
+}
 
-    if !content.trim().is_empty() {
-        payload["content"] = serde_json::Value::String(content.to_string());
+/// Returns the thread id if it is made of digits only (a Discord snowflake).
+fn valid_thread_id(thread_id: &str) -> Option<&str> {
+    let thread_id = thread_id.trim();
+    if thread_id.bytes().any(|b| !b.is_ascii_digit()) {
+        debug!("Thread id '{}' ignored, it isn't made of digits", thread_id);
+        return None;
     }
+    Some(thread_id).filter(|id| !id.is_empty())
+}
 
-    // Sending
-    let resp = ureq::post(url)
-        .set("Content-Type", "application/json")
-        .send_json(payload);
-
-    match resp {
-        Ok(_) => Ok(()),
-        Err(ureq::Error::Status(code, response)) => {
-            let body = response.into_string().unwrap_or_default();
-            Err(format!("webhook send error: status code {code}, body: {body}"))
+/// URL of a webhook posting in a thread of its channel (`?thread_id=`), the URL itself without thread.
+fn thread_url(url: &str, thread_id: Option<&str>) -> String {
+    match thread_id {
+        Some(thread_id) => {
+            let separator = if url.contains('?') { '&' } else { '?' };
+            format!("{url}{separator}thread_id={thread_id}")
         }
-        Err(e) => Err(format!("webhook send error: {e}")),
+        None => url.to_string(),
     }
 }
 
-/// Sends a simple Discord message (without embed) via a webhook for a specific identity.
+/// Returns true if the URL is an http(s) URL that Discord accepts in an embed.
+fn is_http_url(url: &str) -> bool {
+    let url = url.trim();
+    let rest = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"));
+    rest.is_some_and(|host| !host.is_empty() && !host.contains(char::is_whitespace))
+}
+
+/// Posts a JSON payload to a Discord webhook URL.
 ///
-/// # Parameters
-/// - webhook_identity: Which webhook configuration to use.
-/// - content: The message content to send.
+/// Rate limits (429) are retried after the delay given by Discord (`Retry-After` header or `retry_after`
+/// field of the body), server errors (5xx) after an exponential backoff, up to `DISCORD_MAX_RETRIES` times.
+/// Any other error is final.
 ///
-/// # Returns
-/// Ok(()) if the webhook is sent or disabled; Err(String) if an error occurs.
-pub fn send_discord_message(
-    webhook_identity: &str,
-    content: &str,
-) -> Result<(), String> {
-    // Get the webhook configuration
-    let (_identity, activated, url) = get_webhook_config(webhook_identity)?;
-    if !activated.eq_ignore_ascii_case("true") || url.is_empty() {
+/// The request goes through the proxy of the identity (`WEBHOOK_<NAME>_PROXY`), or the global one. Errors of the proxy
+/// are final and reported as "webhook proxy error", to tell them apart from the errors of Discord.
+///
+/// The duration of each HTTP request is recorded in the latency of the webhook identity, and its outcome
+/// in the metrics.
+pub(crate) fn send_discord_content(identity: &str, url: &str, payload: serde_json::Value) -> Result<(), String> {
+    // Nothing is queued in dry-run : this only guards the callers that bypass the queue
+    if dry_run::skip(|| format!("webhook post {}", identity)) {
         return Ok(());
     }
+    let result = post_with_retries(identity, url, payload);
+    metrics::record_webhook(result.is_ok());
+    result
+}
 
-    // Do not send empty messages
-    if content.trim().is_empty() {
-        return Ok(());
+fn post_with_retries(identity: &str, url: &str, payload: serde_json::Value) -> Result<(), String> {
+    let proxy = get_webhook_config(identity).ok().and_then(|webhook| webhook.proxy);
+    let client = HttpClient::with_proxy(url, proxy.as_deref());
+    let mut retries = 0;
+    loop {
+        let started = Instant::now();
+        let resp = client
+            .post(url)
+            .set("Content-Type", "application/json")
+            .send_json(payload.clone());
+        record_latency(identity, started.elapsed());
+
+        let delay = match resp {
+            Ok(_) => {
+                if retries > 0 {
+                    debug!("Webhook sent after {} retries", retries);
+                }
+                return Ok(());
+            }
+            Err(ureq::Error::Status(429, response)) => rate_limit_delay(response),
+            Err(ureq::Error::Status(code, _)) if (500..600).contains(&code) => {
+                DISCORD_BACKOFF_BASE * 2u32.pow(retries)
+            }
+            Err(ureq::Error::Status(code, response)) => {
+                let body = response.into_string().unwrap_or_default();
+                return Err(format!("webhook send error: status code {code}, body: {body}"));
+            }
+            Err(e) if client.is_proxy_error(&e) => return Err(format!("webhook proxy error: {e}")),
+            Err(e) => return Err(format!("webhook send error: {e}")),
+        };
+
+        if retries >= DISCORD_MAX_RETRIES {
+            return Err(format!("webhook send error: still failing after {retries} retries"));
+        }
+        retries += 1;
+        debug!("Webhook retry {}/{} in {:?}", retries, DISCORD_MAX_RETRIES, delay);
+        std::thread::sleep(delay);
     }
+}
+
+/// Latency of a webhook identity over the rolling window, as given by [`webhook_latencies`].
+#[derive(Debug, Clone)]
+pub struct WebhookLatency {
+    pub identity: String,
+    pub count: usize,
+    pub p50: Duration,
+    pub p95: Duration,
+}
+
+pub(crate) fn record_latency(identity: &str, duration: Duration) {
+    WEBHOOK_LATENCIES
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .entry(identity.to_string())
+        .or_insert_with(|| RollingHistogram::new(LATENCY_WINDOW, LATENCY_MAX_SAMPLES))
+        .record(duration);
+}
+
+/// Returns the p50 and p95 latency of the HTTP requests of each webhook identity over the last 24 hours.
+pub fn webhook_latencies() -> Vec<WebhookLatency> {
+    let mut latencies: Vec<WebhookLatency> = WEBHOOK_LATENCIES
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter_mut()
+        .filter_map(|(identity, histogram)| {
+            Some(WebhookLatency {
+                identity: identity.clone(),
+                p50: histogram.percentile(50.0)?,
+                p95: histogram.percentile(95.0)?,
+                count: histogram.count(),
+            })
+        })
+        .collect();
+    latencies.sort_by(|a, b| a.identity.cmp(&b.identity));
+    latencies
+}
 
-    // Build the payload (simple content only)
-    let payload = serde_json::json!({
-        "content": content
+/// Reads the delay asked by Discord on a 429, from the `Retry-After` header or the `retry_after` field of the body.
+fn rate_limit_delay(response: ureq::Response) -> Duration {
+    let header_delay = response
+        .header("Retry-After")
+        .and_then(|v| v.trim().parse::<f64>().ok());
+    let delay = header_delay.or_else(|| {
+        response
+            .into_json::<serde_json::Value>()
+            .ok()
+            .and_then(|body| body["retry_after"].as_f64())
     });
 
-    // Sending
-    let resp = ureq::post(url)
-        .set("Content-Type", "application/json")
-        .send_json(payload);
+    delay
+        .filter(|secs| secs.is_finite() && *secs >= 0.0)
+        .map(|secs| Duration::from_secs_f64(secs.min(DISCORD_MAX_RETRY_AFTER.as_secs_f64())))
+        .unwrap_or(DISCORD_BACKOFF_BASE)
+}
 
-    match resp {
-        Ok(_) => Ok(()),
-        Err(ureq::Error::Status(code, response)) => {
-            let body = response.into_string().unwrap_or_default();
-            Err(format!("webhook send error: status code {code}, body: {body}"))
-        }
-        Err(e) => Err(format!("webhook send error: {e}")),
+/// Truncates a message content to the 2000 characters accepted by Discord, ending it with "…" if cut.
+fn truncate_content(content: &str) -> String {
+    truncate_chars(content, DISCORD_CONTENT_MAX_CHARS)
+}
+
+/// Cuts a text to `max` characters, its last one replaced by "…" when it is cut.
+fn truncate_chars(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    let mut truncated: String = text.chars().take(max.saturating_sub(1)).collect();
+    if max > 0 {
+        truncated.push('…');
     }
+    truncated
 }
 
+/// Retrieves the webhook configuration of an identity.
+///
 /// # Parameters
-/// * `webhook_identity` - A string slice that identifies the webhook. Supported values are:
-///     - `"otternel"`
-///     - `"mineotter"`
-///     - `"multiloutre"`
+/// * `webhook_identity` - The name of the identity: a built-in one (`"otternel"`, `"mineotter"`, `"multiloutre"`,
+///   `"mcmyadmin"`, `"mcmyadmin_secondary"`) or any identity declared with `WEBHOOK_<NAME>_URL`.
 ///
 /// # Returns
-/// If successful, returns a `Result` containing a tuple with three string slices:
-/// - The name of the webhook identity.
-/// - A static reference to whether the webhook is activated (`1` for activated, `0` for deactivated).
-/// - A static reference to the webhook URL.
+/// The `WebhookIdentity` (name, URL, activation).
 ///
-/// In case of an error, returns a `Result` with a `String` describing the issue:
-/// - If the configuration cannot be loaded from the environment, an error of the form
-///   `"config error: {error_message}"` is returned.
-/// - If an unsupported or unknown `webhook_identity` is provided, an error of the form
-///   `"unknown webhook identity: {webhook_identity}"` is returned.
+/// The configuration is read once for the whole process : changing a webhook requires a restart.
 ///
 /// # Errors
 /// - Returns `"config error: {error_message}"` if loading configuration from the environment fails.
-/// - Returns `"unknown webhook identity: {webhook_identity}"` for invalid webhook identities.
-///
-/// # Notes
-/// This function depends on the `Config` struct defined in the `crate::config` module to retrieve
-/// environment-based configurations. The `from_env` method should be implemented for `Config` to
-/// construct the configuration from environment variables.
-///
-fn get_webhook_config(webhook_identity: &str) -> Result<(&'static str, &'static str, &'static str), String> {
-    let cfg = crate::config::Config::from_env()
+/// - Returns `"unknown webhook identity: {webhook_identity}"` for identities neither built-in nor declared.
+fn get_webhook_config(webhook_identity: &str) -> Result<WebhookIdentity, String> {
+    let cfg = crate::config::Config::shared()
         .map_err(|e| format!("config error: {e}"))?;
 
-    match webhook_identity.trim().to_ascii_lowercase().as_str() {
-        "otternel" => {
-            let activated: &'static str = Box::leak(cfg.otternel_webhook_activated.into_boxed_str());
-            let url: &'static str = Box::leak(cfg.otternel_webhook_url.into_boxed_str());
-            Ok(("otternel", activated, url))
-        }
-        "mineotter" => {
-            let activated: &'static str = Box::leak(cfg.mineotter_bot_webhook_activated.into_boxed_str());
-            let url: &'static str = Box::leak(cfg.mineotter_bot_webhook_url.into_boxed_str());
-            Ok(("mineotter", activated, url))
-        }
-        "multiloutre" => {
-            let activated: &'static str = Box::leak(cfg.multiloutre_bot_webhook_activated.into_boxed_str());
-            let url: &'static str = Box::leak(cfg.multiloutre_bot_webhook_url.into_boxed_str());
-            Ok(("multiloutre", activated, url))
-        }
-        "mcmyadmin" => {
-            let activated: &'static str = Box::leak(cfg.mcmyadmin_webhook_activated.into_boxed_str());
-            let url: &'static str = Box::leak(cfg.mcmyadmin_webhook_url.into_boxed_str());
-            Ok(("mcmyadmin", activated, url))
-        }
-        "mcmyadmin_secondary" => {
-            let activated: &'static str = Box::leak(cfg.mcmyadmin_secondary_webhook_activated.into_boxed_str());
-            let url: &'static str = Box::leak(cfg.mcmyadmin_secondary_webhook_url.into_boxed_str());
-            Ok(("mcmyadmin_secondary", activated, url))
-        }
-        other => Err(format!("unknown webhook identity: {other}")),
-    }
+    cfg.webhook(webhook_identity)
+        .ok_or_else(|| format!("unknown webhook identity: {}", webhook_identity.trim().to_ascii_lowercase()))
 }
 
 /// Parses a Discord color string to a u32 integer.
@@ -232,12 +676,22 @@ fn parse_discord_color(s: &str) -> Option<u32> {
     u32::from_str_radix(t, 16).ok()
 }
 
-pub fn get_webhook_identity_by_server_id(game: String) -> &'static str {
-    match game.to_lowercase().as_str() {
+/// Returns the webhook identity posting the events of a game's servers.
+///
+/// An identity declared with `WEBHOOK_<NAME>_GAME=<game>` wins, then the built-in ones
+/// (minecraft -> mineotter, palworld -> multiloutre). Any other game falls back on `otternel`.
+pub fn get_webhook_identity_by_server_id(game: String) -> String {
+    let game = game.trim().to_lowercase();
+    if let Some(webhook) = crate::config::Config::shared().ok().and_then(|cfg| cfg.webhook_for_game(&game)) {
+        return webhook.name.clone();
+    }
+
+    match game.as_str() {
         "minecraft" => "mineotter",
         "palworld" => "multiloutre",
         _ => "otternel",
     }
+    .to_string()
 }
 
 pub fn get_webhook_mcmyadmin_by_server_id(server_id: Option<u32>) -> &'static str {
@@ -245,4 +699,236 @@ pub fn get_webhook_mcmyadmin_by_server_id(server_id: Option<u32>) -> &'static st
         Some(1) => "mcmyadmin",
         _ => "mcmyadmin_secondary",
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn message_payload_has_the_content_and_the_overrides() {
+        let payload = message_payload("salut @everyone", Some("Loutre"), Some("https://mc-heads.net/avatar/loutre"));
+        assert_eq!(
+            payload,
+            json!({
+                "content": "salut @everyone",
+                "allowed_mentions": { "parse": [] },
+                "username": "Loutre",
+                "avatar_url": "https://mc-heads.net/avatar/loutre",
+            })
+        );
+    }
+
+    #[test]
+    fn message_payload_leaves_out_blank_overrides() {
+        let payload = message_payload("salut", Some("  "), Some(""));
+        assert_eq!(payload, json!({ "content": "salut", "allowed_mentions": { "parse": [] } }));
+        assert_eq!(message_payload("salut", None, None), payload);
+    }
+
+    #[test]
+    fn message_content_is_capped_at_2000_characters() {
+        let payload = message_payload(&"é".repeat(2500), Some(&"L".repeat(100)), None);
+        let content = payload["content"].as_str().unwrap();
+        assert_eq!(content.chars().count(), DISCORD_CONTENT_MAX_CHARS);
+        assert!(content.ends_with('…'));
+        assert_eq!(payload["username"].as_str().unwrap().chars().count(), DISCORD_USERNAME_MAX_CHARS);
+
+        let exact = "a".repeat(DISCORD_CONTENT_MAX_CHARS);
+        assert_eq!(message_payload(&exact, None, None)["content"], json!(exact));
+    }
+
+    #[test]
+    fn explicit_thumbnail_wins_over_the_server_image() {
+        let avatar = "https://mc-heads.net/avatar/loutre";
+        let server = Some("https://otternel.fr/img/survie.png");
+        assert_eq!(resolve_embed_thumbnail(avatar, server), avatar);
+        assert_eq!(resolve_embed_thumbnail("", server), "https://otternel.fr/img/survie.png");
+        assert_eq!(resolve_embed_thumbnail("  ", None), "");
+    }
+
+    #[test]
+    fn thumbnails_that_are_not_http_urls_are_skipped() {
+        let server = Some(" https://otternel.fr/img/survie.png ");
+        assert_eq!(resolve_embed_thumbnail("ftp://otternel.fr/a.png", server), "https://otternel.fr/img/survie.png");
+        assert_eq!(resolve_embed_thumbnail("survie.png", Some("http://")), "");
+        assert_eq!(resolve_embed_thumbnail("", Some("https://otternel.fr/mon image.png")), "");
+        assert_eq!(resolve_embed_thumbnail("http://192.168.1.2/a.png", None), "http://192.168.1.2/a.png");
+    }
+
+    #[test]
+    fn a_message_as_a_player_has_no_embed() {
+        let payload = DiscordEmbed::new("mineotter")
+            .content("bonjour")
+            .username("Loutre")
+            .avatar_url(&minecraft_avatar_url(" Loutre "))
+            .payload();
+        assert_eq!(payload["content"], "bonjour");
+        assert_eq!(payload["username"], "Loutre");
+        assert_eq!(payload["avatar_url"], "https://mc-heads.net/avatar/loutre");
+        assert!(payload.get("embeds").is_none());
+    }
+
+    /// Sets, on an embed, the builder fields whose bit is set in `mask`, and returns the embed keys expected.
+    fn embed_with(mask: u32) -> (DiscordEmbed, Vec<&'static str>) {
+        type Setter = fn(DiscordEmbed) -> DiscordEmbed;
+        let setters: [(&str, Setter); 8] = [
+            ("title", |e| e.title("Serveur en ligne")),
+            ("url", |e| e.url("https://otternel.fr")),
+            ("description", |e| e.description("Le serveur a démarré")),
+            ("color", |e| e.color("#1ec274")),
+            ("thumbnail", |e| e.thumbnail("https://otternel.fr/img/survie.png")),
+            ("footer", |e| e.footer("Otternel Service")),
+            ("timestamp", |e| e.timestamp("2026-10-16T12:00:00+00:00")),
+            ("fields", |e| e.add_field("Joueurs", "3", true)),
+        ];
+        let mut embed = DiscordEmbed::new("otternel");
+        let mut keys = Vec::new();
+        for (bit, (key, set)) in setters.into_iter().enumerate() {
+            if mask & (1 << bit) != 0 {
+                embed = set(embed);
+                keys.push(key);
+            }
+        }
+        (embed, keys)
+    }
+
+    #[test]
+    fn every_combination_of_fields_only_serializes_the_set_ones() {
+        for mask in 0..(1u32 << 8) {
+            let (embed, mut expected) = embed_with(mask);
+            let payload = embed.payload();
+            assert_eq!(payload["allowed_mentions"], json!({ "parse": [] }));
+            assert!(payload.get("content").is_none() && payload.get("username").is_none());
+            let Some(embeds) = payload.get("embeds") else {
+                assert_eq!(mask, 0, "an embed with fields set must be sent");
+                continue;
+            };
+            let embed = embeds[0].as_object().unwrap();
+            let mut keys: Vec<&str> = embed.keys().map(String::as_str).collect();
+            keys.sort_unstable();
+            expected.sort_unstable();
+            assert_eq!(keys, expected, "mask {mask:#010b}");
+        }
+    }
+
+    #[test]
+    fn every_field_is_serialized_with_its_discord_shape() {
+        let payload = embed_with(u32::MAX >> 24).0.content("Annonce").username("Otternel").payload();
+        assert_eq!(
+            payload,
+            json!({
+                "allowed_mentions": { "parse": [] },
+                "content": "Annonce",
+                "username": "Otternel",
+                "embeds": [{
+                    "title": "Serveur en ligne",
+                    "url": "https://otternel.fr",
+                    "description": "Le serveur a démarré",
+                    "color": 0x1ec274,
+                    "thumbnail": { "url": "https://otternel.fr/img/survie.png" },
+                    "footer": { "text": "Otternel Service" },
+                    "timestamp": "2026-10-16T12:00:00+00:00",
+                    "fields": [{ "name": "Joueurs", "value": "3", "inline": true }],
+                }],
+            })
+        );
+    }
+
+    #[test]
+    fn blank_or_invalid_values_never_appear() {
+        let payload = DiscordEmbed::new("otternel")
+            .title("  ")
+            .url("otternel.fr")
+            .description("")
+            .color("0")
+            .thumbnail("   ")
+            .footer("")
+            .timestamp(" ")
+            .add_field("", "3", false)
+            .add_field("Joueurs", " ", false)
+            .content("")
+            .username(" ")
+            .avatar_url("not a url")
+            .payload();
+        assert_eq!(payload, json!({ "allowed_mentions": { "parse": [] } }));
+        assert!(DiscordEmbed::new("otternel").color("pas une couleur").payload().get("embeds").is_none());
+    }
+
+    #[test]
+    fn colors_are_accepted_in_every_notation() {
+        for color in ["#1EC274", "0x1ec274", "0X1EC274", "1ec274", "2015860"] {
+            assert_eq!(DiscordEmbed::new("otternel").color(color).payload()["embeds"][0]["color"], json!(0x1ec274), "{color}");
+        }
+    }
+
+    #[test]
+    fn texts_are_cut_to_the_discord_limits() {
+        let mut embed = DiscordEmbed::new("otternel")
+            .title(&"t".repeat(300))
+            .description(&"d".repeat(5000));
+        for i in 0..30 {
+            embed = embed.add_field(&format!("champ {i}"), &"v".repeat(2000), false);
+        }
+        let payload = embed.payload();
+        let embed = &payload["embeds"][0];
+        assert_eq!(embed["title"].as_str().unwrap().chars().count(), DISCORD_TITLE_MAX_CHARS);
+        assert_eq!(embed["description"].as_str().unwrap().chars().count(), DISCORD_DESCRIPTION_MAX_CHARS);
+        let fields = embed["fields"].as_array().unwrap();
+        assert!(fields.len() < DISCORD_MAX_FIELDS);
+        assert!(fields.iter().all(|f| f["value"].as_str().unwrap().chars().count() <= DISCORD_FIELD_VALUE_MAX_CHARS));
+        let total: usize = [&embed["title"], &embed["description"]]
+            .into_iter()
+            .chain(fields.iter().flat_map(|f| [&f["name"], &f["value"]]))
+            .map(|text| text.as_str().unwrap().chars().count())
+            .sum();
+        assert!(total <= DISCORD_EMBED_MAX_CHARS, "{total} characters");
+    }
+
+    #[test]
+    fn image_and_footer_icon_of_the_old_embeds() {
+        let payload = DiscordEmbed::new("otternel")
+            .image(" https://otternel.fr/img/carte.png ")
+            .footer_icon("https://otternel.fr/img/loutre.png")
+            .payload();
+        let embed = &payload["embeds"][0];
+        assert_eq!(embed["image"], json!({ "url": "https://otternel.fr/img/carte.png" }));
+        assert_eq!(embed["footer"], json!({ "text": "", "icon_url": "https://otternel.fr/img/loutre.png" }));
+        assert!(embed.get("thumbnail").is_none());
+
+        let payload = DiscordEmbed::new("otternel").title("t").image("carte.png").footer_icon("ftp://loutre.png").payload();
+        assert!(payload["embeds"][0].get("image").is_none() && payload["embeds"][0].get("footer").is_none());
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn the_deprecated_wrapper_still_sends() {
+        crate::helper::dry_run::enable_on_this_thread();
+        let sent = send_discord_embed(
+            "otternel",
+            "",
+            "Serveur en ligne",
+            "https://otternel.fr",
+            "Le serveur a démarré",
+            Some("#1ec274".to_string()),
+            "https://otternel.fr/img/survie.png",
+            "",
+            "",
+            "Otternel Service",
+            None,
+        );
+        assert_eq!(sent, Ok(()));
+    }
+
+    #[test]
+    fn webhook_config_is_read_once_for_10_000_calls() {
+        let first = get_webhook_config("otternel");
+        let reads = crate::config::Config::env_reads();
+        for _ in 0..10_000 {
+            let webhook = get_webhook_config("otternel");
+            assert_eq!(webhook.as_ref().map(|w| &w.url), first.as_ref().map(|w| &w.url));
+        }
+        assert_eq!(crate::config::Config::env_reads(), reads, "the environment was read again");
+        assert!(get_webhook_config("nope").is_err());
+    }
+}
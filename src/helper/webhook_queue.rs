@@ -0,0 +1,96 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+use colored::Colorize;
+use log::{error, info};
+
+use crate::helper::webhook_discord::send_discord_content;
+
+/// Max webhooks sent per identity during `RATE_WINDOW`
+const RATE_MAX_SENDS: usize = 5;
+const RATE_WINDOW: Duration = Duration::from_secs(2);
+
+/// A webhook payload waiting in the queue.
+pub struct DiscordMessage {
+    pub identity: String,
+    pub url: String,
+    pub payload: serde_json::Value,
+}
+
+enum QueueItem {
+    Message(DiscordMessage),
+    Stop,
+}
+
+/// Sender of the running queue. `None` when the worker isn't running, webhooks are then sent directly.
+static QUEUE: LazyLock<Mutex<Option<Sender<QueueItem>>>> = LazyLock::new(|| Mutex::new(None));
+
+/// Worker sending the queued webhooks, one at a time.
+pub struct QueueWorker {
+    rx: Receiver<QueueItem>,
+}
+
+/// Starts queuing the webhooks. The returned worker must run on its own thread (it blocks).
+pub fn start() -> QueueWorker {
+    let (tx, rx) = mpsc::channel();
+    *QUEUE.lock().unwrap_or_else(|e| e.into_inner()) = Some(tx);
+    QueueWorker { rx }
+}
+
+/// Stops queuing new webhooks. The worker sends the ones already queued, then ends.
+pub fn stop() {
+    if let Some(tx) = QUEUE.lock().unwrap_or_else(|e| e.into_inner()).take() {
+        let _ = tx.send(QueueItem::Stop);
+    }
+}
+
+/// Queues a webhook for the worker.
+///
+/// # Returns
+/// The message back if the queue isn't running, so the caller sends it itself.
+pub fn enqueue(message: DiscordMessage) -> Result<(), DiscordMessage> {
+    let queue = QUEUE.lock().unwrap_or_else(|e| e.into_inner());
+    match queue.as_ref() {
+        Some(tx) => tx.send(QueueItem::Message(message)).map_err(|e| match e.0 {
+            QueueItem::Message(message) => message,
+            QueueItem::Stop => unreachable!(),
+        }),
+        None => Err(message),
+    }
+}
+
+impl QueueWorker {
+    /// Sends the queued webhooks until [`stop`] is called, respecting `RATE_MAX_SENDS` per `RATE_WINDOW`
+    /// for each identity. Final failures (after the retries of the sender) are logged.
+    pub fn run(self) {
+        let mut recent_sends: HashMap<String, VecDeque<Instant>> = HashMap::new();
+        let mut sent = 0u64;
+
+        for item in self.rx {
+            let message = match item {
+                QueueItem::Message(message) => message,
+                QueueItem::Stop => break,
+            };
+
+            // Wait for the rate window of this identity
+            let sends = recent_sends.entry(message.identity.clone()).or_default();
+            while sends.front().is_some_and(|at| at.elapsed() >= RATE_WINDOW) {
+                sends.pop_front();
+            }
+            if sends.len() >= RATE_MAX_SENDS
+                && let Some(oldest) = sends.pop_front()
+            {
+                std::thread::sleep(RATE_WINDOW.saturating_sub(oldest.elapsed()));
+            }
+            sends.push_back(Instant::now());
+
+            if let Err(e) = send_discord_content(&message.identity, &message.url, message.payload) {
+                error!("Webhook {} lost: {}", message.identity.red().bold(), e);
+            }
+            sent += 1;
+        }
+
+        info!("Webhook queue flushed ({} webhooks sent)", sent.to_string().green().bold());
+    }
+}
@@ -0,0 +1,50 @@
+use log::error;
+
+use crate::db::models::StatKind;
+use crate::db::repository_default::Database;
+use crate::helper::webhook_discord::DiscordEmbed;
+
+/// Players listed for each stat
+const LEADERBOARD_SIZE: u32 = 5;
+
+/// Sends the top players of each stat (all servers together) in an embed.
+///
+/// # Returns
+/// An error if a leaderboard couldn't be read from `joueurs_stats`.
+pub fn run_weekly_leaderboard(db: &Database) -> Result<(), String> {
+    let mut lines = Vec::new();
+    for stat in StatKind::ALL {
+        let top = db.get_top_players_by_stat(None, stat, LEADERBOARD_SIZE).map_err(|e| e.to_string())?;
+        if top.is_empty() {
+            continue;
+        }
+        lines.push(format!("**{}**", stat.label()));
+        for (rank, player) in top.iter().enumerate() {
+            lines.push(format!("{}. {} : {}", rank + 1, player.playername, format_value(stat, player.valeur)));
+        }
+    }
+    if lines.is_empty() {
+        lines.push("Aucune statistique enregistrée pour le moment.".to_string());
+    }
+
+    if let Err(e) = DiscordEmbed::new("mineotter")
+        .title("Classement de la semaine")
+        .description(&lines.join("\n"))
+        .color("d4a017")
+        .footer("Otternel Service")
+        .timestamp_now()
+        .send()
+    {
+        error!("{e}");
+    }
+    Ok(())
+}
+
+/// Formats a value of a stat : the play time is stored in ticks (20 per second), the distances in centimeters.
+fn format_value(stat: StatKind, value: i64) -> String {
+    match stat {
+        StatKind::TempsDeJeu => format!("{} h", value / 20 / 3600),
+        StatKind::Distance => format!("{:.1} km", value as f64 / 100_000.0),
+        _ => value.to_string(),
+    }
+}
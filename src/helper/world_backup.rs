@@ -0,0 +1,145 @@
+use colored::Colorize;
+use log::{error, info, warn};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::config::WorldBackupConfig;
+use crate::db::models::ServeurInactif;
+use crate::helper::rcon_helper::RconHelper;
+use crate::helper::webhook_discord::DiscordEmbed;
+use crate::playerstats::DockerFetcher;
+
+/// Longest wait for a server to stop after the `stop` command
+const STOP_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Backs up the worlds nobody joined for `after_days` days, then stops their servers.
+///
+/// For each idle active server : `save-all` and `stop` through RCON, the world folder of its container
+/// is archived to `<dir>/<server>-<date>.tar.gz`, and the server is marked inactive in database.
+/// With `delete_local`, the container is removed once its world is archived.
+/// A summary embed lists the worlds backed up.
+///
+/// # Returns
+/// Ok(()) if the idle servers could be listed, even if some backups failed; Err(String) otherwise.
+pub async fn run_world_backup(config: &WorldBackupConfig) -> Result<(), String> {
+    let rcon = RconHelper::new().map_err(|e| e.to_string())?;
+    let after_days = config.after_days;
+    let servers = rcon
+        .db
+        .call(move |db| db.get_idle_active_servers(after_days))
+        .await
+        .map_err(|e| e.to_string())?;
+    if servers.is_empty() {
+        return Ok(());
+    }
+
+    std::fs::create_dir_all(&config.dir).map_err(|e| format!("Could not create {}: {}", config.dir, e))?;
+
+    let fetcher = DockerFetcher::from_env().map_err(|e| e.to_string())?;
+    let mut lines = Vec::new();
+    let mut failures = 0usize;
+
+    for server in &servers {
+        match backup_server(&rcon, &fetcher, server, config).await {
+            Ok((archive, size)) => {
+                info!(
+                    "World of {} backed up to {} ({} bytes)",
+                    server.nom.green().bold(),
+                    archive.display().to_string().green(),
+                    size
+                );
+                lines.push(format!(
+                    "**{}** : `{}` ({}), inactif depuis le {}",
+                    server.nom,
+                    archive.file_name().unwrap_or_default().to_string_lossy(),
+                    format_size(size),
+                    server.derniere_activite
+                ));
+            }
+            Err(e) => {
+                error!("Backup of {} failed: {}", server.nom.red().bold(), e);
+                failures += 1;
+                lines.push(format!("**{}** : erreur ({})", server.nom, e));
+            }
+        }
+    }
+
+    if let Err(e) = DiscordEmbed::new("otternel")
+        .title("Sauvegarde des mondes inactifs")
+        .description(&format!(
+            "Serveurs sans connexion depuis {} jours :\n{}",
+            config.after_days,
+            lines.join("\n")
+        ))
+        .color(if failures > 0 { "c08020" } else { "126020" })
+        .footer("Otternel Service")
+        .timestamp_now()
+        .send()
+    {
+        error!("{e}");
+    }
+
+    Ok(())
+}
+
+/// Stops a server, archives its world and marks it inactive.
+///
+/// # Returns
+/// The path and size of the archive.
+async fn backup_server(
+    rcon: &RconHelper,
+    fetcher: &DockerFetcher,
+    server: &ServeurInactif,
+    config: &WorldBackupConfig,
+) -> Result<(PathBuf, u64), String> {
+    let container = server.contenaire.as_deref().ok_or("no container")?;
+    let world_name = server.nom_monde.as_deref().ok_or("no world name")?;
+
+    // The server may already be down : the archive is still made from the stopped container
+    if let Err(e) = rcon.execute_command(server.active_id, "save-all").await {
+        warn!("save-all failed on {}: {}", server.nom.yellow(), e);
+    } else if let Err(e) = rcon.execute_command(server.active_id, "stop").await {
+        warn!("stop failed on {}: {}", server.nom.yellow(), e);
+    }
+    if !fetcher.wait_until_stopped(container, STOP_TIMEOUT).await {
+        return Err(format!("container {} still running after {}s", container, STOP_TIMEOUT.as_secs()));
+    }
+
+    let archive = archive_path(&config.dir, &server.nom);
+    let remote_path = format!("/server/{}", world_name);
+    let size = fetcher
+        .archive_to_tar_gz(container, &remote_path, &archive)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let (serveur_id, active_id) = (server.serveur_id, server.active_id);
+    rcon.db
+        .call(move |db| db.set_server_inactive(serveur_id))
+        .await
+        .map_err(|e| format!("archived, but not marked inactive: {}", e))?;
+    if let Err(e) = rcon.db.call(move |db| db.update_active_server_stopped(active_id)).await {
+        warn!("Failed to record the stop of {}: {}", server.nom, e);
+    }
+
+    if config.delete_local {
+        match fetcher.remove_container(container).await {
+            Ok(()) => info!("Container {} removed", container.green()),
+            Err(e) => warn!("Failed to remove the container {}: {}", container, e),
+        }
+    }
+
+    Ok((archive, size))
+}
+
+/// Path of the archive of a server : `<dir>/<server>-<YYYY-MM-DD>.tar.gz`, the name reduced to safe characters.
+fn archive_path(dir: &str, server_name: &str) -> PathBuf {
+    let name: String = server_name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    Path::new(dir).join(format!("{}-{}.tar.gz", name, chrono::Local::now().format("%Y-%m-%d")))
+}
+
+fn format_size(bytes: u64) -> String {
+    format!("{:.1} Mo", bytes as f64 / (1024.0 * 1024.0))
+}
@@ -1,14 +1,14 @@
+mod api;
+mod app;
+mod bench;
 mod config;
 mod db;
 mod serverlog;
 mod helper;
 mod playerstats;
 
-use tokio::task;
-use futures::future;
 use colored::Colorize;
-use log::{info, error};
-use std::time::Duration;
+use log::{info, error, warn};
 
 /**
 Entry point of Otternel
@@ -26,6 +26,116 @@ async fn main() {
         chrono::Local::now()
     );
 
+    // `otternel bench ...` runs the log pipeline against synthetic traffic instead of the service
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("bench") {
+        dotenvy::dotenv().ok();
+        helper::logger_tool::setup_logger(config::Config::log_level_or("error")).ok();
+        let options = match bench::BenchOptions::from_args(&args[1..]) {
+            Ok(options) => options,
+            Err(err) => {
+                error!("Invalid bench options: {}", err);
+                return;
+            }
+        };
+        match bench::run(options).await {
+            Ok(report) => println!("{report}"),
+            Err(err) => error!("Bench failed: {}", err),
+        }
+        return;
+    }
+
+    // `otternel test-trigger --line "<line>" --serverlog-id N` (or `--file <path>`) shows the triggers matching
+    // the lines and the actions they call, without any side effect
+    if args.first().map(String::as_str) == Some("test-trigger") {
+        dotenvy::dotenv().ok();
+        helper::logger_tool::setup_logger(config::Config::log_level_or("warn")).ok();
+        let options = match serverlog::trigger_test::TriggerTestOptions::from_args(&args[1..]) {
+            Ok(options) => options,
+            Err(err) => {
+                error!("Invalid test-trigger options: {}", err);
+                std::process::exit(1);
+            }
+        };
+        // The actions run in dry-run need a runtime thread allowed to block (database reads)
+        let result = tokio::task::spawn_blocking(move || serverlog::trigger_test::run(&options))
+            .await
+            .map_err(|e| e.to_string())
+            .and_then(|result| result);
+        if let Err(err) = result {
+            error!("Trigger test failed: {}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    // `otternel init-triggers` writes the built-in default triggers to triggers.toml
+    if args.first().map(String::as_str) == Some("init-triggers") {
+        helper::logger_tool::setup_logger("info").ok();
+        match serverlog::default_triggers::write_example(serverlog::default_triggers::TRIGGERS_PATH) {
+            Ok(true) => {}
+            Ok(false) => error!("{} already exists, it was not overwritten", serverlog::default_triggers::TRIGGERS_PATH),
+            Err(err) => error!("Failed to write {}: {}", serverlog::default_triggers::TRIGGERS_PATH, err),
+        }
+        return;
+    }
+
+    // `otternel check` checks the activated webhooks and exits with 1 if one is invalid
+    if args.first().map(String::as_str) == Some("check") {
+        helper::logger_tool::setup_logger("info").ok();
+        let cfg = match config::Config::from_env() {
+            Ok(cfg) => cfg,
+            Err(err) => {
+                error!("Failed to load config: {}", err);
+                std::process::exit(1);
+            }
+        };
+        if !helper::webhook_check::enabled() {
+            println!("WEBHOOK_CHECK_ENABLED is false : webhooks not checked");
+            return;
+        }
+        let problems = tokio::task::spawn_blocking(move || helper::webhook_check::check_webhooks(&cfg))
+            .await
+            .unwrap_or_default();
+        if problems.is_empty() {
+            println!("{}", "Every activated webhook is valid".green());
+            return;
+        }
+        for problem in &problems {
+            println!("{} : {}", problem.identity.red().bold(), problem.reason);
+        }
+        std::process::exit(1);
+    }
+
+    // `otternel restore-state <archive>` restores the state files of a nightly archive, the daemon being stopped
+    if args.first().map(String::as_str) == Some("restore-state") {
+        dotenvy::dotenv().ok();
+        helper::logger_tool::setup_logger("info").ok();
+        let Some(archive) = args.get(1) else {
+            error!("Usage: otternel restore-state <archive>");
+            std::process::exit(1);
+        };
+        match helper::state_backup::restore_archive(std::path::Path::new(archive)) {
+            Ok(restored) => {
+                for path in restored {
+                    println!("{} {}", "Restored".green(), path.display());
+                }
+            }
+            Err(err) => {
+                error!("Restore failed: {}", err);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    // `otternel run`, or no subcommand, starts the service
+    if let Some(other) = args.first().filter(|arg| arg.as_str() != "run") {
+        helper::logger_tool::setup_logger("info").ok();
+        error!("Unknown subcommand {} (run, test-trigger, bench, init-triggers, check, restore-state)", other);
+        std::process::exit(1);
+    }
+
     // Try to load configuration from environment variables
     let cfg = match config::Config::from_env() {
         Ok(c) => c,
@@ -41,81 +151,108 @@ async fn main() {
         .expect("Failed to initialize logger");
     info!("Config loaded successfully");
 
-    // Start the watcher
-    let log_folder = cfg.serverlog_folder.clone();
-    task::spawn_blocking(move || { // Use spawn_blocking and a normal closure
-        if let Err(err) = serverlog::log_watcher::watch_serverlogs(&log_folder) {
-            error!("Log watcher failed: {}", err);
-        }
-    });
+    if cfg.otternel_dry_run {
+        helper::dry_run::enable();
+        warn!("{}", "OTTERNEL_DRY_RUN is true : database writes, webhooks and RCON commands are only logged".yellow());
+    }
 
-    // Check if player stats fetch is enabled
-    let get_player_stats_enabled = std::env::var("GET_PLAYER_STATS_ENABLED")
-        .unwrap_or_else(|_| "false".to_string())
-        .to_lowercase() == "true";
+    // A missing table or column would break the actions one by one : the daemon refuses to start instead
+    let auto_migrate = cfg.db_auto_migrate;
+    let schema_ready = tokio::task::spawn_blocking(move || match helper::open_database::open_db_from_env() {
+        Some(db) => db::migrations::prepare_schema(&db, auto_migrate),
+        None => true,
+    })
+    .await
+    .unwrap_or(true);
+    if !schema_ready {
+        std::process::exit(1);
+    }
 
-    if !get_player_stats_enabled {
-        info!("{}", "GET_PLAYER_STATS_ENABLED is false : periodic events will not start".yellow());
-        future::pending::<()>().await; // Even if player stats is disabled, keep the program running
+    // Write the default triggers to triggers.toml if asked and missing
+    if cfg.generate_triggers_example
+        && let Err(err) = serverlog::default_triggers::write_example(serverlog::default_triggers::TRIGGERS_PATH)
+    {
+        error!("Failed to write {}: {}", serverlog::default_triggers::TRIGGERS_PATH, err);
     }
 
-    // Read the interval from environment
-    let every_sec: u64 = std::env::var("PERIODIC_EVENTS_EVERY_SEC")
-        .expect("PERIODIC_EVENTS_EVERY_SEC must be set in the environment")
-        .parse()
-        .expect("PERIODIC_EVENTS_EVERY_SEC must be a valid number");
+    if !cfg.get_player_stats_enabled {
+        info!("{}", "GET_PLAYER_STATS_ENABLED is false : periodic events will not start".yellow());
+    }
 
-    info!("{}", format!("Periodic event launching every {} seconds", every_sec).green());
+    // The tasks enabled by the configuration, read before it is handed to the application
+    let get_player_stats_enabled = cfg.get_player_stats_enabled;
+    let integrity_report_enabled = cfg.integrity_report_enabled;
+    let status_report_enabled = cfg.status_report_enabled;
+    let pvp_leaderboard_enabled = cfg.pvp_leaderboard_enabled;
+    let weekly_leaderboard_enabled = cfg.weekly_leaderboard_enabled;
+    let rcon_schedules_enabled = cfg.rcon_schedules_enabled;
+    let linking_code_enabled = cfg.linking_code_enabled;
+    let api_enabled = cfg.api_enabled;
+    let chat_bridge_enabled = cfg.chat_bridge_addr().is_some();
+    let status_probe_enabled = helper::server_list_ping::probe_every().is_some();
+    let healthcheck_enabled = cfg.healthcheck_addr().is_some();
 
-    // Create the periodic interval
-    let mut interval = tokio::time::interval(Duration::from_secs(every_sec));
+    // The world backup runs only when its delay and destination are set
+    let world_backup_enabled = cfg.world_backup().is_some();
 
-    loop {
-        interval.tick().await;
-        periodic_playerstats_fetch().await;
-    }
+    // The lock file tells `otternel restore-state` that the daemon runs, it is removed when the daemon ends
+    let _lock = helper::state_backup::DaemonLock::acquire()
+        .inspect_err(|err| error!("Failed to write the lock file: {}", err))
+        .ok();
 
-}
+    // Register the tasks and run them until they end or the shutdown is requested
+    let app = app::App::builder(cfg)
+        .task(app::tasks::webhook_queue())
+        .task_if(helper::webhook_check::enabled(), app::tasks::webhook_check())
+        .task(app::tasks::log_watcher())
+        .task(app::tasks::active_servers())
+        .task(app::tasks::online_reconciliation())
+        .task_if(status_probe_enabled, app::tasks::server_status_probe())
+        .task_if(get_player_stats_enabled, app::tasks::periodic_events())
+        .task_if(integrity_report_enabled, app::tasks::integrity_report())
+        .task_if(status_report_enabled, app::tasks::status_report())
+        .task_if(pvp_leaderboard_enabled, app::tasks::pvp_leaderboard())
+        .task_if(weekly_leaderboard_enabled, app::tasks::weekly_leaderboard())
+        .task_if(rcon_schedules_enabled, app::tasks::rcon_schedules())
+        .task_if(linking_code_enabled, app::tasks::account_links())
+        .task(app::tasks::server_mutes())
+        .task(app::tasks::connection_log_replay())
+        .task(app::tasks::profile_repair())
+        .task_if(world_backup_enabled, app::tasks::world_backup())
+        .task_if(helper::state_backup::backup_dir().is_some(), app::tasks::state_backup())
+        .task_if(api_enabled, app::tasks::api_server())
+        .task_if(chat_bridge_enabled, app::tasks::chat_bridge())
+        .task_if(healthcheck_enabled, app::tasks::healthcheck_server())
+        .build();
 
-async fn periodic_playerstats_fetch() {
-    use helper::webhook_discord::send_discord_embed;
-
-    // Send embed
-    if let Err(e) = send_discord_embed(
-        "otternel",
-        "",
-        "Enregistrement des stats de joueurs Minecraft",
-        "",
-        "Passage sur chaque serveur de la table `serveurs`.",
-        Some("126020".to_string()),
-        "",
-        "",
-        "",
-        "Otternel Service",
-        Some(chrono::Utc::now().to_rfc3339()),
-    ) {
-        error!("{e}");
-    }
+    // SIGTERM (`docker stop`) and SIGINT (Ctrl+C) stop the tasks cleanly : the watcher saves its positions
+    // and the queued webhooks are sent, within 15 seconds
+    let shutdown = app.shutdown_handle();
+    tokio::spawn(async move {
+        wait_for_stop_signal().await;
+        info!("{}", "Stop signal received, stopping Otternel".yellow());
+        shutdown();
+    });
 
-    // Launch minecraft player stats
-    if let Err(e) = playerstats::minecraft_players::sync_mc_stats_to_db().await {
-        error!("Erreur sync_mc_stats_to_db: {e:?}");
+    if app.run().await {
+        info!("{}", "Otternel stopped cleanly".green());
+    } else {
+        warn!("Otternel stopped, some tasks were aborted");
     }
+}
 
-    // Minecraft player stats fetch ended, send embed
-    if let Err(e) = send_discord_embed(
-        "otternel",
-        "",
-        "Enregistrement des stats de joueurs Minecraft",
-        "",
-        "Enregistrement des stats de joueurs Minecraft terminé.",
-        Some("126020".to_string()),
-        "",
-        "",
-        "",
-        "Otternel Service",
-        Some(chrono::Utc::now().to_rfc3339()),
-    ) {
-        error!("{e}");
+/// Waits for SIGTERM or SIGINT.
+async fn wait_for_stop_signal() {
+    let mut terminate = match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+        Ok(terminate) => terminate,
+        Err(err) => {
+            error!("Could not listen to SIGTERM, only Ctrl+C stops Otternel cleanly: {}", err);
+            let _ = tokio::signal::ctrl_c().await;
+            return;
+        }
+    };
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {}
+        _ = terminate.recv() => {}
     }
 }
@@ -0,0 +1,177 @@
+use colored::Colorize;
+use log::{debug, error, info, warn};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+use crate::config::Config;
+use crate::db::models::Serveur;
+use crate::db::repository_default::Database;
+use crate::helper;
+use crate::helper::webhook_discord::DiscordEmbed;
+use crate::playerstats::minecraft_players::McStats;
+
+/// A badge of `badges.toml`, given when all its conditions pass.
+///
+/// ```toml
+/// [[badge]]
+/// id = 3                 # id of the badge in the database
+/// nom = "Immortel"       # shown in the embed
+/// conditions = [
+///     { stat = "nb_mort", op = "==", value = 0 },
+///     { stat = "tmps_jeux", op = ">", value = 1728000 },
+/// ]
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+pub struct BadgeRule {
+    pub id: u64,
+    pub nom: String,
+    pub description: Option<String>,
+    pub conditions: Vec<Condition>,
+}
+
+/// A comparison of a stat of `joueurs_stats` (ex: `tmps_jeux`, in ticks, or `dist_total`, in cm) with a value.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Condition {
+    pub stat: String,
+    /// `>`, `>=`, `<`, `<=`, `==` or `!=`
+    pub op: String,
+    pub value: i64,
+}
+
+impl Condition {
+    /// Tells whether the condition passes. An unknown stat or operator never passes.
+    fn passes(&self, stats: &HashMap<&'static str, i64>) -> bool {
+        let Some(stat) = stats.get(self.stat.as_str()) else {
+            return false;
+        };
+        match self.op.as_str() {
+            ">" => *stat > self.value,
+            ">=" => *stat >= self.value,
+            "<" => *stat < self.value,
+            "<=" => *stat <= self.value,
+            "==" => *stat == self.value,
+            "!=" => *stat != self.value,
+            _ => false,
+        }
+    }
+}
+
+impl BadgeRule {
+    /// Tells whether every condition passes. A badge without condition is never given.
+    pub fn passes(&self, stats: &HashMap<&'static str, i64>) -> bool {
+        !self.conditions.is_empty() && self.conditions.iter().all(|condition| condition.passes(stats))
+    }
+}
+
+#[derive(Deserialize)]
+struct BadgeFile {
+    #[serde(default)]
+    badge: Vec<BadgeRule>,
+}
+
+/// A player whose stats were written by a sync, candidate to the badges.
+pub struct BadgeCandidate {
+    pub joueur_id: u64,
+    pub stats: HashMap<&'static str, i64>,
+}
+
+/// The counters of the stats of a player, by column name, for the conditions of the badges.
+pub fn stat_values(stats: &McStats) -> HashMap<&'static str, i64> {
+    HashMap::from([
+        ("tmps_jeux", stats.tmps_jeux),
+        ("nb_mort", stats.nb_mort as i64),
+        ("nb_kills", stats.nb_kills as i64),
+        ("nb_playerkill", stats.nb_playerkill as i64),
+        ("nb_blocs_detr", stats.nb_blocs_detr as i64),
+        ("nb_blocs_pose", stats.nb_blocs_pose as i64),
+        ("dist_total", stats.dist_total as i64),
+        ("dist_pieds", stats.dist_pieds as i64),
+        ("dist_elytres", stats.dist_elytres as i64),
+        ("dist_vol", stats.dist_vol as i64),
+    ])
+}
+
+/// Loads the rules of `BADGES_PATH` (default `badges.toml`), read at each sync so badges can be added without
+/// restart. A missing file gives no badge; a conditions with an unknown stat is logged.
+pub fn load_rules() -> Vec<BadgeRule> {
+    let path = &Config::current().badges_path;
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            debug!("No {} found, no badge given", path);
+            return Vec::new();
+        }
+        Err(e) => {
+            warn!("Could not read {}: {}", path, e);
+            return Vec::new();
+        }
+    };
+    let rules = match toml::from_str::<BadgeFile>(&content) {
+        Ok(file) => file.badge,
+        Err(e) => {
+            error!("No badge loaded, invalid {}: {}", path, e);
+            return Vec::new();
+        }
+    };
+    let known = stat_values(&McStats::default());
+    for rule in &rules {
+        for condition in rule.conditions.iter().filter(|condition| !known.contains_key(condition.stat.as_str())) {
+            warn!("Badge '{}' : unknown stat '{}', it will never be given", rule.nom, condition.stat);
+        }
+    }
+    rules
+}
+
+/// Gives to the players of a server the badges whose conditions their stats pass and they don't have yet,
+/// and congratulates them on the webhook of the game of the server.
+pub fn award_badges(db: &Database, server: &Serveur, rules: &[BadgeRule], candidates: &[BadgeCandidate]) {
+    for candidate in candidates {
+        let passing: Vec<&BadgeRule> = rules.iter().filter(|rule| rule.passes(&candidate.stats)).collect();
+        if passing.is_empty() {
+            continue;
+        }
+        let owned = match db.list_badges_for_joueur(candidate.joueur_id) {
+            Ok(owned) => owned,
+            Err(e) => {
+                warn!("Could not list the badges of player {}: {}", candidate.joueur_id, e);
+                continue;
+            }
+        };
+        for rule in passing.into_iter().filter(|rule| !owned.contains(&rule.id)) {
+            if helper::dry_run::skip(|| format!("badge {} given to player {}", rule.nom, candidate.joueur_id)) {
+                continue;
+            }
+            match db.give_badge_to_joueur(candidate.joueur_id, rule.id) {
+                Ok(true) => congratulate(db, server, candidate.joueur_id, rule),
+                Ok(false) => {}
+                Err(e) => warn!("Could not give the badge {} to player {}: {}", rule.nom, candidate.joueur_id, e),
+            }
+        }
+    }
+}
+
+fn congratulate(db: &Database, server: &Serveur, joueur_id: u64, rule: &BadgeRule) {
+    let playername = db.get_playername_by_joueur_id(joueur_id).ok().flatten().unwrap_or_else(|| "Un joueur".to_string());
+    info!("Badge {} given to {}", rule.nom.green().bold(), playername.green().bold());
+    if helper::player_privacy::is_hidden("minecraft", &playername) {
+        return;
+    }
+
+    let mut description = format!("{} a obtenu le badge **{}** sur {} !", playername, rule.nom, server.nom);
+    if let Some(details) = &rule.description {
+        description.push_str(&format!("\n-# {}", details));
+    }
+    if let Err(e) = DiscordEmbed::new(&helper::webhook_discord::get_webhook_identity_by_server_id(server.jeu.clone()))
+        .thread(server.discord_thread_id.as_deref())
+        .title("Nouveau badge")
+        .url(&format!("https://antredesloutres.fr/joueurs/minecraft/{}", playername.to_lowercase()))
+        .description(&description)
+        .color("d4a017")
+        .thumbnail(&format!("{}/50", helper::webhook_discord::minecraft_avatar_url(&playername)))
+        .footer(&format!("Message de {}", server.nom))
+        .timestamp_now()
+        .send()
+    {
+        error!("{e}");
+    }
+}
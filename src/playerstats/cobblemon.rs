@@ -0,0 +1,436 @@
+use crate::db::models::{JoueurPokemonPc, Serveur};
+use crate::db::repository_default::Database;
+use crate::helper;
+use crate::playerstats::DockerFetcher;
+use anyhow::Result;
+use fastnbt::from_bytes;
+use fastnbt::Value as NbtValue;
+use flate2::read::GzDecoder;
+use log::{info, warn};
+use std::collections::HashMap;
+use std::io::Read;
+
+/// Number of slots of a Cobblemon team
+const PARTY_SLOTS: usize = 6;
+/// Number of slots of a Cobblemon PC box
+const PC_BOX_SLOTS: u32 = 30;
+
+/// A pokemon of a Cobblemon team or PC box.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pokemon {
+    pub species: String,
+    pub form: Option<String>,
+    pub gender: Option<String>,
+    pub nickname: Option<String>,
+    pub level: Option<i32>,
+    pub shiny: Option<bool>,
+    pub do_uuid: Option<String>,
+    pub pokemon_uuid: Option<String>,
+}
+
+/// Returns true if the server runs Cobblemon : its `modpack` or its `type` contains "cobblemon".
+pub fn is_cobblemon_server(server: &Serveur) -> bool {
+    [server.modpack.as_deref(), server.r#type.as_deref()]
+        .into_iter()
+        .flatten()
+        .any(|value| value.to_lowercase().contains("cobblemon"))
+}
+
+/// Parses a `playerpartystore/<uuid>.dat` file, gzipped or not : the pokemon of the `Slot0` to `Slot5` compounds,
+/// in slot order. Empty slots are skipped, even in the middle of the team.
+///
+/// # Errors
+/// Returns an error if the file can't be decompressed or isn't an NBT compound.
+pub fn parse_party_dat(bytes: &[u8]) -> Result<Vec<Pokemon>> {
+    let compound = read_dat(bytes)?;
+    Ok((0..PARTY_SLOTS)
+        .filter_map(|slot| match compound.get(&format!("Slot{}", slot)) {
+            Some(NbtValue::Compound(poke_nbt)) => Some(parse_pokemon(poke_nbt)),
+            _ => None,
+        })
+        .collect())
+}
+
+/// Parses a `pcstore/<uuid>.dat` file, gzipped or not : the pokemon of every `BoxN` compound, each holding up to
+/// 30 `SlotN` compounds. Empty boxes and slots give no pokemon.
+///
+/// # Errors
+/// Returns an error if the file can't be decompressed or isn't an NBT compound.
+pub fn parse_pc_dat(bytes: &[u8]) -> Result<Vec<JoueurPokemonPc>> {
+    let compound = read_dat(bytes)?;
+    let mut pokemons = Vec::new();
+    for (key, value) in &compound {
+        let (Some(boite), NbtValue::Compound(box_nbt)) = (key.strip_prefix("Box").and_then(|n| n.parse::<u32>().ok()), value) else {
+            continue;
+        };
+        for slot in 0..PC_BOX_SLOTS {
+            if let Some(NbtValue::Compound(poke_nbt)) = box_nbt.get(&format!("Slot{}", slot)) {
+                let poke = parse_pokemon(poke_nbt);
+                pokemons.push(JoueurPokemonPc {
+                    boite,
+                    slot,
+                    pkmn: poke.species,
+                    form: poke.form,
+                    gender: poke.gender,
+                    nickname: poke.nickname,
+                    level: poke.level,
+                    shiny: poke.shiny,
+                    do_uuid: poke.do_uuid,
+                    pokemon_uuid: poke.pokemon_uuid,
+                });
+            }
+        }
+    }
+    pokemons.sort_by_key(|pokemon| (pokemon.boite, pokemon.slot));
+    Ok(pokemons)
+}
+
+/// Saves the teams of the Cobblemon players of a world (`/server/<world>/pokemon/playerpartystore`).
+/// A corrupted file or a failed insert is logged and the next players are still saved.
+///
+/// # Returns
+/// `(pokemon saved, trainers saved)`
+///
+/// # Errors
+/// Returns an error if the player party stores can't be fetched.
+pub async fn fetch_cobblemon_stats(server_id: u64, container_name: &str, world_name: &str) -> Result<(usize, usize)> {
+    let Some(db) = helper::open_database::open_db_from_env() else {
+        warn!("Could not load DB configuration to save the Cobblemon teams");
+        return Ok((0, 0));
+    };
+    let fetcher = DockerFetcher::from_env()?;
+    let remote_path = format!("/server/{}/pokemon/playerpartystore", world_name);
+    let dat_files = fetch_dat_files(&fetcher, container_name, &remote_path).await?;
+
+    let mut total_pokemon = 0usize;
+    let mut total_trainers = 0usize;
+    for (uuid, bytes) in dat_files {
+        let team = match parse_party_dat(&bytes) {
+            Ok(team) => team,
+            Err(e) => {
+                warn!("Cobblemon team of {} skipped: {:?}", uuid, e);
+                continue;
+            }
+        };
+        if team.is_empty() {
+            continue;
+        }
+
+        let count = team.len();
+        let joueur_uuid = uuid.clone();
+        match db.call(move |db| insert_team(db, server_id, &joueur_uuid, &team)).await {
+            Ok(()) => {
+                total_pokemon += count;
+                total_trainers += 1;
+            }
+            Err(e) => warn!("Database insertion error for {}: {:?}", uuid, e),
+        }
+    }
+
+    info!(
+        "Cobblemon stats of {} : {} pokemon saved for {} trainers",
+        container_name, total_pokemon, total_trainers
+    );
+    Ok((total_pokemon, total_trainers))
+}
+
+/// Saves the PC boxes of the Cobblemon players of a world (`/server/<world>/pokemon/pcstore`).
+/// A corrupted file or a failed insert is logged and the next players are still saved.
+///
+/// # Returns
+/// `(pokemon saved, players saved)`
+///
+/// # Errors
+/// Returns an error if the PC stores can't be fetched.
+pub async fn fetch_cobblemon_pc(server_id: u64, container_name: &str, world_name: &str) -> Result<(usize, usize)> {
+    let Some(db) = helper::open_database::open_db_from_env() else {
+        warn!("Could not load DB configuration to save the Cobblemon PC boxes");
+        return Ok((0, 0));
+    };
+    let fetcher = DockerFetcher::from_env()?;
+    let remote_path = format!("/server/{}/pokemon/pcstore", world_name);
+    let dat_files = fetch_dat_files(&fetcher, container_name, &remote_path).await?;
+
+    let mut total_pokemon = 0usize;
+    let mut total_players = 0usize;
+    for (uuid, bytes) in dat_files {
+        let pokemons = match parse_pc_dat(&bytes) {
+            Ok(pokemons) => pokemons,
+            Err(e) => {
+                warn!("Cobblemon PC of {} skipped: {:?}", uuid, e);
+                continue;
+            }
+        };
+
+        let count = pokemons.len();
+        let joueur_uuid = uuid.clone();
+        match db.call(move |db| db.insert_joueur_pokemon_pc(server_id, &joueur_uuid, &pokemons)).await {
+            Ok(()) => {
+                total_pokemon += count;
+                total_players += 1;
+            }
+            Err(e) => warn!("Database insertion error for PC {}: {:?}", uuid, e),
+        }
+    }
+
+    info!(
+        "Cobblemon PC boxes of {} : {} pokemon saved for {} players",
+        container_name, total_pokemon, total_players
+    );
+    Ok((total_pokemon, total_players))
+}
+
+/// Downloads the `.dat` files under `remote_path`, by player uuid.
+async fn fetch_dat_files(fetcher: &DockerFetcher, container_name: &str, remote_path: &str) -> Result<HashMap<String, Vec<u8>>> {
+    let dat_files = fetcher.fetch_files_by_extension(container_name, remote_path, "dat").await?;
+    if dat_files.is_empty() {
+        info!("No .dat file found for '{}' (path: {})", container_name, remote_path);
+    }
+    Ok(dat_files)
+}
+
+/// Writes a team in `joueurs_pokemon`, its slots in order.
+fn insert_team(db: &Database, server_id: u64, joueur_uuid: &str, team: &[Pokemon]) -> Result<(), mysql::Error> {
+    let mut pkmn_data = [(None, None, None, None, None, None, None, None); PARTY_SLOTS];
+    for (i, poke) in team.iter().enumerate().take(PARTY_SLOTS) {
+        pkmn_data[i] = (
+            Some(poke.species.as_str()),
+            poke.form.as_deref(),
+            poke.gender.as_deref(),
+            poke.nickname.as_deref(),
+            poke.level,
+            poke.shiny,
+            poke.do_uuid.as_deref(),
+            poke.pokemon_uuid.as_deref(),
+        );
+    }
+    db.insert_joueur_pokemon(server_id, joueur_uuid, &pkmn_data)
+}
+
+/// Decompresses a `.dat` file if it is gzipped, and reads its NBT root compound.
+fn read_dat(bytes: &[u8]) -> Result<HashMap<String, NbtValue>> {
+    let decompressed;
+    let bytes = if is_gzipped(bytes) {
+        decompressed = decompress_gzip(bytes)?;
+        &decompressed[..]
+    } else {
+        bytes
+    };
+    match from_bytes::<NbtValue>(bytes)? {
+        NbtValue::Compound(compound) => Ok(compound),
+        _ => anyhow::bail!("unexpected NBT root type"),
+    }
+}
+
+/// Reads a pokemon from its slot compound
+fn parse_pokemon(poke_nbt: &HashMap<String, NbtValue>) -> Pokemon {
+    // UUID fields may have different names depending on context
+    Pokemon {
+        species: get_string(poke_nbt, "Species").unwrap_or_else(|| "unknown".to_string()),
+        form: get_string(poke_nbt, "FormId"),
+        gender: get_string(poke_nbt, "Gender"),
+        nickname: get_string(poke_nbt, "Nickname"),
+        level: get_int(poke_nbt, "Level"),
+        shiny: get_bool(poke_nbt, "Shiny"),
+        do_uuid: get_uuid(poke_nbt, "PokemonOriginalTrainer"),
+        pokemon_uuid: get_uuid(poke_nbt, "PokemonUUID").or_else(|| get_uuid(poke_nbt, "UUID")),
+    }
+}
+
+/// Checks if the byte slice starts with a GZIP header
+fn is_gzipped(bytes: &[u8]) -> bool {
+    bytes.len() >= 2 && bytes[0] == 0x1f && bytes[1] == 0x8b
+}
+
+/// Decompresses GZIP-compressed data into a Vec<u8>
+fn decompress_gzip(bytes: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut gz = GzDecoder::new(bytes);
+    let mut decompressed = Vec::new();
+    gz.read_to_end(&mut decompressed)?;
+    Ok(decompressed)
+}
+
+/// Extracts a String from an NBT compound
+fn get_string(nbt: &HashMap<String, NbtValue>, key: &str) -> Option<String> {
+    match nbt.get(key) {
+        Some(NbtValue::String(s)) => Some(s.clone()),
+        _ => None,
+    }
+}
+
+/// Extracts an i32 from an NBT compound
+fn get_int(nbt: &HashMap<String, NbtValue>, key: &str) -> Option<i32> {
+    match nbt.get(key) {
+        Some(NbtValue::Int(i)) => Some(*i),
+        _ => None,
+    }
+}
+
+/// Extracts a boolean value (commonly stored as Byte or Int in Minecraft NBT)
+fn get_bool(nbt: &HashMap<String, NbtValue>, key: &str) -> Option<bool> {
+    match nbt.get(key) {
+        Some(NbtValue::Byte(b)) => Some(*b != 0),
+        Some(NbtValue::Int(i)) => Some(*i != 0),
+        _ => None,
+    }
+}
+
+/// Extracts a UUID from either a String or an IntArray[4]
+fn get_uuid(nbt: &HashMap<String, NbtValue>, key: &str) -> Option<String> {
+    match nbt.get(key) {
+        Some(NbtValue::String(s)) => Some(s.clone()),
+        Some(NbtValue::IntArray(arr)) if arr.len() == 4 => {
+            // Standard Minecraft IntArray[4] -> UUID conversion
+            Some(format!(
+                "{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+                arr[0] as u32,
+                (arr[1] >> 16) & 0xFFFF,
+                arr[1] & 0xFFFF,
+                (arr[2] >> 16) & 0xFFFF,
+                (((arr[2] & 0xFFFF) as u64) << 32)
+                    | ((arr[3] as u32) as u64)
+            ))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fastnbt::IntArray;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    const TRAINER: &str = "0b7a1d0e-1111-2222-3333-444455556666";
+
+    fn compound(entries: Vec<(&str, NbtValue)>) -> NbtValue {
+        NbtValue::Compound(entries.into_iter().map(|(k, v)| (k.to_string(), v)).collect())
+    }
+
+    fn string(value: &str) -> NbtValue {
+        NbtValue::String(value.to_string())
+    }
+
+    fn pokemon(species: &str, uuid: NbtValue) -> NbtValue {
+        compound(vec![
+            ("Species", string(species)),
+            ("Level", NbtValue::Int(27)),
+            ("Shiny", NbtValue::Byte(1)),
+            ("Gender", string("FEMALE")),
+            ("PokemonOriginalTrainer", string(TRAINER)),
+            ("PokemonUUID", uuid),
+        ])
+    }
+
+    fn gzip(bytes: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(bytes).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn party() -> Vec<u8> {
+        fastnbt::to_bytes(&compound(vec![
+            ("Slot0", pokemon("cobblemon:bulbasaur", string("aaaaaaaa-bbbb-cccc-dddd-eeeeffff0000"))),
+            ("Slot2", pokemon("cobblemon:eevee", string("11111111-2222-3333-4444-555566667777"))),
+            ("Slot1", NbtValue::String("not a pokemon".to_string())),
+            ("Slot9", pokemon("cobblemon:mew", string("ignored"))),
+        ]))
+        .unwrap()
+    }
+
+    #[test]
+    fn plain_and_gzipped_party_files_give_the_same_team() {
+        let plain = parse_party_dat(&party()).unwrap();
+        assert_eq!(parse_party_dat(&gzip(&party())).unwrap(), plain);
+
+        // Slot 1 isn't a pokemon and slot 9 is past the team : the two others, in slot order
+        let species: Vec<&str> = plain.iter().map(|p| p.species.as_str()).collect();
+        assert_eq!(species, vec!["cobblemon:bulbasaur", "cobblemon:eevee"]);
+        assert_eq!(plain[0], Pokemon {
+            species: "cobblemon:bulbasaur".to_string(),
+            form: None,
+            gender: Some("FEMALE".to_string()),
+            nickname: None,
+            level: Some(27),
+            shiny: Some(true),
+            do_uuid: Some(TRAINER.to_string()),
+            pokemon_uuid: Some("aaaaaaaa-bbbb-cccc-dddd-eeeeffff0000".to_string()),
+        });
+    }
+
+    #[test]
+    fn uuids_as_int_arrays() {
+        // 0b7a1d0e-1111-2222-3333-444455556666 and a uuid with every high bit set
+        let arrays = [
+            (vec![0x0b7a1d0e, 0x11112222, 0x33334444, 0x55556666], "0b7a1d0e-1111-2222-3333-444455556666"),
+            (vec![-1, -1, -1, -1], "ffffffff-ffff-ffff-ffff-ffffffffffff"),
+            (vec![i32::MIN, 0x0000ffff, -0x10000, 1], "80000000-0000-ffff-ffff-000000000001"),
+        ];
+        for (ints, expected) in arrays {
+            let bytes = fastnbt::to_bytes(&compound(vec![
+                ("Slot0", pokemon("cobblemon:pikachu", NbtValue::IntArray(IntArray::new(ints)))),
+            ]))
+            .unwrap();
+            let team = parse_party_dat(&bytes).unwrap();
+            assert_eq!(team[0].pokemon_uuid.as_deref(), Some(expected));
+        }
+
+        // An IntArray of the wrong length isn't a uuid, the older `UUID` key is the fallback
+        let bytes = fastnbt::to_bytes(&compound(vec![("Slot0", compound(vec![
+            ("Species", string("cobblemon:ditto")),
+            ("PokemonUUID", NbtValue::IntArray(IntArray::new(vec![1, 2]))),
+            ("UUID", string("older-uuid")),
+        ]))]))
+        .unwrap();
+        let team = parse_party_dat(&bytes).unwrap();
+        assert_eq!(team[0].pokemon_uuid.as_deref(), Some("older-uuid"));
+        assert_eq!((team[0].level, team[0].shiny, team[0].do_uuid.as_deref()), (None, None, None));
+    }
+
+    #[test]
+    fn a_missing_species_is_unknown() {
+        let bytes = fastnbt::to_bytes(&compound(vec![("Slot3", compound(vec![("Level", NbtValue::Int(5))]))])).unwrap();
+        assert_eq!(parse_party_dat(&bytes).unwrap()[0].species, "unknown");
+    }
+
+    #[test]
+    fn pc_boxes_in_box_and_slot_order() {
+        let bytes = fastnbt::to_bytes(&compound(vec![
+            ("Box1", compound(vec![("Slot0", pokemon("cobblemon:snorlax", string("u3")))])),
+            ("Box0", compound(vec![
+                ("Slot29", pokemon("cobblemon:onix", string("u2"))),
+                ("Slot4", pokemon("cobblemon:abra", string("u1"))),
+                ("Slot30", pokemon("cobblemon:mew", string("past the box"))),
+            ])),
+            ("BoxCount", NbtValue::Int(2)),
+            ("Box2", compound(vec![])),
+        ]))
+        .unwrap();
+        let pc = parse_pc_dat(&gzip(&bytes)).unwrap();
+        let places: Vec<(u32, u32, &str)> = pc.iter().map(|p| (p.boite, p.slot, p.pkmn.as_str())).collect();
+        assert_eq!(places, vec![(0, 4, "cobblemon:abra"), (0, 29, "cobblemon:onix"), (1, 0, "cobblemon:snorlax")]);
+    }
+
+    #[test]
+    fn broken_files_are_errors() {
+        assert!(parse_party_dat(b"").is_err());
+        assert!(parse_party_dat(&[0x1f, 0x8b, 0, 1, 2]).is_err());
+        assert!(parse_party_dat(b"not nbt at all").is_err());
+        assert!(parse_pc_dat(&gzip(b"\x0a\x00")).is_err());
+    }
+
+    #[test]
+    fn cobblemon_servers() {
+        let server = |modpack: Option<&str>, r#type: Option<&str>| Serveur {
+            modpack: modpack.map(str::to_string),
+            r#type: r#type.map(str::to_string),
+            ..Default::default()
+        };
+        assert!(is_cobblemon_server(&server(Some("Cobblemon Official"), None)));
+        assert!(is_cobblemon_server(&server(None, Some("fabric-COBBLEMON"))));
+        assert!(!is_cobblemon_server(&server(Some("Vanilla"), Some("survie"))));
+        assert!(!is_cobblemon_server(&server(None, None)));
+    }
+}
@@ -1,25 +1,58 @@
 use bollard::Docker;
-use bollard::container::DownloadFromContainerOptions;
-use futures_util::stream::TryStreamExt;
+use bollard::errors::Error::DockerResponseServerError;
+use futures_util::stream::{Stream, StreamExt, TryStreamExt};
 use std::collections::HashMap;
-use std::io::Cursor;
-use bollard::query_parameters::InspectContainerOptions;
+use std::io::{self, Read};
+use bollard::query_parameters::{DownloadFromContainerOptions, DownloadFromContainerOptionsBuilder, InspectContainerOptions, RemoveContainerOptionsBuilder};
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use std::io::Write;
+use std::path::Path;
+use std::time::Duration;
 use log::warn;
 use tar::Archive;
 use serde_json::Value;
+use tokio::sync::mpsc;
+
+use crate::config::Config;
 
 pub mod minecraft_players;
-mod cobblemon_pokemon_fetch;
-mod cobblemon_stats;
+pub mod palworld_players;
+mod palworld_sav;
+pub mod cobblemon;
+pub mod badges;
+
+/// Number of downloaded chunks waiting for the tar parser, at most
+const STREAM_CHUNKS_AHEAD: usize = 8;
 
 pub struct DockerFetcher {
     docker: Docker,
 }
 
 impl DockerFetcher {
-    pub fn new() -> Self {
-        let docker = Docker::connect_with_unix_defaults().expect("Impossible to connect to docker");
-        DockerFetcher { docker }
+    /// Connects to the Docker daemon given by `DOCKER_HOST` : the local socket when it's not set, `tcp://` for a
+    /// remote daemon, with TLS when `DOCKER_TLS_VERIFY` is set (certificates read from `DOCKER_CERT_PATH`).
+    ///
+    /// # Errors
+    /// Returns an error if `DOCKER_HOST` isn't supported or the TLS certificates can't be read.
+    pub fn from_env() -> anyhow::Result<Self> {
+        let docker = Docker::connect_with_defaults().map_err(|e| {
+            anyhow::anyhow!(
+                "Could not connect to Docker ({}): {}",
+                Config::current().docker_host.as_deref().unwrap_or("local socket"),
+                e
+            )
+        })?;
+        Ok(DockerFetcher { docker })
+    }
+
+    /// Returns false if the container doesn't exist, and an error if the Docker daemon can't be reached.
+    async fn container_exists(&self, container_name: &str) -> anyhow::Result<bool> {
+        match self.docker.inspect_container(container_name, None::<InspectContainerOptions>).await {
+            Ok(_) => Ok(true),
+            Err(DockerResponseServerError { status_code: 404, .. }) => Ok(false),
+            Err(e) => Err(e.into()),
+        }
     }
 
     /// Fetch all JSON file of a certain file path in the container
@@ -33,100 +66,367 @@ impl DockerFetcher {
         container_name: &str,
         remote_path: &str,
     ) -> anyhow::Result<HashMap<String, Value>> {
-        let options = DownloadFromContainerOptions {
-            path: remote_path.to_string(),
-        };
+        let (result, _) = self.fetch_json_files_matching(container_name, remote_path, |_| true).await?;
+        Ok(result)
+    }
 
-        if self.docker.inspect_container(container_name, None::<InspectContainerOptions>).await.is_err() {
+    /// Same as [`DockerFetcher::fetch_json_files`], keeping only the files whose name (without `.json`) passes `keep`.
+    /// # Returns
+    /// - The kept files { "name" => JSON Value }, and the names of the skipped ones
+    pub async fn fetch_json_files_matching(
+        &self,
+        container_name: &str,
+        remote_path: &str,
+        keep: impl Fn(&str) -> bool + Send + 'static,
+    ) -> anyhow::Result<(HashMap<String, Value>, Vec<String>)> {
+        let options = DownloadFromContainerOptionsBuilder::new().path(remote_path).build();
+
+        if !self.container_exists(container_name).await? {
             // Container not found, moving on
             warn!("Failed to download files from '{}' container", container_name);
-            return Ok(HashMap::new());
+            return Ok((HashMap::new(), Vec::new()));
         }
 
-        let mut stream = self.docker.download_from_container(container_name, Some(options));
+        self.stream_tar(container_name, options, move |reader| json_files_in_archive(reader, keep)).await
+    }
 
-        let mut tar_bytes = Vec::new();
+    /// Récupère tous les fichiers avec l'extension donnée sous `remote_path` dans le container.
+    /// Retourne une map { "<filename sans extension>" => bytes }.
+    pub async fn fetch_files_by_extension(
+        &self,
+        container_name: &str,
+        remote_path: &str,
+        ext: &str,
+    ) -> anyhow::Result<std::collections::HashMap<String, Vec<u8>>> {
+        let options = DownloadFromContainerOptionsBuilder::new().path(remote_path).build();
 
-        while let Some(chunk) = stream.try_next().await? {
-            tar_bytes.extend(chunk);
+        if !self.container_exists(container_name).await? {
+            warn!("Failed to download files from '{}' container", container_name);
+            return Ok(std::collections::HashMap::new());
         }
 
-        let cursor = Cursor::new(tar_bytes);
-        let mut archive = Archive::new(cursor);
+        let ext = format!(".{}", ext);
+        self.stream_tar(container_name, options, move |reader| {
+            let mut archive = Archive::new(reader);
+            let mut result = std::collections::HashMap::new();
 
-        let mut result = HashMap::new();
+            for entry in archive.entries()? {
+                let mut file = entry?;
+                let path_str = file.path()?.to_string_lossy().into_owned();
 
-        for entry in archive.entries()? {
-            let mut file = entry?;
+                if path_str.ends_with(&ext) {
+                    let mut contents = Vec::new();
+                    file.read_to_end(&mut contents)?;
 
-            // Clone the path inside a string
-            let path_str = file
-                .path()?
-                .to_string_lossy()
-                .into_owned();
+                    if let Some(fname) = path_str.split('/').next_back() {
+                        let key = fname.replace(&ext, "");
+                        result.insert(key, contents);
+                    }
+                }
+            }
 
-            if path_str.ends_with(".json") { // We only get JSON files
-                let mut contents = String::new();
-                use std::io::Read;
-                file.read_to_string(&mut contents)?;
+            Ok(result)
+        }).await
+    }
 
-                if let Ok(json) = serde_json::from_str::<Value>(&contents) {
-                    if let Some(uuid) = path_str.split('/').last() {
-                        let uuid = uuid.replace(".json", "");
-                        result.insert(uuid, json);
-                    }
+    /// Downloads `options.path` from the container and hands the tar archive to `parse` while it's downloaded.
+    /// At most `STREAM_CHUNKS_AHEAD` chunks wait for the parser, so the archive is never held in memory as a whole,
+    /// and the download is aborted once it's over `DOCKER_FETCH_MAX_BYTES`.
+    async fn stream_tar<T: Send + 'static>(
+        &self,
+        container_name: &str,
+        options: DownloadFromContainerOptions,
+        parse: impl FnOnce(&mut dyn Read) -> anyhow::Result<T> + Send + 'static,
+    ) -> anyhow::Result<T> {
+        let remote_path = options.path.clone();
+        let mut stream = self.docker.download_from_container(container_name, Some(options));
+
+        let (sender, receiver) = mpsc::channel(STREAM_CHUNKS_AHEAD);
+        let parser = tokio::task::spawn_blocking(move || parse(&mut ChunkReader::new(receiver)));
+
+        let max_bytes = fetch_max_bytes();
+        let mut downloaded: u64 = 0;
+        let mut download_error = None;
+
+        while let Some(chunk) = stream.next().await {
+            let chunk = match chunk {
+                Ok(chunk) => chunk,
+                Err(e) => {
+                    let _ = sender.send(Err(io::Error::other(e.to_string()))).await;
+                    download_error = Some(anyhow::Error::from(e));
+                    break;
                 }
+            };
+
+            downloaded += chunk.len() as u64;
+            if max_bytes.is_some_and(|max| downloaded > max) {
+                let message = format!(
+                    "Download of '{}' from '{}' is over DOCKER_FETCH_MAX_BYTES ({} bytes), aborted",
+                    remote_path,
+                    container_name,
+                    max_bytes.unwrap_or_default()
+                );
+                let _ = sender.send(Err(io::Error::other(message.clone()))).await;
+                download_error = Some(anyhow::anyhow!(message));
+                break;
+            }
+
+            if sender.send(Ok(chunk)).await.is_err() {
+                break; // The parser stopped early, its result tells why
             }
         }
+        drop(sender);
 
-        Ok(result)
+        let parsed = parser.await?;
+        match download_error {
+            Some(e) => Err(e),
+            None => parsed,
+        }
     }
 
-    /// Récupère tous les fichiers avec l'extension donnée sous `remote_path` dans le container.
-    /// Retourne une map { "<filename sans extension>" => bytes }.
-    pub async fn fetch_files_by_extension(
+    /// Writes the content of `remote_path` in the container to `destination`, as a tar.gz archive.
+    /// The archive is streamed from Docker, so a whole world is never held in memory.
+    ///
+    /// # Returns
+    /// - The size of the archive, in bytes
+    pub async fn archive_to_tar_gz(
         &self,
         container_name: &str,
         remote_path: &str,
-        ext: &str,
-    ) -> anyhow::Result<std::collections::HashMap<String, Vec<u8>>> {
-        let options = DownloadFromContainerOptions {
-            path: remote_path.to_string(),
-        };
+        destination: &Path,
+    ) -> anyhow::Result<u64> {
+        let options = DownloadFromContainerOptionsBuilder::new().path(remote_path).build();
+        let stream = self.docker.download_from_container(container_name, Some(options));
+        write_tar_gz(stream, destination).await
+    }
 
-        if self.docker.inspect_container(container_name, None::<InspectContainerOptions>).await.is_err() {
-            warn!("Failed to download files from '{}' container", container_name);
-            return Ok(std::collections::HashMap::new());
+    /// Waits for a container to stop, checking every second.
+    ///
+    /// # Returns
+    /// - `true` if the container stopped (or doesn't exist anymore) before `timeout`
+    pub async fn wait_until_stopped(&self, container_name: &str, timeout: Duration) -> bool {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            let running = match self.docker.inspect_container(container_name, None::<InspectContainerOptions>).await {
+                Ok(container) => container.state.and_then(|state| state.running).unwrap_or(false),
+                Err(_) => false,
+            };
+            if !running {
+                return true;
+            }
+            if tokio::time::Instant::now() >= deadline {
+                return false;
+            }
+            tokio::time::sleep(Duration::from_secs(1)).await;
         }
+    }
 
-        let mut stream = self.docker.download_from_container(container_name, Some(options));
-        let mut tar_bytes = Vec::new();
+    /// Removes a stopped container, with its anonymous volumes.
+    /// Bind mounts and named volumes are left untouched.
+    pub async fn remove_container(&self, container_name: &str) -> anyhow::Result<()> {
+        let options = RemoveContainerOptionsBuilder::new().v(true).build();
+        self.docker.remove_container(container_name, Some(options)).await?;
+        Ok(())
+    }
 
-        while let Some(chunk) = stream.try_next().await? {
-            tar_bytes.extend(chunk);
-        }
+}
 
-        let cursor = Cursor::new(tar_bytes);
-        let mut archive = Archive::new(cursor);
-        let mut result = std::collections::HashMap::new();
+/// Writes a tar downloaded from a container, chunk by chunk, gzipped to `destination` : the archive holds
+/// the whole downloaded folder, with its sub-folders.
+///
+/// # Returns
+/// The size of the archive written.
+async fn write_tar_gz<B, E>(mut chunks: impl Stream<Item = Result<B, E>> + Unpin, destination: &Path) -> anyhow::Result<u64>
+where
+    B: AsRef<[u8]>,
+    E: std::error::Error + Send + Sync + 'static,
+{
+    let file = std::fs::File::create(destination)?;
+    let mut encoder = GzEncoder::new(file, Compression::default());
+    while let Some(chunk) = chunks.try_next().await? {
+        encoder.write_all(chunk.as_ref())?;
+    }
+    encoder.finish()?.sync_all()?;
+    Ok(std::fs::metadata(destination)?.len())
+}
 
-        for entry in archive.entries()? {
-            let mut file = entry?;
-            let path_str = file.path()?.to_string_lossy().into_owned();
+/// Returns the size over which a download from a container is aborted (`DOCKER_FETCH_MAX_BYTES`, 0 = no limit).
+fn fetch_max_bytes() -> Option<u64> {
+    let max = Config::current().docker_fetch_max_bytes;
+    (max > 0).then_some(max)
+}
 
-            if path_str.ends_with(&format!(".{}", ext)) {
-                let mut contents = Vec::new();
-                use std::io::Read;
-                file.read_to_end(&mut contents)?;
+/// Blocking reader over the chunks of a download, sent by [`DockerFetcher::stream_tar`].
+struct ChunkReader<B> {
+    chunks: mpsc::Receiver<io::Result<B>>,
+    current: Option<B>,
+    position: usize,
+}
 
-                if let Some(fname) = path_str.split('/').last() {
-                    let key = fname.replace(&format!(".{}", ext), "");
-                    result.insert(key, contents);
+impl<B> ChunkReader<B> {
+    fn new(chunks: mpsc::Receiver<io::Result<B>>) -> Self {
+        ChunkReader { chunks, current: None, position: 0 }
+    }
+}
+
+impl<B: AsRef<[u8]>> Read for ChunkReader<B> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if let Some(chunk) = &self.current {
+                let remaining = &chunk.as_ref()[self.position..];
+                if !remaining.is_empty() {
+                    let n = remaining.len().min(buf.len());
+                    buf[..n].copy_from_slice(&remaining[..n]);
+                    self.position += n;
+                    return Ok(n);
+                }
+            }
+
+            // Current chunk fully read, waiting for the next one (None = end of the download)
+            match self.chunks.blocking_recv() {
+                Some(chunk) => {
+                    self.current = Some(chunk?);
+                    self.position = 0;
                 }
+                None => return Ok(0),
             }
         }
+    }
+}
 
-        Ok(result)
+/// Reads the `.json` files of a tar archive, keeping only those whose name (without `.json`) passes `keep`.
+/// Files that are not valid JSON are ignored.
+/// # Returns
+/// - The kept files { "name" => JSON Value }, and the names of the skipped ones
+fn json_files_in_archive(
+    reader: &mut dyn Read,
+    keep: impl Fn(&str) -> bool,
+) -> anyhow::Result<(HashMap<String, Value>, Vec<String>)> {
+    let mut archive = Archive::new(reader);
+
+    let mut result = HashMap::new();
+    let mut skipped = Vec::new();
+
+    for entry in archive.entries()? {
+        let mut file = entry?;
+
+        // Clone the path inside a string
+        let path_str = file
+            .path()?
+            .to_string_lossy()
+            .into_owned();
+
+        if path_str.ends_with(".json") { // We only get JSON files
+            let name = path_str.split('/').next_back().unwrap_or_default().replace(".json", "");
+            if !keep(&name) {
+                skipped.push(name);
+                continue;
+            }
+
+            let mut contents = String::new();
+            file.read_to_string(&mut contents)?;
+
+            if let Ok(json) = serde_json::from_str::<Value>(&contents) {
+                result.insert(name, json);
+            }
+        }
+    }
+
+    Ok((result, skipped))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use std::path::PathBuf;
+
+    /// World folder of a server : level.dat, the regions of each dimension and the player data
+    fn world_folder(root: &Path) -> Vec<(String, Vec<u8>)> {
+        let files = vec![
+            ("world/level.dat".to_string(), b"level".to_vec()),
+            ("world/region/r.0.0.mca".to_string(), vec![7; 5000]),
+            ("world/region/r.-1.0.mca".to_string(), vec![8; 3000]),
+            ("world/DIM-1/region/r.0.0.mca".to_string(), vec![9; 1000]),
+            ("world/playerdata/0b7a1d0e-0000-0000-0000-000000000000.dat".to_string(), b"player".to_vec()),
+        ];
+        for (name, bytes) in &files {
+            let path = root.join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, bytes).unwrap();
+        }
+        files
+    }
+
+    #[tokio::test]
+    async fn world_archive_holds_the_whole_folder() {
+        let dir = std::env::temp_dir().join(format!("otternel-world-archive-{}", uuid::Uuid::new_v4()));
+        let files = world_folder(&dir);
+
+        // Docker sends the folder as a tar, in chunks cut anywhere
+        let mut tar = tar::Builder::new(Vec::new());
+        tar.append_dir_all("world", dir.join("world")).unwrap();
+        let tar = tar.into_inner().unwrap();
+        let chunks: Vec<Result<Vec<u8>, io::Error>> = tar.chunks(777).map(|chunk| Ok(chunk.to_vec())).collect();
+
+        let destination = dir.join("survie-2026-01-01.tar.gz");
+        let size = write_tar_gz(futures_util::stream::iter(chunks), &destination).await.unwrap();
+        assert_eq!(size, std::fs::metadata(&destination).unwrap().len());
+
+        let mut archived: Vec<(String, Vec<u8>)> = Vec::new();
+        let mut folders: Vec<PathBuf> = Vec::new();
+        let mut archive = Archive::new(GzDecoder::new(std::fs::File::open(&destination).unwrap()));
+        for entry in archive.entries().unwrap() {
+            let mut entry = entry.unwrap();
+            let path = entry.path().unwrap().into_owned();
+            if entry.header().entry_type().is_dir() {
+                folders.push(path);
+                continue;
+            }
+            let mut bytes = Vec::new();
+            entry.read_to_end(&mut bytes).unwrap();
+            archived.push((path.to_string_lossy().into_owned(), bytes));
+        }
+        archived.sort();
+        let mut expected = files;
+        expected.sort();
+        assert_eq!(archived, expected);
+        for folder in ["world/region", "world/DIM-1/region", "world/playerdata"] {
+            assert!(folders.iter().any(|f| f == Path::new(folder)), "{folder} missing from {folders:?}");
+        }
+
+        let _ = std::fs::remove_dir_all(&dir);
     }
 
+    #[test]
+    fn stats_folder_with_junk_files_keeps_only_the_players() {
+        let player = "0b7a1d0e-1111-2222-3333-444455556666";
+        let files: Vec<(String, &[u8])> = vec![
+            (format!("stats/{player}.json"), br#"{"stats":{}}"#),
+            ("stats/README.json".to_string(), br#"{"note":"not a player"}"#),
+            ("stats/.json".to_string(), b"{}"),
+            ("stats/notes.txt".to_string(), b"hello"),
+            ("stats/0b7a1d0e-aaaa-bbbb-cccc-ddddeeeeffff.json".to_string(), b"{ broken"),
+        ];
+        let mut tar = tar::Builder::new(Vec::new());
+        for (name, bytes) in &files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(bytes.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            tar.append_data(&mut header, name, *bytes).unwrap();
+        }
+        let tar = tar.into_inner().unwrap();
+
+        let (kept, mut skipped) = json_files_in_archive(
+            &mut tar.as_slice(),
+            crate::helper::minecraft_account_formatter::is_minecraft_uuid,
+        ).unwrap();
+
+        // The broken json of a valid uuid is dropped, the non json file is not even looked at
+        assert_eq!(kept.len(), 1);
+        assert_eq!(kept[player], serde_json::json!({"stats": {}}));
+        skipped.sort();
+        assert_eq!(skipped, vec!["".to_string(), "README".to_string()]);
+    }
 }
@@ -0,0 +1,194 @@
+use crate::playerstats::minecraft_players::{self, SyncReport};
+use crate::playerstats::palworld_sav::{self, PalworldSavePlayer};
+use crate::playerstats::DockerFetcher;
+use std::collections::HashMap;
+use colored::Colorize;
+use log::{debug, error, info, warn};
+use crate::config::Config;
+use crate::helper;
+use crate::helper::webhook_discord::DiscordEmbed;
+
+/// Default of `PALWORLD_SAVEGAMES_PATH` : folder of the worlds in the Palworld container
+const DEFAULT_SAVEGAMES_PATH: &str = "/palworld/Pal/Saved/SaveGames/0";
+/// Minecraft play time is stored in ticks, Palworld play time too so both can be compared
+const TICKS_PER_SECOND: i64 = 20;
+
+/// Players of a Palworld world, read from its save files.
+#[derive(Debug, Clone, Default)]
+pub struct PalworldWorldStats {
+    pub players: Vec<PalworldSavePlayer>,
+    /// Pals captured by player uid, for the players whose file has the count
+    pub pals_captures: HashMap<String, i64>,
+}
+
+/// Reads the players of a Palworld world : name and level from `Level.sav`, pals captured from `Players/<uid>.sav`.
+/// The world is `<PALWORLD_SAVEGAMES_PATH>/<world_name>` (Default `/palworld/Pal/Saved/SaveGames/0`).
+///
+/// # Errors
+/// Returns an error if `Level.sav` can't be downloaded or decompressed. A player file that can't be read is skipped.
+pub async fn fetch_palworld_world_stats(container_name: &str, world_name: &str) -> anyhow::Result<PalworldWorldStats> {
+    let fetcher = DockerFetcher::from_env()?;
+    let world_path = format!("{}/{}", savegames_path(), world_name);
+
+    let mut level_files = fetcher.fetch_files_by_extension(container_name, &format!("{}/Level.sav", world_path), "sav").await?;
+    let Some(level) = level_files.remove("Level") else {
+        anyhow::bail!("Level.sav not found in {}", world_path);
+    };
+    let player_files = fetcher
+        .fetch_files_by_extension(container_name, &format!("{}/Players", world_path), "sav")
+        .await
+        .unwrap_or_else(|e| {
+            warn!("Could not download the Palworld player files of {}: {}", container_name.yellow(), e);
+            HashMap::new()
+        });
+
+    // Decompressing and scanning the saves is CPU bound
+    tokio::task::spawn_blocking(move || {
+        let gvas = palworld_sav::decompress_sav(&level).map_err(|e| anyhow::anyhow!("Level.sav: {}", e))?;
+        let players = palworld_sav::level_players(&gvas);
+
+        let mut pals_captures = HashMap::new();
+        for (uid, bytes) in player_files {
+            match palworld_sav::decompress_sav(&bytes) {
+                Ok(gvas) => {
+                    if let Some(count) = palworld_sav::captured_pals(&gvas) {
+                        pals_captures.insert(uid.to_uppercase(), count);
+                    }
+                }
+                Err(e) => debug!("Palworld player file {}.sav skipped: {}", uid, e),
+            }
+        }
+
+        Ok(PalworldWorldStats { players, pals_captures })
+    })
+    .await?
+}
+
+fn savegames_path() -> String {
+    Config::current()
+        .palworld_savegames_path
+        .as_deref()
+        .map(|path| path.trim().trim_end_matches('/').to_string())
+        .filter(|path| !path.is_empty())
+        .unwrap_or_else(|| DEFAULT_SAVEGAMES_PATH.to_string())
+}
+
+/// Saves the level, play time and pals captured of the players of every active Palworld server.
+/// Players are matched on their name with the accounts added from the connection logs, the others are skipped.
+/// The play time is the sum of the sessions logged on the server.
+///
+/// # Errors
+/// Returns an error only if the servers can't be listed.
+pub async fn sync_palworld_stats_to_db() -> anyhow::Result<SyncReport> {
+    let started = std::time::Instant::now();
+    let mut report = SyncReport::default();
+
+    let db = match helper::open_database::open_db_from_env() {
+        Some(db) => db,
+        None => {
+            warn!("Could not load DB configuration to sync Palworld stats");
+            return Ok(report);
+        }
+    };
+
+    let mut palworld_servers = db.call(|db| db.get_all_server_by_game("palworld".into())).await?;
+    minecraft_players::keep_active_servers(&mut palworld_servers);
+    if palworld_servers.is_empty() {
+        debug!("No Palworld server, Palworld stats skipped");
+        return Ok(report);
+    }
+
+    info!(
+        "{} {} {}",
+        "Starting periodic playerstats fetch for".blue().bold(),
+        "Palworld".green().bold(),
+        "players :".blue().bold()
+    );
+    for server in palworld_servers {
+        let (Some(container), Some(world_name)) = (server.contenaire.as_deref(), server.nom_monde.as_deref()) else {
+            warn!("Server {} has no container or world name, skipped", server.nom.yellow().bold());
+            report.serveurs_en_erreur.push((server.nom.clone(), "conteneur ou nom du monde manquant".to_string()));
+            continue;
+        };
+
+        let world = match fetch_palworld_world_stats(container, world_name).await {
+            Ok(world) => world,
+            Err(e) => {
+                warn!("Failed to fetch Palworld stats for server {}: {}", server.nom.yellow().bold(), e);
+                report.serveurs_en_erreur.push((server.nom.clone(), e.to_string()));
+                continue;
+            }
+        };
+
+        let total_players = world.players.len();
+        let mut saved_count = 0;
+        let mut unknown_players: Vec<String> = Vec::new();
+        for player in world.players {
+            let serveur_id = server.id;
+            let pals_captures = world.pals_captures.get(&player.uid).copied();
+            let nickname = player.nickname.clone();
+            if helper::dry_run::skip(|| format!("Palworld playerstats of {} upserted on {}", nickname, server.nom)) {
+                continue;
+            }
+
+            let saved = db.call(move |db| -> Result<bool, mysql::Error> {
+                let (Some(compte_id), Some(joueur_id)) = (
+                    db.get_compte_id_by_playername("palworld", &nickname)?,
+                    db.get_player_id_by_playername("palworld", &nickname)?,
+                ) else {
+                    return Ok(false);
+                };
+                let seconds = db.get_total_session_time(joueur_id, serveur_id)?;
+                let tmps_jeux = i64::try_from(seconds).unwrap_or(i64::MAX).saturating_mul(TICKS_PER_SECOND);
+                db.upsert_palworld_playerstats(serveur_id, &compte_id, tmps_jeux, player.level, pals_captures)?;
+                Ok(true)
+            }).await;
+
+            match saved {
+                Ok(true) => {
+                    saved_count += 1;
+                    debug!("Palworld playerstats added for player : {}", player.nickname.green().bold());
+                }
+                Ok(false) => unknown_players.push(player.nickname),
+                Err(e) => warn!("Failed to add/update Palworld stats for {}: {}", player.nickname.yellow().bold(), e),
+            }
+        }
+
+        if !unknown_players.is_empty() {
+            warn!(
+                "{} Palworld players never seen in the logs skipped for server {} : {}",
+                unknown_players.len(),
+                server.nom.yellow().bold(),
+                unknown_players.join(", ")
+            );
+        }
+        info!("Server {} : {} Palworld players updated", server.nom.green().bold(), saved_count.to_string().green().bold());
+
+        report.joueurs_maj += saved_count;
+        let failed = total_players - saved_count - unknown_players.len();
+        if failed == 0 {
+            report.serveurs_ok += 1;
+        } else {
+            report.serveurs_en_erreur.push((server.nom.clone(), format!("{} joueurs non enregistrés", failed)));
+        }
+
+        let mut embed_supertext = format!("Enregistrement de {} joueurs sur {}", saved_count, total_players);
+        if !unknown_players.is_empty() {
+            embed_supertext.push_str(&format!("\n{} joueurs ignorés (jamais vus dans les logs)", unknown_players.len()));
+        }
+        if let Err(e) = DiscordEmbed::new("otternel")
+            .title(&format!("Playerstats fetch for {}", server.nom))
+            .description(&embed_supertext)
+            .color(if failed == 0 { "126020" } else { "601010" })
+            .thumbnail(server.image.as_deref().unwrap_or_default())
+            .footer(&server.nom)
+            .timestamp_now()
+            .send()
+        {
+            error!("{e}");
+        }
+    }
+
+    report.duree = started.elapsed();
+    Ok(report)
+}
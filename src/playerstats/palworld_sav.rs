@@ -0,0 +1,190 @@
+use flate2::read::ZlibDecoder;
+use std::io::Read;
+
+/// Palworld player found in `Level.sav`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PalworldSavePlayer {
+    /// Player uid, as in the name of its `Players/<uid>.sav` file
+    pub uid: String,
+    pub nickname: String,
+    pub level: i64,
+}
+
+/// Decompresses a Palworld `.sav` file to its GVAS (Unreal save) content.
+///
+/// # Format
+/// - u32 : uncompressed size, u32 : compressed size, `PlZ` (zlib) or `PlM` (Oodle), u8 : save type,
+/// - save type `0x31` : zlib once, `0x32` : zlib twice, `0x30` : not compressed,
+/// - some saves repeat the header after a `CNK` one.
+///
+/// # Errors
+/// Returns an error for a truncated file, an unknown header, or an Oodle save (`PlM`), not supported.
+pub fn decompress_sav(bytes: &[u8]) -> Result<Vec<u8>, String> {
+    let (magic, save_type, data) = match bytes.get(8..11) {
+        Some(b"CNK") => (bytes.get(20..23), bytes.get(23), bytes.get(24..)),
+        _ => (bytes.get(8..11), bytes.get(11), bytes.get(12..)),
+    };
+    let (Some(magic), Some(save_type), Some(data)) = (magic, save_type, data) else {
+        return Err("truncated .sav file".to_string());
+    };
+
+    match magic {
+        b"PlZ" => {}
+        b"PlM" => return Err("Oodle compressed .sav (PlM) is not supported".to_string()),
+        _ => return Err(format!("unknown .sav header {:?}", String::from_utf8_lossy(magic))),
+    }
+
+    match save_type {
+        0x30 => Ok(data.to_vec()),
+        0x31 => inflate(data),
+        0x32 => inflate(&inflate(data)?),
+        other => Err(format!("unknown .sav save type 0x{:02x}", other)),
+    }
+}
+
+fn inflate(data: &[u8]) -> Result<Vec<u8>, String> {
+    let mut decoded = Vec::new();
+    ZlibDecoder::new(data)
+        .read_to_end(&mut decoded)
+        .map_err(|e| format!("zlib error: {e}"))?;
+    Ok(decoded)
+}
+
+/// Reads the players of a decompressed `Level.sav`, from its characters (`CharacterSaveParameterMap`).
+///
+/// The save isn't fully parsed : each character starts at a `PlayerUId` property, and its `NickName`, `Level`
+/// and `IsPlayer` properties are looked for until the next one. Pals are skipped (`IsPlayer` not set).
+/// The level is 1 when the save omits it (default value).
+pub fn level_players(gvas: &[u8]) -> Vec<PalworldSavePlayer> {
+    let starts = find_all(gvas, &encode_fstring("PlayerUId"));
+    starts
+        .iter()
+        .enumerate()
+        .filter_map(|(i, &start)| {
+            let end = starts.get(i + 1).copied().unwrap_or(gvas.len());
+            let character = &gvas[start..end];
+
+            if property(character, "IsPlayer") != Some(PropertyValue::Bool(true)) {
+                return None;
+            }
+            let uid = match property(character, "PlayerUId") {
+                Some(PropertyValue::Guid(uid)) => uid,
+                _ => return None,
+            };
+            let nickname = match property(character, "NickName") {
+                Some(PropertyValue::Str(nickname)) if !nickname.is_empty() => nickname,
+                _ => return None,
+            };
+            let level = match property(character, "Level") {
+                Some(PropertyValue::Int(level)) => level,
+                _ => 1,
+            };
+            Some(PalworldSavePlayer { uid, nickname, level })
+        })
+        .collect()
+}
+
+/// Reads the number of pals captured by a player, from its decompressed `Players/<uid>.sav`
+/// (`TribeCaptureCount` of its records). `None` when the save has none.
+pub fn captured_pals(gvas: &[u8]) -> Option<i64> {
+    match property(gvas, "TribeCaptureCount") {
+        Some(PropertyValue::Int(count)) => Some(count),
+        _ => None,
+    }
+}
+
+/// Value of a GVAS property, for the types read here.
+#[derive(Debug, Clone, PartialEq)]
+enum PropertyValue {
+    Int(i64),
+    Str(String),
+    Bool(bool),
+    /// Guid formatted like the Palworld player files (32 uppercase hex digits)
+    Guid(String),
+}
+
+/// Reads the first property called `name` in `data`.
+fn property(data: &[u8], name: &str) -> Option<PropertyValue> {
+    let encoded = encode_fstring(name);
+    find_all(data, &encoded)
+        .into_iter()
+        .find_map(|pos| read_property_value(data, pos + encoded.len()))
+}
+
+/// Reads a property value, `pos` being just after its name : type, size, then the value (after the guid flag).
+fn read_property_value(data: &[u8], pos: usize) -> Option<PropertyValue> {
+    let (property_type, pos) = read_fstring(data, pos)?;
+    let pos = pos + 8; // size
+    match property_type.as_str() {
+        "IntProperty" => Some(PropertyValue::Int(i32::from_le_bytes(data.get(pos + 1..pos + 5)?.try_into().ok()?) as i64)),
+        "Int64Property" => Some(PropertyValue::Int(i64::from_le_bytes(data.get(pos + 1..pos + 9)?.try_into().ok()?))),
+        "ByteProperty" => {
+            // Enum name ("None" for a plain byte), guid flag, value
+            let (enum_name, pos) = read_fstring(data, pos)?;
+            if enum_name != "None" {
+                return None;
+            }
+            Some(PropertyValue::Int(*data.get(pos + 1)? as i64))
+        }
+        "BoolProperty" => Some(PropertyValue::Bool(*data.get(pos)? != 0)),
+        "StrProperty" => read_fstring(data, pos + 1).map(|(value, _)| PropertyValue::Str(value)),
+        "StructProperty" => {
+            // Struct name, struct guid (16 bytes), guid flag, value
+            let (struct_name, pos) = read_fstring(data, pos)?;
+            if struct_name != "Guid" {
+                return None;
+            }
+            let bytes = data.get(pos + 17..pos + 33)?;
+            let guid: String = bytes
+                .chunks(4)
+                .map(|chunk| format!("{:08X}", u32::from_le_bytes(chunk.try_into().unwrap_or_default())))
+                .collect();
+            Some(PropertyValue::Guid(guid))
+        }
+        _ => None,
+    }
+}
+
+/// Reads an Unreal FString : i32 length (null included, negative for UTF-16), then the characters.
+fn read_fstring(data: &[u8], pos: usize) -> Option<(String, usize)> {
+    let len = i32::from_le_bytes(data.get(pos..pos + 4)?.try_into().ok()?);
+    let start = pos + 4;
+    if len == 0 {
+        return Some((String::new(), start));
+    }
+    if len > 0 {
+        let end = start + len as usize;
+        let bytes = data.get(start..end)?;
+        let text = String::from_utf8_lossy(bytes.strip_suffix(&[0]).unwrap_or(bytes)).into_owned();
+        Some((text, end))
+    } else {
+        let end = start + len.unsigned_abs() as usize * 2;
+        let units: Vec<u16> = data
+            .get(start..end)?
+            .chunks_exact(2)
+            .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+            .take_while(|unit| *unit != 0)
+            .collect();
+        Some((String::from_utf16_lossy(&units), end))
+    }
+}
+
+/// Encodes a name as an FString, to look for a property name in the save.
+fn encode_fstring(name: &str) -> Vec<u8> {
+    let mut encoded = ((name.len() + 1) as i32).to_le_bytes().to_vec();
+    encoded.extend_from_slice(name.as_bytes());
+    encoded.push(0);
+    encoded
+}
+
+/// Returns the positions of every occurrence of `needle` in `data`.
+fn find_all(data: &[u8], needle: &[u8]) -> Vec<usize> {
+    if needle.is_empty() || data.len() < needle.len() {
+        return Vec::new();
+    }
+    data.windows(needle.len())
+        .enumerate()
+        .filter(|(_, window)| *window == needle)
+        .map(|(pos, _)| pos)
+        .collect()
+}
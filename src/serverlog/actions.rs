@@ -1,7 +1,80 @@
 use colored::Colorize;
 use log::{debug, error, info, warn};
-use crate::{helper};
-use crate::db::models::{JoueurConnectionLog, Serveur};
+use crate::{helper, serverlog};
+use crate::config::Config;
+use crate::db::models::{ConnectionType, JoueurConnectionLog, Serveur, StatColumn};
+use crate::db::repository_default::Database;
+use crate::helper::webhook_discord::DiscordEmbed;
+use crate::serverlog::moderation::SanctionKind;
+use std::collections::HashMap;
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+/// Minimum time between two error reports of the same server
+const ERROR_REPORT_COOLDOWN: Duration = Duration::from_secs(5 * 60);
+/// Maximum length of an error report, leaving room for the code block in the embed
+const ERROR_REPORT_MAX_CHARS: usize = 4000;
+
+/// Minimum time between two alerts of whitelist refusals of the same player
+const WHITELIST_ALERT_COOLDOWN: Duration = Duration::from_secs(10 * 60);
+
+/// Last whitelist refusal alert sent, by playername (lowercase)
+static LAST_WHITELIST_ALERTS: LazyLock<Mutex<HashMap<String, Instant>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Last error report sent, by serverlog_id
+static LAST_ERROR_REPORTS: LazyLock<Mutex<HashMap<u32, Instant>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// A Palworld account read from the database is looked up again after this delay
+const PALWORLD_ACCOUNT_TTL: Duration = Duration::from_secs(600);
+
+/// Moment a cached Palworld account expires, and its user id (`None` if unknown)
+type CachedAccount = (Instant, Option<String>);
+
+/// Palworld user id of the players, by playername (lowercase). The chat lines have no user id : without the cache,
+/// every message would open the database to find the Steam avatar.
+static PALWORLD_ACCOUNTS: LazyLock<Mutex<HashMap<String, CachedAccount>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Chat command of the players : "!privacy off" stops announcing their activity, "!privacy on" restores it
+const PRIVACY_COMMAND: &str = "!privacy";
+
+/// Number of actions dispatched since the start
+static DISPATCHED: AtomicU64 = AtomicU64::new(0);
+
+/// Named groups captured by the regex of a trigger, by group name
+pub type TriggerCaptures = HashMap<String, String>;
+
+/// How an action posts to Discord.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum MessageStyle {
+    /// A Discord embed (default)
+    #[default]
+    Embed,
+    /// A plain message, with the player's name and avatar as webhook overrides
+    Message,
+}
+
+impl MessageStyle {
+    /// Parses the `style` field of a trigger ("embed" or "message").
+    pub fn parse(style: &str) -> Option<Self> {
+        match style.trim().to_ascii_lowercase().as_str() {
+            "embed" => Some(MessageStyle::Embed),
+            "message" => Some(MessageStyle::Message),
+            _ => None,
+        }
+    }
+}
+
+/// Options of the trigger calling an action, set in `triggers.toml`.
+#[derive(Debug, Clone, Default)]
+pub struct ActionOptions {
+    pub style: MessageStyle,
+    /// `no_db = true` : the action only posts to Discord, without writing players, connections, sessions or stats
+    pub no_db: bool,
+    /// Name of the trigger, recorded by the audit of the dispatched actions
+    pub trigger_name: String,
+}
 
 /// Dispatches a function call based on the input function name. Logs an error message if no function matches.
 ///
@@ -10,6 +83,9 @@ use crate::db::models::{JoueurConnectionLog, Serveur};
 /// * `function` - A string slice that contains the name of the function to dispatch.
 /// * `line` - A string slice passed as an argument to the matched function.
 /// * `serverlog_id` - The numeric identifier of the server log file, derived from its file name.
+/// * `captures` - The named groups captured by the trigger regex (ex: `(?P<player>...)`). Actions
+///   use them when present and fall back on parsing the line themselves otherwise.
+/// * `options` - The options of the trigger (ex: `style = "message"`, `no_db = true`).
 ///
 ///  # Returns
 /// This function does not return any value. It either executes the matched function
@@ -20,19 +96,60 @@ use crate::db::models::{JoueurConnectionLog, Serveur};
 /// - If `function` is `"on_player_joined"`, it calls `on_player_joined(line, serverlog_id)`.
 /// - If `function` is `"on_player_left"`, it calls `on_player_left(line, serverlog_id)`.
 /// - etc...
-/// - If `function` does not match any of the above cases, it logs an error
-///   message to the standard error output.
+/// - If `function` does not match any of the above cases, it is counted for the status report
+///   (only its first call is logged).
+/// - If the action panics, the panic is caught and logged so the watcher keeps dispatching.
+/// - With `EVENT_AUDIT_ENABLED=true`, the call is then recorded in `serverlog_events` (see [`helper::event_audit`]).
 ///
-pub fn dispatch(function: &str, line: &str, serverlog_id: u32) {
+pub fn dispatch(function: &str, line: &str, serverlog_id: u32, captures: &TriggerCaptures, options: &ActionOptions) {
+    DISPATCHED.fetch_add(1, Ordering::Relaxed);
+    debug!("dispatch function={} serverlog_id={} no_db={}", function, serverlog_id, options.no_db);
+
+    // A panicking action must never stop the watcher loop
+    let started = Instant::now();
+    let result = std::panic::catch_unwind(AssertUnwindSafe(|| dispatch_action(function, line, serverlog_id, captures, options)));
+    let outcome = result.unwrap_or_else(|_| {
+        error!("Action {} panicked on line: {}", function.red().bold(), line);
+        Err("panic".to_string())
+    });
+    helper::event_audit::record(&options.trigger_name, function, serverlog_id, line, &outcome, started.elapsed());
+}
+
+/// Returns the number of actions dispatched since the start.
+pub fn dispatched_count() -> u64 {
+    DISPATCHED.load(Ordering::Relaxed)
+}
+
+/// Calls the action of `function`. Returns an error for an unknown function.
+fn dispatch_action(function: &str, line: &str, serverlog_id: u32, captures: &TriggerCaptures, options: &ActionOptions) -> Result<(), String> {
     match function {
         "on_test" => on_test(serverlog_id),
-        "on_player_message" => on_player_message(line, serverlog_id),
-        "on_player_joined" => on_player_connection_update(line, serverlog_id, "rejoint"),
-        "on_player_left" => on_player_connection_update(line, serverlog_id, "quitté"),
-        "on_minecraft_player_advancement" => on_minecraft_player_advancement(line, serverlog_id),
-        "on_player_death" => on_player_death(line, serverlog_id),
-        _ => warn!("Unknown action function: {}", function.yellow()),
+        "on_player_message" => on_player_message(line, serverlog_id, captures, options),
+        "on_player_privacy" => on_player_privacy(line, serverlog_id, captures, options),
+        "on_player_me" => on_player_me(line, serverlog_id, captures),
+        "on_player_joined" => on_player_connection_update(line, serverlog_id, "rejoint", captures, options),
+        "on_player_left" => on_player_connection_update(line, serverlog_id, "quitté", captures, options),
+        "on_palworld_player_joined" => on_palworld_player_connection_update(line, serverlog_id, "rejoint", captures, options),
+        "on_palworld_player_left" => on_palworld_player_connection_update(line, serverlog_id, "quitté", captures, options),
+        "on_palworld_player_message" => on_palworld_player_message(line, serverlog_id, captures, options),
+        "on_palworld_player_death" => on_palworld_player_death(line, serverlog_id, captures, options),
+        "on_minecraft_player_advancement" => on_minecraft_player_advancement(line, serverlog_id, captures),
+        "on_player_death" => on_player_death(line, serverlog_id, captures, options),
+        "on_server_started" => on_server_started(line, serverlog_id, captures, options),
+        "on_server_stopped" => on_server_stopped(serverlog_id, options),
+        "on_server_error" => on_server_error(line, serverlog_id),
+        "on_whitelist_denied" => on_whitelist_denied(line, serverlog_id, captures, options),
+        "on_player_kicked" => on_player_sanctioned(line, serverlog_id, SanctionKind::Kick, captures, options),
+        "on_player_banned" => on_player_sanctioned(line, serverlog_id, SanctionKind::Ban, captures, options),
+        "on_server_lagging" => on_server_lagging(line, serverlog_id, captures),
+        #[cfg(test)]
+        "on_trap_test" => tests::on_trap_test(line),
+        _ => {
+            serverlog::trigger_tuning::record_unknown_action(function);
+            return Err(format!("unknown action {}", function));
+        }
     }
+    Ok(())
 }
 
 // Actions
@@ -40,76 +157,37 @@ fn on_test(serverlog_id: u32) {
     info!("{} triggered with serverlog_id={}", "on_test".green().bold(), serverlog_id.to_string().green().bold());
 }
 
-fn on_player_connection_update(line: &str, serverlog_id: u32, co_type: &str) {
+fn on_player_connection_update(line: &str, serverlog_id: u32, co_type: &str, captures: &TriggerCaptures, options: &ActionOptions) {
     // Resolve active server at serverlog_id
     let server:Serveur = get_server_by_active_server_id(serverlog_id);
 
-    // Extract playername from a line like:
+    // Use the `player` group of the trigger, or extract playername from a line like:
     // "[00:00:000] [Server thread/INFO]: playername joined/left the game"
-    let playername = line
-        .split("]: ")
-        .nth(1)
-        .and_then(|s| {
-            s.strip_suffix(" left the game")
-                .or_else(|| s.strip_suffix(" joined the game"))
+    let playername = capture(captures, "player")
+        .or_else(|| {
+            line.split("]: ")
+                .nth(1)
+                .and_then(|s| {
+                    s.strip_suffix(" left the game")
+                        .or_else(|| s.strip_suffix(" joined the game"))
+                })
         })
         .map(|s| s.trim())
         .filter(|s| !s.is_empty())
         .unwrap_or("Joueur");
-
-    // Load configuration for DB pool before logging player connection
-    let db = match helper::open_database::open_db_from_env() {
-        Some(db) => db,
-        None => {
-            warn!("Could not load DB configuration to resolve active server");
-            return;
-        }
-    };
-
-    // We need to get the player id. If the player isn't in the database, they will be added
-    let player_id = match db.add_and_get_minecraft_player_id(playername) {
-        Ok(id) => id, // Successfully retrieved the player ID
-        Err(err) => {
-            error!("Player {}'s ID couldn't be fetched or added to the database: {}", playername, err);
-            return; // or handle the error appropriately
-        }
+    let Some(playername) = normalized_playername(playername) else {
+        return;
     };
+    let playername = playername.as_str();
 
-    // We check if the player's account is link & if `co_type` = rejoint. If not, we generate a code to link it
-    if co_type == "rejoint" {
-        if let Err(e) = helper::code_generator::handle_unlinked_player_join(&db, player_id, &playername, serverlog_id) {
-            error!("Failed to process player join for '{}': {}", playername, e);
-        }
-    }
-    
-    // We log the player connection in database
-    let log = JoueurConnectionLog {
-        serveur_id: server.id,
-        joueur_id: player_id,
-        date: chrono::Utc::now().naive_utc(),
+    let Some(embed) = player_connection_update(&server, serverlog_id, playername, co_type, options, &mut DatabaseWrites) else {
+        return;
     };
-    if let Err(e) = db.insert_joueur_connection_log(&log) {
-        warn!("Failed to insert player connection log: {:?}", e);
-    }
-    if let Err(e) = db.update_last_connection(player_id) {
-        warn!("Failed to update last player connection: {:?}", e);
-    }
 
     // Send Discord embed with the player's name
-    if let Err(e) = helper::webhook_discord::send_discord_embed(
-        helper::webhook_discord::get_webhook_identity_by_server_id(server.jeu),
-        " ",
-        playername,
-        &format!("https://antredesloutres.fr/joueurs/minecraft/{}", playername.to_lowercase()),
-        &format!("{playername} a {co_type} {}", server.nom),
-        server.embed_color,
-        " ",
-        " ",
-        " ",
-        &format!("Message de {}", server.nom),
-        Some(chrono::Utc::now()
-            .to_rfc3339())
-    ) {
+    if !announcements_muted(serverlog_id)
+        && let Err(e) = embed.send()
+    {
         error!("{e}");
     }
 
@@ -158,36 +236,252 @@ fn on_player_connection_update(line: &str, serverlog_id: u32, co_type: &str) {
     });
 }
 
-fn on_player_message(line: &str, serverlog_id: u32) {
+/// The database writes of a connection, left out when the trigger has `no_db`.
+trait ConnectionWrites {
+    /// Saves the online players of the server
+    fn persist_online(&mut self, serverlog_id: u32, serveur_id: u64);
+    /// Records the player and their connection, returns false if it failed
+    fn record_connection(&mut self, serverlog_id: u32, serveur_id: u64, playername: &str, co_type: &str) -> bool;
+}
+
+/// The writes of a connection in MySQL.
+struct DatabaseWrites;
+
+impl ConnectionWrites for DatabaseWrites {
+    fn persist_online(&mut self, serverlog_id: u32, serveur_id: u64) {
+        if let Some(db) = writable_db(&ActionOptions::default(), || format!("online players of server {} recorded", serveur_id)) {
+            serverlog::online_tracker::persist(&db, serverlog_id, serveur_id);
+        }
+    }
+
+    fn record_connection(&mut self, serverlog_id: u32, serveur_id: u64, playername: &str, co_type: &str) -> bool {
+        record_minecraft_connection(serverlog_id, serveur_id, playername, co_type)
+    }
+}
+
+/// Tracks a Minecraft player joining or leaving (`co_type`), records it through `writes` unless the trigger has `no_db`,
+/// and returns the embed announcing it.
+///
+/// # Returns
+/// `None` if the connection couldn't be recorded or the player is hidden : nothing is announced.
+fn player_connection_update(
+    server: &Serveur,
+    serverlog_id: u32,
+    playername: &str,
+    co_type: &str,
+    options: &ActionOptions,
+    writes: &mut impl ConnectionWrites,
+) -> Option<DiscordEmbed> {
+    if co_type == "rejoint" {
+        serverlog::online_tracker::joined(serverlog_id, playername);
+    } else {
+        serverlog::online_tracker::left(serverlog_id, playername);
+    }
+
+    // The player and their connection are recorded, unless the trigger has `no_db`
+    if !options.no_db {
+        writes.persist_online(serverlog_id, server.id);
+        if !writes.record_connection(serverlog_id, server.id, playername, co_type) {
+            return None;
+        }
+    }
+
+    // Hidden players are recorded, but their connections aren't announced anywhere
+    if helper::player_privacy::is_hidden("minecraft", playername) {
+        debug!("{} is hidden, connection not announced", playername);
+        return None;
+    }
+
+    Some(
+        DiscordEmbed::new(&helper::webhook_discord::get_webhook_identity_by_server_id(server.jeu.clone()))
+            .thread(server.discord_thread_id.as_deref())
+            .title(playername)
+            .url(&format!("https://antredesloutres.fr/joueurs/minecraft/{}", playername.to_lowercase()))
+            .description(&format!("{playername} a {co_type} {}", server.nom))
+            .color(server.embed_color.clone().unwrap_or_default())
+            .footer(&format!("Message de {}", server.nom))
+            .timestamp_now(),
+    )
+}
+
+/// Adds the Minecraft player if needed, handles the linking code of an unlinked player on a join,
+/// and records the connection.
+///
+/// # Returns
+/// `false` if the player couldn't be fetched or added (the connection isn't announced then).
+fn record_minecraft_connection(serverlog_id: u32, serveur_id: u64, playername: &str, co_type: &str) -> bool {
+    if helper::dry_run::skip(|| format!("connection of {} recorded ({}) on server {}", playername, co_type, serveur_id)) {
+        return true;
+    }
+
+    // Load configuration for DB pool before logging player connection
+    let db = match helper::open_database::open_db_from_env() {
+        Some(db) => db,
+        None => {
+            warn!("Could not load DB configuration to resolve active server");
+            return false;
+        }
+    };
+
+    // We need to get the player id. If the player isn't in the database, they will be added
+    let player_id = match db.add_and_get_minecraft_player_id(playername) {
+        Ok(id) => id, // Successfully retrieved the player ID
+        Err(err) => {
+            error!("Player {}'s ID couldn't be fetched or added to the database: {}", playername, err);
+            return false;
+        }
+    };
+
+    // We check if the player's account is link & if `co_type` = rejoint. If not, we generate a code to link it
+    if co_type == "rejoint"
+        && let Err(e) = helper::code_generator::handle_unlinked_player_join(&db, player_id, playername, serverlog_id)
+    {
+        error!("Failed to process player join for '{}': {}", playername, e);
+    }
+
+    // We log the player connection in database
+    record_connection(&db, serveur_id, player_id, co_type);
+    true
+}
+
+/// Records a join, or closes the session of the player on a leave, and updates their last connection.
+fn record_connection(db: &Database, serveur_id: u64, joueur_id: u64, co_type: &str) {
+    let now = chrono::Utc::now().naive_utc();
+    let r#type = if co_type == "rejoint" { ConnectionType::Join } else { ConnectionType::Leave };
+    // Retried on transient errors, then queued and replayed rather than lost
+    let log = JoueurConnectionLog { serveur_id, joueur_id, date: now, r#type };
+    if let Some(duration) = helper::connection_log_retry::record(db, log) {
+        debug!("Session of player {} closed after {}s", joueur_id, duration);
+    }
+    if let Err(e) = db.touch_player_last_connection(joueur_id, now) {
+        warn!("Failed to update last player connection: {:?}", e);
+    }
+}
+
+fn on_palworld_player_connection_update(line: &str, serverlog_id: u32, co_type: &str, captures: &TriggerCaptures, options: &ActionOptions) {
     // Resolve active server at serverlog_id
     let server: Serveur = get_server_by_active_server_id(serverlog_id);
 
-    let re = regex::Regex::new(r"<([^>]+)>\s(.+)").unwrap();
-    let caps = re.captures(line).unwrap();
+    // Use the `player` and `user_id` groups of the trigger, or parse a line like:
+    // "[2025-01-31 14:43:14] [LOG] playername joined the server. (User id: steam_76561198000000000)"
+    let (playername, user_id) = match (capture(captures, "player"), capture(captures, "user_id")) {
+        (Some(playername), Some(user_id)) => (playername, user_id),
+        _ => match serverlog::palworld::parse_connection_line(line) {
+            Some(player) => (player.playername, player.user_id),
+            None => {
+                debug!("no palworld connection match: {}", line);
+                return;
+            }
+        },
+    };
 
-    let playername = caps.get(1).unwrap().as_str();
-    let message = caps.get(2).unwrap().as_str();
-    let embed_color = server.embed_color.clone().unwrap_or_else(|| "white".to_string());
+    let dry_run = helper::dry_run::skip(|| format!("connection of {} recorded ({}) on {}", playername, co_type, server.nom));
+    if !options.no_db && !dry_run {
+        let db = match helper::open_database::open_db_from_env() {
+            Some(db) => db,
+            None => {
+                warn!("Could not load DB configuration to resolve active server");
+                return;
+            }
+        };
+
+        // The Mojang API doesn't know Palworld players, the name comes from the log line
+        let player_id = match db.add_player_if_not_exist("palworld", user_id.to_string(), Some(playername)) {
+            Ok(id) => id,
+            Err(err) => {
+                error!("Palworld player {}'s ID couldn't be fetched or added to the database: {}", playername, err);
+                return;
+            }
+        };
 
-    // Send Discord embed with the player's message
-    if let Err(e) = helper::webhook_discord::send_discord_embed(
-        helper::webhook_discord::get_webhook_identity_by_server_id(server.jeu),
-        " ",
-        playername,
-        &format!("https://antredesloutres.fr/joueurs/minecraft/{}", playername.to_lowercase()),
-        message,
-        server.embed_color,
-        &format!("https://mc-heads.net/avatar/{}/50", playername.to_lowercase()),
-        " ",
-        " ",
-        &format!("Message de {}", server.nom),
-        Some(chrono::Utc::now().to_rfc3339()),
-    ) {
+        // We log the player connection in database
+        record_connection(&db, server.id, player_id, co_type);
+    }
+
+    // Hidden players are recorded, but their connections aren't announced
+    if !player_announced(serverlog_id, "palworld", playername) {
+        return;
+    }
+
+    // Send Discord embed with the player's name
+    if let Err(e) = DiscordEmbed::new(&helper::webhook_discord::get_webhook_identity_by_server_id(server.jeu))
+        .thread(server.discord_thread_id.as_deref())
+        .title(playername)
+        .description(&format!("{playername} a {co_type} {}", server.nom))
+        .color(server.embed_color.unwrap_or_default())
+        .footer(&format!("Message de {}", server.nom))
+        .timestamp_now()
+        .send()
+    {
         error!("{e}");
     }
+}
+
+fn on_player_message(line: &str, serverlog_id: u32, captures: &TriggerCaptures, options: &ActionOptions) {
+    // Resolve active server at serverlog_id
+    let server: Serveur = get_server_by_active_server_id(serverlog_id);
+
+    // Use the `player` and `message` groups of the trigger, or parse the line
+    let (playername, message) = match (capture(captures, "player"), capture(captures, "message")) {
+        (Some(playername), Some(message)) => (playername, message),
+        _ => {
+            let re = regex::Regex::new(r"<([^>]+)>\s(.+)").unwrap();
+            match re.captures(line).and_then(|caps| Some((caps.get(1)?.as_str(), caps.get(2)?.as_str()))) {
+                Some(parsed) => parsed,
+                None => {
+                    debug!("no player message match: {}", line);
+                    return;
+                }
+            }
+        }
+    };
+    let Some(playername) = normalized_playername(playername) else {
+        return;
+    };
+    let playername = playername.as_str();
+    let embed_color = server.embed_color.clone().unwrap_or_else(|| "white".to_string());
+
+    // Privacy commands are handled by `on_player_privacy`, they aren't relayed
+    if is_privacy_command(message) {
+        return;
+    }
+
+    // Messages of hidden players are relayed as "Un joueur", without profile link nor head
+    let hidden = helper::player_privacy::is_hidden("minecraft", playername);
+    let shown_name = helper::player_privacy::display_name("minecraft", playername);
+
+    // Send the player's message to Discord, as the player or as an embed
+    if !announcements_muted(serverlog_id) {
+        let style = if chat_relay_impersonates() { MessageStyle::Message } else { options.style };
+        let sent = match style {
+            MessageStyle::Message if hidden => helper::webhook_discord::send_discord_message(
+                &helper::webhook_discord::get_webhook_identity_by_server_id(server.jeu),
+                message,
+                Some(&shown_name),
+                None,
+                server.discord_thread_id.as_deref(),
+            ),
+            MessageStyle::Message => helper::webhook_discord::send_discord_as_player(
+                &helper::webhook_discord::get_webhook_identity_by_server_id(server.jeu),
+                playername,
+                message,
+                server.discord_thread_id.as_deref(),
+            ),
+            MessageStyle::Embed => player_message_embed(
+                &helper::webhook_discord::get_webhook_identity_by_server_id(server.jeu.clone()),
+                &server,
+                playername,
+                message,
+            )
+            .send(),
+        };
+        if let Err(e) = sent {
+            error!("{e}");
+        }
+    }
 
     // Send the message to the players in other servers (except the one it comes from)
-    let playername = playername.to_string();
+    let playername = shown_name;
     let message = message.to_string();
     let origin_id = serverlog_id as u64;
 
@@ -230,33 +524,243 @@ fn on_player_message(line: &str, serverlog_id: u32) {
     });
 }
 
-fn on_minecraft_player_advancement(line: &str, serverlog_id: u32) {
+/// Relays a `/me` of a player ("* Loutre fait quelque chose") to the chat webhook, in italics, as the player.
+fn on_player_me(line: &str, serverlog_id: u32, captures: &TriggerCaptures) {
+    // Use the `player` and `action` groups of the trigger, or parse a line like:
+    // "[17:58:38] [Server thread/INFO]: * playername fait quelque chose"
+    let (playername, action) = match (capture(captures, "player"), capture(captures, "action")) {
+        (Some(playername), Some(action)) => (playername, action),
+        _ => {
+            let re = regex::Regex::new(r"\]: \* (\S+) (.+)").unwrap();
+            match re.captures(line).and_then(|caps| Some((caps.get(1)?.as_str(), caps.get(2)?.as_str()))) {
+                Some(parsed) => parsed,
+                None => {
+                    debug!("no player /me match: {}", line);
+                    return;
+                }
+            }
+        }
+    };
+    let Some(playername) = normalized_playername(playername) else {
+        return;
+    };
+    let playername = playername.as_str();
+    if announcements_muted(serverlog_id) {
+        return;
+    }
+
+    let server: Serveur = get_server_by_active_server_id(serverlog_id);
+    let identity = helper::webhook_discord::get_webhook_identity_by_server_id(server.jeu);
+    // Hidden players are relayed as "Un joueur", without head
+    let shown_name = helper::player_privacy::display_name("minecraft", playername);
+    let content = player_me_content(&shown_name, action);
+    let sent = if helper::player_privacy::is_hidden("minecraft", playername) {
+        helper::webhook_discord::send_discord_message(&identity, &content, Some(&shown_name), None, server.discord_thread_id.as_deref())
+    } else {
+        helper::webhook_discord::send_discord_as_player(&identity, playername, &content, server.discord_thread_id.as_deref())
+    };
+    if let Err(e) = sent {
+        error!("{e}");
+    }
+}
+
+fn on_player_privacy(line: &str, serverlog_id: u32, captures: &TriggerCaptures, options: &ActionOptions) {
+    // The privacy setting is stored in the database
+    if options.no_db {
+        debug!("Privacy command ignored, the trigger has no_db: {}", line);
+        return;
+    }
+
+
+    // Use the `player` and `state` groups of the trigger, or parse a line like:
+    // "[17:58:38] [Async Chat Thread - #0/INFO]: <playername> !privacy off"
+    let re = regex::Regex::new(r"<([^>]+)> !privacy (on|off)\s*$").unwrap();
+    let parsed = match (capture(captures, "player"), capture(captures, "state")) {
+        (Some(playername), Some(state)) => Some((playername, state)),
+        _ => re.captures(line).and_then(|caps| Some((caps.get(1)?.as_str(), caps.get(2)?.as_str()))),
+    };
+    let Some((playername, state)) = parsed else {
+        debug!("no privacy command match: {}", line);
+        return;
+    };
+    let Some(playername) = normalized_playername(playername) else {
+        return;
+    };
+    let visible = state.eq_ignore_ascii_case("on");
+    if helper::dry_run::skip(|| format!("{} set {} with !privacy", playername, if visible { "visible" } else { "hidden" })) {
+        return;
+    }
+
+    let db = match helper::open_database::open_db_from_env() {
+        Some(db) => db,
+        None => {
+            warn!("Could not load DB configuration to update player privacy");
+            return;
+        }
+    };
+
+    let reply = match helper::player_privacy::set_visible(&db, "minecraft", &playername, visible) {
+        Ok(true) if visible => "Ton activité est de nouveau annoncée sur Discord.",
+        Ok(true) => "Ton activité n'est plus annoncée sur Discord. Tes stats restent enregistrées.",
+        Ok(false) => "Ton compte n'est pas encore connu d'Otternel, reconnecte-toi puis réessaie.",
+        Err(e) => {
+            error!("Failed to update the privacy of {}: {}", playername, e);
+            "Ta demande n'a pas pu être enregistrée, réessaie plus tard."
+        }
+    };
+
+    // Answer the player in game
+    let command = format!(r#"tellraw {} {{"text":"{}","color":"gray"}}"#, playername, reply);
+    tokio::spawn(async move {
+        let rcon = match helper::rcon_helper::RconHelper::new() {
+            Ok(r) => r,
+            Err(e) => {
+                error!("Failed to init RconHelper for privacy reply: {}", e);
+                return;
+            }
+        };
+        if let Err(e) = rcon.execute_command(serverlog_id as u64, &command).await {
+            warn!("Failed to answer privacy command on server id={}: {}", serverlog_id, e);
+        }
+    });
+}
+
+/// Relays a Palworld chat message to Discord.
+///
+/// Only the `[CHAT]` lines the server writes in its log are relayed : Palworld's REST API and RCON give
+/// no access to the chat, so a server that doesn't log it has no chat relay.
+fn on_palworld_player_message(line: &str, serverlog_id: u32, captures: &TriggerCaptures, options: &ActionOptions) {
+    // Use the `player` and `message` groups of the trigger, or parse a line like:
+    // "[2025-01-31 14:43:14] [CHAT] <playername> message"
+    let (playername, message) = match (capture(captures, "player"), capture(captures, "message")) {
+        (Some(playername), Some(message)) => (playername, message),
+        _ => match serverlog::palworld::parse_chat_line(line) {
+            Some(parsed) => parsed,
+            None => {
+                debug!("no palworld player message match: {}", line);
+                return;
+            }
+        },
+    };
+
+    // Resolve active server at serverlog_id
+    let server: Serveur = get_server_by_active_server_id(serverlog_id);
+
+    if announcements_muted(serverlog_id) {
+        return;
+    }
+
+    // Messages of hidden players are relayed as "Un joueur", without avatar
+    let shown_name = helper::player_privacy::display_name("palworld", playername);
+
+    // Steam avatar of the player, if their Steam account is known. No thumbnail otherwise
+    let avatar_url = palworld_chat_avatar(capture(captures, "user_id"), playername);
+
+    // Send the player's message to Discord, as a plain message or as an embed
+    let sent = match options.style {
+        MessageStyle::Message => helper::webhook_discord::send_discord_message(
+            &helper::webhook_discord::get_webhook_identity_by_server_id(server.jeu),
+            message,
+            Some(&format!("{} ({})", shown_name, server.nom)),
+            Some(&avatar_url),
+            server.discord_thread_id.as_deref(),
+        ),
+        MessageStyle::Embed => DiscordEmbed::new(&helper::webhook_discord::get_webhook_identity_by_server_id(server.jeu))
+            .thread(server.discord_thread_id.as_deref())
+            .title(&shown_name)
+            .description(message)
+            .color(server.embed_color.unwrap_or_default())
+            .thumbnail(&avatar_url)
+            .footer(&format!("Message de {}", server.nom))
+            .timestamp_now()
+            .send(),
+    };
+    if let Err(e) = sent {
+        error!("{e}");
+    }
+}
+
+/// Returns the Steam avatar of a Palworld player, from the user id of the line or from the account stored in database.
+/// Both the account and the avatar are cached, so a busy chat neither queries MySQL nor Steam on every line.
+fn palworld_steam_avatar(user_id: Option<&str>, playername: &str) -> Option<String> {
+    let user_id = match user_id {
+        Some(user_id) => user_id.to_string(),
+        None => palworld_user_id(playername, Instant::now(), |playername| {
+            helper::open_database::open_db_from_env()?
+                .get_compte_id_by_playername("palworld", playername)
+                .ok()
+                .flatten()
+        })?,
+    };
+    let steam_id = serverlog::palworld::steam_id(&user_id)?;
+    helper::steam_api::cached_avatar_url(steam_id)
+}
+
+/// Returns the user id of a Palworld player from the cache, or else from `lookup` (the database).
+fn palworld_user_id(playername: &str, now: Instant, lookup: impl FnOnce(&str) -> Option<String>) -> Option<String> {
+    let key = playername.to_lowercase();
+    if let Some((expires_at, user_id)) = PALWORLD_ACCOUNTS.lock().unwrap_or_else(|e| e.into_inner()).get(&key)
+        && now < *expires_at
+    {
+        return user_id.clone();
+    }
+
+    let user_id = lookup(playername);
+    let mut accounts = PALWORLD_ACCOUNTS.lock().unwrap_or_else(|e| e.into_inner());
+    accounts.retain(|_, (expires_at, _)| now < *expires_at);
+    accounts.insert(key, (now + PALWORLD_ACCOUNT_TTL, user_id.clone()));
+    user_id
+}
+
+fn on_minecraft_player_advancement(line: &str, serverlog_id: u32, captures: &TriggerCaptures) {
     // Resolve active server at serverlog_id
     let server:Serveur = get_server_by_active_server_id(serverlog_id);
 
-    let re = regex::Regex::new(
-        r"^(?:\[[^\]]+\]\s*:?\s*)*([^ ]+)\s+(?:has made the advancement|completed the challenge|reached the goal)\s+\[?(.+?)\]$"
-    ).unwrap();
+    // Use the `player` and `advancement` groups of the trigger, or parse the line
+    let parsed = match (capture(captures, "player"), capture(captures, "advancement")) {
+        (Some(playername), Some(advancement)) => Some((playername, advancement)),
+        _ => {
+            let re = regex::Regex::new(
+                r"^(?:\[[^\]]+\]\s*:?\s*)*([^ ]+)\s+(?:has made the advancement|completed the challenge|reached the goal)\s+\[?(.+?)\]$"
+            ).unwrap();
+            re.captures(line).map(|caps| (
+                caps.get(1).map(|m| m.as_str()).unwrap_or("Joueur"),
+                caps.get(2).map(|m| m.as_str()).unwrap_or(""),
+            ))
+        }
+    };
 
-    if let Some(caps) = re.captures(line) {
-        let playername = caps.get(1).map(|m| m.as_str()).unwrap_or("Joueur");
-        let advancement = caps.get(2).map(|m| m.as_str()).unwrap_or("");
+    if let Some((playername, advancement)) = parsed {
 
-        // Send Discord embed with the player's message
-        if let Err(e) = helper::webhook_discord::send_discord_embed(
-        helper::webhook_discord::get_webhook_identity_by_server_id(server.jeu),
-            " ",
+        // Hidden players' advancements aren't announced
+        if !player_announced(serverlog_id, "minecraft", playername) {
+            return;
+        }
+
+        // Modded servers log the id of the advancement ("minecraft:story/mine_diamond") : its title is shown instead
+        let mapped = helper::advancements::lookup(advancement);
+        let mut description = format!(
+            "{} a obtenu l'avancement {} sur {} !",
             playername,
-            &format!("https://antredesloutres.fr/joueurs/minecraft/{}", playername.to_lowercase()),
-            &format!("{} a obtenu l'avancement {} sur {} !", playername, advancement, server.nom),
-            server.embed_color,
-            " ",
-            " ",
-            " ",
-            &format!("Message de {}", server.nom),
-            Some(chrono::Utc::now()
-                .to_rfc3339())
-        ) {
+            mapped.map_or(advancement, |a| a.title.as_str()),
+            server.nom
+        );
+        if let Some(details) = mapped.and_then(|a| a.description.as_deref()) {
+            description.push_str(&format!("\n-# {}", details));
+        }
+
+        // Send Discord embed with the player's message
+        if let Err(e) = DiscordEmbed::new(&helper::webhook_discord::get_webhook_identity_by_server_id(server.jeu))
+            .thread(server.discord_thread_id.as_deref())
+            .title(playername)
+            .url(&format!("https://antredesloutres.fr/joueurs/minecraft/{}", playername.to_lowercase()))
+            .description(&description)
+            .color(server.embed_color.unwrap_or_default())
+            .thumbnail(mapped.and_then(|a| a.icon.as_deref()).unwrap_or_default())
+            .footer(&format!("Message de {}", server.nom))
+            .timestamp_now()
+            .send()
+        {
             error!("{e}");
         }
     } else {
@@ -265,37 +769,530 @@ fn on_minecraft_player_advancement(line: &str, serverlog_id: u32) {
     }
 }
 
-fn on_player_death(line: &str, serverlog_id: u32) {
+fn on_player_death(line: &str, serverlog_id: u32, captures: &TriggerCaptures, options: &ActionOptions) {
+    // No death is recorded or announced without the name of the player
+    let Some((playername, death_message)) = death_of(line, captures) else {
+        warn!("Death line without a player name, ignored: {}", line);
+        return;
+    };
+    let Some(playername) = normalized_playername(playername) else {
+        return;
+    };
+
     // Resolve active server from serverlog_id
     let server: Serveur = get_server_by_active_server_id(serverlog_id);
 
+    // The death is counted in the stats right away, the next sync of the stats file replaces the counter
+    if !options.no_db {
+        increment_minecraft_stat(server.id, &playername, StatColumn::NbMort);
+    }
+
+    // A death naming another player is a PvP kill, recorded even if the players are hidden
+    if let Some(killer) = serverlog::minecraft_death::parse_killer(death_message).filter(|_| !options.no_db) {
+        record_pvp_kill(server.id, &playername, &killer);
+    }
+
+    // Les morts des joueurs masqués ne sont pas annoncées
+    if !player_announced(serverlog_id, "minecraft", &playername) {
+        return;
+    }
+
+    // Envoi de l'embed Discord
+    if let Err(e) = DiscordEmbed::new(&helper::webhook_discord::get_webhook_identity_by_server_id(server.jeu))
+        .thread(server.discord_thread_id.as_deref())
+        .title(&format!("{playername} est mort sur {} !", server.nom))
+        .url(&format!("https://antredesloutres.fr/joueurs/minecraft/{}", playername.to_lowercase()))
+        .description(&death_description(&playername, death_message))
+        .color(server.embed_color.unwrap_or_default())
+        .footer(&format!("Message de {}", server.nom))
+        .timestamp_now()
+        .send()
+    {
+        error!("{e}");
+    }
+}
+
+/// Description of a death embed. A hidden killer is shown as "Un joueur", like a hidden victim.
+fn death_description(playername: &str, death_message: &str) -> String {
+    let mut message = death_message.to_string();
+    if let Some(killer) = serverlog::minecraft_death::parse_killer(death_message)
+        && helper::player_privacy::is_hidden("minecraft", killer.killer)
+    {
+        // The killer is a slice of the message : only its own place is replaced, not a weapon named after it
+        let start = killer.killer.as_ptr() as usize - death_message.as_ptr() as usize;
+        message.replace_range(start..start + killer.killer.len(), helper::player_privacy::ANONYMOUS_PLAYERNAME);
+    }
+    format!("{playername} {message}")
+}
+
+/// Returns the player and the death message of a death line : the `player` and `message` groups of the trigger,
+/// or else the first word after the header of the line and the rest of it.
+fn death_of<'a>(line: &'a str, captures: &'a TriggerCaptures) -> Option<(&'a str, &'a str)> {
+    if let (Some(playername), Some(message)) = (capture(captures, "player"), capture(captures, "message")) {
+        return Some((playername, message));
+    }
     // Exemple de ligne : "[17:58:38] [Server thread/INFO]: TheAzertor fell from a high place"
-    let re = regex::Regex::new(r": ([^ ]+) (.+)$").unwrap();
-    let (playername, death_message) = if let Some(caps) = re.captures(line) {
+    static DEATH: LazyLock<regex::Regex> = LazyLock::new(|| regex::Regex::new(r": ([^ ]+) (.+)$").unwrap());
+    let caps = DEATH.captures(line)?;
+    Some((caps.get(1)?.as_str(), caps.get(2)?.as_str()))
+}
+
+/// Increments a counter of the stats of a Minecraft player, added to the database if unknown.
+/// See `Database::increment_stat` for the priority with the syncs of the stats files.
+fn increment_minecraft_stat(serveur_id: u64, playername: &str, column: StatColumn) {
+    if helper::dry_run::skip(|| format!("{} of {} incremented", column.as_str(), playername)) {
+        return;
+    }
+    let Some(db) = helper::open_database::open_db_from_env() else {
+        return;
+    };
+
+    let compte_id = db
+        .add_and_get_minecraft_player_id(playername)
+        .map_err(|e| e.to_string())
+        .and_then(|_| db.get_compte_id_by_playername("minecraft", playername).map_err(|e| e.to_string()));
+    match compte_id {
+        Ok(Some(compte_id)) => {
+            if let Err(e) = db.increment_stat(serveur_id, &compte_id, column, 1) {
+                warn!("Failed to increment {} of {}: {:?}", column.as_str(), playername, e);
+            }
+        }
+        Ok(None) => debug!("{} has no account id, {} not incremented", playername, column.as_str()),
+        Err(e) => warn!("Failed to fetch the account of {}: {}", playername, e),
+    }
+}
+
+/// Inserts a PvP kill in `joueurs_pvp`, if the victim and the killer are both known Minecraft players
+/// (the killer of a death message may be a mob).
+fn record_pvp_kill(serveur_id: u64, victim: &str, killer: &serverlog::minecraft_death::DeathKiller) {
+    let Some(killer_name) = normalized_playername(killer.killer) else {
+        return;
+    };
+    if helper::dry_run::skip(|| format!("PvP kill of {} by {} recorded", victim, killer_name)) {
+        return;
+    }
+    let Some(db) = helper::open_database::open_db_from_env() else {
+        return;
+    };
+
+    let date = chrono::Utc::now().naive_utc();
+    match db.record_pvp_kill(serveur_id, "minecraft", victim, &killer_name, killer.weapon, date) {
+        Ok(true) => {
+            info!("PvP kill recorded : {} killed by {}", victim.green().bold(), killer_name.green().bold());
+            increment_minecraft_stat(serveur_id, &killer_name, StatColumn::NbPlayerkill);
+        }
+        Ok(false) => debug!("{} or {} is not a known player, death not recorded as PvP", victim, killer_name),
+        Err(e) => warn!("Failed to record the PvP kill of {} by {}: {:?}", victim, killer_name, e),
+    }
+}
+
+fn on_palworld_player_death(line: &str, serverlog_id: u32, captures: &TriggerCaptures, options: &ActionOptions) {
+    // Resolve active server from serverlog_id
+    let server: Serveur = get_server_by_active_server_id(serverlog_id);
+
+    // Parse a line like "[2025-01-31 14:43:14] [LOG] playername was killed by Anubis."
+    // The `player` and `cause` groups of the trigger take precedence when present
+    let parsed = serverlog::palworld::parse_death_line(line);
+    let Some(playername) = capture(captures, "player").or(parsed.map(|(playername, _)| playername)) else {
+        debug!("no palworld death match: {}", line);
+        return;
+    };
+    let cause = capture(captures, "cause").or(parsed.and_then(|(_, cause)| cause));
+
+    // Count the death in the player's stats
+    if let Some(db) = writable_db(options, || format!("death of {} counted on {}", playername, server.nom)) {
+        match db.get_compte_id_by_playername("palworld", playername) {
+            Ok(Some(compte_id)) => {
+                if let Err(e) = db.increment_player_death(server.id, &compte_id) {
+                    warn!("Failed to count the death of {}: {:?}", playername, e);
+                }
+            }
+            Ok(None) => debug!("Palworld player {} is not in the database, death not counted", playername),
+            Err(e) => warn!("Failed to fetch the account of {}: {:?}", playername, e),
+        }
+    }
+
+    // Deaths of hidden players are counted, but not announced
+    if !player_announced(serverlog_id, "palworld", playername) {
+        return;
+    }
+
+    // The Palworld death messages are short, so the embed is generic with the cause if any
+    let supertext = match cause {
+        Some(cause) => format!("{playername} est mort sur {} ({cause})", server.nom),
+        None => format!("{playername} est mort sur {}", server.nom),
+    };
+
+    if let Err(e) = DiscordEmbed::new(&helper::webhook_discord::get_webhook_identity_by_server_id(server.jeu))
+        .thread(server.discord_thread_id.as_deref())
+        .title(&format!("{playername} est mort sur {} !", server.nom))
+        .description(&supertext)
+        .color(server.embed_color.unwrap_or_default())
+        .footer(&format!("Message de {}", server.nom))
+        .timestamp_now()
+        .send()
+    {
+        error!("{e}");
+    }
+}
+
+fn on_server_started(line: &str, serverlog_id: u32, captures: &TriggerCaptures, options: &ActionOptions) {
+    // Resolve active server from serverlog_id
+    let server: Serveur = get_server_by_active_server_id(serverlog_id);
+
+    // Use the `duration` group of the trigger, or parse a line like:
+    // "[17:58:38] [Server thread/INFO]: Done (12.345s)! For help, type "help""
+    let re = regex::Regex::new(r"Done \(([0-9.,]+)s\)").unwrap();
+    let duration = capture(captures, "duration")
+        .or_else(|| re.captures(line).and_then(|caps| caps.get(1)).map(|m| m.as_str()));
+
+    // Nobody is online on a server that just started
+    serverlog::online_tracker::clear(serverlog_id);
+
+    if let Some(db) = writable_db(options, || format!("start of {} recorded, its open sessions closed", server.nom)) {
+        if let Err(e) = db.update_active_server_started(serverlog_id as u64) {
+            warn!("Failed to record start of active server {}: {:?}", serverlog_id, e);
+        }
+        serverlog::online_tracker::persist(&db, serverlog_id, server.id);
+        // Sessions still open at startup were cut by a crash : their real end is unknown
+        match db.close_open_sessions_of_server(server.id, chrono::Utc::now().naive_utc(), false) {
+            Ok(0) => {}
+            Ok(closed) => info!("{} sessions left open on {} closed without duration", closed, server.nom),
+            Err(e) => warn!("Failed to close the open sessions of {}: {:?}", server.nom, e),
+        }
+    }
+
+    let supertext = match duration {
+        Some(duration) => format!("{} a démarré en {} secondes !", server.nom, duration),
+        None => format!("{} a démarré !", server.nom),
+    };
+
+    if announcements_muted(serverlog_id) {
+        return;
+    }
+
+    if let Err(e) = DiscordEmbed::new(&helper::webhook_discord::get_webhook_identity_by_server_id(server.jeu))
+        .thread(server.discord_thread_id.as_deref())
+        .title(&format!("{} est en ligne", server.nom))
+        .description(&supertext)
+        .color(server.embed_color.unwrap_or_default())
+        .thumbnail(&helper::webhook_discord::resolve_embed_thumbnail("", server.image.as_deref()))
+        .footer(&format!("Message de {}", server.nom))
+        .timestamp_now()
+        .send()
+    {
+        error!("{e}");
+    }
+}
+
+fn on_server_stopped(serverlog_id: u32, options: &ActionOptions) {
+    // Resolve active server from serverlog_id
+    let server: Serveur = get_server_by_active_server_id(serverlog_id);
+
+    serverlog::online_tracker::clear(serverlog_id);
+
+    if let Some(db) = writable_db(options, || format!("stop of {} recorded, its open sessions closed", server.nom)) {
+        if let Err(e) = db.update_active_server_stopped(serverlog_id as u64) {
+            warn!("Failed to record stop of active server {}: {:?}", serverlog_id, e);
+        }
+        serverlog::online_tracker::persist(&db, serverlog_id, server.id);
+        // Players still online when the server stops leave with it
+        if let Err(e) = db.close_open_sessions_of_server(server.id, chrono::Utc::now().naive_utc(), true) {
+            warn!("Failed to close the open sessions of {}: {:?}", server.nom, e);
+        }
+    }
+
+    if announcements_muted(serverlog_id) {
+        return;
+    }
+
+    if let Err(e) = DiscordEmbed::new(&helper::webhook_discord::get_webhook_identity_by_server_id(server.jeu))
+        .thread(server.discord_thread_id.as_deref())
+        .title(&format!("{} est hors ligne", server.nom))
+        .description(&format!("{} s'est arrêté.", server.nom))
+        .color(server.embed_color.unwrap_or_default())
+        .thumbnail(&helper::webhook_discord::resolve_embed_thumbnail("", server.image.as_deref()))
+        .footer(&format!("Message de {}", server.nom))
+        .timestamp_now()
+        .send()
+    {
+        error!("{e}");
+    }
+}
+
+/// Sends an error or crash report of a server, with the lines collected after it, to the `otternel` webhook.
+/// At most one report is sent per server every 5 minutes.
+fn on_server_error(lines: &str, serverlog_id: u32) {
+    {
+        let mut last_reports = LAST_ERROR_REPORTS.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+        if let Some(last) = last_reports.get(&serverlog_id)
+            && now.duration_since(*last) < ERROR_REPORT_COOLDOWN
+        {
+            debug!("Error report of server {} skipped, one was sent less than 5 minutes ago", serverlog_id);
+            return;
+        }
+        last_reports.insert(serverlog_id, now);
+    }
+
+    // Resolve active server from serverlog_id
+    let server: Serveur = get_server_by_active_server_id(serverlog_id);
+
+    // Keep the report under the 4096 characters of an embed description
+    let mut report: String = lines.chars().take(ERROR_REPORT_MAX_CHARS).collect();
+    if report.len() < lines.len() {
+        report.push_str("\n[...]");
+    }
+
+    if let Err(e) = DiscordEmbed::new("otternel")
+        .title(&format!("Erreur sur {}", server.nom))
+        .description(&format!("```\n{}\n```", report))
+        .color("601010")
+        .thumbnail(&helper::webhook_discord::resolve_embed_thumbnail("", server.image.as_deref()))
+        .footer(&format!("Message de {}", server.nom))
+        .timestamp_now()
+        .send()
+    {
+        error!("{e}");
+    }
+}
+
+/// Adds the delay of a "Can't keep up!" line to the 5 minutes window of the server, and alerts the admins on
+/// `otternel` when the cumulated delay exceeds `LAG_ALERT_MS_PER_5MIN`.
+fn on_server_lagging(line: &str, serverlog_id: u32, captures: &TriggerCaptures) {
+    // Parse a line like "Can't keep up! Is the server overloaded? Running 2034ms or 40 ticks behind"
+    // The `ms` and `ticks` groups of the trigger take precedence when present
+    let parsed = serverlog::server_lag::parse_lag_line(line);
+    let ms = capture(captures, "ms").and_then(|ms| ms.parse().ok()).or(parsed.map(|report| report.ms));
+    let Some(ms) = ms else {
+        debug!("no lag match: {}", line);
+        return;
+    };
+    let ticks = capture(captures, "ticks").and_then(|ticks| ticks.parse().ok()).or(parsed.map(|report| report.ticks)).unwrap_or(0);
+
+    let Some(summary) = serverlog::server_lag::record(serverlog_id, serverlog::server_lag::LagReport { ms, ticks }) else {
+        return;
+    };
+    let server: Serveur = get_server_by_active_server_id(serverlog_id);
+    warn!(
+        "{} is lagging: {}ms ({} ticks) behind over the last 5 minutes",
+        server.nom.yellow().bold(),
+        summary.total_ms,
+        summary.total_ticks
+    );
+
+    if let Err(e) = DiscordEmbed::new("otternel")
+        .title(&format!("{} est surchargé", server.nom))
+        .description(&format!(
+            "{} ms de retard ({} ticks) en {} alertes sur les 5 dernières minutes.",
+            summary.total_ms, summary.total_ticks, summary.occurrences
+        ))
+        .color("c06010")
+        .thumbnail(&helper::webhook_discord::resolve_embed_thumbnail("", server.image.as_deref()))
+        .footer(&format!("Message de {}", server.nom))
+        .timestamp_now()
+        .send()
+    {
+        error!("{e}");
+    }
+}
+
+/// Counts a connection refused by the whitelist, and alerts the admins on `otternel`.
+/// Every refusal is counted in `whitelist_refus`; the alert of a player is sent at most every 10 minutes.
+fn on_whitelist_denied(line: &str, serverlog_id: u32, captures: &TriggerCaptures, options: &ActionOptions) {
+    // Parse a line like "Disconnecting Loutre (/1.2.3.4:50000): You are not white-listed on this server!"
+    // The `player` and `ip` groups of the trigger take precedence when present
+    let parsed = serverlog::whitelist::parse_denied_line(line);
+    let Some(playername) = capture(captures, "player").or(parsed.as_ref().map(|denied| denied.playername)) else {
+        debug!("no whitelist refusal match: {}", line);
+        return;
+    };
+    let Some(playername) = normalized_playername(playername) else {
+        return;
+    };
+    let ip = capture(captures, "ip").or(parsed.as_ref().and_then(|denied| denied.ip));
+
+    let server: Serveur = get_server_by_active_server_id(serverlog_id);
+
+    let attempts = writable_db(options, || format!("whitelist refusal of {} counted on {}", playername, server.nom))
+        .and_then(|db| match db.increment_whitelist_refusal(server.id, &playername, ip) {
+            Ok(attempts) => Some(attempts),
+            Err(e) => {
+                warn!("Failed to count the whitelist refusal of {}: {:?}", playername, e);
+                None
+            }
+        });
+
+    {
+        let mut last_alerts = LAST_WHITELIST_ALERTS.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+        let key = playername.to_lowercase();
+        if last_alerts.get(&key).is_some_and(|last| now.duration_since(*last) < WHITELIST_ALERT_COOLDOWN) {
+            debug!("Whitelist refusal alert of {} skipped, one was sent less than 10 minutes ago", playername);
+            return;
+        }
+        last_alerts.insert(key, now);
+    }
+
+    let mut description = format!("{} a tenté de se connecter à {} sans être dans la whitelist.", playername, server.nom);
+    if let Some(ip) = ip {
+        description.push_str(&format!("\nIP : `{}`", ip));
+    }
+    if let Some(attempts) = attempts.filter(|attempts| *attempts > 1) {
+        description.push_str(&format!("\nTentatives sur ce serveur : {}", attempts));
+    }
+
+    if let Err(e) = DiscordEmbed::new("otternel")
+        .title(&format!("Connexion refusée sur {}", server.nom))
+        .description(&description)
+        .color("c08020")
+        .thumbnail(&helper::webhook_discord::resolve_embed_thumbnail("", server.image.as_deref()))
+        .footer(&format!("Message de {}", server.nom))
+        .timestamp_now()
+        .send()
+    {
+        error!("{e}");
+    }
+}
+
+fn on_player_sanctioned(line: &str, serverlog_id: u32, kind: SanctionKind, captures: &TriggerCaptures, options: &ActionOptions) {
+    // Parse a line like "[Server thread/INFO]: Kicked Loutre: Spam" or "[Admin: Banned Loutre: Triche]"
+    // The `player` and `reason` groups of the trigger take precedence when present
+    let parsed = serverlog::moderation::parse_sanction_line(line);
+    let Some(playername) = capture(captures, "player").or(parsed.as_ref().map(|sanction| sanction.playername)) else {
+        debug!("no {} match: {}", kind.as_str(), line);
+        return;
+    };
+    let Some(playername) = normalized_playername(playername) else {
+        return;
+    };
+    let reason = capture(captures, "reason").or(parsed.as_ref().and_then(|sanction| sanction.reason));
+    let author = capture(captures, "author").or(parsed.as_ref().and_then(|sanction| sanction.author));
+
+    let server: Serveur = get_server_by_active_server_id(serverlog_id);
+    info!("{} {} on {} ({})", playername.green().bold(), kind.as_str(), server.nom, reason.unwrap_or("no reason"));
+
+    if let Some(db) = writable_db(options, || format!("{} of {} recorded on {}", kind.as_str(), playername, server.nom))
+        && let Err(e) = db.insert_moderation_log(server.id, &playername, kind.as_str(), reason, author)
+    {
+        warn!("Failed to record the {} of {}: {:?}", kind.as_str(), playername, e);
+    }
+
+    if announcements_muted(serverlog_id) {
+        return;
+    }
+    let (title, verb) = match kind {
+        SanctionKind::Kick => ("Joueur expulsé", "expulsé"),
+        SanctionKind::Ban => ("Joueur banni", "banni"),
+    };
+    let mut description = format!("{} a été {} de {}", playername, verb, server.nom);
+    description.push_str(&format!("\nRaison : {}", reason.unwrap_or("aucune")));
+    if let Some(author) = author {
+        description.push_str(&format!("\nPar : {}", author));
+    }
+    if let Err(e) = DiscordEmbed::new(&helper::webhook_discord::get_webhook_identity_by_server_id(server.jeu))
+        .thread(server.discord_thread_id.as_deref())
+        .title(title)
+        .description(&description)
+        .color("a01010")
+        .footer(&format!("Message de {}", server.nom))
+        .timestamp_now()
+        .send()
+    {
+        error!("{e}");
+    }
+}
+
+/// Opens the database for the writes of an action, `None` if the trigger has `no_db` or the database is unavailable.
+/// In dry-run, `writes` (what the action would write) is logged and `None` is returned.
+fn writable_db(options: &ActionOptions, writes: impl FnOnce() -> String) -> Option<Database> {
+    if options.no_db || helper::dry_run::skip(writes) {
+        return None;
+    }
+    helper::open_database::open_db_from_env()
+}
+
+/// Returns true if the Discord announcements of the server are muted (see `POST /api/servers/{id}/mute`).
+fn announcements_muted(serverlog_id: u32) -> bool {
+    let muted = helper::server_mute::is_muted(serverlog_id);
+    if muted {
+        debug!("Announcement of server {} skipped, the server is muted", serverlog_id);
+    }
+    muted
+}
+
+/// Returns true if the activity of a player is announced : their server isn't muted and they aren't hidden.
+/// Hidden players are still recorded, only their announcements are skipped.
+fn player_announced(serverlog_id: u32, game: &str, playername: &str) -> bool {
+    if helper::player_privacy::is_hidden(game, playername) {
+        debug!("{} is hidden, announcement skipped", playername);
+        return false;
+    }
+    !announcements_muted(serverlog_id)
+}
+
+/// Returns true if a chat message is a privacy command ("!privacy off"), handled by `on_player_privacy` and never relayed.
+fn is_privacy_command(message: &str) -> bool {
+    message.trim_start().starts_with(PRIVACY_COMMAND)
+}
+
+/// Embed relaying the chat message of a Minecraft player.
+/// A hidden player is shown as "Un joueur", without profile link nor head.
+fn player_message_embed(identity: &str, server: &Serveur, playername: &str, message: &str) -> DiscordEmbed {
+    let hidden = helper::player_privacy::is_hidden("minecraft", playername);
+    let (url, thumbnail) = if hidden {
+        (String::new(), String::new())
+    } else {
         (
-            caps.get(1).map(|m| m.as_str()).unwrap_or("Joueur"),
-            caps.get(2).map(|m| m.as_str()).unwrap_or("est mort."),
+            format!("https://antredesloutres.fr/joueurs/minecraft/{}", playername.to_lowercase()),
+            format!("{}/50", helper::webhook_discord::minecraft_avatar_url(playername)),
         )
-    } else {
-        ("Joueur", "est mort.")
     };
+    DiscordEmbed::new(identity)
+        .thread(server.discord_thread_id.as_deref())
+        .title(&helper::player_privacy::display_name("minecraft", playername))
+        .url(&url)
+        .description(message)
+        .color(server.embed_color.clone().unwrap_or_default())
+        .thumbnail(&thumbnail)
+        .footer(&format!("Message de {}", server.nom))
+        .timestamp_now()
+}
 
-    // Envoi de l'embed Discord
-    if let Err(e) = helper::webhook_discord::send_discord_embed(
-        helper::webhook_discord::get_webhook_identity_by_server_id(server.jeu),
-        " ",
-        &format!("{playername} est mort sur {} !", server.nom),
-        &format!("https://antredesloutres.fr/joueurs/minecraft/{}", playername.to_lowercase()),
-        &format!("{playername} {death_message}"),
-        server.embed_color,
-        " ",
-        " ",
-        " ",
-        &format!("Message de {}", server.nom),
-        Some(chrono::Utc::now().to_rfc3339()),
-    ) {
-        error!("{e}");
+/// Content relaying a `/me` : the shown name and the action, in italics.
+fn player_me_content(shown_name: &str, action: &str) -> String {
+    // The asterisks of the action would close the italics early
+    format!("*{} {}*", shown_name, action.trim().replace('*', "\\*"))
+}
+
+/// Avatar of a Palworld player relaying a chat message : none for a hidden player, their Steam avatar otherwise.
+fn palworld_chat_avatar(user_id: Option<&str>, playername: &str) -> String {
+    if helper::player_privacy::is_hidden("palworld", playername) {
+        return String::new();
     }
+    palworld_steam_avatar(user_id, playername).unwrap_or_default()
+}
+
+/// Normalizes a playername read from a line, logging the rejected ones (the event is then skipped).
+fn normalized_playername(raw: &str) -> Option<String> {
+    match helper::minecraft_account_formatter::normalize_playername(raw) {
+        Ok(playername) => Some(playername),
+        Err(e) => {
+            debug!("Event skipped, invalid playername: {}", e);
+            None
+        }
+    }
+}
+
+/// Returns true if `CHAT_RELAY_MODE` is "impersonate" : Minecraft chat messages are then posted
+/// as the player (name and head as webhook author) whatever the style of the trigger.
+fn chat_relay_impersonates() -> bool {
+    Config::current().chat_relay_mode.trim().eq_ignore_ascii_case("impersonate")
+}
+
+/// Returns a named group captured by the trigger regex, if present and not empty.
+fn capture<'a>(captures: &'a TriggerCaptures, name: &str) -> Option<&'a str> {
+    captures.get(name).map(|s| s.as_str()).filter(|s| !s.trim().is_empty())
 }
 
 fn get_server_by_active_server_id(serverlog_id: u32) -> Serveur {
@@ -330,3 +1327,229 @@ fn get_server_by_active_server_id(serverlog_id: u32) -> Serveur {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Lines reaching `on_trap_test` without panicking
+    static REACHED: AtomicU64 = AtomicU64::new(0);
+
+    /// Test action panicking on the lines containing "trap"
+    pub(super) fn on_trap_test(line: &str) {
+        assert!(!line.contains("trap"), "trap line: {}", line);
+        REACHED.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[test]
+    fn message_styles() {
+        assert_eq!(MessageStyle::parse(" Message "), Some(MessageStyle::Message));
+        assert_eq!(MessageStyle::parse("EMBED"), Some(MessageStyle::Embed));
+        assert_eq!(MessageStyle::parse("text"), None);
+        assert_eq!(ActionOptions::default().style, MessageStyle::Embed);
+    }
+
+    #[test]
+    fn player_message_action_ignores_a_line_without_a_chat_message() {
+        let line = "[10:00:00] [Server thread/INFO]: Loaded 3 < 5 chunks";
+        let outcome = dispatch_action("on_player_message", line, 1, &TriggerCaptures::new(), &ActionOptions::default());
+        assert_eq!(outcome, Ok(()));
+    }
+
+    #[test]
+    fn a_panicking_action_does_not_stop_the_following_dispatches() {
+        let options = ActionOptions::default();
+        dispatch("on_trap_test", "[10:00:00] trap line", 1, &TriggerCaptures::new(), &options);
+        dispatch("on_trap_test", "[10:00:01] next line", 1, &TriggerCaptures::new(), &options);
+        dispatch("on_trap_test", "[10:00:02] trap line again", 1, &TriggerCaptures::new(), &options);
+        dispatch("on_trap_test", "[10:00:03] last line", 1, &TriggerCaptures::new(), &options);
+        assert_eq!(REACHED.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn death_gives_the_captured_player_or_the_first_word_of_the_message() {
+        let captures = TriggerCaptures::from([
+            ("player".to_string(), "Alex".to_string()),
+            ("message".to_string(), "was slain by Steve".to_string()),
+        ]);
+        assert_eq!(death_of("anything", &captures), Some(("Alex", "was slain by Steve")));
+
+        let line = "[17:58:38] [Server thread/INFO]: TheAzertor fell from a high place";
+        assert_eq!(death_of(line, &TriggerCaptures::new()), Some(("TheAzertor", "fell from a high place")));
+    }
+
+    #[test]
+    fn death_without_a_player_name_is_ignored() {
+        assert_eq!(death_of("[17:58:38] [Server thread/INFO]:", &TriggerCaptures::new()), None);
+        assert_eq!(death_of("a death happened", &TriggerCaptures::new()), None);
+
+        // Returns before any server or database lookup, with no fake player
+        let outcome = dispatch_action("on_player_death", "a death happened", 1, &TriggerCaptures::new(), &ActionOptions::default());
+        assert_eq!(outcome, Ok(()));
+    }
+
+    #[test]
+    fn unknown_action_is_an_error() {
+        let outcome = dispatch_action("on_missing_action_test", "line", 1, &TriggerCaptures::new(), &ActionOptions::default());
+        assert_eq!(outcome, Err("unknown action on_missing_action_test".to_string()));
+    }
+
+    const ANONYMOUS: &str = helper::player_privacy::ANONYMOUS_PLAYERNAME;
+
+    fn server() -> Serveur {
+        Serveur { nom: "Survie".to_string(), jeu: "minecraft".to_string(), embed_color: Some("#1ec274".to_string()), ..Default::default() }
+    }
+
+    #[test]
+    fn hidden_players_are_not_announced() {
+        helper::player_privacy::remember("minecraft", "HiddenOtter", false);
+        helper::player_privacy::remember("palworld", "HiddenPal", false);
+        // Connections, advancements and deaths all go through this check
+        assert!(!player_announced(90_001, "minecraft", "HiddenOtter"));
+        assert!(!player_announced(90_001, "minecraft", "hiddenotter"));
+        assert!(!player_announced(90_001, "palworld", "HiddenPal"));
+        assert!(player_announced(90_001, "palworld", "HiddenOtter"));
+        assert!(player_announced(90_001, "minecraft", "VisibleOtter"));
+    }
+
+    #[test]
+    fn a_hidden_killer_is_anonymized_in_the_death_embed() {
+        helper::player_privacy::remember("minecraft", "SneakyOtter", false);
+        assert_eq!(death_description("Loutre", "was slain by SneakyOtter"), "Loutre was slain by Un joueur");
+        assert_eq!(
+            death_description("Loutre", "was slain by SneakyOtter using [SneakyOtter]"),
+            "Loutre was slain by Un joueur using [SneakyOtter]"
+        );
+        assert_eq!(death_description("Loutre", "drowned whilst trying to escape sneakyotter"), "Loutre drowned whilst trying to escape Un joueur");
+
+        // Visible killers, mobs and deaths without a killer are kept as they are
+        assert_eq!(death_description("Loutre", "was shot by BoldOtter"), "Loutre was shot by BoldOtter");
+        assert_eq!(death_description("Loutre", "was slain by Zombie"), "Loutre was slain by Zombie");
+        assert_eq!(death_description("Loutre", "fell from a high place"), "Loutre fell from a high place");
+    }
+
+    #[test]
+    fn chat_of_a_hidden_player_is_anonymized() {
+        helper::player_privacy::remember("minecraft", "ShyOtter", false);
+        let embed = player_message_embed("mineotter", &server(), "ShyOtter", "salut").payload()["embeds"][0].clone();
+        assert_eq!(embed["title"], ANONYMOUS);
+        assert_eq!(embed["description"], "salut");
+        assert!(embed.get("url").is_none());
+        assert!(embed.get("thumbnail").is_none());
+
+        let embed = player_message_embed("mineotter", &server(), "ChattyOtter", "salut").payload()["embeds"][0].clone();
+        assert_eq!(embed["title"], "ChattyOtter");
+        assert_eq!(embed["url"], "https://antredesloutres.fr/joueurs/minecraft/chattyotter");
+        assert_eq!(embed["thumbnail"]["url"], "https://mc-heads.net/avatar/chattyotter/50");
+    }
+
+    #[test]
+    fn me_of_a_hidden_player_is_anonymized() {
+        helper::player_privacy::remember("minecraft", "QuietOtter", false);
+        let shown = helper::player_privacy::display_name("minecraft", "QuietOtter");
+        assert_eq!(player_me_content(&shown, " danse *fort* "), "*Un joueur danse \\*fort\\**");
+    }
+
+    #[test]
+    fn palworld_accounts_are_looked_up_once_per_player() {
+        let lookups = std::cell::Cell::new(0);
+        let lookup = |playername: &str| {
+            lookups.set(lookups.get() + 1);
+            (playername == "ChattyPal").then(|| "steam_76561198000000301".to_string())
+        };
+        let start = Instant::now();
+        for second in [0, 1, 30, 599] {
+            let at = start + Duration::from_secs(second);
+            assert_eq!(palworld_user_id("ChattyPal", at, lookup).as_deref(), Some("steam_76561198000000301"));
+            assert_eq!(palworld_user_id("chattypal", at, lookup).as_deref(), Some("steam_76561198000000301"));
+        }
+        assert_eq!(lookups.get(), 1);
+
+        // An unknown player isn't looked up on each of their messages either
+        assert_eq!(palworld_user_id("NewPal", start, lookup), None);
+        assert_eq!(palworld_user_id("NewPal", start + Duration::from_secs(5), lookup), None);
+        assert_eq!(lookups.get(), 2);
+
+        assert!(palworld_user_id("ChattyPal", start + PALWORLD_ACCOUNT_TTL, lookup).is_some());
+        assert_eq!(lookups.get(), 3);
+    }
+
+    #[test]
+    fn palworld_chat_of_a_hidden_player_has_no_avatar() {
+        helper::player_privacy::remember("palworld", "ShyPal", false);
+        assert_eq!(palworld_chat_avatar(Some("steam_76561198000000000"), "ShyPal"), "");
+        assert_eq!(helper::player_privacy::display_name("palworld", "ShyPal"), ANONYMOUS);
+    }
+
+    #[test]
+    fn privacy_commands_are_not_relayed() {
+        assert!(is_privacy_command("!privacy off"));
+        assert!(is_privacy_command("  !privacy on"));
+        assert!(!is_privacy_command("qui a fait !privacy off ?"));
+        assert!(!is_privacy_command("salut"));
+    }
+
+
+    /// Counts the database writes asked by an action
+    #[derive(Default)]
+    struct CountingWrites {
+        persisted: usize,
+        recorded: Vec<(u64, String, String)>,
+        fail: bool,
+    }
+
+    impl ConnectionWrites for CountingWrites {
+        fn persist_online(&mut self, _serverlog_id: u32, _serveur_id: u64) {
+            self.persisted += 1;
+        }
+
+        fn record_connection(&mut self, _serverlog_id: u32, serveur_id: u64, playername: &str, co_type: &str) -> bool {
+            self.recorded.push((serveur_id, playername.to_string(), co_type.to_string()));
+            !self.fail
+        }
+    }
+
+    fn no_db() -> ActionOptions {
+        ActionOptions { no_db: true, ..Default::default() }
+    }
+
+    #[test]
+    fn a_no_db_join_is_announced_without_any_database_write() {
+        let server = Serveur { id: 12, ..server() };
+        let mut writes = CountingWrites::default();
+
+        let embed = player_connection_update(&server, 92_001, "NoDbOtter", "rejoint", &no_db(), &mut writes).unwrap();
+        let payload = embed.payload();
+        assert_eq!(payload["embeds"][0]["title"], "NoDbOtter");
+        assert_eq!(payload["embeds"][0]["description"], "NoDbOtter a rejoint Survie");
+        assert_eq!((writes.persisted, writes.recorded.len()), (0, 0));
+
+        // The online players are still tracked, in memory
+        assert_eq!(serverlog::online_tracker::players_of(92_001), vec!["NoDbOtter"]);
+        assert!(player_connection_update(&server, 92_001, "NoDbOtter", "quitté", &no_db(), &mut writes).is_some());
+        assert!(serverlog::online_tracker::players_of(92_001).is_empty());
+        assert_eq!((writes.persisted, writes.recorded.len()), (0, 0));
+    }
+
+    #[test]
+    fn a_join_is_recorded_then_announced() {
+        let server = Serveur { id: 12, ..server() };
+        let mut writes = CountingWrites::default();
+
+        assert!(player_connection_update(&server, 92_002, "DbOtter", "rejoint", &ActionOptions::default(), &mut writes).is_some());
+        assert_eq!(writes.persisted, 1);
+        assert_eq!(writes.recorded, vec![(12, "DbOtter".to_string(), "rejoint".to_string())]);
+
+        // Not announced when the connection couldn't be recorded
+        let mut failing = CountingWrites { fail: true, ..Default::default() };
+        assert!(player_connection_update(&server, 92_002, "DbOtter", "quitté", &ActionOptions::default(), &mut failing).is_none());
+    }
+
+    #[test]
+    fn a_hidden_no_db_join_is_neither_written_nor_announced() {
+        helper::player_privacy::remember("minecraft", "HiddenNoDbOtter", false);
+        let mut writes = CountingWrites::default();
+        assert!(player_connection_update(&server(), 92_003, "HiddenNoDbOtter", "rejoint", &no_db(), &mut writes).is_none());
+        assert_eq!((writes.persisted, writes.recorded.len()), (0, 0));
+    }
+}
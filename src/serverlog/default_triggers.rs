@@ -0,0 +1,157 @@
+use std::io::ErrorKind;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use colored::Colorize;
+use log::{error, info};
+
+/// Path of the triggers file, relative to the working directory
+pub const TRIGGERS_PATH: &str = "triggers.toml";
+
+/// Trigger set compiled into the binary, used when no `triggers.toml` is found.
+/// Vanilla Minecraft join, leave, chat, death and advancement patterns, for every serverlog_id.
+pub const DEFAULT_TRIGGERS: &str = include_str!("default_triggers.toml");
+
+/// Whether the use of the built-in defaults was already logged
+static NO_FILE_LOGGED: AtomicBool = AtomicBool::new(false);
+
+/// Reads the triggers file, or falls back on the built-in defaults if it doesn't exist.
+///
+/// # Returns
+/// The TOML content to parse, or `None` if the file exists but can't be read.
+pub fn read_triggers_file(path: &str) -> Option<String> {
+    match std::fs::read_to_string(path) {
+        Ok(content) => Some(content),
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            // The triggers are reloaded periodically : this is only said once
+            if NO_FILE_LOGGED.swap(true, Ordering::Relaxed) {
+                return Some(DEFAULT_TRIGGERS.to_string());
+            }
+            info!(
+                "No {} found : {} are active. Run {} or set {} to write them to a file you can edit",
+                path.yellow().bold(),
+                "built-in default triggers".green().bold(),
+                "otternel init-triggers".green(),
+                "GENERATE_TRIGGERS_EXAMPLE=true".green()
+            );
+            Some(DEFAULT_TRIGGERS.to_string())
+        }
+        Err(e) => {
+            error!("Could not read {}: {}", path, e);
+            None
+        }
+    }
+}
+
+/// Writes the built-in default triggers to `path`, so they can be edited. An existing file is never overwritten.
+///
+/// # Returns
+/// - `Ok(true)` if the file was written.
+/// - `Ok(false)` if a file already exists at `path`.
+/// - `Err(std::io::Error)` if the file couldn't be written.
+pub fn write_example(path: &str) -> std::io::Result<bool> {
+    if Path::new(path).exists() {
+        return Ok(false);
+    }
+    std::fs::write(path, DEFAULT_TRIGGERS)?;
+    info!("Default triggers written to {}", path.green().bold());
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serverlog::triggers;
+
+    fn missing_path() -> String {
+        std::env::temp_dir()
+            .join(format!("otternel-no-triggers-{}.toml", uuid::Uuid::new_v4()))
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    #[test]
+    fn every_default_trigger_compiles() {
+        let declared = DEFAULT_TRIGGERS.lines().filter(|line| line.trim() == "[[trigger]]").count();
+        let loaded = triggers::load(&missing_path());
+        assert_eq!(loaded.compiled.len(), declared);
+        assert!(loaded.compiled.iter().all(|trigger| trigger.applies_to(7, Some("minecraft"))));
+        assert!(loaded.compiled.iter().all(|trigger| !trigger.applies_to(7, Some("palworld"))));
+    }
+
+    #[test]
+    fn default_triggers_match_vanilla_lines() {
+        let loaded = triggers::load(&missing_path());
+        let cases = [
+            ("[10:00:00] [Server thread/INFO]: Loutre joined the game", "minecraft_player_joined", Some("Loutre")),
+            ("[10:00:00] [Server thread/INFO]: Loutre left the game", "minecraft_player_left", Some("Loutre")),
+            (
+                "[10:00:00] [Server thread/INFO]: Loutre has made the advancement [Stone Age]",
+                "minecraft_player_advancement",
+                Some("Loutre"),
+            ),
+            (
+                "[10:00:00] [Server thread/INFO]: Loutre has completed the challenge [Arbalest]",
+                "minecraft_player_advancement",
+                Some("Loutre"),
+            ),
+            ("[10:00:00] [Server thread/INFO]: <Loutre> salut tout le monde", "minecraft_player_message", Some("Loutre")),
+            ("[10:00:00] [Server thread/INFO]: * Loutre danse", "minecraft_player_me", Some("Loutre")),
+            ("[10:00:00] [Server thread/INFO]: Loutre was slain by Zombie", "minecraft_player_death", Some("Loutre")),
+            ("[10:00:00] [Server thread/INFO]: Loutre hit the ground too hard", "minecraft_player_death", Some("Loutre")),
+            ("[10:00:00] [Server thread/INFO]: Loutre drowned", "minecraft_player_death", Some("Loutre")),
+            ("[10:00:00] [Server thread/INFO]: Done (12.345s)! For help, type \"help\"", "minecraft_server_started", None),
+            ("[10:00:00] [Server thread/INFO]: Stopping server", "minecraft_server_stopped", None),
+            (
+                "[10:00:00] [Server thread/INFO]: Disconnecting Loutre (/127.0.0.1:51234): You are not whitelisted on this server!",
+                "minecraft_whitelist_denied",
+                None,
+            ),
+            ("[10:00:00] [Server thread/INFO]: Kicked Loutre: Flying is not enabled", "minecraft_player_kicked", None),
+            ("[10:00:00] [Server thread/INFO]: Banned Loutre: Griefing", "minecraft_player_banned", None),
+            (
+                "[10:00:00] [Server thread/WARN]: Can't keep up! Is the server overloaded? Running 2503ms or 50 ticks behind",
+                "minecraft_server_lagging",
+                None,
+            ),
+            ("---- Minecraft Crash Report ----", "minecraft_server_crash_report", None),
+        ];
+        for (line, trigger, player) in cases {
+            let matches = loaded.matching(line, 1, Some("minecraft"), false).unwrap();
+            let names: Vec<&str> = matches.iter().map(|m| m.trigger.name.as_str()).collect();
+            assert_eq!(names, vec![trigger], "{}", line);
+            assert_eq!(matches[0].captures.get("player").map(String::as_str), player, "{}", line);
+        }
+
+        let privacy_line = "[10:00:00] [Server thread/INFO]: <Loutre> !privacy off";
+        let privacy = loaded.matching(privacy_line, 1, Some("minecraft"), false).unwrap();
+        let names: Vec<&str> = privacy.iter().map(|m| m.trigger.name.as_str()).collect();
+        assert_eq!(names, vec!["minecraft_player_message", "minecraft_player_privacy"]);
+        assert_eq!(privacy[1].captures["state"], "off");
+
+        // A chat message or a startup line is never taken for a death
+        for line in ["[10:00:00] [Server thread/INFO]: Preparing spawn area: 84%", "[10:00:00] [Server thread/INFO]: <Loutre> drowned"] {
+            let matches = loaded.matching(line, 1, Some("minecraft"), false).unwrap();
+            assert!(matches.iter().all(|m| m.trigger.name != "minecraft_player_death"), "{line}");
+        }
+    }
+
+    #[test]
+    fn an_existing_file_wins_over_the_defaults() {
+        let path = missing_path();
+        assert_eq!(read_triggers_file(&path).as_deref(), Some(DEFAULT_TRIGGERS));
+        std::fs::write(&path, "[[trigger]]\npattern = 'x'\nfunction = 'f'\n").unwrap();
+        assert_eq!(read_triggers_file(&path).as_deref(), Some("[[trigger]]\npattern = 'x'\nfunction = 'f'\n"));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn the_example_never_overwrites_a_file() {
+        let path = missing_path();
+        assert!(write_example(&path).unwrap());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), DEFAULT_TRIGGERS);
+        std::fs::write(&path, "# mes triggers").unwrap();
+        assert!(!write_example(&path).unwrap());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "# mes triggers");
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,156 @@
+/// Bytes of the start of a file kept to notice it was rewritten
+const HEAD_LEN: usize = 64;
+
+/// Read state of a log file : where the next read starts, and the end of the last chunk not ended by a line break.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FileCursor {
+    /// Bytes of the file consumed so far
+    pub offset: u64,
+    /// Start of a line whose end isn't written yet, completed by the next chunk
+    pub remainder: String,
+    /// First bytes of the file when it was last read, empty if unknown (position restored at startup)
+    head: Vec<u8>,
+}
+
+impl FileCursor {
+    /// Cursor of a file read up to `offset`, as restored at startup or for a file skipped to its end.
+    pub fn at(offset: u64) -> Self {
+        Self { offset, ..Self::default() }
+    }
+
+    /// Number of bytes to read at the start of the file for [`FileCursor::rewind_if_replaced`].
+    pub fn head_len(len: u64) -> usize {
+        HEAD_LEN.min(len as usize)
+    }
+
+    /// Starts the file over if it was replaced since the last read : it is shorter than the offset (truncated or
+    /// rotated), or its first bytes changed (emptied then written again, longer than the offset).
+    ///
+    /// # Returns
+    /// True if the cursor was rewound to the start of the file.
+    pub fn rewind_if_replaced(&mut self, len: u64, head: &[u8]) -> bool {
+        let common = self.head.len().min(head.len());
+        let replaced = len < self.offset || self.head[..common] != head[..common];
+        if replaced {
+            self.offset = 0;
+            self.remainder.clear();
+        }
+        if replaced || head.len() > self.head.len() {
+            self.head = head.to_vec();
+        }
+        replaced
+    }
+
+    /// Records a decoded chunk : `consumed` bytes were read from the offset.
+    ///
+    /// # Returns
+    /// The lines completed by the chunk, see [`extract_complete_lines`].
+    pub fn consume(&mut self, consumed: usize, chunk: &str) -> Vec<String> {
+        self.offset += consumed as u64;
+        let (lines, remainder) = extract_complete_lines(&self.remainder, chunk);
+        self.remainder = remainder;
+        lines
+    }
+}
+
+/// Splits a chunk read from a log file into complete lines.
+///
+/// # Arguments
+/// * `previous_remainder` - The end of the previous chunk, not ended by a line break.
+/// * `chunk` - The text just read, following `previous_remainder`.
+///
+/// # Returns
+/// The complete lines, without their `\n` or `\r\n`, and the text after the last line break : the start of a line
+/// to complete with the next chunk (empty if the chunk ends with a line break).
+pub fn extract_complete_lines(previous_remainder: &str, chunk: &str) -> (Vec<String>, String) {
+    let text = format!("{previous_remainder}{chunk}");
+    let Some(last_break) = text.rfind('\n') else {
+        return (Vec::new(), text);
+    };
+    let lines = text[..last_break]
+        .split('\n')
+        .map(|line| line.strip_suffix('\r').unwrap_or(line).to_string())
+        .collect();
+    (lines, text[last_break + 1..].to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn simple_append() {
+        let (lines, remainder) = extract_complete_lines("", "first\n");
+        assert_eq!(lines, vec!["first"]);
+        assert_eq!(remainder, "");
+    }
+
+    #[test]
+    fn multi_line_append_gives_every_line() {
+        let (lines, remainder) = extract_complete_lines("", "one\r\ntwo\n\nthree\nfour");
+        assert_eq!(lines, vec!["one", "two", "", "three"]);
+        assert_eq!(remainder, "four");
+    }
+
+    #[test]
+    fn partial_line_is_completed_by_the_next_read() {
+        let mut cursor = FileCursor::default();
+        assert_eq!(cursor.consume(9, "a\nbeginni"), vec!["a"]);
+        assert_eq!(cursor.remainder, "beginni");
+        assert_eq!(cursor.offset, 9);
+
+        assert_eq!(cursor.consume(7, "ng\nnext"), vec!["beginning"]);
+        assert_eq!(cursor.remainder, "next");
+        assert_eq!(cursor.offset, 16);
+
+        assert!(cursor.consume(0, "").is_empty());
+        assert_eq!(cursor.remainder, "next");
+    }
+
+    #[test]
+    fn truncated_or_rotated_file_is_read_from_its_start() {
+        let mut cursor = FileCursor::default();
+        let head = b"[10:00:00] first line\n";
+        assert!(!cursor.rewind_if_replaced(head.len() as u64, head));
+        cursor.consume(head.len() + 5, "[10:00:00] first line\npart");
+
+        // Rotated : the new file is shorter than the offset
+        let rotated = b"[11:00:00] new\n";
+        assert!(cursor.rewind_if_replaced(rotated.len() as u64, rotated));
+        assert_eq!(cursor.offset, 0);
+        assert_eq!(cursor.remainder, "", "the line of the old file isn't completed by the new one");
+    }
+
+    #[test]
+    fn file_emptied_then_written_longer_than_the_offset_is_read_from_its_start() {
+        let mut cursor = FileCursor::default();
+        let head = b"[10:00:00] old content\n";
+        cursor.rewind_if_replaced(head.len() as u64, head);
+        cursor.consume(head.len(), "[10:00:00] old content\n");
+
+        let rewritten = b"[12:00:00] rewritten, and longer than before\n";
+        assert!(cursor.rewind_if_replaced(rewritten.len() as u64, rewritten));
+        assert_eq!(cursor.offset, 0);
+    }
+
+    #[test]
+    fn appended_file_is_not_replaced() {
+        let mut cursor = FileCursor::default();
+        cursor.rewind_if_replaced(6, b"short\n");
+        cursor.consume(6, "short\n");
+
+        // The head grows with the file, its start is unchanged
+        assert!(!cursor.rewind_if_replaced(20, b"short\nmore content\n"));
+        assert_eq!(cursor.offset, 6);
+        assert!(!cursor.rewind_if_replaced(20, b"short\nmore content\n"));
+    }
+
+    #[test]
+    fn restored_cursor_without_head_only_rewinds_when_the_file_is_shorter() {
+        let mut cursor = FileCursor::at(100);
+        assert!(!cursor.rewind_if_replaced(150, b"anything"));
+        assert_eq!(cursor.offset, 100);
+        assert!(cursor.rewind_if_replaced(50, b"anything"));
+        assert_eq!(cursor.offset, 0);
+    }
+}
@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use colored::Colorize;
+use log::{debug, error, info, warn};
+use notify::event::{EventKind, ModifyKind, RenameMode};
+
+use crate::helper;
+use crate::helper::webhook_discord::DiscordEmbed;
+
+/// A removed log file recreated at the same path within this delay was rotated, not removed
+pub const ROTATION_GRACE: Duration = Duration::from_secs(10);
+
+/// What a notify event means for the existence of a log file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileEvent {
+    /// The file exists at this path now (created, or renamed to this path)
+    Created,
+    /// The file doesn't exist at this path anymore (removed, or renamed from this path)
+    Removed,
+}
+
+/// Notice about a log file, decided by [`FileLifecycle`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileNotice {
+    /// A log file never watched before was created (ex: a freshly provisioned server)
+    Appeared { path: PathBuf, serverlog_id: Option<u32> },
+    /// A log file was removed and not recreated within [`ROTATION_GRACE`]
+    Disappeared { path: PathBuf, serverlog_id: Option<u32> },
+    /// A log file was removed, then recreated within [`ROTATION_GRACE`] : a rotation, nothing to report
+    Rotated { path: PathBuf },
+}
+
+/// Returns what a notify event kind means for the existence of its `path_index`-th path, if anything.
+/// A rename seen from both sides gives the old path first, then the new one.
+pub fn classify(kind: &EventKind, path_index: usize) -> Option<FileEvent> {
+    match kind {
+        EventKind::Create(_) | EventKind::Modify(ModifyKind::Name(RenameMode::To)) => Some(FileEvent::Created),
+        EventKind::Remove(_) | EventKind::Modify(ModifyKind::Name(RenameMode::From)) => Some(FileEvent::Removed),
+        EventKind::Modify(ModifyKind::Name(RenameMode::Both)) if path_index == 0 => Some(FileEvent::Removed),
+        EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => Some(FileEvent::Created),
+        _ => None,
+    }
+}
+
+/// Tells log files appearing and disappearing apart from log rotations.
+///
+/// A removal is only reported once [`ROTATION_GRACE`] passed without the file being recreated,
+/// so `latest.log` being renamed and recreated by the server never alerts.
+#[derive(Debug, Default)]
+pub struct FileLifecycle {
+    /// Removed files waiting for the end of the grace delay, with the serverlog_id they had
+    pending_removals: HashMap<PathBuf, (Instant, Option<u32>)>,
+}
+
+impl FileLifecycle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a creation or a removal of a log file.
+    ///
+    /// # Arguments
+    /// - `known`: whether the file was already watched (read at least once) before this event
+    /// - `serverlog_id`: the serverlog_id of the file, given back in the notice
+    ///
+    /// # Returns
+    /// The notice to report now, if any. Removals are reported later, by [`FileLifecycle::expired`].
+    pub fn on_event(&mut self, path: &Path, event: FileEvent, known: bool, serverlog_id: Option<u32>, now: Instant) -> Option<FileNotice> {
+        match event {
+            FileEvent::Removed => {
+                self.pending_removals.insert(path.to_path_buf(), (now, serverlog_id));
+                None
+            }
+            FileEvent::Created => {
+                if self.pending_removals.remove(path).is_some() {
+                    Some(FileNotice::Rotated { path: path.to_path_buf() })
+                } else if !known {
+                    Some(FileNotice::Appeared { path: path.to_path_buf(), serverlog_id })
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    /// Returns the removals not followed by a creation within [`ROTATION_GRACE`], and forgets them.
+    pub fn expired(&mut self, now: Instant) -> Vec<FileNotice> {
+        let expired: Vec<PathBuf> = self
+            .pending_removals
+            .iter()
+            .filter(|(_, (removed_at, _))| now.duration_since(*removed_at) >= ROTATION_GRACE)
+            .map(|(path, _)| path.clone())
+            .collect();
+
+        expired
+            .into_iter()
+            .filter_map(|path| {
+                let (_, serverlog_id) = self.pending_removals.remove(&path)?;
+                Some(FileNotice::Disappeared { path, serverlog_id })
+            })
+            .collect()
+    }
+}
+
+/// Sends an admin embed for a log file appearing or disappearing, if its server is marked active.
+///
+/// A file of an unknown or inactive server is only logged : appearing, it's not tied to any server yet,
+/// disappearing, its server was decommissioned on purpose.
+pub fn report(notice: &FileNotice) {
+    let (path, serverlog_id, title, description, color) = match notice {
+        FileNotice::Rotated { path } => {
+            debug!("Log file {} rotated", path.display());
+            return;
+        }
+        FileNotice::Appeared { path, serverlog_id } => (
+            path,
+            *serverlog_id,
+            "Nouveau fichier de log",
+            "Un nouveau fichier de log est surveillé (serveur fraîchement installé ?)",
+            "126020", // Green
+        ),
+        FileNotice::Disappeared { path, serverlog_id } => (
+            path,
+            *serverlog_id,
+            "Fichier de log supprimé",
+            "Le fichier de log d'un serveur toujours actif a disparu (erreur de configuration ?)",
+            "c08020", // Orange
+        ),
+    };
+
+    let server = serverlog_id
+        .and_then(|id| helper::open_database::open_db_from_env().map(|db| (id, db)))
+        .and_then(|(id, db)| match db.get_server_by_active_server_id(id as u64) {
+            Ok(server) => server,
+            Err(e) => {
+                error!("Error fetching server for serverlog_id {}: {}", id, e);
+                None
+            }
+        })
+        .filter(|server| server.actif);
+
+    let Some(server) = server else {
+        info!("{} : {} (no active server)", title, path.display());
+        return;
+    };
+    warn!("{} : {} ({})", title, path.display().to_string().yellow(), server.nom.yellow().bold());
+
+    if let Err(e) = DiscordEmbed::new("otternel")
+        .title(title)
+        .description(&format!("{}\n\nServeur : **{}**\nFichier : `{}`", description, server.nom, path.display()))
+        .color(color)
+        .footer("Otternel Service")
+        .timestamp_now()
+        .send()
+    {
+        error!("{e}");
+    }
+}
@@ -0,0 +1,39 @@
+use chrono::{Duration, Local, NaiveDate, NaiveDateTime, NaiveTime};
+use regex::Regex;
+use std::sync::LazyLock;
+
+static TIMESTAMP_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^\[(?:(\d{4})[-.](\d{2})[-.](\d{2})[ T-])?(\d{2})[:.](\d{2})[:.](\d{2})").unwrap()
+});
+
+/// Parses the timestamp at the start of a cleaned log line (starting with `[`).
+///
+/// # Supported formats
+/// - `[14:43:14] [Server thread/INFO]: ...` (Minecraft) : the date is today, or yesterday if
+///   the time is in the future (line written just before midnight).
+/// - `[2025-01-31 14:43:14] ...` and `[2025.01.31-14.43.14:000] ...` (Palworld and others).
+///
+/// # Returns
+/// The local datetime of the line, or `None` if the line doesn't start with a timestamp.
+pub fn parse_line_timestamp(line: &str) -> Option<NaiveDateTime> {
+    parse_line_timestamp_at(line, Local::now().naive_local())
+}
+
+/// Same as [`parse_line_timestamp`], with the current local datetime given explicitly.
+pub fn parse_line_timestamp_at(line: &str, now: NaiveDateTime) -> Option<NaiveDateTime> {
+    let caps = TIMESTAMP_RE.captures(line)?;
+    let num = |i: usize| caps.get(i).and_then(|m| m.as_str().parse::<u32>().ok());
+
+    let time = NaiveTime::from_hms_opt(num(4)?, num(5)?, num(6)?)?;
+
+    if let (Some(y), Some(m), Some(d)) = (num(1), num(2), num(3)) {
+        return Some(NaiveDate::from_ymd_opt(y as i32, m, d)?.and_time(time));
+    }
+
+    let today = now.date().and_time(time);
+    if today > now {
+        Some(today - Duration::days(1))
+    } else {
+        Some(today)
+    }
+}
@@ -0,0 +1,213 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::LazyLock;
+use encoding_rs::Encoding;
+use log::warn;
+
+use crate::config::Config;
+
+/// Bytes looked at to guess whether a chunk without BOM is UTF-16
+const UTF16_SNIFF_LEN: usize = 512;
+
+/// Encoding of a log file, forced by `SERVERLOG_ENCODING` (detected when not set).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogEncoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    /// Latin-1 and its Windows-1252 superset (`€`, `’`, `œ`... in 0x80-0x9F)
+    Latin1,
+}
+
+impl LogEncoding {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().replace(['-', '_'], "").as_str() {
+            "utf8" => Some(Self::Utf8),
+            "utf16le" | "utf16" => Some(Self::Utf16Le),
+            "utf16be" => Some(Self::Utf16Be),
+            "latin1" | "iso88591" | "windows1252" | "cp1252" => Some(Self::Latin1),
+            _ => None,
+        }
+    }
+
+    /// The `encoding_rs` decoder of the encoding. Latin-1 is decoded as Windows-1252, as browsers do : both only
+    /// differ on control characters that a log doesn't hold.
+    pub fn encoding(self) -> &'static Encoding {
+        match self {
+            Self::Utf8 => encoding_rs::UTF_8,
+            Self::Utf16Le => encoding_rs::UTF_16LE,
+            Self::Utf16Be => encoding_rs::UTF_16BE,
+            Self::Latin1 => encoding_rs::WINDOWS_1252,
+        }
+    }
+}
+
+/// Encodings forced by `SERVERLOG_ENCODING`.
+#[derive(Debug, Default)]
+struct ForcedEncodings {
+    /// Bare value, for every folder
+    all: Option<LogEncoding>,
+    /// `folder=encoding` entries, by log folder name
+    by_folder: HashMap<String, LogEncoding>,
+}
+
+static FORCED: LazyLock<ForcedEncodings> = LazyLock::new(|| parse_forced(&Config::current().serverlog_encoding));
+
+/// Parses `SERVERLOG_ENCODING` : `latin1` for every folder, or `folder=encoding` entries separated by commas
+/// (ex: `palworld=utf16be,12=latin1`). Both can be mixed, a folder entry wins over the bare value.
+fn parse_forced(value: &str) -> ForcedEncodings {
+    let mut forced = ForcedEncodings::default();
+    for entry in value.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+        let (folder, name) = match entry.split_once('=') {
+            Some((folder, name)) => (Some(folder.trim()), name),
+            None => (None, entry),
+        };
+        let Some(encoding) = LogEncoding::parse(name) else {
+            warn!("Unknown encoding in SERVERLOG_ENCODING: {} (utf8, utf16le, utf16be, latin1 or windows1252)", entry);
+            continue;
+        };
+        match folder {
+            Some(folder) => {
+                forced.by_folder.insert(folder.to_string(), encoding);
+            }
+            None => forced.all = Some(encoding),
+        }
+    }
+    forced
+}
+
+/// Returns the entries of `SERVERLOG_ENCODING` naming an unknown encoding, reported by [`Config::validate`].
+pub fn unknown_entries(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .filter(|entry| LogEncoding::parse(entry.split_once('=').map_or(*entry, |(_, name)| name)).is_none())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Returns the encoding forced for a log file, by the name of its folder, `None` to detect it.
+pub fn forced_for(path: &Path) -> Option<LogEncoding> {
+    let folder = path.parent().and_then(|p| p.file_name()).and_then(|s| s.to_str());
+    folder.and_then(|folder| FORCED.by_folder.get(folder).copied()).or(FORCED.all)
+}
+
+/// Guesses the byte order of UTF-16 text without BOM, from its NUL bytes : the ASCII characters of a log
+/// have their high byte at 0, second in little-endian and first in big-endian.
+///
+/// # Returns
+/// The UTF-16 encoding, or `None` if the bytes don't look like UTF-16 (text in UTF-8 or latin-1 has no NUL byte).
+pub fn sniff_utf16(bytes: &[u8]) -> Option<LogEncoding> {
+    let sample = &bytes[..bytes.len().min(UTF16_SNIFF_LEN)];
+    let pairs = sample.len() / 2;
+    if pairs == 0 {
+        return None;
+    }
+    let even_nuls = sample.iter().step_by(2).filter(|b| **b == 0).count();
+    let odd_nuls = sample.iter().skip(1).step_by(2).filter(|b| **b == 0).count();
+    if odd_nuls * 4 >= pairs && odd_nuls > even_nuls {
+        Some(LogEncoding::Utf16Le)
+    } else if even_nuls * 4 >= pairs && even_nuls > odd_nuls {
+        Some(LogEncoding::Utf16Be)
+    } else {
+        None
+    }
+}
+
+/// Returns the length of the bytes holding complete characters, without the character cut at the end of a chunk :
+/// an odd byte or a high surrogate in UTF-16, an incomplete sequence in UTF-8.
+pub fn complete_len(bytes: &[u8], encoding: LogEncoding) -> usize {
+    match encoding {
+        LogEncoding::Utf8 => match std::str::from_utf8(bytes) {
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            _ => bytes.len(),
+        },
+        LogEncoding::Latin1 => bytes.len(),
+        LogEncoding::Utf16Le | LogEncoding::Utf16Be => {
+            let even = bytes.len() & !1;
+            let last_unit = match bytes.get(even.saturating_sub(2)..even) {
+                Some([a, b]) if encoding == LogEncoding::Utf16Be => u16::from_be_bytes([*a, *b]),
+                Some([a, b]) => u16::from_le_bytes([*a, *b]),
+                _ => return even,
+            };
+            if (0xD800..=0xDBFF).contains(&last_unit) { even - 2 } else { even }
+        }
+    }
+}
+
+/// Decodes complete characters (see [`complete_len`]) without looking for a BOM. Invalid sequences are replaced by
+/// `U+FFFD`.
+pub fn decode(bytes: &[u8], encoding: LogEncoding) -> String {
+    encoding.encoding().decode_without_bom_handling(bytes).0.into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encoding_names() {
+        assert_eq!(LogEncoding::parse("UTF-8"), Some(LogEncoding::Utf8));
+        assert_eq!(LogEncoding::parse("utf_16"), Some(LogEncoding::Utf16Le));
+        assert_eq!(LogEncoding::parse("ISO-8859-1"), Some(LogEncoding::Latin1));
+        assert_eq!(LogEncoding::parse("Windows-1252"), Some(LogEncoding::Latin1));
+        assert_eq!(LogEncoding::parse("ebcdic"), None);
+    }
+
+    #[test]
+    fn forced_encodings_by_folder_win_over_the_bare_value() {
+        let forced = parse_forced("latin1, palworld=utf16be, 12=nope");
+        assert_eq!(forced.all, Some(LogEncoding::Latin1));
+        assert_eq!(forced.by_folder.get("palworld"), Some(&LogEncoding::Utf16Be));
+        assert!(!forced.by_folder.contains_key("12"));
+        assert_eq!(unknown_entries("latin1, palworld=utf16be, 12=nope"), vec!["12=nope".to_string()]);
+    }
+
+    #[test]
+    fn latin1_bytes_are_decoded() {
+        // "Zoé a dit : ça reçu ÿ" in ISO-8859-1
+        let bytes = b"Zo\xE9 a dit : \xE7a re\xE7u \xFF";
+        assert_eq!(decode(bytes, LogEncoding::Latin1), "Zoé a dit : ça reçu ÿ");
+    }
+
+    #[test]
+    fn windows_1252_characters_are_decoded() {
+        let bytes = b"\x80 5, l\x92\x9Cuvre \x85";
+        assert_eq!(decode(bytes, LogEncoding::Latin1), "€ 5, l’œuvre …");
+    }
+
+    #[test]
+    fn utf16_is_decoded_in_both_byte_orders() {
+        let le: Vec<u8> = "é🦦".encode_utf16().flat_map(u16::to_le_bytes).collect();
+        let be: Vec<u8> = "é🦦".encode_utf16().flat_map(u16::to_be_bytes).collect();
+        assert_eq!(decode(&le, LogEncoding::Utf16Le), "é🦦");
+        assert_eq!(decode(&be, LogEncoding::Utf16Be), "é🦦");
+    }
+
+    #[test]
+    fn invalid_bytes_are_replaced() {
+        assert_eq!(decode(b"a\xFFb", LogEncoding::Utf8), "a\u{FFFD}b");
+    }
+
+    #[test]
+    fn a_character_cut_at_the_end_is_not_complete() {
+        let otter = "a🦦".as_bytes();
+        assert_eq!(complete_len(&otter[..3], LogEncoding::Utf8), 1);
+        assert_eq!(complete_len(otter, LogEncoding::Utf8), otter.len());
+        assert_eq!(complete_len(b"\xE9\xE7", LogEncoding::Latin1), 2);
+
+        let le: Vec<u8> = "a🦦".encode_utf16().flat_map(u16::to_le_bytes).collect();
+        assert_eq!(complete_len(&le[..5], LogEncoding::Utf16Le), 2, "odd byte and high surrogate are kept");
+        assert_eq!(complete_len(&le, LogEncoding::Utf16Le), le.len());
+    }
+
+    #[test]
+    fn utf16_is_sniffed_from_its_nul_bytes() {
+        let le: Vec<u8> = "[10:00:00] hello".encode_utf16().flat_map(u16::to_le_bytes).collect();
+        let be: Vec<u8> = "[10:00:00] hello".encode_utf16().flat_map(u16::to_be_bytes).collect();
+        assert_eq!(sniff_utf16(&le), Some(LogEncoding::Utf16Le));
+        assert_eq!(sniff_utf16(&be), Some(LogEncoding::Utf16Be));
+        assert_eq!(sniff_utf16("[10:00:00] héllo".as_bytes()), None);
+    }
+}
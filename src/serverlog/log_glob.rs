@@ -0,0 +1,143 @@
+use std::path::{Path, PathBuf};
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use log::error;
+
+use crate::config::Config;
+
+/// Files followed when `SERVERLOG_GLOB` isn't set
+const DEFAULT_GLOB: &str = "*.log";
+
+/// Files of the log folder followed by the watcher, from `SERVERLOG_GLOB`.
+///
+/// # Patterns
+/// Comma separated globs (`*` = any characters except `/`, `**` = any folders, `?` = one character, `[ab]` / `[!ab]` = one
+/// character of / not in the class, `\` escapes the next character) :
+/// - a pattern without `/` is compared to the file name, in any subfolder (ex: `*.log`),
+/// - a pattern with `/` is compared to the path relative to the log folder (ex: `console/*.txt`).
+///
+/// The `serverlog_id` of a followed file is still resolved from its parent folder (see `ServerlogResolver`) :
+/// `console/*.txt` gives the files of a `console` folder, which needs a `[mapping]` entry unless it is a server id.
+pub struct LogFileFilter {
+    root: PathBuf,
+    by_name: GlobSet,
+    by_path: GlobSet,
+}
+
+impl LogFileFilter {
+    /// Builds the filter of `SERVERLOG_GLOB` (default `*.log`) for the files under `root`.
+    pub fn from_env(root: &Path) -> Self {
+        let globs = Config::current().serverlog_glob.clone().filter(|v| !v.trim().is_empty());
+        Self::new(root, globs.as_deref().unwrap_or(DEFAULT_GLOB))
+    }
+
+    /// Builds the filter of comma separated globs. An invalid glob is logged and ignored.
+    pub fn new(root: &Path, globs: &str) -> Self {
+        let mut by_name = GlobSetBuilder::new();
+        let mut by_path = GlobSetBuilder::new();
+        for glob in globs.split(',').map(str::trim).filter(|glob| !glob.is_empty()) {
+            let glob = glob.trim_start_matches("./");
+            match GlobBuilder::new(glob).literal_separator(true).backslash_escape(true).build() {
+                Ok(compiled) if glob.contains('/') => {
+                    by_path.add(compiled);
+                }
+                Ok(compiled) => {
+                    by_name.add(compiled);
+                }
+                Err(e) => error!("Invalid glob in SERVERLOG_GLOB '{}': {}", glob, e),
+            }
+        }
+        Self {
+            root: root.to_path_buf(),
+            by_name: by_name.build().unwrap_or_else(|_| GlobSet::empty()),
+            by_path: by_path.build().unwrap_or_else(|_| GlobSet::empty()),
+        }
+    }
+
+    /// Tells whether a file is followed.
+    pub fn matches(&self, path: &Path) -> bool {
+        let name = path.file_name().and_then(|s| s.to_str()).unwrap_or_default();
+        if self.by_name.is_match(name) {
+            return true;
+        }
+        let Some(relative) = path.strip_prefix(&self.root).ok().and_then(|p| p.to_str()) else {
+            return false;
+        };
+        self.by_path.is_match(relative)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(globs: &str) -> LogFileFilter {
+        LogFileFilter::new(Path::new("/srv/logs"), globs)
+    }
+
+    fn followed(filter: &LogFileFilter, relative: &str) -> bool {
+        filter.matches(&Path::new("/srv/logs").join(relative))
+    }
+
+    #[test]
+    fn a_pattern_without_slash_matches_the_name_in_any_folder() {
+        let filter = filter("*.log");
+        assert!(followed(&filter, "12/latest.log"));
+        assert!(followed(&filter, "12/old/2025-01-01.log"));
+        assert!(!followed(&filter, "12/latest.log.gz"));
+    }
+
+    #[test]
+    fn star_stops_at_folders_and_double_star_crosses_them() {
+        let star = filter("*/console.txt");
+        assert!(followed(&star, "12/console.txt"));
+        assert!(!followed(&star, "12/sub/console.txt"));
+
+        let double_star = filter("**/console.txt");
+        assert!(followed(&double_star, "console.txt"));
+        assert!(followed(&double_star, "12/sub/console.txt"));
+
+        let under = filter("palworld/**");
+        assert!(followed(&under, "palworld/a/b.log"));
+        assert!(!followed(&under, "minecraft/a/b.log"));
+    }
+
+    #[test]
+    fn character_classes_and_question_mark() {
+        let classes = filter("server[12].log,[!x]?.txt");
+        assert!(followed(&classes, "12/server1.log"));
+        assert!(followed(&classes, "12/server2.log"));
+        assert!(!followed(&classes, "12/server3.log"));
+        assert!(followed(&classes, "12/ab.txt"));
+        assert!(!followed(&classes, "12/xb.txt"));
+        assert!(!followed(&classes, "12/abc.txt"));
+
+        let range = filter("log-[0-9].txt");
+        assert!(followed(&range, "12/log-7.txt"));
+        assert!(!followed(&range, "12/log-a.txt"));
+    }
+
+    #[test]
+    fn escaped_and_regex_characters_are_literal() {
+        let escaped = filter(r"\[raw\]*.log");
+        assert!(followed(&escaped, "12/[raw]latest.log"));
+        assert!(!followed(&escaped, "12/rlatest.log"));
+
+        let dots = filter("latest.log");
+        assert!(!followed(&dots, "12/latestXlog"));
+        let plus = filter("a+b(1).log");
+        assert!(followed(&plus, "12/a+b(1).log"));
+    }
+
+    #[test]
+    fn an_invalid_glob_is_ignored() {
+        let filter = filter("[unclosed, *.log");
+        assert!(followed(&filter, "12/latest.log"));
+        assert!(!followed(&filter, "12/[unclosed"));
+    }
+
+    #[test]
+    fn files_outside_the_root_only_match_by_name() {
+        let filter = filter("console/*.txt");
+        assert!(!filter.matches(Path::new("/elsewhere/console/a.txt")));
+    }
+}
@@ -1,70 +1,174 @@
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::{Read, Seek, SeekFrom};
+use std::os::unix::fs::MetadataExt;
 use std::path::PathBuf;
-use std::sync::mpsc::channel;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use std::sync::{LazyLock, RwLock};
+use chrono::{DateTime, Utc};
 use std::thread;
+use std::time::{Duration, Instant};
 use colored::Colorize;
 use log::{debug, error, info, warn};
-use regex::Regex;
-use serde::Deserialize;
 
 use notify::{
-    Config as NotifyConfig, Event, Error as NotifyError, RecommendedWatcher, RecursiveMode, Watcher,
+    Config as NotifyConfig, Event, Error as NotifyError, PollWatcher, RecommendedWatcher, RecursiveMode, Watcher,
 };
 
-use crate::helper::webhook_discord;
+use crate::config::Config;
+use crate::helper::{metrics, webhook_discord};
 use crate::serverlog;
+use crate::serverlog::actions::ActionOptions;
+use crate::serverlog::serverlog_resolver::ServerlogResolver;
+use crate::serverlog::file_lifecycle::{self, FileEvent, FileLifecycle};
+use crate::serverlog::log_encoding::{self, LogEncoding};
+use crate::serverlog::log_glob::LogFileFilter;
+use crate::serverlog::file_cursor::FileCursor;
+use crate::serverlog::offset_store::{self, FileOffset, OffsetStore};
+use crate::serverlog::triggers::{self, Triggers};
+use crate::serverlog::{default_triggers, line_timestamp, processing_lag, self_guard, trigger_tuning};
 
-/// Decodes a sequence of bytes into a `String`, attempting to interpret the input as UTF-8 or UTF-16 with a fallback mechanism.
+/// Last turn of the watcher loop (it turns at least every second), unix seconds, 0 before it starts
+static LAST_HEARTBEAT: AtomicI64 = AtomicI64::new(0);
+/// Last file event handled, unix seconds, 0 if none yet
+static LAST_EVENT: AtomicI64 = AtomicI64::new(0);
+
+/// Returns the last turn of the watcher loop, `None` before it starts.
+pub fn last_heartbeat() -> Option<DateTime<Utc>> {
+    stored_time(&LAST_HEARTBEAT)
+}
+
+/// Returns when the watcher last handled a file event, `None` if it hasn't yet.
+pub fn last_event() -> Option<DateTime<Utc>> {
+    stored_time(&LAST_EVENT)
+}
+
+fn stored_time(timestamp: &AtomicI64) -> Option<DateTime<Utc>> {
+    let secs = timestamp.load(Ordering::Relaxed);
+    if secs > 0 { DateTime::from_timestamp(secs, 0) } else { None }
+}
+
+/// Names of the triggers loaded by the watcher (`function` when a trigger has no name)
+static LOADED_TRIGGERS: LazyLock<RwLock<Vec<String>>> = LazyLock::new(|| RwLock::new(Vec::new()));
+
+/// Returns the names of the triggers loaded by the watcher, empty before it starts.
+pub fn loaded_trigger_names() -> Vec<String> {
+    LOADED_TRIGGERS.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+
+/// Set by [`request_triggers_reload`], read by the watcher loop
+static TRIGGERS_RELOAD_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Asks the watcher to reload its triggers at its next turn (ex: on SIGHUP).
+pub fn request_triggers_reload() {
+    TRIGGERS_RELOAD_REQUESTED.store(true, Ordering::Relaxed);
+}
+
+/// Returns the interval between two reloads of the triggers (`TRIGGERS_REFRESH_SEC`, Default 60, 0 = never).
+fn triggers_refresh_interval() -> Option<Duration> {
+    let secs = Config::current().triggers_refresh_sec;
+    (secs > 0).then(|| Duration::from_secs(secs))
+}
+
+/// A collection is sent with the lines it has if the file stays silent for this long
+const COLLECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Polling interval when the watcher of the OS can't be started and `SERVERLOG_POLL_INTERVAL_MS` isn't set
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// Shortest delay between two saves of the read positions
+const POSITIONS_SAVE_INTERVAL: Duration = Duration::from_secs(1);
+/// Most mcmyadmin messages sent for one read : the oldest lines of a bigger burst aren't mirrored
+const MIRROR_MAX_MESSAGES_PER_READ: usize = 3;
+
+/// Lines being collected after a multi-line trigger matched (ex: a crash report and its stacktrace).
+struct PendingCollection {
+    serverlog_id: u32,
+    function: String,
+    captures: serverlog::actions::TriggerCaptures,
+    options: ActionOptions,
+    lines: Vec<String>,
+    remaining: usize,
+    last_update: Instant,
+}
+
+impl PendingCollection {
+    /// Calls the action with every collected line, joined by new lines.
+    fn dispatch(self) {
+        serverlog::actions::dispatch(&self.function, &self.lines.join("\n"), self.serverlog_id, &self.captures, &self.options);
+    }
+}
+
+/// Decodes a log file, or a chunk of it, into a `String`. An incomplete character at the end is dropped,
+/// see [`decode_log_chunk`].
+pub(crate) fn decode_log_bytes(bytes: &[u8], forced: Option<LogEncoding>) -> String {
+    decode_log_chunk(bytes, forced).0
+}
+
+/// Decodes a chunk of a log file into a `String`.
 ///
 /// # Parameters
-/// - `bytes`: A byte slice (`&[u8]`) representing the encoded data to decode.
+/// - `bytes`: The bytes read from the log file.
+/// - `forced`: The encoding forced for the file by `SERVERLOG_ENCODING`, `None` to detect it.
 ///
 /// # Returns
-/// - A `String` containing the decoded text. If the input bytes are empty, it returns an empty string. If decoding fails, a lossy UTF-8 representation of the input bytes is returned.
+/// The decoded text, and the number of bytes it consumed. A character cut at the end of the chunk (odd byte or
+/// high surrogate in UTF-16, incomplete sequence in UTF-8) isn't consumed : it is read again with the next chunk.
 ///
 /// # Behavior
 /// 1. If the input `bytes` is empty, an empty string is returned.
-/// 2. If the input can successfully be decoded as UTF-8, that string is returned.
-/// 3. If decoding as UTF-8 fails, the function attempts to decode as UTF-16:
-///    - If the first two bytes match the UTF-16 little-endian byte order mark (BOM, `0xFFFE`), they are skipped.
-///    - If the length of the slice is odd (not divisible by 2), the last trailing byte is removed to align pairs of bytes for UTF-16 decoding.
-///    - The byte pairs are interpreted as UTF-16 little-endian code units, converted into a `String`.
-/// 4. If the UTF-16 decoding fails, the function finally falls back to a lossy UTF-8 representation of the input bytes for the output.
+/// 2. A forced encoding is used as is.
+/// 3. A UTF-16 byte order mark (`FF FE` little-endian, `FE FF` big-endian) is skipped and gives the byte order.
+/// 4. Without BOM, a chunk with many NUL bytes is UTF-16, its byte order is guessed from where they are
+///    (a chunk read after the start of the file has no BOM).
+/// 5. Otherwise, valid UTF-8 is returned as is, and anything else is decoded as latin-1 / Windows-1252.
 ///
-fn decode_log_bytes(bytes: &[u8]) -> String {
+/// The bytes are decoded by `encoding_rs`, invalid sequences are replaced by `U+FFFD`.
+pub(crate) fn decode_log_chunk(bytes: &[u8], forced: Option<LogEncoding>) -> (String, usize) {
     if bytes.is_empty() {
-        return String::new();
-    }
-    if let Ok(s) = std::str::from_utf8(bytes) {
-        return s.to_string();
+        return (String::new(), 0);
     }
-    let mut slice = bytes;
-    if slice.len() >= 2 && slice[0] == 0xFF && slice[1] == 0xFE {
-        slice = &slice[2..];
-    }
-    if slice.len() < 2 {
-        return String::new();
-    }
-    if slice.len() % 2 != 0 {
-        slice = &slice[..slice.len() - 1];
-    }
-    let code_units: Vec<u16> = slice
-        .chunks_exact(2)
-        .map(|c| u16::from_le_bytes([c[0], c[1]]))
-        .collect();
-    String::from_utf16(&code_units).unwrap_or_else(|_| String::from_utf8_lossy(bytes).to_string())
+    let (encoding, content) = match (forced, bytes) {
+        (Some(encoding), _) => (encoding, strip_bom(bytes, encoding)),
+        (None, [0xFF, 0xFE, rest @ ..]) => (LogEncoding::Utf16Le, rest),
+        (None, [0xFE, 0xFF, rest @ ..]) => (LogEncoding::Utf16Be, rest),
+        (None, _) => match log_encoding::sniff_utf16(bytes) {
+            Some(encoding) => (encoding, bytes),
+            None => match std::str::from_utf8(bytes) {
+                Ok(s) => return (s.to_string(), bytes.len()),
+                // Only a sequence cut at the end : the chunk is UTF-8
+                Err(e) if e.error_len().is_none() => (LogEncoding::Utf8, bytes),
+                Err(_) => (LogEncoding::Latin1, bytes),
+            },
+        },
+    };
+    let bom_len = bytes.len() - content.len();
+    let content = &content[..log_encoding::complete_len(content, encoding)];
+    (log_encoding::decode(content, encoding), bom_len + content.len())
+}
+
+/// Skips the byte order mark of a forced encoding, if the chunk starts with it.
+fn strip_bom(bytes: &[u8], encoding: LogEncoding) -> &[u8] {
+    let bom: &[u8] = match encoding {
+        LogEncoding::Utf8 => &[0xEF, 0xBB, 0xBF],
+        LogEncoding::Utf16Le => &[0xFF, 0xFE],
+        LogEncoding::Utf16Be => &[0xFE, 0xFF],
+        LogEncoding::Latin1 => &[],
+    };
+    bytes.strip_prefix(bom).unwrap_or(bytes)
 }
 
 /// This function monitors a folder for `.log` files using file system notifications.
-/// It prints the content of newly created or modified `.log` files and tracks the last
-/// read position in the file to ensure only new additions are read subsequently. Deleted
+/// Every complete line appended to a `.log` file is matched against the triggers, and the
+/// last read position of each file is tracked so only new additions are read subsequently. Deleted
 /// `.log` files are also handled by removing them from the internal tracking state.
+/// New log files, and log files removed without being recreated by a rotation, are reported in an admin embed.
 ///
 /// # Arguments
 ///
 /// * `folder` - A string slice representing the path to the folder that should be watched for changes.
+/// * `shutdown` - Stops the watcher once a message is received : the pending collections are sent
+///   and the read positions saved before it returns.
 ///
 /// # Returns
 ///
@@ -74,10 +178,15 @@ fn decode_log_bytes(bytes: &[u8]) -> String {
 ///
 /// # Behavior
 ///
-/// 1. Loads the triggers from the `triggers.toml` file.
-/// 2. Reads the initial content of all `.log` files within the folder upon starting.
-/// 3. Listens for file system events, such as creation, modification, or deletion of `.log` files.
-/// - For created or modified `.log` files, it prints the new content appended to the files.
+/// 1. Loads the triggers from the `triggers.toml` file, or the built-in defaults if it doesn't exist.
+/// 2. Restores the saved read positions, if enabled. The `.log` files already there without one are read from
+///    their end, or replayed from their start upon starting if `SERVERLOG_REPLAY_ON_START=true`.
+/// 3. Listens for file system events, such as creation, modification, or deletion of `.log` files
+///    (polled every `SERVERLOG_POLL_INTERVAL_MS` if set, or if the events of the OS are unavailable).
+///
+/// The followed files are the ones matching `SERVERLOG_GLOB` (default `*.log`, see [`LogFileFilter`]), both in the
+/// initial scan and in the events; the other files get no position.
+/// - For created or modified `.log` files, every complete line appended is handled in order (see [`read_new`]).
 /// - Removes deleted `.log` files from the tracking state.
 /// - Handles errors, such as unable to read a file or watcher errors, and retries the watcher.
 ///
@@ -88,75 +197,168 @@ fn decode_log_bytes(bytes: &[u8]) -> String {
 /// - If the specified folder does not exist, it returns a generic `NotifyError`.
 /// - Any errors inherent to `notify` library operations, such as watcher setup or event handling, are returned.
 ///
-pub fn watch_serverlogs(folder: &str) -> Result<(), NotifyError> {
+pub fn watch_serverlogs(folder: &str, shutdown: Receiver<()>) -> Result<(), NotifyError> {
     let folder = PathBuf::from(folder);
     if !folder.exists() { // We check that the folder exists
         return Err(NotifyError::generic(&format!("Folder {} does not exist", folder.display())));
     }
 
-    // Load the triggers from the triggers.toml file at the root of the project
-    #[derive(Deserialize)]
-    struct Trigger { name: Option<String>, pattern: String, function: String, serverlog_ids: Option<Vec<u32>> }
-    #[derive(Deserialize)]
-    struct TriggerFile { trigger: Vec<Trigger> }
-
-    let compiled_triggers: Vec<(Regex, String, Option<Vec<u32>>)> = (|| {
-        let content = std::fs::read_to_string("triggers.toml").ok()?;
-        let tf: TriggerFile = toml::from_str(&content).ok()?;
-        let mut out = Vec::new();
-        for t in tf.trigger {
-            match Regex::new(&t.pattern) {
-                Ok(re) => out.push((re, t.function, t.serverlog_ids)),
-                Err(e) => error!(
-                    "Invalid regex in trigger '{}': {} ({})",
-                    t.name.unwrap_or_default(),
-                    t.pattern,
-                    e
-                ),
+    // Load the triggers of the database and of the triggers.toml file at the root of the project
+    let mut loaded = triggers::load_with_database(default_triggers::TRIGGERS_PATH);
+    *LOADED_TRIGGERS.write().unwrap_or_else(|e| e.into_inner()) = loaded.compiled.iter().map(|t| t.name.clone()).collect();
+    let triggers_refresh = triggers_refresh_interval();
+    let mut triggers_loaded_at = Instant::now();
+
+    // Resolves each log file to its serverlog_id, using the [mapping] section if present
+    let mut resolver = ServerlogResolver::new(loaded.mapping.clone());
+
+    // Files followed, from SERVERLOG_GLOB
+    let filter = LogFileFilter::from_env(&folder);
+
+    // Maps each file path to its last read offset by storing its byte position
+    let mut positions: HashMap<PathBuf, FileCursor> = HashMap::new();
+
+    // Positions saved before the last stop, if enabled : files still as long are read from there
+    // A file recreated since (shorter, or another inode) is read again from its start
+    let offset_store = offset_store::configured_store();
+    if let Some(store) = &offset_store {
+        match store.load() {
+            Ok(saved) => {
+                let (restored, recreated) = restore_positions(saved, &filter);
+                positions = restored;
+                info!(
+                    "{} log positions restored ({} files recreated, read from their start)",
+                    positions.len().to_string().green().bold(),
+                    recreated
+                );
             }
+            Err(e) => error!("Could not restore the log positions, files are read as new: {}", e),
         }
-        Some(out)
-    })().unwrap_or_else(|| {
-        error!("No triggers loaded (missing or invalid triggers.toml)");
-        Vec::new()
-    });
+    }
+
+    // Files already there without a saved position : skipped to their end, unless SERVERLOG_REPLAY_ON_START=true
+    let replay_on_start = Config::current().serverlog_replay_on_start;
+    let mut startup_collections: HashMap<PathBuf, PendingCollection> = HashMap::new();
+    // Last action of each trigger with a cooldown, by trigger name and serverlog_id
+    let mut cooldowns: HashMap<(String, u32), Instant> = HashMap::new();
+    let skipped = position_existing_logs(existing_logs(&folder, &filter), replay_on_start, &mut positions, &mut resolver, &loaded, &mut startup_collections, &mut cooldowns);
+    if skipped > 0 {
+        info!("{} existing log files read from their end", skipped.to_string().green().bold());
+    }
+    let mut positions_dirty = replay_on_start || skipped > 0;
+    let mut positions_saved_at = Instant::now();
+
+    // Multi-line collections in progress, by file
+    let mut collections = startup_collections;
+
+    // Files removed recently, to tell a rotation from a removal
+    let mut lifecycle = FileLifecycle::new();
 
-    // Maps each file path to its last read offset by storing its byte position
-    let mut positions: HashMap<PathBuf, u64> = HashMap::new();
-    
     // Create the watcher and start watching the folder
+    // Both watchers send the same events, read by the loop below
     let (tx, rx) = channel::<Result<Event, NotifyError>>();
-    let mut watcher: RecommendedWatcher = RecommendedWatcher::new(move |res| {
-        // Ignore if the watcher thread panics
-        let _ = tx.send(res);
-    }, NotifyConfig::default())?;
-
-    // Watch the folder for changes
-    watcher.watch(&folder, RecursiveMode::Recursive)?;
+    let _watcher = start_watcher(&folder, tx)?;
 
     // Loop forever, reading new content of log files as they are appended
-    info!("Watching folder {} for .log changes with {} triggers", folder.display().to_string().green().bold(), compiled_triggers.len().to_string().green().bold());
+    info!("Watching folder {} for .log changes with {} triggers", folder.display().to_string().green().bold(), loaded.compiled.len().to_string().green().bold());
     loop {
-        match rx.recv() {
+        LAST_HEARTBEAT.store(Utc::now().timestamp(), Ordering::Relaxed);
+
+        // On shutdown, nothing collected or read is lost
+        if shutdown.try_recv().is_ok() {
+            for (_, collection) in collections.drain() {
+                collection.dispatch();
+            }
+            if let Some(store) = &offset_store {
+                save_positions(store.as_ref(), &positions);
+            }
+            info!("Log watcher stopped");
+            return Ok(());
+        }
+
+        // Triggers edited in the database or in triggers.toml are taken into account without restart
+        let now = Instant::now();
+        let reload_requested = TRIGGERS_RELOAD_REQUESTED.swap(false, Ordering::Relaxed);
+        if reload_requested || triggers_refresh.is_some_and(|every| now.duration_since(triggers_loaded_at) >= every) {
+            let reloaded = triggers::load_with_database(default_triggers::TRIGGERS_PATH);
+            if reloaded.mapping != loaded.mapping {
+                resolver = ServerlogResolver::new(reloaded.mapping.clone());
+            }
+            let names: Vec<String> = reloaded.compiled.iter().map(|t| t.name.clone()).collect();
+            if reload_requested || names != loaded_trigger_names() {
+                info!("{} triggers loaded", names.len().to_string().green().bold());
+            }
+            *LOADED_TRIGGERS.write().unwrap_or_else(|e| e.into_inner()) = names;
+            loaded = reloaded;
+            triggers_loaded_at = now;
+        }
+
+        // Collections whose file stays silent are sent with the lines they have
+        let timed_out: Vec<PathBuf> = collections
+            .iter()
+            .filter(|(_, c)| now.duration_since(c.last_update) >= COLLECT_TIMEOUT)
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in timed_out {
+            if let Some(collection) = collections.remove(&path) {
+                collection.dispatch();
+            }
+        }
+
+        // Removed files not recreated in time weren't rotated
+        for notice in lifecycle.expired(now) {
+            file_lifecycle::report(&notice);
+        }
+
+        // Positions are saved at most once per interval, not after every line
+        if let Some(store) = &offset_store
+            && positions_dirty && now.duration_since(positions_saved_at) >= POSITIONS_SAVE_INTERVAL
+        {
+            save_positions(store.as_ref(), &positions);
+            positions_dirty = false;
+            positions_saved_at = now;
+        }
+
+        match rx.recv_timeout(Duration::from_secs(1)) {
             Ok(Ok(event)) => {
-                for path in &event.paths { // For each file that changed...
-                    if path.extension().and_then(|s| s.to_str()) != Some("log") { // ...if it's not a log file, ignore it
+                LAST_EVENT.store(Utc::now().timestamp(), Ordering::Relaxed);
+                for (path_index, path) in event.paths.iter().enumerate() { // For each file that changed...
+                    if !filter.matches(path) { // ...if it's not a followed log file, ignore it
                         continue;
                     }
 
                     use notify::event::EventKind;
 
+                    // New files and files removed without being recreated (not a rotation) are reported
+                    let file_event = file_lifecycle::classify(&event.kind, path_index);
+                    if file_event == Some(FileEvent::Created) {
+                        let known = positions.contains_key(path);
+                        let serverlog_id = if known { None } else { resolver.resolve(path) };
+                        if let Some(notice) = lifecycle.on_event(path, FileEvent::Created, known, serverlog_id, Instant::now()) {
+                            file_lifecycle::report(&notice);
+                        }
+                    }
+
                     match &event.kind {
+                        // When a .log file is removed (or renamed by a rotation), we remove it from the position map
+                        _ if file_event == Some(FileEvent::Removed) => {
+                            let serverlog_id = resolver.resolve(path);
+                            lifecycle.on_event(path, FileEvent::Removed, true, serverlog_id, Instant::now());
+                            positions.remove(path);
+                            positions_dirty = true;
+                            resolver.forget(path);
+                            if let Some(collection) = collections.remove(path) {
+                                collection.dispatch();
+                            }
+                            processing_lag::forget(path);
+                            debug!("File removed: {}", path.display());
+                        }
                         // When a .log file is created or modified, we read its new content
                         EventKind::Create(_) | EventKind::Modify(_) => {
-                            if let Err(e) = read_new(path, &mut positions, &compiled_triggers) {
+                            if let Err(e) = read_new(path, &mut positions, &mut resolver, &loaded, &mut collections, &mut cooldowns) {
                                 error!("Error reading {}: {}", path.display(), e);
                             }
-                        }
-                        // When a .log file is removed, we remove it from the position map
-                        EventKind::Remove(_) => {
-                            positions.remove(path);
-                            warn!("File removed: {}", path.display());
+                            positions_dirty = true;
                         }
                         _ => {}
                     }
@@ -164,15 +366,140 @@ pub fn watch_serverlogs(folder: &str) -> Result<(), NotifyError> {
             }
             // The file was read, but an error occurred
             Ok(Err(e)) => error!("Watcher error: {}", e),
+            // No event during the last second
+            Err(RecvTimeoutError::Timeout) => {}
             // The file could not be read
             Err(e) => {
                 error!("Watcher channel receive error: {}", e);
                 const WAIT_TIME: u64 = 1;
                 info!("Retrying in {} second...", WAIT_TIME.to_string().green().bold());
-                thread::sleep(std::time::Duration::from_secs(WAIT_TIME));
+                thread::sleep(Duration::from_secs(WAIT_TIME));
+            }
+        }
+    }
+}
+
+/// Starts watching `folder`, its events being sent to `tx`.
+///
+/// Uses polling every `SERVERLOG_POLL_INTERVAL_MS` when set (NFS mounts and some containers get no inotify event),
+/// otherwise the watcher of the OS, polling every `DEFAULT_POLL_INTERVAL` if it can't be started.
+fn start_watcher(folder: &std::path::Path, tx: Sender<Result<Event, NotifyError>>) -> Result<Box<dyn Watcher>, NotifyError> {
+    let poll_interval = Some(Config::current().serverlog_poll_interval_ms)
+        .filter(|ms| *ms > 0)
+        .map(Duration::from_millis);
+
+    let poll_tx = tx.clone();
+    let poll = move |interval: Duration| -> Result<Box<dyn Watcher>, NotifyError> {
+        let mut watcher = PollWatcher::new(
+            move |res| {
+                let _ = poll_tx.send(res);
+            },
+            NotifyConfig::default().with_poll_interval(interval),
+        )?;
+        watcher.watch(folder, RecursiveMode::Recursive)?;
+        info!("Polling {} every {} ms", folder.display(), interval.as_millis());
+        Ok(Box::new(watcher))
+    };
+    if let Some(interval) = poll_interval {
+        return poll(interval);
+    }
+
+    let recommended = RecommendedWatcher::new(
+        move |res| {
+            // Ignore if the watcher thread panics
+            let _ = tx.send(res);
+        },
+        NotifyConfig::default(),
+    )
+    .and_then(|mut watcher| watcher.watch(folder, RecursiveMode::Recursive).map(|_| watcher));
+    match recommended {
+        Ok(watcher) => Ok(Box::new(watcher)),
+        Err(e) => {
+            warn!("File system events unavailable ({}), falling back to polling", e);
+            poll(DEFAULT_POLL_INTERVAL)
+        }
+    }
+}
+
+/// Positions the files already there at startup that have no saved position : replayed from their start if
+/// `replay` (`SERVERLOG_REPLAY_ON_START=true`), otherwise skipped to their end so their old lines aren't handled again.
+///
+/// # Returns
+/// The number of files skipped to their end.
+fn position_existing_logs(paths: Vec<PathBuf>, replay: bool, positions: &mut HashMap<PathBuf, FileCursor>, resolver: &mut ServerlogResolver, loaded: &Triggers, collections: &mut HashMap<PathBuf, PendingCollection>, cooldowns: &mut HashMap<(String, u32), Instant>) -> usize {
+    let mut skipped = 0;
+    for path in paths {
+        if positions.contains_key(&path) {
+            continue;
+        }
+        if replay {
+            if let Err(e) = read_new(&path, positions, resolver, loaded, collections, cooldowns) {
+                error!("Error reading {}: {}", path.display(), e);
             }
+        } else if let Ok(metadata) = std::fs::metadata(&path) {
+            positions.insert(path, FileCursor::at(metadata.len()));
+            skipped += 1;
         }
     }
+    skipped
+}
+
+/// Returns the files of `folder` and its subfolders followed by the watcher.
+fn existing_logs(folder: &std::path::Path, filter: &LogFileFilter) -> Vec<PathBuf> {
+    let mut logs = Vec::new();
+    let mut folders = vec![folder.to_path_buf()];
+    while let Some(dir) = folders.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for path in entries.flatten().map(|entry| entry.path()) {
+            if path.is_dir() {
+                folders.push(path);
+            } else if filter.matches(&path) {
+                logs.push(path);
+            }
+        }
+    }
+    logs
+}
+
+/// Returns the cursors of the saved positions still usable, and the number of files recreated since they were saved
+/// (shorter than their position, or another inode) : those are read again from their start.
+/// Files gone or no longer followed are forgotten.
+fn restore_positions(saved: HashMap<PathBuf, FileOffset>, filter: &LogFileFilter) -> (HashMap<PathBuf, FileCursor>, usize) {
+    let mut positions = HashMap::new();
+    let mut recreated = 0;
+    for (path, saved) in saved {
+        // A file excluded since by SERVERLOG_GLOB is forgotten
+        if !filter.matches(&path) {
+            continue;
+        }
+        let Ok(metadata) = std::fs::metadata(&path) else {
+            continue;
+        };
+        if metadata.len() < saved.offset || (saved.inode != 0 && metadata.ino() != saved.inode) {
+            positions.insert(path, FileCursor::at(0));
+            recreated += 1;
+        } else {
+            positions.insert(path, FileCursor::at(saved.offset));
+        }
+    }
+    (positions, recreated)
+}
+
+/// Saves the read positions, a failure is only logged : the watcher keeps going.
+/// The position of a file is the bytes consumed when it was last read : a line not ended yet is lost on a restart.
+fn save_positions(store: &dyn OffsetStore, positions: &HashMap<PathBuf, FileCursor>) {
+    let offsets = positions
+        .iter()
+        .map(|(path, cursor)| {
+            let inode = std::fs::metadata(path).map(|m| m.ino()).unwrap_or(0);
+            (path.clone(), FileOffset { offset: cursor.offset, len: cursor.offset, inode })
+        })
+        .collect();
+    if let Err(e) = store.save(&offsets) {
+        error!("Could not save the log positions: {}", e);
+    }
 }
 
 /// Reads the newly appended content from a file starting from the last known position.
@@ -180,8 +507,10 @@ pub fn watch_serverlogs(folder: &str) -> Result<(), NotifyError> {
 ///
 /// # Arguments
 /// - `path`: A `PathBuf` reference representing the path of the file to read from.
-/// - `positions`: A mutable reference to a `HashMap` that tracks the last read position of each file.
-///   The key is the `PathBuf` of the file, and the value is the last read byte position (`u64`).
+/// - `positions`: The read state of each file (offset, and start of a line not ended yet), see [`FileCursor`].
+/// - `resolver`: The `ServerlogResolver` giving the `serverlog_id` of the file.
+/// - `loaded`: The triggers and ignores of `triggers.toml`.
+/// - `cooldowns`: The last action of each trigger with a `cooldown_secs`, by trigger name and `serverlog_id`.
 ///
 /// # Returns
 /// Returns a `Result`:
@@ -189,90 +518,477 @@ pub fn watch_serverlogs(folder: &str) -> Result<(), NotifyError> {
 /// - `Err(std::io::Error)`: If there is an error during file operations such as opening, seeking, or reading.
 ///
 /// # Behavior
-/// 1. Opens the file at the specified `path`.
-/// 2. Retrieves the metadata of the file, including its size.
-/// 3. Uses the last known position from the `positions` map to determine where to start reading:
-///     - If the file's length is less than the last known position, it assumes the file was
-///       truncated or rotated, resets the position to the start of the file, and logs this to `stderr`.
-/// 4. Seeks to the determined position in the file and reads the content from there.
-/// 5. Displays the newly appended content (if any) to `stdout` prefixed with metadata.
-/// 6. Updates the position in the `positions` map with the new seek position after reading.
-///
-/// # Notes
-/// - The function assumes that the file may be appended over time and reads any new content since the last recorded position.
-/// - Handles log rotation or truncation scenarios by resetting the read position to the start of the file.
+/// 1. Opens the file at the specified `path`, and reads its size and its first bytes.
+/// 2. Starts the file over if it was replaced since the last read (see [`FileCursor::rewind_if_replaced`]) :
+///    truncated or rotated, or emptied then written again longer than the offset.
+/// 3. Reads and decodes the content from the offset, and splits it into complete lines (see [`FileCursor::consume`]).
+///    A line not ended yet is kept in the cursor and completed by the next read.
+/// 4. Handles every complete line in order : feeds it to the collection in progress, then matches the triggers
+///    against it (see [`match_line`]).
+/// 5. Mirrors the lines to mcmyadmin in a few batched messages (see [`mirror_messages`]), unless they were read
+///    to catch up : a file seen for the first time or read again from its start.
 ///
-fn read_new(path: &PathBuf, positions: &mut HashMap<PathBuf, u64>, compiled_triggers: &[(Regex, String, Option<Vec<u32>>)]) -> std::io::Result<()> {
+fn read_new(path: &PathBuf, positions: &mut HashMap<PathBuf, FileCursor>, resolver: &mut ServerlogResolver, loaded: &Triggers, collections: &mut HashMap<PathBuf, PendingCollection>, cooldowns: &mut HashMap<(String, u32), Instant>) -> std::io::Result<()> {
     let mut f = File::open(path)?;
-    let metadata = f.metadata()?;
-    let len = metadata.len();
-    let last = positions.get(path).cloned().unwrap_or(0);
+    let len = f.metadata()?.len();
+    let mut head = vec![0; FileCursor::head_len(len)];
+    f.read_exact(&mut head)?;
 
-    // Check if the file was truncated or rotated
-    if len < last {
-        // Truncated or rotated; reset the position to the start of the file
+    // A file seen for the first time is read from its start
+    let known = positions.contains_key(path);
+    let cursor = positions.entry(path.clone()).or_default();
+    let replaced = cursor.rewind_if_replaced(len, &head);
+    if replaced {
         warn!("File {} was truncated/rotated; reading from start", path.display());
-        positions.insert(path.clone(), 0);
     }
+    // A rotated file or a file seen for the first time is read from its start : its lines are old
+    let catch_up = replaced || !known;
 
     // Seek to the last known position and read the new content
-    let start = positions.get(path).cloned().unwrap_or(0);
-    f.seek(SeekFrom::Start(start))?;
-
-    // Read the new content (bytes)
+    f.seek(SeekFrom::Start(cursor.offset))?;
     let mut bytes = Vec::new();
     f.read_to_end(&mut bytes)?;
-    let buf = decode_log_bytes(&bytes);
-
-    // Only proceed if there is any new text
-    if !buf.is_empty() {
-        // Get serverlog_id from file name once
-        let serverlog_id = path
-            .parent()
-            .and_then(|p| p.file_name())
-            .and_then(|s| s.to_str())
-            .and_then(|s| s.parse::<u32>().ok());
-
-        // Decide which line to use:
-        // - If chunk ends with newline => use last line.
-        // - Else => use the previous line (last complete).
-        let ends_with_newline = buf.ends_with('\n') || buf.ends_with("\r\n");
-        let mut lines = buf.lines().rev();
-
-        let last_line = if ends_with_newline {
-            lines.next()
+    let (chunk, consumed) = decode_log_chunk(&bytes, log_encoding::forced_for(path));
+    let lines = cursor.consume(consumed, &chunk);
+    metrics::record_log_lines(&path.display().to_string(), lines.len() as u64);
+
+    // Only proceed if a line was completed
+    if lines.is_empty() {
+        return Ok(());
+    }
+    // Get serverlog_id from the folder name or the [mapping] section once
+    let serverlog_id = resolver.resolve(path);
+
+    for line in &lines {
+        // Feed the line to the collection in progress for this file, if any
+        if let Some(collection) = collections.get_mut(path) {
+            collection.lines.push(line.clone());
+            collection.remaining -= 1;
+            collection.last_update = Instant::now();
+            if collection.remaining == 0
+                && let Some(collection) = collections.remove(path)
+            {
+                collection.dispatch();
+            }
+        }
+
+        let Some(id) = serverlog_id else {
+            continue;
+        };
+        // Check first '['. If found, cut string starting there.
+        let cleaned_line = triggers::clean_line(line);
+        debug!("{}", path.display().to_string().green().bold());
+        debug!("{}", cleaned_line.to_string().bright_blue().italic());
+
+        // Track how far behind real time we are, except on catch-up reads
+        if !catch_up
+            && let Some(line_ts) = line_timestamp::parse_line_timestamp(cleaned_line)
+        {
+            processing_lag::record(path, line_ts, chrono::Local::now().naive_local());
+        }
+
+        // The game of the server is only looked up (once per server) if a trigger filters on it
+        let game = if loaded.need_game() {
+            resolver.game_of(id)
         } else {
-            lines.nth(1) // skip the trailing partial, take previous
+            None
         };
+        match_line(path, cleaned_line, id, game.as_deref(), loaded, collections, cooldowns);
+    }
 
-        if let (Some(id), Some(line)) = (serverlog_id, last_line) {
-            // Check first '['. If found, cut string starting there.
-            let start_index = line.find('[').unwrap_or(0);
-            let cleaned_line = &line[start_index..];
-
-            // Debug: print only the last line for visibility
-            debug!("{} (last line)", path.display().to_string().green().bold());
-            debug!("{}", cleaned_line.to_string().bright_blue().italic());
-
-            // Send line to mcmyadmin
-            let _ = webhook_discord::send_discord_message(webhook_discord::get_webhook_mcmyadmin_by_server_id(serverlog_id), line);
-
-            // Match triggers only against the last (complete) line
-            for (re, func, ids_opt) in compiled_triggers {
-                // Using cleaned line and not line
-                if re.is_match(cleaned_line) {
-                    if ids_opt.as_ref().map(|ids| ids.contains(&id)).unwrap_or(true) {
-                        // Envoie la ligne nettoyée ou l'originale selon ton besoin
-                        serverlog::actions::dispatch(func, cleaned_line, id);
-                    }
-                }
+    // Send the new lines to mcmyadmin, the old lines of a catch-up read would flood it
+    if serverlog_id.is_some() && !catch_up {
+        let (messages, dropped) = mirror_messages(&lines, webhook_discord::DISCORD_CONTENT_MAX_CHARS, MIRROR_MAX_MESSAGES_PER_READ);
+        if dropped > 0 {
+            debug!("{} lines of {} not mirrored to mcmyadmin", dropped, path.display());
+        }
+        let identity = webhook_discord::get_webhook_mcmyadmin_by_server_id(serverlog_id);
+        for message in messages {
+            let _ = webhook_discord::send_discord_message(identity, &message, None, None, None);
+        }
+    }
+
+    Ok(())
+}
+
+/// Packs the lines of a read into at most `max_messages` messages of `max_chars`, one line per row.
+/// The most recent lines are kept : the oldest ones of a bigger burst are left out, and their number returned.
+/// A line longer than `max_chars` gets a message of its own, cut when it is sent.
+fn mirror_messages(lines: &[String], max_chars: usize, max_messages: usize) -> (Vec<String>, usize) {
+    let mut messages: Vec<Vec<&str>> = Vec::new();
+    let mut current_chars = 0;
+    for (kept, line) in lines.iter().rev().enumerate() {
+        let chars = line.chars().count();
+        if let Some(message) = messages.last_mut()
+            && current_chars + 1 + chars <= max_chars
+        {
+            message.push(line);
+            current_chars += 1 + chars;
+            continue;
+        }
+        if messages.len() == max_messages {
+            return (pack(messages), lines.len() - kept);
+        }
+        messages.push(vec![line]);
+        current_chars = chars;
+    }
+    (pack(messages), 0)
+}
+
+/// Puts the messages and their rows, gathered from the most recent line, back in the order of the file
+fn pack(messages: Vec<Vec<&str>>) -> Vec<String> {
+    messages
+        .into_iter()
+        .rev()
+        .map(|rows| rows.into_iter().rev().collect::<Vec<_>>().join("\n"))
+        .collect()
+}
+
+/// Matches the triggers against a cleaned line of a server, and calls their actions (or starts collecting the
+/// following lines of the file).
+fn match_line(path: &PathBuf, cleaned_line: &str, id: u32, game: Option<&str>, loaded: &Triggers, collections: &mut HashMap<PathBuf, PendingCollection>, cooldowns: &mut HashMap<(String, u32), Instant>) {
+    // Lines written by Otternel itself through RCON only reach triggers allowing it, to avoid loops
+    let from_self = self_guard::is_self_line(cleaned_line);
+    if from_self {
+        debug!("Line comes from Otternel itself, only triggers with allow_self are evaluated");
+        self_guard::record_skipped();
+    }
+
+    // Match triggers once the ignores are checked. Named groups of the trigger are given to the action
+    let matches = loaded.matching(cleaned_line, id, game, from_self);
+    let ignored = matches.is_none();
+    if ignored {
+        debug!("Line dropped by an [[ignore]] of triggers.toml");
+    }
+    let matches = matches.unwrap_or_default();
+    let matched = !matches.is_empty();
+    for triggers::TriggerMatch { trigger, captures } in matches {
+        metrics::record_trigger_match(&trigger.function);
+        if let Some(cooldown) = trigger.cooldown {
+            let key = (trigger.name.clone(), id);
+            if cooldowns.get(&key).is_some_and(|last| last.elapsed() < cooldown) {
+                debug!("Trigger {} on server {} skipped, in cooldown", trigger.name, id);
+                continue;
             }
+            cooldowns.insert(key, Instant::now());
+        }
+        if trigger.collect_lines == 0 {
+            serverlog::actions::dispatch(&trigger.function, cleaned_line, id, &captures, &trigger.options);
+        } else if !collections.contains_key(path) {
+            // The action is called once the following lines are collected
+            debug!("Collecting the next {} lines of {}", trigger.collect_lines, path.display());
+            collections.insert(path.clone(), PendingCollection {
+                serverlog_id: id,
+                function: trigger.function.clone(),
+                captures,
+                options: trigger.options.clone(),
+                lines: vec![cleaned_line.to_string()],
+                remaining: trigger.collect_lines,
+                last_update: Instant::now(),
+            });
         }
     }
 
-    // Then keep your existing position update:
-    let new_pos = f.seek(SeekFrom::Current(0))?;
-    positions.insert(path.clone(), new_pos);
+    // Lines missed by every trigger are sampled to help writing new ones
+    if !matched && !from_self && !ignored {
+        trigger_tuning::sample_unmatched(id, cleaned_line);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
 
-    Ok(())
-}
\ No newline at end of file
+    /// A log file `<tmp>/12/latest.log` (serverlog_id 12 from its folder) and a triggers file
+    fn fixture(triggers_toml: &str) -> (PathBuf, PathBuf, Triggers) {
+        let root = std::env::temp_dir().join(format!("otternel-watcher-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(root.join("12")).unwrap();
+        let triggers_path = root.join("triggers.toml");
+        std::fs::write(&triggers_path, triggers_toml).unwrap();
+        let loaded = triggers::load(triggers_path.to_str().unwrap());
+        (root.clone(), root.join("12").join("latest.log"), loaded)
+    }
+
+    fn append(path: &PathBuf, text: &str) {
+        append_bytes(path, text.as_bytes());
+    }
+
+    fn append_bytes(path: &PathBuf, bytes: &[u8]) {
+        std::fs::OpenOptions::new().create(true).append(true).open(path).unwrap().write_all(bytes).unwrap();
+    }
+
+    /// Matches counted by `/metrics` for a trigger function
+    fn matches_of(function: &str) -> u64 {
+        let prefix = format!("otternel_trigger_matches_total{{function=\"{}\"}} ", function);
+        metrics::render().lines().find_map(|line| line.strip_prefix(&prefix).map(|count| count.parse().unwrap())).unwrap_or(0)
+    }
+
+    #[test]
+    fn every_line_of_a_multi_line_chunk_is_matched() {
+        let (root, log, loaded) = fixture("[[trigger]]\npattern = 'burst line'\nfunction = 'on_burst_line_test'\n");
+        let mut positions = HashMap::new();
+        let mut resolver = ServerlogResolver::new(HashMap::new());
+        let mut collections = HashMap::new();
+        let mut cooldowns = HashMap::new();
+
+        append(&log, "[10:00:00] [Server thread/INFO]: burst line 1\n[10:00:00] [Server thread/INFO]: something else\n");
+        append(&log, "[10:00:01] [Server thread/INFO]: burst line 2\r\n[10:00:01] [Server thread/INFO]: burst line 3\n");
+        append(&log, "[10:00:02] [Server thread/INFO]: burst line 4");
+        read_new(&log, &mut positions, &mut resolver, &loaded, &mut collections, &mut cooldowns).unwrap();
+        assert_eq!(matches_of("on_burst_line_test"), 3, "the line not ended yet isn't matched");
+
+        append(&log, "\n[10:00:03] [Server thread/INFO]: burst line 5\n");
+        read_new(&log, &mut positions, &mut resolver, &loaded, &mut collections, &mut cooldowns).unwrap();
+        assert_eq!(matches_of("on_burst_line_test"), 5);
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn a_rotated_file_is_read_again_from_its_start() {
+        let (root, log, loaded) = fixture("[[trigger]]\npattern = 'rotation line'\nfunction = 'on_rotation_test'\n");
+        let mut positions = HashMap::new();
+        let mut resolver = ServerlogResolver::new(HashMap::new());
+        let mut collections = HashMap::new();
+        let mut cooldowns = HashMap::new();
+
+        append(&log, "[10:00:00] [Server thread/INFO]: rotation line before\n[10:00:01] [Server thread/INFO]: padding padding\n");
+        read_new(&log, &mut positions, &mut resolver, &loaded, &mut collections, &mut cooldowns).unwrap();
+        assert_eq!(matches_of("on_rotation_test"), 1);
+
+        std::fs::write(&log, "[11:00:00] rotation line after\n").unwrap();
+        read_new(&log, &mut positions, &mut resolver, &loaded, &mut collections, &mut cooldowns).unwrap();
+        assert_eq!(matches_of("on_rotation_test"), 2);
+        assert_eq!(positions[&log].offset, 31);
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn existing_files_are_skipped_to_their_end_unless_replayed_on_start() {
+        let (root, log, loaded) = fixture("[[trigger]]\npattern = 'startup line'\nfunction = 'on_startup_test'\n");
+        let mut resolver = ServerlogResolver::new(HashMap::new());
+        let mut collections = HashMap::new();
+        let mut cooldowns = HashMap::new();
+        append(&log, "[10:00:00] [Server thread/INFO]: startup line 1\n[10:00:01] [Server thread/INFO]: startup line 2\n");
+        let len = std::fs::metadata(&log).unwrap().len();
+
+        let mut positions = HashMap::new();
+        let skipped = position_existing_logs(vec![log.clone()], false, &mut positions, &mut resolver, &loaded, &mut collections, &mut cooldowns);
+        assert_eq!(skipped, 1);
+        assert_eq!(positions[&log].offset, len);
+        assert_eq!(matches_of("on_startup_test"), 0);
+
+        // A file with a restored position is left where it was
+        let mut restored = HashMap::from([(log.clone(), FileCursor::at(0))]);
+        position_existing_logs(vec![log.clone()], true, &mut restored, &mut resolver, &loaded, &mut collections, &mut cooldowns);
+        assert_eq!(restored[&log].offset, 0);
+        assert_eq!(matches_of("on_startup_test"), 0);
+
+        let mut replayed = HashMap::new();
+        let skipped = position_existing_logs(vec![log.clone()], true, &mut replayed, &mut resolver, &loaded, &mut collections, &mut cooldowns);
+        assert_eq!(skipped, 0);
+        assert_eq!(replayed[&log].offset, len);
+        assert_eq!(matches_of("on_startup_test"), 2, "the initial content is read on start");
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn a_file_created_while_watching_is_read_from_its_start() {
+        let (root, log, loaded) = fixture("[[trigger]]\npattern = 'new file line'\nfunction = 'on_new_file_test'\n");
+        let mut resolver = ServerlogResolver::new(HashMap::new());
+        let mut collections = HashMap::new();
+        let mut cooldowns = HashMap::new();
+
+        let mut positions = HashMap::new();
+        append(&log, "[10:00:00] [Server thread/INFO]: new file line 1\n[10:00:01] [Server thread/INFO]: new file line 2\n");
+        read_new(&log, &mut positions, &mut resolver, &loaded, &mut collections, &mut cooldowns).unwrap();
+        assert_eq!(matches_of("on_new_file_test"), 2);
+        assert!(!processing_lag::gauges().contains_key(&log), "a catch-up read doesn't count as lag");
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn a_restored_position_only_reads_the_new_lines() {
+        let (root, log, loaded) = fixture("[[trigger]]\npattern = 'restored line'\nfunction = 'on_restored_test'\n");
+        let mut resolver = ServerlogResolver::new(HashMap::new());
+        let mut collections = HashMap::new();
+        let mut cooldowns = HashMap::new();
+
+        append(&log, "[10:00:00] [Server thread/INFO]: restored line 1\n");
+        let saved = std::fs::metadata(&log).unwrap().len();
+        let mut positions = HashMap::from([(log.clone(), FileCursor::at(saved))]);
+
+        append(&log, "[10:00:01] [Server thread/INFO]: restored line 2\n");
+        read_new(&log, &mut positions, &mut resolver, &loaded, &mut collections, &mut cooldowns).unwrap();
+        assert_eq!(matches_of("on_restored_test"), 1);
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    fn utf16le(text: &str) -> Vec<u8> {
+        text.encode_utf16().flat_map(u16::to_le_bytes).collect()
+    }
+
+    #[test]
+    fn utf16_chunk_cut_in_a_code_unit_keeps_the_odd_byte_for_the_next_read() {
+        let bytes = [&[0xFF, 0xFE][..], &utf16le("héllo\n")].concat();
+        let (text, consumed) = decode_log_chunk(&bytes[..7], None);
+        assert_eq!(text, "hé");
+        assert_eq!(consumed, 6);
+        let (text, consumed) = decode_log_chunk(&bytes[consumed..], None);
+        assert_eq!(text, "llo\n");
+        assert_eq!(consumed, bytes.len() - 6);
+    }
+
+    #[test]
+    fn utf8_character_cut_between_two_reads_is_decoded_whole() {
+        let bytes = "[10:00:00] <Zoé> 🦦\n".as_bytes();
+        // Cut after the first byte of `é`, then in the middle of the otter
+        let first = bytes.iter().position(|b| *b == 0xC3).unwrap() + 1;
+        let (text, consumed) = decode_log_chunk(&bytes[..first], None);
+        assert_eq!(text, "[10:00:00] <Zo");
+        let otter = bytes.len() - 3;
+        let (text, second) = decode_log_chunk(&bytes[consumed..otter], None);
+        assert_eq!(text, "é> ");
+        let (text, _) = decode_log_chunk(&bytes[consumed + second..], None);
+        assert_eq!(text, "🦦\n");
+    }
+
+    #[test]
+    fn latin1_and_windows_1252_chunks_are_detected() {
+        let bytes = b"[10:00:00] <Zo\xE9> \x80 l\x92\x9Cuvre\n";
+        let (text, consumed) = decode_log_chunk(bytes, None);
+        assert_eq!(text, "[10:00:00] <Zoé> € l’œuvre\n");
+        assert_eq!(consumed, bytes.len());
+    }
+
+    #[test]
+    fn utf8_message_written_in_two_cut_writes_gives_the_right_line() {
+        let (root, log, loaded) = fixture("[[trigger]]\npattern = '<Zoé> ça marche 🦦$'\nfunction = 'on_utf8_cut_test'\n");
+        let mut positions = HashMap::new();
+        let mut resolver = ServerlogResolver::new(HashMap::new());
+        let mut collections = HashMap::new();
+        let mut cooldowns = HashMap::new();
+
+        let bytes = "[10:00:00] [Server thread/INFO]: <Zoé> ça marche 🦦\n".as_bytes();
+        let cut = bytes.len() - 3;
+        for part in [&bytes[..cut], &bytes[cut..]] {
+            append_bytes(&log, part);
+            read_new(&log, &mut positions, &mut resolver, &loaded, &mut collections, &mut cooldowns).unwrap();
+        }
+        assert_eq!(matches_of("on_utf8_cut_test"), 1);
+        assert_eq!(positions[&log].offset, bytes.len() as u64);
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn utf16_message_written_in_two_cut_writes_gives_the_right_line() {
+        let (root, log, loaded) = fixture("[[trigger]]\npattern = '<Zoé> ça marche 🦦$'\nfunction = 'on_utf16_cut_test'\n");
+        let mut positions = HashMap::new();
+        let mut resolver = ServerlogResolver::new(HashMap::new());
+        let mut collections = HashMap::new();
+        let mut cooldowns = HashMap::new();
+
+        let bytes = [&[0xFF, 0xFE][..], &utf16le("[10:00:00] [Server thread/INFO]: <Zoé> ça marche 🦦\n")].concat();
+        // Cut in the middle of a code unit, then between the two code units of the otter (a surrogate pair)
+        let otter = bytes.len() - 4;
+        let cuts = [0, 41, otter, bytes.len()];
+        for window in cuts.windows(2) {
+            append_bytes(&log, &bytes[window[0]..window[1]]);
+            read_new(&log, &mut positions, &mut resolver, &loaded, &mut collections, &mut cooldowns).unwrap();
+        }
+        assert_eq!(matches_of("on_utf16_cut_test"), 1);
+        assert_eq!(positions[&log].offset, bytes.len() as u64);
+        assert!(positions[&log].remainder.is_empty());
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn a_collection_started_in_a_chunk_gets_the_following_lines_of_the_chunk() {
+        let (root, log, loaded) = fixture("[[trigger]]\npattern = 'Exception'\nfunction = 'on_collect_test'\ncollect_lines = 5\n");
+        let mut positions = HashMap::new();
+        let mut resolver = ServerlogResolver::new(HashMap::new());
+        let mut collections = HashMap::new();
+        let mut cooldowns = HashMap::new();
+
+        append(&log, "[10:00:00] [Server thread/INFO]: before\n[10:00:00] [Server thread/ERROR]: Exception\n\tat a\n\tat b\n");
+        read_new(&log, &mut positions, &mut resolver, &loaded, &mut collections, &mut cooldowns).unwrap();
+
+        let collection = collections.get(&log).expect("collection in progress");
+        assert_eq!(collection.serverlog_id, 12);
+        assert_eq!(collection.lines, vec!["[10:00:00] [Server thread/ERROR]: Exception", "\tat a", "\tat b"]);
+        assert_eq!(collection.remaining, 3);
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn positions_survive_a_restart() {
+        let root = std::env::temp_dir().join(format!("otternel-restart-{}", uuid::Uuid::new_v4()));
+        for id in ["12", "13", "14", "15"] {
+            std::fs::create_dir_all(root.join(id)).unwrap();
+        }
+        let log = |id: &str| root.join(id).join("latest.log");
+        let line = "[10:00:00] [Server thread/INFO]: Loutre joined the game\n";
+        for id in ["12", "13", "14", "15"] {
+            append(&log(id), &line.repeat(2));
+        }
+        let notes = root.join("12").join("notes.txt");
+        append(&notes, "not a log");
+
+        // Everything read, then Otternel stops
+        let read = (2 * line.len()) as u64;
+        let positions: HashMap<PathBuf, FileCursor> = [log("12"), log("13"), log("14"), log("15"), notes.clone()]
+            .into_iter()
+            .map(|path| (path, FileCursor::at(read)))
+            .collect();
+        let store = offset_store::JsonOffsetStore::new(root.join(".otternel_positions.json"));
+        save_positions(&store, &positions);
+
+        // While stopped : 12 gets new lines, 13 is recreated longer, 14 is truncated, 15 is deleted
+        append(&log("12"), line);
+        // (written aside then renamed, as the old inode could be given again to a file created after the delete)
+        let rotated = root.join("13").join("latest.log.new");
+        append(&rotated, &line.repeat(3));
+        std::fs::rename(&rotated, log("13")).unwrap();
+        std::fs::write(log("14"), "").unwrap();
+        std::fs::remove_file(log("15")).unwrap();
+
+        let (restored, recreated) = restore_positions(store.load().unwrap(), &LogFileFilter::new(&root, "*.log"));
+        let mut offsets: Vec<(PathBuf, u64)> = restored.into_iter().map(|(path, cursor)| (path, cursor.offset)).collect();
+        offsets.sort();
+        assert_eq!(offsets, vec![(log("12"), read), (log("13"), 0), (log("14"), 0)]);
+        assert_eq!(recreated, 2);
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn live_lines_are_batched_for_mcmyadmin() {
+        let lines: Vec<String> = (1..=5).map(|i| format!("[10:00:0{i}] ligne {i}")).collect();
+        let (messages, dropped) = mirror_messages(&lines, 2000, 3);
+        assert_eq!(messages, vec![lines.join("\n")]);
+        assert_eq!(dropped, 0);
+
+        // 2 lines of 18 characters fit in 40 characters, with the line break
+        let (messages, dropped) = mirror_messages(&lines, 40, 3);
+        assert_eq!(messages, vec!["[10:00:01] ligne 1", "[10:00:02] ligne 2\n[10:00:03] ligne 3", "[10:00:04] ligne 4\n[10:00:05] ligne 5"]);
+        assert_eq!(dropped, 0);
+        assert!(mirror_messages(&[], 2000, 3).0.is_empty());
+    }
+
+    #[test]
+    fn a_burst_only_mirrors_its_most_recent_lines() {
+        let lines: Vec<String> = (0..1000).map(|i| format!("[10:00:00] ligne {i:04}")).collect();
+        let (messages, dropped) = mirror_messages(&lines, 2000, MIRROR_MAX_MESSAGES_PER_READ);
+        assert_eq!(messages.len(), MIRROR_MAX_MESSAGES_PER_READ);
+        assert!(messages.iter().all(|m| m.chars().count() <= 2000));
+        let mirrored: Vec<&str> = messages.iter().flat_map(|m| m.lines()).collect();
+        assert_eq!(mirrored.len() + dropped, 1000);
+        assert_eq!(mirrored.last(), Some(&"[10:00:00] ligne 0999"));
+        assert_eq!(mirrored[0], lines[dropped]);
+
+        // A line longer than a message is sent alone
+        let long = vec!["a".repeat(2500), "b".to_string()];
+        assert_eq!(mirror_messages(&long, 2000, 3), (vec!["a".repeat(2500), "b".to_string()], 0));
+        assert_eq!(mirror_messages(&long, 2000, 1), (vec!["b".to_string()], 1));
+    }
+}
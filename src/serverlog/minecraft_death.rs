@@ -0,0 +1,71 @@
+use regex::Regex;
+use std::sync::LazyLock;
+
+/// Killer at the end of a death message, after "by", "whilst fighting" or "whilst trying to escape" (a bedrock
+/// playername may start with a dot), optionally followed by the weapon ("using [Sword of Doom]")
+static KILLER_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?:\bby|\bwhilst fighting|\bwhilst trying to escape) (\.?[A-Za-z0-9_]{1,16})(?: using (.+))?$").unwrap()
+});
+
+/// Killer of a Minecraft death, as named in the death message.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeathKiller<'a> {
+    pub killer: &'a str,
+    /// Name of the weapon, without its brackets
+    pub weapon: Option<&'a str>,
+}
+
+/// Extracts the killer of a Minecraft death message, the part after the victim's name.
+/// The killer may be a mob (ex: "Zombie") : the caller checks it is a known player.
+///
+/// # Supported formats
+/// - `was slain by Loutre`, `was shot by Loutre`, `was blown up by Loutre`...
+/// - `was slain by Loutre using [Sword of Doom]`
+/// - `drowned whilst trying to escape Loutre`, `walked into fire whilst fighting Loutre`...
+///
+/// # Returns
+/// The killer and the weapon, or `None` if the message names no killer (or a killer with spaces, like "Cave Spider").
+pub fn parse_killer(death_message: &str) -> Option<DeathKiller<'_>> {
+    let caps = KILLER_RE.captures(death_message.trim())?;
+    let weapon = caps
+        .get(2)
+        .map(|m| m.as_str().trim())
+        .map(|weapon| weapon.strip_prefix('[').and_then(|w| w.strip_suffix(']')).unwrap_or(weapon))
+        .filter(|weapon| !weapon.is_empty());
+    Some(DeathKiller {
+        killer: caps.get(1)?.as_str(),
+        weapon,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn killers_and_weapons_of_death_messages() {
+        let cases = [
+            ("was slain by Loutre", Some(("Loutre", None))),
+            ("was shot by Castor_42", Some(("Castor_42", None))),
+            ("was blown up by Loutre", Some(("Loutre", None))),
+            ("was slain by Loutre using [Sword of Doom]", Some(("Loutre", Some("Sword of Doom")))),
+            ("was shot by Loutre using Bow ", Some(("Loutre", Some("Bow")))),
+            ("was slain by Loutre using [[Excalibur]]", Some(("Loutre", Some("[Excalibur]")))),
+            ("was slain by .BedrockOtter using [Trident]", Some((".BedrockOtter", Some("Trident")))),
+            ("drowned whilst trying to escape Loutre", Some(("Loutre", None))),
+            ("walked into fire whilst fighting Loutre using [Flint]", Some(("Loutre", Some("Flint")))),
+            ("was slain by Zombie", Some(("Zombie", None))),
+            ("was slain by Loutre using []", Some(("Loutre", None))),
+            // No killer, or a killer that can't be a player
+            ("fell from a high place", None),
+            ("drowned", None),
+            ("was slain by Cave Spider", None),
+            ("was slain by ThisNameIsWayTooLong", None),
+            ("went off with a bang", None),
+        ];
+        for (message, expected) in cases {
+            let parsed = parse_killer(message);
+            assert_eq!(parsed.map(|k| (k.killer, k.weapon)), expected, "{message}");
+        }
+    }
+}
@@ -1,2 +1,21 @@
 pub mod log_watcher;
+pub mod log_encoding;
+pub mod log_glob;
+pub mod file_cursor;
 pub mod actions;
+pub mod serverlog_resolver;
+pub mod line_timestamp;
+pub mod processing_lag;
+pub mod self_guard;
+pub mod palworld;
+pub mod default_triggers;
+pub mod online_tracker;
+pub mod file_lifecycle;
+pub mod offset_store;
+pub mod trigger_tuning;
+pub mod minecraft_death;
+pub mod triggers;
+pub mod trigger_test;
+pub mod whitelist;
+pub mod moderation;
+pub mod server_lag;
@@ -0,0 +1,57 @@
+use regex::Regex;
+use std::sync::LazyLock;
+
+/// `Kicked <player>: <reason>` or `Banned <player>: <reason>`, optionally wrapped in `[<author>: ...]` when the
+/// command was run by a player (a bedrock playername may start with a dot)
+static SANCTION_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?:\[(?P<author>[^:\[\]]+): )?(?P<kind>Kicked|Banned) (?P<player>\.?[A-Za-z0-9_]{1,16}): (?P<reason>.*?)\]?$").unwrap()
+});
+
+/// Kind of a sanction, as stored in `moderation_log.type`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SanctionKind {
+    Kick,
+    Ban,
+}
+
+impl SanctionKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SanctionKind::Kick => "kick",
+            SanctionKind::Ban => "ban",
+        }
+    }
+}
+
+/// A kick or a ban of a Minecraft log line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sanction<'a> {
+    pub kind: SanctionKind,
+    pub playername: &'a str,
+    /// Reason given to the command, `None` if empty
+    pub reason: Option<&'a str>,
+    /// Player who ran the command, `None` from the console
+    pub author: Option<&'a str>,
+}
+
+/// Parses a kick or a ban of a Minecraft log line.
+///
+/// # Supported formats
+/// - `[Server thread/INFO]: Kicked Loutre: Spam` (console)
+/// - `[Server thread/INFO]: [Admin: Banned Loutre: Triche]` (command of a player)
+///
+/// # Returns
+/// The sanction, or `None` if the line isn't a kick nor a ban (IP bans are ignored).
+pub fn parse_sanction_line(line: &str) -> Option<Sanction<'_>> {
+    let caps = SANCTION_RE.captures(line)?;
+    let kind = match caps.name("kind")?.as_str() {
+        "Kicked" => SanctionKind::Kick,
+        _ => SanctionKind::Ban,
+    };
+    Some(Sanction {
+        kind,
+        playername: caps.name("player")?.as_str(),
+        reason: caps.name("reason").map(|m| m.as_str().trim()).filter(|reason| !reason.is_empty()),
+        author: caps.name("author").map(|m| m.as_str().trim()),
+    })
+}
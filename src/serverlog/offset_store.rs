@@ -0,0 +1,408 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::config::Config;
+
+/// First bytes of an offsets file
+const MAGIC: &[u8; 4] = b"OTOF";
+/// Version of the format, bumped on any layout change (1 : without the inode)
+const VERSION: u8 = 2;
+/// Default of `SERVERLOG_STATE_FILE`
+const DEFAULT_PATH: &str = ".otternel_positions.json";
+/// Magic, version, entry count (u32) and CRC32 of the body (u32)
+const HEADER_LEN: usize = 4 + 1 + 4 + 4;
+
+/// Read position of a log file, with the size the file had when it was read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileOffset {
+    pub offset: u64,
+    pub len: u64,
+    /// Inode of the file when it was read, 0 if unknown : another inode means the file was recreated
+    #[serde(default)]
+    pub inode: u64,
+}
+
+#[derive(Debug, Error)]
+pub enum OffsetStoreError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Corrupted offsets file: {0}")]
+    Corrupted(String),
+}
+
+/// Returns the path of the offsets file (`SERVERLOG_STATE_FILE`, Default `.otternel_positions.json`),
+/// `None` when the variable is set empty : the positions aren't kept.
+pub fn configured_path() -> Option<PathBuf> {
+    match Config::current().serverlog_state_file.as_deref() {
+        Some(path) if path.trim().is_empty() => None,
+        Some(path) => Some(PathBuf::from(path.trim())),
+        None => Some(PathBuf::from(DEFAULT_PATH)),
+    }
+}
+
+/// Returns the store of the configured offsets file : JSON for a `.json` file, binary otherwise.
+pub fn configured_store() -> Option<Box<dyn OffsetStore>> {
+    let path = configured_path()?;
+    Some(if is_json(&path) {
+        Box::new(JsonOffsetStore::new(path))
+    } else {
+        Box::new(BinaryOffsetStore::new(path))
+    })
+}
+
+/// Tells whether the offsets file at `path` is kept as JSON (by its extension).
+pub fn is_json(path: &Path) -> bool {
+    path.extension().and_then(|ext| ext.to_str()) == Some("json")
+}
+
+/// Where the read positions of the log files are kept between two restarts.
+pub trait OffsetStore {
+    /// Loads the positions saved last. A store never written returns no position.
+    fn load(&self) -> Result<HashMap<PathBuf, FileOffset>, OffsetStoreError>;
+
+    /// Replaces the saved positions.
+    fn save(&self, offsets: &HashMap<PathBuf, FileOffset>) -> Result<(), OffsetStoreError>;
+}
+
+/// Positions kept in a compact binary file, written atomically, with the previous generation kept next to it.
+///
+/// # Layout (little endian)
+/// - header : `OTOF`, version (u8), entry count (u32), CRC32 of the body (u32)
+/// - body, for each entry : path length (u16), path (UTF-8), file size (u64), offset (u64), inode (u64)
+///   (no inode in version 1 files, still read)
+///
+/// # Recovery
+/// Saving writes `<path>.tmp`, moves the current file to `<path>.1`, then renames the temporary file.
+/// A file whose checksum doesn't match (crash mid-write, disk error) is ignored on load,
+/// and the previous generation `<path>.1` is used instead.
+pub struct BinaryOffsetStore {
+    path: PathBuf,
+}
+
+impl BinaryOffsetStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    fn previous_generation(&self) -> PathBuf {
+        with_suffix(&self.path, ".1")
+    }
+}
+
+impl OffsetStore for BinaryOffsetStore {
+    fn load(&self) -> Result<HashMap<PathBuf, FileOffset>, OffsetStoreError> {
+        let current = match fs::read(&self.path) {
+            Ok(bytes) => decode(&bytes),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(HashMap::new()),
+            Err(e) => Err(e.into()),
+        };
+        let error = match current {
+            Ok(offsets) => return Ok(offsets),
+            Err(e) => e,
+        };
+
+        let previous = self.previous_generation();
+        warn!("Offsets file {} unusable ({}), trying {}", self.path.display(), error, previous.display());
+        decode(&fs::read(&previous)?)
+    }
+
+    fn save(&self, offsets: &HashMap<PathBuf, FileOffset>) -> Result<(), OffsetStoreError> {
+        let tmp = with_suffix(&self.path, ".tmp");
+        {
+            let mut file = File::create(&tmp)?;
+            file.write_all(&encode(offsets)?)?;
+            file.sync_all()?;
+        }
+        if self.path.exists() {
+            fs::rename(&self.path, self.previous_generation())?;
+        }
+        fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+/// Positions kept in a small JSON file, readable by hand, written atomically (`<path>.tmp` then a rename).
+///
+/// # Layout
+/// `{"files": [{"path": "...", "len": 1234, "offset": 1234, "inode": 5678}, ...]}`
+pub struct JsonOffsetStore {
+    path: PathBuf,
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonOffsets {
+    files: Vec<JsonOffsetEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonOffsetEntry {
+    path: PathBuf,
+    #[serde(flatten)]
+    offset: FileOffset,
+}
+
+impl JsonOffsetStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl OffsetStore for JsonOffsetStore {
+    fn load(&self) -> Result<HashMap<PathBuf, FileOffset>, OffsetStoreError> {
+        match fs::read(&self.path) {
+            Ok(bytes) => decode_json(&bytes),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(HashMap::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn save(&self, offsets: &HashMap<PathBuf, FileOffset>) -> Result<(), OffsetStoreError> {
+        let mut files: Vec<JsonOffsetEntry> = offsets
+            .iter()
+            .map(|(path, offset)| JsonOffsetEntry { path: path.clone(), offset: *offset })
+            .collect();
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        let bytes = serde_json::to_vec_pretty(&JsonOffsets { files })
+            .map_err(|e| OffsetStoreError::Corrupted(e.to_string()))?;
+
+        let tmp = with_suffix(&self.path, ".tmp");
+        {
+            let mut file = File::create(&tmp)?;
+            file.write_all(&bytes)?;
+            file.sync_all()?;
+        }
+        fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+/// Deserializes the positions of a JSON offsets file.
+pub fn decode_json(bytes: &[u8]) -> Result<HashMap<PathBuf, FileOffset>, OffsetStoreError> {
+    let offsets: JsonOffsets = serde_json::from_slice(bytes).map_err(|e| OffsetStoreError::Corrupted(e.to_string()))?;
+    Ok(offsets.files.into_iter().map(|entry| (entry.path, entry.offset)).collect())
+}
+
+/// Serializes the positions, paths sorted so the same positions give the same bytes.
+pub fn encode(offsets: &HashMap<PathBuf, FileOffset>) -> Result<Vec<u8>, OffsetStoreError> {
+    let mut entries: Vec<_> = offsets.iter().collect();
+    entries.sort_by(|a, b| a.0.cmp(b.0));
+
+    let mut body = Vec::new();
+    for (path, offset) in &entries {
+        let path = path.to_string_lossy();
+        let path_len = u16::try_from(path.len())
+            .map_err(|_| OffsetStoreError::Corrupted(format!("path too long: {}", path)))?;
+        body.extend_from_slice(&path_len.to_le_bytes());
+        body.extend_from_slice(path.as_bytes());
+        body.extend_from_slice(&offset.len.to_le_bytes());
+        body.extend_from_slice(&offset.offset.to_le_bytes());
+        body.extend_from_slice(&offset.inode.to_le_bytes());
+    }
+
+    let mut bytes = Vec::with_capacity(HEADER_LEN + body.len());
+    bytes.extend_from_slice(MAGIC);
+    bytes.push(VERSION);
+    bytes.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(&checksum(&body).to_le_bytes());
+    bytes.extend_from_slice(&body);
+    Ok(bytes)
+}
+
+/// Deserializes the positions, checking the header, the checksum and that nothing is left over.
+pub fn decode(bytes: &[u8]) -> Result<HashMap<PathBuf, FileOffset>, OffsetStoreError> {
+    let corrupted = |reason: &str| OffsetStoreError::Corrupted(reason.to_string());
+
+    if bytes.len() < HEADER_LEN {
+        return Err(corrupted("truncated header"));
+    }
+    if &bytes[..4] != MAGIC {
+        return Err(corrupted("bad magic"));
+    }
+    let version = bytes[4];
+    if version != 1 && version != VERSION {
+        return Err(OffsetStoreError::Corrupted(format!("unknown version {}", version)));
+    }
+    let count = u32::from_le_bytes(bytes[5..9].try_into().unwrap_or_default());
+    let expected = u32::from_le_bytes(bytes[9..13].try_into().unwrap_or_default());
+    let body = &bytes[HEADER_LEN..];
+    if checksum(body) != expected {
+        return Err(corrupted("checksum mismatch"));
+    }
+
+    let mut offsets = HashMap::new();
+    let mut rest = body;
+    for _ in 0..count {
+        let path_len = u16::from_le_bytes(take(&mut rest, 2).ok_or_else(|| corrupted("truncated entry"))?.try_into().unwrap_or_default()) as usize;
+        let path = take(&mut rest, path_len).ok_or_else(|| corrupted("truncated path"))?;
+        let path = String::from_utf8(path.to_vec()).map_err(|_| corrupted("path is not UTF-8"))?;
+        let len = take(&mut rest, 8).ok_or_else(|| corrupted("truncated entry"))?;
+        let offset = take(&mut rest, 8).ok_or_else(|| corrupted("truncated entry"))?;
+        let inode = match version {
+            1 => 0,
+            _ => u64::from_le_bytes(take(&mut rest, 8).ok_or_else(|| corrupted("truncated entry"))?.try_into().unwrap_or_default()),
+        };
+        offsets.insert(
+            PathBuf::from(path),
+            FileOffset {
+                len: u64::from_le_bytes(len.try_into().unwrap_or_default()),
+                offset: u64::from_le_bytes(offset.try_into().unwrap_or_default()),
+                inode,
+            },
+        );
+    }
+    if !rest.is_empty() {
+        return Err(corrupted("trailing bytes"));
+    }
+    Ok(offsets)
+}
+
+/// Splits the first `n` bytes off `rest`.
+fn take<'a>(rest: &mut &'a [u8], n: usize) -> Option<&'a [u8]> {
+    if rest.len() < n {
+        return None;
+    }
+    let (head, tail) = rest.split_at(n);
+    *rest = tail;
+    Some(head)
+}
+
+fn checksum(bytes: &[u8]) -> u32 {
+    let mut crc = flate2::Crc::new();
+    crc.update(bytes);
+    crc.sum()
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serverlog::file_cursor::FileCursor;
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("otternel-offsets-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn offsets() -> HashMap<PathBuf, FileOffset> {
+        HashMap::from([
+            (PathBuf::from("/srv/logs/12/latest.log"), FileOffset { offset: 120, len: 150, inode: 42 }),
+            (PathBuf::from("/srv/logs/palworld/Zoé.log"), FileOffset { offset: 0, len: 0, inode: 0 }),
+        ])
+    }
+
+    #[test]
+    fn positions_are_restored_as_saved() {
+        let dir = temp_dir();
+        let stores: [Box<dyn OffsetStore>; 2] = [
+            Box::new(BinaryOffsetStore::new(dir.join("positions.bin"))),
+            Box::new(JsonOffsetStore::new(dir.join("positions.json"))),
+        ];
+        for store in stores {
+            assert!(store.load().unwrap().is_empty(), "a store never written has no position");
+            store.save(&offsets()).unwrap();
+            assert_eq!(store.load().unwrap(), offsets());
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn a_corrupted_binary_file_falls_back_to_the_previous_generation() {
+        let dir = temp_dir();
+        let path = dir.join("positions.bin");
+        let store = BinaryOffsetStore::new(&path);
+        store.save(&offsets()).unwrap();
+        let mut newer = offsets();
+        newer.insert(PathBuf::from("/srv/logs/13/latest.log"), FileOffset { offset: 1, len: 1, inode: 1 });
+        store.save(&newer).unwrap();
+        assert_eq!(store.load().unwrap(), newer);
+
+        // Crash in the middle of the write : a byte of the body is lost
+        let mut bytes = fs::read(&path).unwrap();
+        bytes.truncate(bytes.len() - 1);
+        fs::write(&path, bytes).unwrap();
+        assert_eq!(store.load().unwrap(), offsets());
+
+        fs::write(with_suffix(&path, ".1"), b"garbage").unwrap();
+        assert!(matches!(store.load(), Err(OffsetStoreError::Corrupted(_))));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn a_corrupted_json_file_is_an_error() {
+        let dir = temp_dir();
+        let path = dir.join("positions.json");
+        fs::write(&path, "{\"files\": [{\"path\": \"/srv/logs/12/latest.log\", \"offset\": ").unwrap();
+        assert!(matches!(JsonOffsetStore::new(&path).load(), Err(OffsetStoreError::Corrupted(_))));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn bad_headers_are_rejected() {
+        let bytes = encode(&offsets()).unwrap();
+        assert_eq!(decode(&bytes).unwrap(), offsets());
+
+        let reasons = [
+            (bytes[..HEADER_LEN - 1].to_vec(), "truncated header"),
+            ([b"NOPE", &bytes[4..]].concat(), "bad magic"),
+            ([&bytes[..4], &[9], &bytes[5..]].concat(), "unknown version 9"),
+            ([&bytes[..], &[0]].concat(), "checksum mismatch"),
+        ];
+        for (bytes, reason) in reasons {
+            match decode(&bytes) {
+                Err(OffsetStoreError::Corrupted(e)) => assert_eq!(e, reason),
+                other => panic!("expected '{}', got {:?}", reason, other.map(|_| ())),
+            }
+        }
+    }
+
+    #[test]
+    fn version_1_files_are_read_without_inode() {
+        let path = b"/srv/logs/12/latest.log";
+        let mut body = Vec::new();
+        body.extend_from_slice(&(path.len() as u16).to_le_bytes());
+        body.extend_from_slice(path);
+        body.extend_from_slice(&150u64.to_le_bytes());
+        body.extend_from_slice(&120u64.to_le_bytes());
+        let bytes = [&MAGIC[..], &[1], &1u32.to_le_bytes(), &checksum(&body).to_le_bytes(), &body].concat();
+
+        let offsets = decode(&bytes).unwrap();
+        assert_eq!(offsets[Path::new("/srv/logs/12/latest.log")], FileOffset { offset: 120, len: 150, inode: 0 });
+    }
+
+    #[test]
+    fn rotation_is_seen_in_the_first_64_bytes() {
+        let line = "[10:00:00] [Server thread/INFO]: a line long enough to fill the head\n".repeat(3);
+        assert_eq!(FileCursor::head_len(line.len() as u64), 64);
+        let mut cursor = FileCursor::default();
+        cursor.rewind_if_replaced(line.len() as u64, &line.as_bytes()[..64]);
+        cursor.consume(line.len(), &line);
+
+        // A file written again longer, starting the same way over its first 64 bytes, is an append
+        let longer = format!("{}more\n", line);
+        assert!(!cursor.rewind_if_replaced(longer.len() as u64, &longer.as_bytes()[..64]));
+
+        // A file recreated with another first line is read again from its start
+        let recreated = format!("[11:00:00]{}", &longer[10..]);
+        assert!(cursor.rewind_if_replaced(recreated.len() as u64, &recreated.as_bytes()[..64]));
+        assert_eq!(cursor.offset, 0);
+    }
+
+    #[test]
+    fn the_store_is_chosen_by_the_extension() {
+        assert!(is_json(Path::new(".otternel_positions.json")));
+        assert!(!is_json(Path::new("/var/lib/otternel/positions.bin")));
+        assert!(!is_json(Path::new("positions")));
+    }
+}
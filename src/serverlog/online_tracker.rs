@@ -0,0 +1,205 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{LazyLock, RwLock};
+
+use log::warn;
+
+use crate::db::repository_default::Database;
+use crate::helper::minecraft_account_formatter::normalize_playername;
+use crate::helper::player_privacy;
+
+/// Players currently online, by serverlog_id. Fed by the join/leave lines and reconciled with the servers at startup.
+static ONLINE: LazyLock<RwLock<HashMap<u32, HashSet<String>>>> = LazyLock::new(|| RwLock::new(HashMap::new()));
+
+/// Difference between the players tracked as online and the players actually online on a server.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct OnlineDiff {
+    /// Online on the server, but not tracked (joined while Otternel was down)
+    pub appeared: Vec<String>,
+    /// Tracked as online, but not on the server anymore (left while Otternel was down)
+    pub vanished: Vec<String>,
+}
+
+impl OnlineDiff {
+    pub fn is_empty(&self) -> bool {
+        self.appeared.is_empty() && self.vanished.is_empty()
+    }
+}
+
+/// Records a player joining a server.
+pub fn joined(serverlog_id: u32, playername: &str) {
+    ONLINE
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .entry(serverlog_id)
+        .or_default()
+        .insert(playername.to_string());
+}
+
+/// Records a player leaving a server.
+pub fn left(serverlog_id: u32, playername: &str) {
+    if let Some(players) = ONLINE.write().unwrap_or_else(|e| e.into_inner()).get_mut(&serverlog_id) {
+        players.remove(playername);
+    }
+}
+
+/// Forgets the players of a server, when it starts or stops.
+pub fn clear(serverlog_id: u32) {
+    ONLINE.write().unwrap_or_else(|e| e.into_inner()).remove(&serverlog_id);
+}
+
+/// Returns the players tracked as online on a server, sorted.
+pub fn players_of(serverlog_id: u32) -> Vec<String> {
+    let mut players: Vec<String> = ONLINE
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(&serverlog_id)
+        .map(|players| players.iter().cloned().collect())
+        .unwrap_or_default();
+    players.sort();
+    players
+}
+
+/// Writes the players tracked on a server in `serveurs_actifs_etat`, for the website.
+/// Hidden players are counted, but their names aren't written.
+pub fn persist(db: &Database, serverlog_id: u32, serveur_id: u64) {
+    let players = players_of(serverlog_id);
+    let visible: Vec<&String> = players.iter().filter(|player| !player_privacy::is_hidden("minecraft", player)).collect();
+    let noms_json = serde_json::to_string(&visible).unwrap_or_else(|_| "[]".to_string());
+    if let Err(e) = db.update_online_players(serveur_id, players.len() as u32, &noms_json) {
+        warn!("Failed to record the online players of server {}: {:?}", serveur_id, e);
+    }
+}
+
+/// Returns the servers (serverlog_id) where a player is tracked as online.
+pub fn servers_of(playername: &str) -> Vec<u32> {
+    ONLINE
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .filter(|(_, players)| players.iter().any(|player| player.eq_ignore_ascii_case(playername)))
+        .map(|(serverlog_id, _)| *serverlog_id)
+        .collect()
+}
+
+/// Replaces the players tracked on a server by the ones actually online.
+///
+/// # Returns
+/// The players that appeared and vanished compared to what was tracked.
+pub fn reconcile(serverlog_id: u32, actual: HashSet<String>) -> OnlineDiff {
+    let mut online = ONLINE.write().unwrap_or_else(|e| e.into_inner());
+    let tracked = online.entry(serverlog_id).or_default();
+    let result = diff(tracked, &actual);
+    *tracked = actual;
+    result
+}
+
+/// Compares the players tracked as online with the players actually online.
+pub fn diff(tracked: &HashSet<String>, actual: &HashSet<String>) -> OnlineDiff {
+    let mut appeared: Vec<String> = actual.difference(tracked).cloned().collect();
+    let mut vanished: Vec<String> = tracked.difference(actual).cloned().collect();
+    appeared.sort();
+    vanished.sort();
+    OnlineDiff { appeared, vanished }
+}
+
+/// Parses the answer of the RCON `list` command.
+///
+/// # Supported formats
+/// - `There are 2 of a max of 20 players online: Steve, Alex` (1.13+)
+/// - `There are 2/20 players online:Steve, Alex` (older versions and some forks)
+///
+/// # Returns
+/// The normalized names of the online players, or `None` if the answer isn't a player list.
+pub fn parse_list_response(response: &str) -> Option<HashSet<String>> {
+    let (head, names) = response.split_once(':')?;
+    if !head.contains("players online") {
+        return None;
+    }
+    Some(
+        names
+            .split([',', '\n'])
+            .filter_map(|name| normalize_playername(name).ok())
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Players tracked as online, players actually online, players that appeared, players that vanished
+    type Case = (&'static [&'static str], &'static [&'static str], &'static [&'static str], &'static [&'static str]);
+
+    fn set(names: &[&str]) -> HashSet<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn every_combination_of_tracked_and_actual_players() {
+        let cases: [Case; 7] = [
+            // Nothing tracked, nobody online
+            (&[], &[], &[], &[]),
+            // Open and still online : nothing to do
+            (&["Loutre"], &["Loutre"], &[], &[]),
+            // Missing : joined while Otternel was down
+            (&[], &["Loutre"], &["Loutre"], &[]),
+            // Stale : left while Otternel was down
+            (&["Loutre"], &[], &[], &["Loutre"]),
+            // Open, stale and missing at once
+            (&["Loutre", "Steve"], &["Loutre", "Alex"], &["Alex"], &["Steve"]),
+            // Everyone replaced
+            (&["Steve", "Zed"], &["Alex", "Bob"], &["Alex", "Bob"], &["Steve", "Zed"]),
+            // Names are compared exactly, as normalized by the join lines and the list answer
+            (&["loutre"], &["Loutre"], &["Loutre"], &["loutre"]),
+        ];
+        for (tracked, actual, appeared, vanished) in cases {
+            let diff = diff(&set(tracked), &set(actual));
+            assert_eq!(diff.appeared, appeared, "{tracked:?} -> {actual:?}");
+            assert_eq!(diff.vanished, vanished, "{tracked:?} -> {actual:?}");
+            assert_eq!(diff.is_empty(), appeared.is_empty() && vanished.is_empty());
+        }
+    }
+
+    #[test]
+    fn reconcile_replaces_the_tracked_players() {
+        let serverlog_id = 91_001;
+        joined(serverlog_id, "Loutre");
+        joined(serverlog_id, "Steve");
+        let diff = reconcile(serverlog_id, set(&["Loutre", "Alex"]));
+        assert_eq!(diff, OnlineDiff { appeared: vec!["Alex".to_string()], vanished: vec!["Steve".to_string()] });
+        assert_eq!(players_of(serverlog_id), vec!["Alex", "Loutre"]);
+        assert!(reconcile(serverlog_id, set(&["Loutre", "Alex"])).is_empty(), "a second pass changes nothing");
+
+        // A server never tracked (started while Otternel was down)
+        let diff = reconcile(91_002, set(&["Bob"]));
+        assert_eq!(diff.appeared, vec!["Bob"]);
+        assert!(servers_of("bob").contains(&91_002));
+        clear(serverlog_id);
+        clear(91_002);
+        assert!(players_of(serverlog_id).is_empty());
+    }
+
+    #[test]
+    fn joins_and_leaves_are_tracked() {
+        let serverlog_id = 91_003;
+        joined(serverlog_id, "Loutre");
+        joined(serverlog_id, "Loutre");
+        joined(serverlog_id, "Tracked_Alex");
+        left(serverlog_id, "Loutre");
+        left(91_004, "Tracked_Alex");
+        assert_eq!(players_of(serverlog_id), vec!["Tracked_Alex"]);
+        assert_eq!(servers_of("TRACKED_ALEX"), vec![serverlog_id]);
+        clear(serverlog_id);
+    }
+
+    #[test]
+    fn list_answers_of_every_version_are_parsed() {
+        assert_eq!(parse_list_response("There are 2 of a max of 20 players online: Steve, Alex"), Some(set(&["Steve", "Alex"])));
+        assert_eq!(parse_list_response("There are 2/20 players online:Steve, Alex"), Some(set(&["Steve", "Alex"])));
+        assert_eq!(parse_list_response("There are 0 of a max of 20 players online: "), Some(set(&[])));
+        assert_eq!(parse_list_response("There are 1 of a max of 20 players online: §aLoutre§r"), Some(set(&["Loutre"])));
+        assert_eq!(parse_list_response("There are 2 of a max of 20 players online: Steve, x"), Some(set(&["Steve"])));
+        assert_eq!(parse_list_response("Unknown command: list"), None);
+        assert_eq!(parse_list_response("no colon here"), None);
+    }
+}
@@ -0,0 +1,162 @@
+use regex::Regex;
+use std::sync::LazyLock;
+
+static CONNECTION_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\[LOG\] (.+) (?:joined|left) the server\. \(User id: ([^)\s]+)\)").unwrap()
+});
+
+static CHAT_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\[CHAT\] <([^>]+)> (.*)$").unwrap()
+});
+
+static DEATH_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\[LOG\] (.+?) (?:was killed by (.+?)|died(?: (?:from|of|to) (.+?))?)\.?$").unwrap()
+});
+
+/// A Palworld player, as written in the server logs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PalworldPlayer<'a> {
+    pub playername: &'a str,
+    /// Account id of the player (ex: `steam_76561198000000000`)
+    pub user_id: &'a str,
+}
+
+/// Parses the player of a Palworld connection line.
+///
+/// # Supported formats
+/// - `[2025-01-31 14:43:14] [LOG] Loutre joined the server. (User id: steam_76561198000000000)`
+/// - `[2025-01-31 14:43:14] [LOG] Loutre left the server. (User id: steam_76561198000000000)`
+///
+/// # Returns
+/// The player of the line, or `None` if the line isn't a Palworld connection line.
+pub fn parse_connection_line(line: &str) -> Option<PalworldPlayer<'_>> {
+    let caps = CONNECTION_RE.captures(line)?;
+    Some(PalworldPlayer {
+        playername: caps.get(1)?.as_str().trim(),
+        user_id: caps.get(2)?.as_str(),
+    })
+}
+
+/// Parses a Palworld chat line. Only the first `<playername>` after `[CHAT]` delimits the player,
+/// so a message containing brackets or colons is kept whole.
+/// The log is the only source of the chat : neither the REST API nor RCON of Palworld give the messages.
+///
+/// # Supported formats
+/// - `[2025-01-31 14:43:14] [CHAT] <Loutre> message`
+///
+/// # Returns
+/// `(playername, message)`, or `None` if the line isn't a Palworld chat line.
+pub fn parse_chat_line(line: &str) -> Option<(&str, &str)> {
+    let caps = CHAT_RE.captures(line)?;
+    let message = caps.get(2)?.as_str().trim();
+    if message.is_empty() {
+        return None;
+    }
+    Some((caps.get(1)?.as_str().trim(), message))
+}
+
+/// Returns the SteamID64 of a Palworld user id (ex: `steam_76561198000000000`), if it is a Steam account.
+pub fn steam_id(user_id: &str) -> Option<&str> {
+    user_id
+        .strip_prefix("steam_")
+        .filter(|id| !id.is_empty() && id.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// Parses a Palworld death line, with its cause when the line gives one.
+///
+/// # Supported formats
+/// - `[2025-01-31 14:43:14] [LOG] Loutre died.`
+/// - `[2025-01-31 14:43:14] [LOG] Loutre died from fall damage.`
+/// - `[2025-01-31 14:43:14] [LOG] Loutre was killed by Anubis.`
+///
+/// # Returns
+/// `(playername, cause)`, or `None` if the line isn't a Palworld death line.
+pub fn parse_death_line(line: &str) -> Option<(&str, Option<&str>)> {
+    let caps = DEATH_RE.captures(line)?;
+    let playername = caps.get(1)?.as_str().trim();
+    let cause = caps.get(2).or_else(|| caps.get(3)).map(|m| m.as_str().trim()).filter(|c| !c.is_empty());
+    Some((playername, cause))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn connection_lines_give_player_and_user_id() {
+        let joined = "[2025-01-31 14:43:14] [LOG] Loutre joined the server. (User id: steam_76561198000000000)";
+        assert_eq!(
+            parse_connection_line(joined),
+            Some(PalworldPlayer { playername: "Loutre", user_id: "steam_76561198000000000" })
+        );
+        let left = "[2025-01-31 15:02:51] [LOG] Petite Loutre left the server. (User id: xbox_2535400000000000)";
+        assert_eq!(
+            parse_connection_line(left),
+            Some(PalworldPlayer { playername: "Petite Loutre", user_id: "xbox_2535400000000000" })
+        );
+        assert_eq!(parse_connection_line("[10:00:00] [Server thread/INFO]: Loutre joined the game"), None);
+    }
+
+    #[test]
+    fn example_triggers_match_palworld_lines() {
+        let path = std::env::temp_dir().join(format!("otternel-palworld-{}.toml", uuid::Uuid::new_v4()));
+        std::fs::write(&path, include_str!("../../triggers.toml")).unwrap();
+        let loaded = crate::serverlog::triggers::load(path.to_str().unwrap());
+        std::fs::remove_file(path).unwrap();
+
+        let cases = [
+            ("[2025-01-31 14:43:14] [LOG] Loutre joined the server. (User id: steam_76561198000000000)", "palworld_player_joined"),
+            ("[2025-01-31 15:02:51] [LOG] Loutre left the server. (User id: steam_76561198000000000)", "palworld_player_left"),
+            ("[2025-01-31 14:50:02] [CHAT] <Loutre> on capture Anubis ?", "palworld_player_message"),
+        ];
+        for (line, trigger) in cases {
+            let matches = loaded.matching(line, 3, Some("palworld"), false).unwrap();
+            let names: Vec<&str> = matches.iter().map(|m| m.trigger.name.as_str()).collect();
+            assert_eq!(names, vec![trigger], "{}", line);
+            assert_eq!(matches[0].captures.get("player").map(String::as_str), Some("Loutre"));
+        }
+        let joined = loaded.matching(cases[0].0, 3, Some("palworld"), false).unwrap();
+        assert_eq!(joined[0].captures["user_id"], "steam_76561198000000000");
+        assert!(loaded.matching(cases[0].0, 1, Some("minecraft"), false).unwrap().is_empty());
+    }
+
+    #[test]
+    fn chat_line_gives_player_and_message() {
+        let line = "[2025-01-31 14:43:14] [CHAT] <Loutre> salut tout le monde";
+        assert_eq!(parse_chat_line(line), Some(("Loutre", "salut tout le monde")));
+    }
+
+    #[test]
+    fn chat_message_keeps_brackets_and_colons() {
+        let line = "[2025-01-31 14:43:14] [CHAT] <Loutre> [info] rdv : 21h <base>";
+        assert_eq!(parse_chat_line(line), Some(("Loutre", "[info] rdv : 21h <base>")));
+    }
+
+    #[test]
+    fn first_chevrons_are_the_player() {
+        let line = "[2025-01-31 14:43:14] [CHAT] <a> <b> bonjour";
+        assert_eq!(parse_chat_line(line), Some(("a", "<b> bonjour")));
+    }
+
+    #[test]
+    fn empty_chat_message_is_ignored() {
+        assert_eq!(parse_chat_line("[2025-01-31 14:43:14] [CHAT] <Loutre>  "), None);
+    }
+
+    #[test]
+    fn other_lines_are_not_chat() {
+        assert_eq!(parse_chat_line("[2025-01-31 14:43:14] [LOG] Loutre died."), None);
+        assert_eq!(
+            parse_chat_line("[2025-01-31 14:43:14] [LOG] Loutre joined the server. (User id: steam_76561198000000000)"),
+            None
+        );
+    }
+
+    #[test]
+    fn steam_id_only_for_steam_accounts() {
+        assert_eq!(steam_id("steam_76561198000000000"), Some("76561198000000000"));
+        assert_eq!(steam_id("steam_"), None);
+        assert_eq!(steam_id("steam_abc"), None);
+        assert_eq!(steam_id("xbox_2535400000000000"), None);
+    }
+}
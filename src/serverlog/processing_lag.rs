@@ -0,0 +1,137 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
+use chrono::NaiveDateTime;
+use colored::Colorize;
+use log::{info, warn};
+
+use crate::config::Config;
+
+/// Processing lag of one log file, in milliseconds.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LagGauge {
+    /// Lag of the last processed line
+    pub latest_ms: i64,
+    /// Highest lag since the last call to [`take_snapshot`]
+    pub max_ms: i64,
+}
+
+static LAGS: LazyLock<Mutex<HashMap<PathBuf, LagGauge>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Records the lag between now and the timestamp of a line processed from `path`.
+///
+/// Catch-up reads (a rotated file read from its start, or a file read for the first time) must not
+/// be recorded, their lines are old by nature and would flag the watcher as drowning.
+pub fn record(path: &Path, line_timestamp: NaiveDateTime, now: NaiveDateTime) {
+    let lag_ms = (now - line_timestamp).num_milliseconds().max(0);
+    let threshold_ms = lag_threshold_sec() * 1000;
+
+    let mut lags = LAGS.lock().unwrap_or_else(|e| e.into_inner());
+    let gauge = lags.entry(path.to_path_buf()).or_default();
+    let was_drowning = gauge.latest_ms > threshold_ms;
+
+    gauge.latest_ms = lag_ms;
+    gauge.max_ms = gauge.max_ms.max(lag_ms);
+
+    // Only log when crossing the threshold, not on every line
+    if lag_ms > threshold_ms && !was_drowning {
+        warn!(
+            "Processing of {} is {} seconds behind real time",
+            path.display().to_string().yellow().bold(),
+            (lag_ms / 1000).to_string().yellow().bold()
+        );
+    } else if lag_ms <= threshold_ms && was_drowning {
+        info!("Processing of {} caught up with real time", path.display().to_string().green().bold());
+    }
+}
+
+/// Forgets the lag of a file, used when it is removed.
+pub fn forget(path: &Path) {
+    LAGS.lock().unwrap_or_else(|e| e.into_inner()).remove(path);
+}
+
+/// Returns the current gauges of every file and resets their max for the next interval.
+pub fn take_snapshot() -> HashMap<PathBuf, LagGauge> {
+    let mut lags = LAGS.lock().unwrap_or_else(|e| e.into_inner());
+    let snapshot = lags.clone();
+    for gauge in lags.values_mut() {
+        gauge.max_ms = gauge.latest_ms;
+    }
+    snapshot
+}
+
+/// Returns the current gauges of every file, without resetting their max (for `/metrics`).
+pub fn gauges() -> HashMap<PathBuf, LagGauge> {
+    LAGS.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Returns the files whose latest lag is above `PROCESSING_LAG_THRESHOLD_SEC` (default 60).
+pub fn drowning_files() -> Vec<PathBuf> {
+    let threshold_ms = lag_threshold_sec() * 1000;
+    LAGS.lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .filter(|(_, gauge)| gauge.latest_ms > threshold_ms)
+        .map(|(path, _)| path.clone())
+        .collect()
+}
+
+fn lag_threshold_sec() -> i64 {
+    Config::current().processing_lag_threshold_sec
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, NaiveDate};
+
+    fn at(secs: i64) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 1, 1).unwrap().and_hms_opt(12, 0, 0).unwrap() + Duration::seconds(secs)
+    }
+
+    /// Each test has its own file, the gauges being shared by the tests
+    fn file(name: &str) -> PathBuf {
+        PathBuf::from(format!("/tmp/otternel-lag-{}-{}/latest.log", name, uuid::Uuid::new_v4()))
+    }
+
+    #[test]
+    fn gauge_keeps_the_latest_lag_and_the_max_of_the_interval() {
+        let path = file("gauge");
+        record(&path, at(0), at(5));
+        record(&path, at(10), at(30));
+        record(&path, at(40), at(42));
+
+        let gauge = gauges()[&path];
+        assert_eq!((gauge.latest_ms, gauge.max_ms), (2_000, 20_000));
+
+        // The snapshot starts a new interval, the max restarts from the latest lag
+        assert_eq!(take_snapshot()[&path].max_ms, 20_000);
+        assert_eq!(gauges()[&path].max_ms, 2_000);
+        forget(&path);
+        assert!(!gauges().contains_key(&path));
+    }
+
+    #[test]
+    fn line_from_the_future_has_no_lag() {
+        let path = file("future");
+        record(&path, at(10), at(0));
+        assert_eq!(gauges()[&path].latest_ms, 0);
+        forget(&path);
+    }
+
+    #[test]
+    fn file_is_drowning_only_while_its_latest_lag_is_above_the_threshold() {
+        let threshold = lag_threshold_sec();
+        let path = file("threshold");
+
+        record(&path, at(0), at(threshold));
+        assert!(!drowning_files().contains(&path), "a lag equal to the threshold is not drowning");
+
+        record(&path, at(0), at(threshold + 1));
+        assert!(drowning_files().contains(&path));
+
+        record(&path, at(100), at(101));
+        assert!(!drowning_files().contains(&path), "the file caught up");
+        forget(&path);
+    }
+}
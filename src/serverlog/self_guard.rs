@@ -0,0 +1,140 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::config::Config;
+
+/// Marker put by Otternel in front of the text of its RCON `say` commands
+const DEFAULT_SELF_MARKER: &str = "[Otternel]";
+/// Sources written by the server in front of a `say` (RCON on recent versions, console on older ones)
+const SERVER_SOURCES: [&str; 2] = ["[Rcon] ", "[Server] "];
+
+/// Number of log lines whose triggers were skipped because they came from Otternel itself
+static SKIPPED_SELF_LINES: AtomicU64 = AtomicU64::new(0);
+
+/// Returns the marker identifying Otternel's own output in server logs.
+/// Read from `RCON_SELF_MARKER`, defaults to `[Otternel]`.
+pub fn self_marker() -> String {
+    Config::current()
+        .rcon_self_marker
+        .clone()
+        .filter(|m| !m.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_SELF_MARKER.to_string())
+}
+
+/// Tags an RCON command so the lines it writes in the server log can be recognized.
+///
+/// Only `say` writes its text in the log : the marker is put in front of the text, unless it is already there.
+/// The other commands are unchanged.
+pub fn tag_command(command: &str) -> String {
+    tag_with(command, &self_marker())
+}
+
+fn tag_with(command: &str, marker: &str) -> String {
+    let trimmed = command.trim_start_matches('/');
+    match trimmed.strip_prefix("say ") {
+        Some(text) if !text.starts_with(marker) => format!("say {} {}", marker, text),
+        _ => command.to_string(),
+    }
+}
+
+/// Returns `true` if the log line was written by Otternel itself through RCON.
+///
+/// The marker must start the message written by the server, after the `[time] [thread/LEVEL]: ` header and
+/// the `[Rcon]` source : a player writing the marker in the chat (`<Steve> [Otternel] hi`) isn't Otternel.
+pub fn is_self_line(line: &str) -> bool {
+    is_self_line_with(line, &self_marker())
+}
+
+fn is_self_line_with(line: &str, marker: &str) -> bool {
+    let message = line.split_once("]: ").map_or(line, |(_, message)| message);
+    let message = SERVER_SOURCES
+        .iter()
+        .find_map(|source| message.strip_prefix(source))
+        .unwrap_or(message);
+    message.starts_with(marker)
+}
+
+/// Counts a line skipped by the self guard.
+pub fn record_skipped() {
+    SKIPPED_SELF_LINES.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Returns the number of lines skipped by the self guard since startup.
+pub fn skipped_count() -> u64 {
+    SKIPPED_SELF_LINES.load(Ordering::Relaxed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serverlog::triggers;
+
+    const MARKER: &str = "[Otternel]";
+
+    /// Line written by Minecraft for a `say` sent through RCON
+    fn logged_say(command: &str) -> String {
+        let text = command.trim_start_matches('/').strip_prefix("say ").unwrap();
+        format!("[12:00:00] [Server thread/INFO]: [Rcon] {}", text)
+    }
+
+    #[test]
+    fn say_is_always_tagged_once() {
+        assert_eq!(tag_with("say hello", MARKER), "say [Otternel] hello");
+        assert_eq!(tag_with("/say hello", MARKER), "say [Otternel] hello");
+        assert_eq!(tag_with("say [Otternel] hello", MARKER), "say [Otternel] hello");
+        assert_eq!(tag_with("say hello", "[Rcon]"), "say [Rcon] hello");
+        assert_eq!(tag_with("whitelist add Steve", MARKER), "whitelist add Steve");
+    }
+
+    #[test]
+    fn marker_is_only_recognized_at_the_start_of_the_server_message() {
+        assert!(is_self_line_with("[12:00:00] [Server thread/INFO]: [Rcon] [Otternel] hello", MARKER));
+        assert!(is_self_line_with("[12:00:00] [Server thread/INFO]: [Server] [Otternel] hello", MARKER));
+        assert!(is_self_line_with("[12:00:00] [Server thread/INFO]: [Otternel] hello", MARKER));
+
+        // A player can't pass for Otternel by writing the marker
+        assert!(!is_self_line_with("[12:00:00] [Server thread/INFO]: <Steve> [Otternel] hello", MARKER));
+        assert!(!is_self_line_with("[12:00:00] [Server thread/INFO]: <Steve> [Rcon] [Otternel] hello", MARKER));
+        // Nor any other RCON client
+        assert!(!is_self_line_with("[12:00:00] [Server thread/INFO]: [Rcon] hello", MARKER));
+    }
+
+    /// A chat message relayed by a `say` action writes a line matching the chat trigger again :
+    /// the guard must keep it from reaching the trigger, or each relay would be relayed forever.
+    #[test]
+    fn relayed_chat_message_does_not_trigger_the_relay_again() {
+        let dir = std::env::temp_dir().join(format!("otternel-self-guard-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("triggers.toml");
+        std::fs::write(
+            &path,
+            r#"
+[[trigger]]
+name = "relay"
+pattern = "<(?P<player>[^>]+)> (?P<message>.*)"
+function = "on_player_message"
+
+[[trigger]]
+name = "audit"
+pattern = "<(?P<player>[^>]+)> (?P<message>.*)"
+function = "on_self_audit"
+allow_self = true
+"#,
+        )
+        .unwrap();
+        let loaded = triggers::load(path.to_str().unwrap());
+
+        let player_line = "[12:00:00] [Server thread/INFO]: <Steve> hello";
+        let from_self = is_self_line_with(player_line, MARKER);
+        let names = |line, from_self| -> Vec<String> {
+            loaded.matching(line, 1, None, from_self).unwrap().iter().map(|m| m.trigger.name.clone()).collect()
+        };
+        assert_eq!(names(player_line, from_self), ["relay", "audit"]);
+
+        // The relay action sends the message to the server, which writes it back in its log
+        let relayed = logged_say(&tag_with("say <Steve> hello", MARKER));
+        assert!(is_self_line_with(&relayed, MARKER));
+        assert_eq!(names(&relayed, true), ["audit"]);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
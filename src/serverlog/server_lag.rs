@@ -0,0 +1,159 @@
+use regex::Regex;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+
+/// Length of the sliding window of the lag of a server
+pub const LAG_WINDOW: Duration = Duration::from_secs(5 * 60);
+
+/// "Can't keep up! Is the server overloaded? Running 2034ms or 40 ticks behind"
+static LAG_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"Can't keep up! Is the server overloaded\? Running (?P<ms>\d+)ms or (?P<ticks>\d+) ticks behind").unwrap());
+
+/// Lag windows, by serverlog_id
+static WINDOWS: LazyLock<Mutex<HashMap<u32, LagWindow>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Delay reported by a "Can't keep up!" line.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LagReport {
+    pub ms: u64,
+    pub ticks: u64,
+}
+
+/// Lag of a server over the window, when it crossed the alert threshold.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LagSummary {
+    pub total_ms: u64,
+    pub total_ticks: u64,
+    pub occurrences: usize,
+}
+
+/// Parses a "Can't keep up!" line of a Minecraft server.
+pub fn parse_lag_line(line: &str) -> Option<LagReport> {
+    let caps = LAG_RE.captures(line)?;
+    Some(LagReport {
+        ms: caps.name("ms")?.as_str().parse().ok()?,
+        ticks: caps.name("ticks")?.as_str().parse().ok()?,
+    })
+}
+
+/// The reports of a server over the last [`LAG_WINDOW`].
+#[derive(Debug, Default)]
+pub struct LagWindow {
+    reports: VecDeque<(Instant, LagReport)>,
+}
+
+impl LagWindow {
+    /// Adds a report received at `now`, forgets the ones older than the window, and returns the lag of the
+    /// window if it exceeds `threshold_ms`. The window is then emptied : the next alert needs a new cumulated
+    /// lag above the threshold, instead of one alert per line while the server lags.
+    pub fn push(&mut self, now: Instant, report: LagReport, threshold_ms: u64) -> Option<LagSummary> {
+        while self.reports.front().is_some_and(|(at, _)| now.duration_since(*at) > LAG_WINDOW) {
+            self.reports.pop_front();
+        }
+        self.reports.push_back((now, report));
+
+        let summary = LagSummary {
+            total_ms: self.reports.iter().map(|(_, r)| r.ms).sum(),
+            total_ticks: self.reports.iter().map(|(_, r)| r.ticks).sum(),
+            occurrences: self.reports.len(),
+        };
+        if summary.total_ms <= threshold_ms {
+            return None;
+        }
+        self.reports.clear();
+        Some(summary)
+    }
+}
+
+/// Records a report of a server in its window (see [`LagWindow::push`]), with the threshold of
+/// `LAG_ALERT_MS_PER_5MIN` (default 10000).
+pub fn record(serverlog_id: u32, report: LagReport) -> Option<LagSummary> {
+    let threshold_ms = Config::current().lag_alert_ms_per_5min;
+    WINDOWS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .entry(serverlog_id)
+        .or_default()
+        .push(Instant::now(), report, threshold_ms)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const THRESHOLD_MS: u64 = 10_000;
+
+    fn lag_line(ms: u64, ticks: u64) -> String {
+        format!("[10:00:00] [Server thread/WARN]: Can't keep up! Is the server overloaded? Running {ms}ms or {ticks} ticks behind")
+    }
+
+    fn report(ms: u64) -> LagReport {
+        LagReport { ms, ticks: ms / 50 }
+    }
+
+    #[test]
+    fn lag_lines_give_their_delay() {
+        assert_eq!(parse_lag_line(&lag_line(2034, 40)), Some(LagReport { ms: 2034, ticks: 40 }));
+        let paper = "[10:00:00 WARN]: [Server] Can't keep up! Is the server overloaded? Running 5003ms or 100 ticks behind";
+        assert_eq!(parse_lag_line(paper), Some(LagReport { ms: 5003, ticks: 100 }));
+
+        assert_eq!(parse_lag_line("[10:00:00] [Server thread/INFO]: Loutre joined the game"), None);
+        assert_eq!(parse_lag_line("Can't keep up! Is the server overloaded? Running -5ms or 0 ticks behind"), None);
+        assert_eq!(parse_lag_line(&format!("Can't keep up! Is the server overloaded? Running {}0ms or 1 ticks behind", u64::MAX)), None);
+    }
+
+    #[test]
+    fn alert_once_the_window_exceeds_the_threshold() {
+        let start = Instant::now();
+        let mut window = LagWindow::default();
+        for (second, ms) in [(0, 2_500), (30, 2_500), (60, 2_500), (90, 2_500)] {
+            let line = lag_line(ms, ms / 50);
+            let at = start + Duration::from_secs(second);
+            assert_eq!(window.push(at, parse_lag_line(&line).unwrap(), THRESHOLD_MS), None, "{second}s");
+        }
+
+        // Exactly at the threshold is not an alert, above it is
+        let summary = window.push(start + Duration::from_secs(120), report(1), THRESHOLD_MS);
+        assert_eq!(summary, Some(LagSummary { total_ms: 10_001, total_ticks: 200, occurrences: 5 }));
+
+        // The window was emptied by the alert : a single line doesn't alert again
+        assert_eq!(window.push(start + Duration::from_secs(121), report(2_500), THRESHOLD_MS), None);
+    }
+
+    #[test]
+    fn reports_older_than_5_minutes_are_forgotten() {
+        let start = Instant::now();
+        let mut window = LagWindow::default();
+        assert_eq!(window.push(start, report(6_000), THRESHOLD_MS), None);
+
+        // Still in the window at exactly 5 minutes
+        let summary = window.push(start + LAG_WINDOW, report(4_001), THRESHOLD_MS);
+        assert_eq!(summary.map(|s| s.total_ms), Some(10_001));
+
+        let mut window = LagWindow::default();
+        assert_eq!(window.push(start, report(6_000), THRESHOLD_MS), None);
+        assert_eq!(window.push(start + Duration::from_secs(200), report(3_000), THRESHOLD_MS), None);
+        // The first report left the window, the second is kept
+        assert_eq!(window.push(start + LAG_WINDOW + Duration::from_secs(1), report(4_000), THRESHOLD_MS), None);
+        let summary = window.push(start + Duration::from_secs(400), report(3_001), THRESHOLD_MS);
+        assert_eq!(summary, Some(LagSummary { total_ms: 10_001, total_ticks: 200, occurrences: 3 }));
+    }
+
+    #[test]
+    fn each_server_has_its_own_window() {
+        let threshold_ms = Config::current().lag_alert_ms_per_5min;
+        let half = threshold_ms / 2 + 1;
+
+        assert_eq!(record(95_001, report(half)), None);
+        assert_eq!(record(95_002, report(half)), None);
+        let summary = record(95_001, report(half)).unwrap();
+        assert_eq!((summary.total_ms, summary.occurrences), (2 * half, 2));
+
+        // The other server kept its own report, the alerted one starts over
+        assert!(record(95_002, report(half)).is_some());
+        assert_eq!(record(95_001, report(half)), None);
+    }
+}
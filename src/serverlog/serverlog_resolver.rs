@@ -0,0 +1,307 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use colored::Colorize;
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use log::{debug, error, warn};
+
+use crate::helper;
+use crate::helper::active_servers;
+
+/// Resolves the `serverlog_id` (id of the `serveurs_actifs` table) of a log file.
+///
+/// # Behavior
+/// The resolution is done in this order, the first match wins:
+/// 1. The parent folder name is numeric (ex: `/serverlog/1/latest.log`) : this number is used.
+/// 2. An entry of the `[mapping]` section of `triggers.toml` matches :
+///    - a key without `/` nor `*` is compared to the parent folder name (ex: `"purpur-survie" = 1`),
+///    - any other key is a glob compared to the full path (ex: `"/serverlog/*/modded.log" = 2`), with the syntax of
+///      `SERVERLOG_GLOB` (see [`crate::serverlog::log_glob::LogFileFilter`]).
+/// 3. The parent folder name is looked up against the `nom` column of the active servers in database.
+///
+/// Results are cached per path, so the database is only queried once per file, until the list of
+/// active servers changes (see [`active_servers::refresh`]). A database error isn't cached, the next line of the
+/// file tries again.
+pub struct ServerlogResolver {
+    by_folder: HashMap<String, u32>,
+    by_glob: GlobSet,
+    /// serverlog_id of each glob of `by_glob`, by index
+    glob_ids: Vec<u32>,
+    cache: HashMap<PathBuf, Option<u32>>,
+    /// Game of each serverlog_id (`serveurs.jeu`, lowercase), `None` if it couldn't be found
+    games: HashMap<u32, Option<String>>,
+    /// Generation of the active server list the caches were filled with
+    generation: u64,
+}
+
+impl ServerlogResolver {
+    /// Builds a resolver from the `[mapping]` section of `triggers.toml`.
+    pub fn new(mapping: HashMap<String, u32>) -> Self {
+        let mut by_folder = HashMap::new();
+        let mut by_glob = GlobSetBuilder::new();
+        let mut glob_ids = Vec::new();
+
+        for (key, id) in mapping {
+            if key.contains('/') || key.contains('*') || key.contains('?') {
+                match GlobBuilder::new(&key).literal_separator(true).backslash_escape(true).build() {
+                    Ok(glob) => {
+                        by_glob.add(glob);
+                        glob_ids.push(id);
+                    }
+                    Err(e) => error!("Invalid glob in log mapping '{}': {}", key, e),
+                }
+            } else {
+                by_folder.insert(key, id);
+            }
+        }
+
+        Self {
+            by_folder,
+            by_glob: by_glob.build().unwrap_or_else(|_| GlobSet::empty()),
+            glob_ids,
+            cache: HashMap::new(),
+            games: HashMap::new(),
+            generation: active_servers::generation(),
+        }
+    }
+
+    /// Returns the `serverlog_id` of the given log file, or `None` if it could not be resolved (yet).
+    pub fn resolve(&mut self, path: &Path) -> Option<u32> {
+        self.resolve_with(path, active_server_named)
+    }
+
+    /// [`ServerlogResolver::resolve`], the folders matching neither a number nor the mapping being looked up with
+    /// `by_name`.
+    fn resolve_with(&mut self, path: &Path, by_name: impl FnOnce(&str) -> Result<Option<u32>, String>) -> Option<u32> {
+        self.drop_stale_caches();
+        if let Some(cached) = self.cache.get(path) {
+            return *cached;
+        }
+
+        let resolved = match self.resolve_uncached(path, by_name) {
+            Ok(resolved) => resolved,
+            Err(e) => {
+                error!("Error resolving the serverlog_id of {}: {}", path.display(), e);
+                return None;
+            }
+        };
+        match resolved {
+            Some(id) => debug!(
+                "Log file {} resolved to serverlog_id {}",
+                path.display().to_string().green().bold(),
+                id.to_string().green().bold()
+            ),
+            None => warn!("No serverlog_id could be resolved for {}", path.display().to_string().yellow()),
+        }
+        self.cache.insert(path.to_path_buf(), resolved);
+        resolved
+    }
+
+    /// Returns the game (`serveurs.jeu`, lowercase) of the server of a serverlog_id, looked up once per id.
+    ///
+    /// # Returns
+    /// The game, or `None` if no server matches or the database is unavailable.
+    /// A database error isn't cached, the next line tries again.
+    pub fn game_of(&mut self, serverlog_id: u32) -> Option<String> {
+        self.game_of_with(serverlog_id, game_in_database)
+    }
+
+    /// [`ServerlogResolver::game_of`], the game being looked up with `lookup` on a cache miss.
+    fn game_of_with(&mut self, serverlog_id: u32, lookup: impl FnOnce(u32) -> Result<Option<String>, String>) -> Option<String> {
+        self.drop_stale_caches();
+        if let Some(cached) = self.games.get(&serverlog_id) {
+            return cached.clone();
+        }
+
+        let game = match lookup(serverlog_id) {
+            Ok(game) => game.map(|game| game.trim().to_lowercase()),
+            Err(e) => {
+                error!("Error resolving the game of serverlog_id {}: {}", serverlog_id, e);
+                return None;
+            }
+        };
+        match &game {
+            Some(game) => debug!("serverlog_id {} is a {} server", serverlog_id, game.green().bold()),
+            None => warn!("No game could be resolved for serverlog_id {}, triggers filtered by game apply to it", serverlog_id),
+        }
+        self.games.insert(serverlog_id, game.clone());
+        game
+    }
+
+    /// Forgets the cached resolution of a file, used when it is removed.
+    pub fn forget(&mut self, path: &Path) {
+        self.cache.remove(path);
+    }
+
+    /// Empties the caches once the active server list changed : a folder named after a server added
+    /// since then resolves, and a renamed server gets its new resolution.
+    fn drop_stale_caches(&mut self) {
+        let generation = active_servers::generation();
+        if generation != self.generation {
+            debug!("Active server list changed, log file resolutions cleared");
+            self.cache.clear();
+            self.games.clear();
+            self.generation = generation;
+        }
+    }
+
+    fn resolve_uncached(
+        &self,
+        path: &Path,
+        by_name: impl FnOnce(&str) -> Result<Option<u32>, String>,
+    ) -> Result<Option<u32>, String> {
+        let folder_name = path
+            .parent()
+            .and_then(|p| p.file_name())
+            .and_then(|s| s.to_str());
+
+        // Numeric folder name, the historical layout
+        if let Some(id) = folder_name.and_then(|s| s.parse::<u32>().ok()) {
+            return Ok(Some(id));
+        }
+
+        // Mapping section of triggers.toml
+        if let Some(id) = folder_name.and_then(|s| self.by_folder.get(s)) {
+            return Ok(Some(*id));
+        }
+        if let Some(index) = self.by_glob.matches(path).first() {
+            return Ok(Some(self.glob_ids[*index]));
+        }
+
+        // Server name in database
+        match folder_name {
+            Some(folder_name) => by_name(folder_name),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Returns the id of the active server named `name` in database, `None` without database.
+fn active_server_named(name: &str) -> Result<Option<u32>, String> {
+    let Some(db) = helper::open_database::open_db_from_env() else {
+        return Ok(None);
+    };
+    db.get_active_server_id_by_name(name)
+        .map(|id| id.map(|id| id as u32))
+        .map_err(|e| e.to_string())
+}
+
+/// Returns the game (`serveurs.jeu`) of the server of a serverlog_id in database, `None` without database.
+fn game_in_database(serverlog_id: u32) -> Result<Option<String>, String> {
+    let Some(db) = helper::open_database::open_db_from_env() else {
+        return Ok(None);
+    };
+    db.get_server_by_active_server_id(serverlog_id as u64)
+        .map(|server| server.map(|server| server.jeu))
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    fn resolver(mapping: &[(&str, u32)]) -> ServerlogResolver {
+        ServerlogResolver::new(mapping.iter().map(|(key, id)| (key.to_string(), *id)).collect())
+    }
+
+    #[test]
+    fn numeric_folder_then_mapping_then_database() {
+        let mut resolver = resolver(&[("12", 99), ("survie", 3), ("/srv/logs/*/modded.log", 4)]);
+        let looked_up = Cell::new(0);
+        let database = |name: &str| {
+            looked_up.set(looked_up.get() + 1);
+            Ok((name == "creatif").then_some(5))
+        };
+
+        assert_eq!(resolver.resolve_with(Path::new("/srv/logs/12/latest.log"), database), Some(12), "the folder number wins");
+        assert_eq!(resolver.resolve_with(Path::new("/srv/logs/survie/latest.log"), database), Some(3));
+        assert_eq!(resolver.resolve_with(Path::new("/srv/logs/other/modded.log"), database), Some(4));
+        assert_eq!(looked_up.get(), 0, "the database is only asked last");
+
+        assert_eq!(resolver.resolve_with(Path::new("/srv/logs/creatif/latest.log"), database), Some(5));
+        assert_eq!(resolver.resolve_with(Path::new("/srv/logs/inconnu/latest.log"), database), None);
+        assert_eq!(looked_up.get(), 2);
+    }
+
+    #[test]
+    fn mapping_globs() {
+        let mut resolver = resolver(&[("/srv/logs/*/modded.log", 4), ("/srv/**/console-?.txt", 6)]);
+        let none = |_: &str| Ok(None);
+        assert_eq!(resolver.resolve_with(Path::new("/srv/logs/a/modded.log"), none), Some(4));
+        assert_eq!(resolver.resolve_with(Path::new("/srv/logs/a/b/modded.log"), none), None, "* stops at folders");
+        assert_eq!(resolver.resolve_with(Path::new("/srv/logs/a/modded.logs"), none), None, "globs are anchored");
+        assert_eq!(resolver.resolve_with(Path::new("/srv/a/b/console-1.txt"), none), Some(6));
+        assert_eq!(resolver.resolve_with(Path::new("/srv/a/b/console-12.txt"), none), None);
+    }
+
+    #[test]
+    fn only_the_glob_characters_are_special() {
+        let mut resolver = resolver(&[("/srv/a.b+(c)/*.log", 7)]);
+        let none = |_: &str| Ok(None);
+        assert_eq!(resolver.resolve_with(Path::new("/srv/a.b+(c)/latest.log"), none), Some(7));
+        assert_eq!(resolver.resolve_with(Path::new("/srv/aXb+(c)/latest.log"), none), None);
+    }
+
+    #[test]
+    fn resolutions_are_cached_until_the_active_servers_change() {
+        let mut resolver = resolver(&[]);
+        let path = Path::new("/srv/logs/creatif/latest.log");
+        let looked_up = Cell::new(0);
+        let database = |id: Option<u32>| {
+            let looked_up = &looked_up;
+            move |_: &str| {
+                looked_up.set(looked_up.get() + 1);
+                Ok(id)
+            }
+        };
+
+        assert_eq!(resolver.resolve_with(path, database(None)), None);
+        assert_eq!(resolver.resolve_with(path, database(Some(5))), None, "a failed resolution is cached too");
+        assert_eq!(looked_up.get(), 1);
+
+        // The server was added since : the list changed
+        resolver.generation = active_servers::generation().wrapping_sub(1);
+        assert_eq!(resolver.resolve_with(path, database(Some(5))), Some(5));
+        assert_eq!(looked_up.get(), 2);
+
+        resolver.forget(path);
+        assert_eq!(resolver.resolve_with(path, database(Some(7))), Some(7));
+    }
+
+    #[test]
+    fn database_errors_are_not_cached() {
+        let mut resolver = resolver(&[]);
+        let path = Path::new("/srv/logs/creatif/latest.log");
+
+        assert_eq!(resolver.resolve_with(path, |_| Err("connection lost".to_string())), None);
+        assert_eq!(resolver.resolve_with(path, |_| Ok(Some(5))), Some(5), "an error is retried");
+        assert_eq!(resolver.resolve_with(path, |_| Ok(Some(7))), Some(5), "the real answer is cached");
+    }
+
+    #[test]
+    fn games_are_cached_but_not_database_errors() {
+        let mut resolver = resolver(&[]);
+        let looked_up = Cell::new(0);
+        let database = |answer: Result<Option<&'static str>, &'static str>| {
+            let looked_up = &looked_up;
+            move |_: u32| {
+                looked_up.set(looked_up.get() + 1);
+                answer.map(|game| game.map(str::to_string)).map_err(str::to_string)
+            }
+        };
+
+        assert_eq!(resolver.game_of_with(1, database(Ok(Some(" Minecraft ")))), Some("minecraft".to_string()));
+        assert_eq!(resolver.game_of_with(1, database(Ok(Some("palworld")))), Some("minecraft".to_string()));
+        assert_eq!(looked_up.get(), 1, "the game is looked up once per serverlog_id");
+
+        assert_eq!(resolver.game_of_with(2, database(Err("connection lost"))), None);
+        assert_eq!(resolver.game_of_with(2, database(Ok(Some("palworld")))), Some("palworld".to_string()), "an error is retried");
+
+        assert_eq!(resolver.game_of_with(3, database(Ok(None))), None);
+        assert_eq!(resolver.game_of_with(3, database(Ok(Some("palworld")))), None, "an unknown server is cached");
+        assert_eq!(looked_up.get(), 4);
+
+        resolver.generation = active_servers::generation().wrapping_sub(1);
+        assert_eq!(resolver.game_of_with(3, database(Ok(Some("palworld")))), Some("palworld".to_string()));
+    }
+}
@@ -0,0 +1,146 @@
+use std::path::PathBuf;
+use colored::Colorize;
+
+use crate::helper;
+use crate::serverlog::log_watcher::decode_log_bytes;
+use crate::serverlog::serverlog_resolver::ServerlogResolver;
+use crate::serverlog::triggers::{self, Triggers};
+use crate::serverlog::{actions, default_triggers, log_encoding, self_guard};
+
+/// Lines tested by `otternel test-trigger`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TestInput {
+    Line(String),
+    File(PathBuf),
+}
+
+/// Options of `otternel test-trigger`.
+#[derive(Debug, Clone)]
+pub struct TriggerTestOptions {
+    pub input: TestInput,
+    /// Server of the lines, resolved from the path of the file when not given
+    pub serverlog_id: Option<u32>,
+    /// Game of the server, for the triggers filtering on `games` (they all apply when not given)
+    pub game: Option<String>,
+    /// Runs the actions of the matches, in dry-run
+    pub dispatch: bool,
+    pub triggers_path: String,
+}
+
+impl TriggerTestOptions {
+    /// Parses the arguments following `test-trigger` : `--line "<line>"` or `--file <path>`, and
+    /// `--serverlog-id N`, `--game <game>`, `--triggers <path>`, `--dispatch`.
+    pub fn from_args(args: &[String]) -> Result<Self, String> {
+        let mut input = None;
+        let mut serverlog_id = None;
+        let mut game = None;
+        let mut dispatch = false;
+        let mut triggers_path = default_triggers::TRIGGERS_PATH.to_string();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| args.next().cloned().ok_or_else(|| format!("missing value for {name}"));
+            match arg.as_str() {
+                "--line" => input = Some(TestInput::Line(value(arg)?)),
+                "--file" => input = Some(TestInput::File(PathBuf::from(value(arg)?))),
+                "--serverlog-id" => {
+                    serverlog_id = Some(value(arg)?.parse().map_err(|e| format!("invalid value for --serverlog-id: {e}"))?)
+                }
+                "--game" => game = Some(value(arg)?.to_lowercase()),
+                "--triggers" => triggers_path = value(arg)?,
+                "--dispatch" => dispatch = true,
+                other => return Err(format!("unknown option: {other}")),
+            }
+        }
+        let input = input.ok_or("--line or --file is required")?;
+        if matches!(input, TestInput::Line(_)) && serverlog_id.is_none() {
+            return Err("--serverlog-id is required with --line".to_string());
+        }
+        Ok(Self { input, serverlog_id, game, dispatch, triggers_path })
+    }
+}
+
+/// Loads the triggers (database and file) and prints, for each tested line, the triggers matching it, their captures and the action
+/// called. Nothing is written nor sent : with `--dispatch` the actions run in dry-run (they still read the database).
+///
+/// # Returns
+/// The number of lines matched by at least one trigger.
+pub fn run(options: &TriggerTestOptions) -> Result<usize, String> {
+    helper::dry_run::enable();
+    let loaded = triggers::load_with_database(&options.triggers_path);
+    println!("{} triggers loaded from {}", loaded.compiled.len().to_string().green().bold(), options.triggers_path);
+
+    let (lines, serverlog_id) = match &options.input {
+        TestInput::Line(line) => (vec![line.clone()], options.serverlog_id),
+        TestInput::File(path) => {
+            let bytes = std::fs::read(path).map_err(|e| format!("cannot read {}: {e}", path.display()))?;
+            let lines = decode_log_bytes(&bytes, log_encoding::forced_for(path)).lines().map(str::to_string).collect();
+            let serverlog_id = options.serverlog_id.or_else(|| ServerlogResolver::new(loaded.mapping.clone()).resolve(path));
+            (lines, serverlog_id)
+        }
+    };
+    let serverlog_id = serverlog_id.ok_or("the serverlog_id of the file can't be resolved, give --serverlog-id")?;
+
+    let mut matched_lines = 0;
+    for (number, line) in lines.iter().enumerate() {
+        if test_line(&loaded, line, serverlog_id, options, number + 1) {
+            matched_lines += 1;
+        }
+    }
+    println!(
+        "{} of {} lines matched (serverlog_id {})",
+        matched_lines.to_string().green().bold(),
+        lines.len(),
+        serverlog_id
+    );
+    Ok(matched_lines)
+}
+
+/// Prints the matches of a line, and dispatches them if asked. Returns true if a trigger matched.
+fn test_line(loaded: &Triggers, line: &str, serverlog_id: u32, options: &TriggerTestOptions, number: usize) -> bool {
+    let cleaned_line = triggers::clean_line(line);
+    let from_self = self_guard::is_self_line(cleaned_line);
+    let matches = loaded.matching(cleaned_line, serverlog_id, options.game.as_deref(), from_self);
+    // A file shows its matching lines only, a single line always shows its result
+    if matches.as_ref().is_none_or(Vec::is_empty) && matches!(options.input, TestInput::File(_)) {
+        return false;
+    }
+
+    println!("{} {}", format!("{number}:").bright_black(), cleaned_line.bright_blue());
+    let Some(matches) = matches else {
+        println!("  {}", "dropped by an [[ignore]], no trigger evaluated".yellow());
+        return false;
+    };
+    if from_self {
+        println!("  written by Otternel itself : only the triggers with allow_self are evaluated");
+    }
+    if matches.is_empty() {
+        println!("  {}", "no trigger matches".yellow());
+    }
+    for m in &matches {
+        println!("  {} -> {}", m.trigger.name.green().bold(), m.trigger.function);
+        let mut captures: Vec<_> = m.captures.iter().collect();
+        captures.sort();
+        for (name, value) in captures {
+            println!("    {name} = {value:?}");
+        }
+        if m.trigger.collect_lines > 0 {
+            println!("    collects the next {} lines before the action", m.trigger.collect_lines);
+        }
+        if let Some(cooldown) = m.trigger.cooldown {
+            println!("    cooldown of {}s on the server after a match", cooldown.as_secs());
+        }
+        if m.trigger.priority != 0 {
+            println!("    priority {}", m.trigger.priority);
+        }
+        if m.trigger.stop {
+            println!("    stop : the next triggers aren't evaluated for this line");
+        }
+        if m.trigger.options.no_db {
+            println!("    no_db : no database write");
+        }
+        if options.dispatch {
+            actions::dispatch(&m.trigger.function, cleaned_line, serverlog_id, &m.captures, &m.trigger.options);
+        }
+    }
+    !matches.is_empty()
+}
@@ -0,0 +1,256 @@
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+use colored::Colorize;
+use log::{info, warn};
+
+use crate::config::Config;
+
+const DEFAULT_SAMPLE_DIR: &str = "trigger_samples";
+const SAMPLE_WINDOW: Duration = Duration::from_secs(3600);
+
+/// Sampler of the lines matching no trigger, `None` when `TRIGGER_SAMPLE_UNMATCHED` isn't set
+static SAMPLER: LazyLock<Option<Mutex<UnmatchedSampler>>> = LazyLock::new(|| UnmatchedSampler::from_env().map(Mutex::new));
+
+/// Number of lines stored by the sampler since the start
+static SAMPLED: AtomicU64 = AtomicU64::new(0);
+
+/// Calls of each unknown action name since the last status report
+static UNKNOWN_ACTIONS: LazyLock<Mutex<HashMap<String, u64>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Stores a random share of the lines matching no trigger in `<TRIGGER_SAMPLE_DIR>/<serverlog_id>.log`,
+/// to find the lines the triggers miss :
+/// - `TRIGGER_SAMPLE_UNMATCHED` : share of the lines stored, between 0 and 1 (ex: 0.01, Not set = disabled),
+/// - `TRIGGER_SAMPLE_MAX_PER_HOUR` : lines stored per hour at most, all servers together (Default 100),
+/// - `TRIGGER_SAMPLE_DIR` : folder of the sample files (Default `trigger_samples`),
+/// - `TRIGGER_SAMPLE_MAX_KB` : size of a sample file before it is renamed to `<serverlog_id>.log.1` (Default 512).
+struct UnmatchedSampler {
+    rate: f64,
+    max_per_hour: u32,
+    dir: PathBuf,
+    max_bytes: u64,
+    window_start: Instant,
+    stored_in_window: u32,
+}
+
+impl UnmatchedSampler {
+    fn new(rate: f64, max_per_hour: u32, dir: PathBuf, max_bytes: u64) -> Self {
+        Self {
+            rate: rate.clamp(0.0, 1.0),
+            max_per_hour,
+            dir,
+            max_bytes,
+            window_start: Instant::now(),
+            stored_in_window: 0,
+        }
+    }
+
+    fn from_env() -> Option<Self> {
+        let cfg = Config::current();
+        let rate = parse_rate(&cfg.trigger_sample_unmatched)?;
+        let max_per_hour = cfg.trigger_sample_max_per_hour;
+        let dir = cfg
+            .trigger_sample_dir
+            .clone()
+            .filter(|dir| !dir.trim().is_empty())
+            .unwrap_or_else(|| DEFAULT_SAMPLE_DIR.to_string());
+        let max_kb = cfg.trigger_sample_max_kb;
+
+        info!(
+            "Sampling {}% of the unmatched lines in {} ({} lines per hour at most)",
+            rate * 100.0,
+            dir.green(),
+            max_per_hour
+        );
+        Some(Self::new(rate, max_per_hour, PathBuf::from(dir), max_kb * 1024))
+    }
+
+    /// Decides if a line is stored, from `draw` (uniform between 0 and 1) : lines are kept with a probability of
+    /// `rate`, until `max_per_hour` lines are stored in the current hour.
+    fn should_store(&mut self, draw: f64, now: Instant) -> bool {
+        if now.duration_since(self.window_start) >= SAMPLE_WINDOW {
+            self.window_start = now;
+            self.stored_in_window = 0;
+        }
+        if draw >= self.rate || self.stored_in_window >= self.max_per_hour {
+            return false;
+        }
+        self.stored_in_window += 1;
+        true
+    }
+
+    /// Appends a line to the sample file of the server, rotated once over `max_bytes`.
+    fn store(&self, serverlog_id: u32, line: &str) -> std::io::Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        let path = self.dir.join(format!("{}.log", serverlog_id));
+
+        let entry = format!("{} {}", chrono::Local::now().format("%Y-%m-%d %H:%M:%S"), line.trim_end());
+        let size = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+        if size > 0 && size + entry.len() as u64 + 1 > self.max_bytes {
+            std::fs::rename(&path, self.dir.join(format!("{}.log.1", serverlog_id)))?;
+        }
+
+        let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+        writeln!(file, "{}", entry)
+    }
+}
+
+/// Parses `TRIGGER_SAMPLE_UNMATCHED`, capped to 1.
+///
+/// # Returns
+/// `None` (sampling disabled) if the value isn't a number above 0.
+fn parse_rate(value: &str) -> Option<f64> {
+    let rate: f64 = value.trim().parse().ok()?;
+    (rate.is_finite() && rate > 0.0).then(|| rate.min(1.0))
+}
+
+/// Gives a line that matched no trigger to the sampler, if sampling is enabled.
+pub fn sample_unmatched(serverlog_id: u32, line: &str) {
+    let Some(sampler) = SAMPLER.as_ref() else {
+        return;
+    };
+    let mut sampler = sampler.lock().unwrap_or_else(|e| e.into_inner());
+    if !sampler.should_store(rand::random::<f64>(), Instant::now()) {
+        return;
+    }
+    match sampler.store(serverlog_id, line) {
+        Ok(()) => {
+            SAMPLED.fetch_add(1, Ordering::Relaxed);
+        }
+        Err(e) => warn!("Could not store a sampled line of serverlog {}: {}", serverlog_id, e),
+    }
+}
+
+/// Returns the number of unmatched lines stored since the start, or `None` if sampling is disabled.
+pub fn sampled_count() -> Option<u64> {
+    SAMPLER.as_ref().map(|_| SAMPLED.load(Ordering::Relaxed))
+}
+
+/// Counts a call to an unknown action. Only the first call of each name since the last status report is logged.
+pub fn record_unknown_action(function: &str) {
+    let mut counts = UNKNOWN_ACTIONS.lock().unwrap_or_else(|e| e.into_inner());
+    let count = counts.entry(function.to_string()).or_insert(0);
+    if *count == 0 {
+        warn!("Unknown action function: {} (next calls are counted in the status report)", function.yellow());
+    }
+    *count += 1;
+}
+
+/// Returns the calls of each unknown action name since the last call, most called first, and resets them.
+pub fn take_unknown_actions() -> Vec<(String, u64)> {
+    let mut counts: Vec<(String, u64)> = UNKNOWN_ACTIONS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .drain()
+        .collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sampler(rate: f64, max_per_hour: u32) -> UnmatchedSampler {
+        UnmatchedSampler::new(rate, max_per_hour, std::env::temp_dir(), 1024)
+    }
+
+    #[test]
+    fn rate_bounds() {
+        let cases = [
+            ("0.01", Some(0.01)),
+            (" 0.5 ", Some(0.5)),
+            ("1", Some(1.0)),
+            ("3", Some(1.0)),
+            ("0", None),
+            ("-0.2", None),
+            ("NaN", None),
+            ("inf", None),
+            ("", None),
+            ("1%", None),
+        ];
+        for (value, rate) in cases {
+            assert_eq!(parse_rate(value), rate, "{value:?}");
+        }
+
+        assert_eq!(UnmatchedSampler::new(7.0, 1, PathBuf::new(), 1).rate, 1.0);
+        assert_eq!(UnmatchedSampler::new(-1.0, 1, PathBuf::new(), 1).rate, 0.0);
+    }
+
+    #[test]
+    fn lines_are_kept_at_the_rate() {
+        let now = Instant::now();
+
+        // Draws spread evenly between 0 and 1 : the share kept is the rate
+        let draws: Vec<f64> = (0..10_000).map(|i| i as f64 / 10_000.0).collect();
+        for (rate, kept) in [(0.01, 100), (0.25, 2_500), (1.0, 10_000)] {
+            let mut sampler = sampler(rate, u32::MAX);
+            assert_eq!(draws.iter().filter(|draw| sampler.should_store(**draw, now)).count(), kept, "rate {rate}");
+        }
+
+        // The bounds : a draw equal to the rate is dropped, a zero rate keeps nothing
+        assert!(!sampler(0.5, 10).should_store(0.5, now));
+        assert!(sampler(0.5, 10).should_store(0.499, now));
+        assert!(!sampler(0.0, 10).should_store(0.0, now));
+    }
+
+    #[test]
+    fn at_most_max_per_hour_lines_are_stored() {
+        let mut sampler = sampler(1.0, 3);
+        let start = sampler.window_start;
+
+        let kept = (0..10).filter(|_| sampler.should_store(0.0, start)).count();
+        assert_eq!(kept, 3);
+        assert!(!sampler.should_store(0.0, start + Duration::from_secs(3599)));
+
+        // A new hour starts a new count
+        let next_hour = start + SAMPLE_WINDOW;
+        let kept = (0..10).filter(|_| sampler.should_store(0.0, next_hour)).count();
+        assert_eq!(kept, 3);
+
+        assert!(!self::sampler(1.0, 0).should_store(0.0, Instant::now()));
+    }
+
+    #[test]
+    fn sample_files_are_rotated() {
+        let dir = std::env::temp_dir().join(format!("otternel-samples-{}", uuid::Uuid::new_v4()));
+        let sampler = UnmatchedSampler::new(1.0, 100, dir.clone(), 100);
+        let line = "x".repeat(40);
+
+        sampler.store(7, &format!("{line}\n")).unwrap();
+        let first = std::fs::read_to_string(dir.join("7.log")).unwrap();
+        assert!(first.ends_with(&format!(" {line}\n")), "{first:?}");
+
+        sampler.store(7, &line).unwrap();
+        assert_eq!(std::fs::read_to_string(dir.join("7.log.1")).unwrap(), first);
+        assert_eq!(std::fs::read_to_string(dir.join("7.log")).unwrap().lines().count(), 1);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn unknown_actions_are_counted_by_name() {
+        for _ in 0..3 {
+            record_unknown_action("tuning_test_often");
+        }
+        record_unknown_action("tuning_test_once");
+        record_unknown_action("tuning_test_also_once");
+
+        let counts: Vec<(String, u64)> = take_unknown_actions()
+            .into_iter()
+            .filter(|(name, _)| name.starts_with("tuning_test_"))
+            .collect();
+        assert_eq!(counts, vec![
+            ("tuning_test_often".to_string(), 3),
+            ("tuning_test_also_once".to_string(), 1),
+            ("tuning_test_once".to_string(), 1),
+        ]);
+
+        // Reset by the report
+        assert!(take_unknown_actions().iter().all(|(name, _)| !name.starts_with("tuning_test_")));
+    }
+}
@@ -0,0 +1,526 @@
+use std::collections::HashMap;
+use std::time::Duration;
+use log::{error, warn};
+use regex::{Regex, RegexSet};
+use serde::Deserialize;
+
+use crate::db::models::ServerlogTrigger;
+use crate::serverlog::actions::{ActionOptions, MessageStyle, TriggerCaptures};
+use crate::helper::open_database::open_db_from_env;
+use crate::serverlog::default_triggers;
+
+/// Default number of lines collected after an `on_server_error` trigger
+const DEFAULT_COLLECT_LINES: usize = 30;
+
+/// A trigger of `triggers.toml`, as written.
+#[derive(Deserialize)]
+struct Trigger {
+    name: Option<String>,
+    pattern: Patterns,
+    function: String,
+    serverlog_ids: Option<Vec<u32>>,
+    game: Option<String>,
+    games: Option<Vec<String>>,
+    allow_self: Option<bool>,
+    style: Option<String>,
+    collect_lines: Option<usize>,
+    no_db: Option<bool>,
+    /// Negative values are rejected by the parsing
+    cooldown_secs: Option<u64>,
+    priority: Option<i32>,
+    stop: Option<bool>,
+}
+
+/// `pattern` of a trigger : one regex, or several regexes of which any can match.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Patterns {
+    One(String),
+    Many(Vec<String>),
+}
+
+/// An `[[ignore]]` of `triggers.toml` : the lines it matches reach no trigger.
+#[derive(Deserialize)]
+struct Ignore {
+    pattern: String,
+    serverlog_ids: Option<Vec<u32>>,
+}
+
+#[derive(Deserialize)]
+struct TriggerFile {
+    trigger: Vec<Trigger>,
+    #[serde(default)]
+    ignore: Vec<Ignore>,
+    mapping: Option<HashMap<String, u32>>,
+}
+
+/// A trigger of `triggers.toml` with its regex compiled.
+pub struct CompiledTrigger {
+    /// `name` of the trigger, or its `function` when it has none
+    pub name: String,
+    /// Valid regexes of `pattern`, in order
+    pub regexes: Vec<Regex>,
+    /// The regexes tested together, only built when there are several
+    set: Option<RegexSet>,
+    pub function: String,
+    pub serverlog_ids: Option<Vec<u32>>,
+    /// Games of the servers concerned (lowercase), from `games` or `game`
+    pub games: Option<Vec<String>>,
+    /// Whether the trigger also matches lines written by Otternel itself through RCON
+    pub allow_self: bool,
+    /// Number of following lines of the same file to collect before calling the action (0 = none)
+    pub collect_lines: usize,
+    /// After a match, the trigger is ignored on the same server for this long (`cooldown_secs`, Not set or 0 = none)
+    pub cooldown: Option<Duration>,
+    /// Triggers are evaluated by increasing priority (Not set = 0), then in the order of the file
+    pub priority: i32,
+    /// When the trigger matches a line, the triggers after it aren't evaluated for this line
+    pub stop: bool,
+    pub options: ActionOptions,
+}
+
+impl CompiledTrigger {
+    /// Tells whether the trigger applies to a server.
+    ///
+    /// # Precedence
+    /// 1. `serverlog_ids` set : the trigger applies to these servers only, `games` is ignored.
+    /// 2. `games` set : the trigger applies to the servers of these games. If the game of the server
+    ///    couldn't be resolved (`game` is `None`), it applies, as a trigger without `serverlog_ids` would.
+    /// 3. Neither set : the trigger applies to every server.
+    pub fn applies_to(&self, serverlog_id: u32, game: Option<&str>) -> bool {
+        if let Some(ids) = &self.serverlog_ids {
+            return ids.contains(&serverlog_id);
+        }
+        match (&self.games, game) {
+            (Some(games), Some(game)) => games.iter().any(|g| g.eq_ignore_ascii_case(game)),
+            _ => true,
+        }
+    }
+
+    /// Matches a cleaned line, and returns the named groups captured (given to the action).
+    /// With several patterns, the first one matching gives the groups.
+    pub fn captures(&self, line: &str) -> Option<TriggerCaptures> {
+        let regex = match &self.set {
+            Some(set) => &self.regexes[set.matches(line).into_iter().next()?],
+            None => self.regexes.first()?,
+        };
+        let caps = regex.captures(line)?;
+        Some(
+            regex
+                .capture_names()
+                .flatten()
+                .filter_map(|name| caps.name(name).map(|m| (name.to_string(), m.as_str().to_string())))
+                .collect(),
+        )
+    }
+}
+
+/// An `[[ignore]]` with its regex compiled.
+pub struct CompiledIgnore {
+    pub regex: Regex,
+    /// Servers concerned (Not set = every server)
+    pub serverlog_ids: Option<Vec<u32>>,
+}
+
+/// The triggers of a triggers file, its ignores and its `[mapping]` section.
+#[derive(Default)]
+pub struct Triggers {
+    pub compiled: Vec<CompiledTrigger>,
+    pub ignores: Vec<CompiledIgnore>,
+    /// Folder name -> serverlog_id, for the folders not named by their id
+    pub mapping: HashMap<String, u32>,
+}
+
+/// Loads and compiles the triggers of `path` (the built-in defaults if it doesn't exist).
+/// An invalid regex is logged and left out of its trigger, a trigger without any valid regex is skipped;
+/// an unreadable or invalid file gives no trigger.
+pub fn load(path: &str) -> Triggers {
+    let Some(content) = default_triggers::read_triggers_file(path) else {
+        error!("No triggers loaded (unreadable {})", path);
+        return Triggers::default();
+    };
+    let trigger_file: TriggerFile = match toml::from_str(&content) {
+        Ok(trigger_file) => trigger_file,
+        Err(e) => {
+            error!("No triggers loaded, invalid {}: {}", path, e);
+            return Triggers::default();
+        }
+    };
+
+    let mut compiled = Vec::new();
+    for t in trigger_file.trigger {
+        let name = t.name.clone().unwrap_or_else(|| t.function.clone());
+        match compile(t) {
+            Ok(trigger) => compiled.push(trigger),
+            Err(e) => error!("Trigger '{}' skipped: {}", name, e),
+        }
+    }
+    let ignores = trigger_file
+        .ignore
+        .into_iter()
+        .filter_map(|ignore| match Regex::new(&ignore.pattern) {
+            Ok(regex) => Some(CompiledIgnore { regex, serverlog_ids: ignore.serverlog_ids }),
+            Err(e) => {
+                error!("Invalid regex in ignore: {} ({})", ignore.pattern, e);
+                None
+            }
+        })
+        .collect();
+    sort_by_priority(&mut compiled);
+    Triggers { compiled, ignores, mapping: trigger_file.mapping.unwrap_or_default() }
+}
+
+/// Orders the triggers by priority. The sort is stable : the order of the file breaks the ties.
+fn sort_by_priority(compiled: &mut [CompiledTrigger]) {
+    compiled.sort_by_key(|trigger| trigger.priority);
+}
+
+/// A trigger matching a line, with the named groups it captured.
+pub struct TriggerMatch<'a> {
+    pub trigger: &'a CompiledTrigger,
+    pub captures: TriggerCaptures,
+}
+
+/// Compiles a trigger of `triggers.toml` (or of the database).
+/// Each invalid regex of `pattern` is logged and left out, the trigger keeping the valid ones.
+///
+/// # Returns
+/// The compiled trigger, or an error if `pattern` is an empty list or none of its regexes is valid.
+fn compile(t: Trigger) -> Result<CompiledTrigger, String> {
+    let name = t.name.clone().unwrap_or_else(|| t.function.clone());
+    let patterns = match t.pattern {
+        Patterns::One(pattern) => vec![pattern],
+        Patterns::Many(patterns) => patterns,
+    };
+    if patterns.is_empty() {
+        return Err("empty pattern list".to_string());
+    }
+    let regexes: Vec<Regex> = patterns
+        .iter()
+        .filter_map(|pattern| match Regex::new(pattern) {
+            Ok(regex) => Some(regex),
+            Err(e) => {
+                error!("Invalid regex in trigger '{}': {} ({})", name, pattern, e);
+                None
+            }
+        })
+        .collect();
+    if regexes.is_empty() {
+        return Err("no valid regex".to_string());
+    }
+    let set = if regexes.len() > 1 {
+        Some(RegexSet::new(regexes.iter().map(Regex::as_str)).map_err(|e| e.to_string())?)
+    } else {
+        None
+    };
+
+    let style = match t.style.as_deref().map(MessageStyle::parse) {
+        Some(Some(style)) => style,
+        Some(None) => {
+            warn!("Unknown style in trigger '{}', using embed", t.name.clone().unwrap_or_default());
+            MessageStyle::Embed
+        }
+        None => MessageStyle::Embed,
+    };
+    // `games` and `game` can be used together, their games add up
+    let games: Vec<String> = t.games.iter().flatten().chain(t.game.iter())
+        .map(|game| game.trim().to_lowercase())
+        .filter(|game| !game.is_empty())
+        .collect();
+    Ok(CompiledTrigger {
+        regexes,
+        set,
+        name: name.clone(),
+        serverlog_ids: t.serverlog_ids,
+        games: if games.is_empty() { None } else { Some(games) },
+        allow_self: t.allow_self.unwrap_or(false),
+        collect_lines: t.collect_lines.unwrap_or(if t.function == "on_server_error" { DEFAULT_COLLECT_LINES } else { 0 }),
+        cooldown: t.cooldown_secs.filter(|secs| *secs > 0).map(Duration::from_secs),
+        priority: t.priority.unwrap_or(0),
+        stop: t.stop.unwrap_or(false),
+        function: t.function,
+        options: ActionOptions { style, no_db: t.no_db.unwrap_or(false), trigger_name: name },
+    })
+}
+
+/// Loads the triggers of the database (`serverlog_triggers`) and of `path`, the file being the fallback :
+/// the database triggers come first, then the triggers of the file whose name isn't used in the database,
+/// all then ordered by `priority` (the database triggers have none, they keep priority 0).
+/// Ignores and `[mapping]` come from the file. Without database, only the file is used.
+/// A database trigger with an invalid regex is skipped with a warning naming it.
+pub fn load_with_database(path: &str) -> Triggers {
+    let mut loaded = load(path);
+    let Some(db) = open_db_from_env() else {
+        return loaded;
+    };
+    let rows = match db.get_active_triggers() {
+        Ok(rows) => rows,
+        Err(e) => {
+            warn!("Could not load the triggers of the database, only {} is used: {}", path, e);
+            return loaded;
+        }
+    };
+    loaded.compiled = with_database_triggers(rows, loaded.compiled);
+    loaded
+}
+
+/// Compiles the database triggers and adds the triggers of the file whose name isn't used in the database,
+/// then orders them all by priority (see [`load_with_database`]).
+fn with_database_triggers(rows: Vec<ServerlogTrigger>, from_file: Vec<CompiledTrigger>) -> Vec<CompiledTrigger> {
+    let mut compiled: Vec<CompiledTrigger> = Vec::new();
+    for row in rows {
+        let trigger = Trigger {
+            name: Some(row.nom.clone()),
+            pattern: Patterns::One(row.pattern),
+            function: row.fonction,
+            serverlog_ids: row.serveur_actif_id.map(|id| vec![id as u32]),
+            game: None,
+            games: None,
+            allow_self: None,
+            style: None,
+            collect_lines: None,
+            no_db: None,
+            cooldown_secs: None,
+            priority: None,
+            stop: None,
+        };
+        match compile(trigger) {
+            Ok(trigger) => compiled.push(trigger),
+            Err(e) => warn!("Database trigger '{}' (id {}) skipped: {}", row.nom, row.id, e),
+        }
+    }
+    let in_database: Vec<String> = compiled.iter().map(|t| t.name.clone()).collect();
+    compiled.extend(from_file.into_iter().filter(|t| !in_database.contains(&t.name)));
+    sort_by_priority(&mut compiled);
+    compiled
+}
+
+impl Triggers {
+    /// Tells whether a line of a server is dropped by an `[[ignore]]`.
+    pub fn is_ignored(&self, line: &str, serverlog_id: u32) -> bool {
+        self.ignores.iter().any(|ignore| {
+            ignore.serverlog_ids.as_ref().is_none_or(|ids| ids.contains(&serverlog_id)) && ignore.regex.is_match(line)
+        })
+    }
+
+    /// Returns the triggers matching a cleaned line of a server, in the order of the triggers file.
+    ///
+    /// # Order of evaluation
+    /// 1. The `[[ignore]]` entries : a line matching one of them returns `None`, no trigger is evaluated.
+    ///    An ignore wins over every trigger, even one matching the same line.
+    /// 2. A line written by Otternel itself (`from_self`) only reaches the triggers with `allow_self`.
+    /// 3. The triggers matching the line and applying to the server (see [`CompiledTrigger::applies_to`]), by increasing
+    ///    `priority`, then in the order of the triggers file. The first one with `stop` ends the evaluation.
+    pub fn matching(&self, line: &str, serverlog_id: u32, game: Option<&str>, from_self: bool) -> Option<Vec<TriggerMatch<'_>>> {
+        if self.is_ignored(line, serverlog_id) {
+            return None;
+        }
+        let mut matches = Vec::new();
+        for trigger in self.compiled.iter().filter(|trigger| !from_self || trigger.allow_self) {
+            if !trigger.applies_to(serverlog_id, game) {
+                continue;
+            }
+            let Some(captures) = trigger.captures(line) else {
+                continue;
+            };
+            matches.push(TriggerMatch { trigger, captures });
+            if trigger.stop {
+                break;
+            }
+        }
+        Some(matches)
+    }
+
+    /// Tells whether a trigger filters on the game of the server (its game is only looked up then).
+    pub fn need_game(&self) -> bool {
+        self.compiled.iter().any(|t| t.serverlog_ids.is_none() && t.games.is_some())
+    }
+}
+
+/// Cuts a log line at its first '[' (the timestamp), as the triggers are matched against it.
+pub fn clean_line(line: &str) -> &str {
+    &line[line.find('[').unwrap_or(0)..]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Loads the triggers of a temporary `triggers.toml`
+    fn load_str(content: &str) -> Triggers {
+        let path = std::env::temp_dir().join(format!("otternel-triggers-{}.toml", uuid::Uuid::new_v4()));
+        std::fs::write(&path, content).unwrap();
+        let loaded = load(path.to_str().unwrap());
+        std::fs::remove_file(path).unwrap();
+        loaded
+    }
+
+    fn names(matches: &[TriggerMatch<'_>]) -> Vec<String> {
+        matches.iter().map(|m| m.trigger.name.clone()).collect()
+    }
+
+    const LINE: &str = "[10:00:00] [Server thread/INFO]: Loutre joined the game";
+
+    #[test]
+    fn triggers_are_evaluated_by_priority_then_file_order() {
+        let loaded = load_str(
+            "[[trigger]]\nname = 'first'\npattern = 'joined'\nfunction = 'f'\n\
+             [[trigger]]\nname = 'late'\npattern = 'joined'\nfunction = 'f'\npriority = 10\n\
+             [[trigger]]\nname = 'second'\npattern = 'joined'\nfunction = 'f'\n\
+             [[trigger]]\nname = 'early'\npattern = 'joined'\nfunction = 'f'\npriority = -5\n",
+        );
+        let matches = loaded.matching(LINE, 1, None, false).unwrap();
+        assert_eq!(names(&matches), vec!["early", "first", "second", "late"]);
+    }
+
+    #[test]
+    fn stop_ends_the_evaluation_of_the_line() {
+        let loaded = load_str(
+            "[[trigger]]\nname = 'catch_all'\npattern = '.'\nfunction = 'f'\npriority = 5\n\
+             [[trigger]]\nname = 'join'\npattern = 'joined'\nfunction = 'f'\nstop = true\n\
+             [[trigger]]\nname = 'other'\npattern = 'left'\nfunction = 'f'\n",
+        );
+        assert_eq!(names(&loaded.matching(LINE, 1, None, false).unwrap()), vec!["join"]);
+        // A stop trigger not matching the line doesn't stop anything
+        let left = "[10:00:00] [Server thread/INFO]: Loutre left the game";
+        assert_eq!(names(&loaded.matching(left, 1, None, false).unwrap()), vec!["other", "catch_all"]);
+    }
+
+    #[test]
+    fn ignores_and_self_lines() {
+        let loaded = load_str(
+            "[[trigger]]\nname = 'join'\npattern = 'joined'\nfunction = 'f'\n\
+             [[trigger]]\nname = 'self'\npattern = 'joined'\nfunction = 'f'\nallow_self = true\n\
+             [[ignore]]\npattern = 'Bot joined'\nserverlog_ids = [2]\n",
+        );
+        assert_eq!(names(&loaded.matching(LINE, 1, None, true).unwrap()), vec!["self"]);
+        let bot = "[10:00:00] [Server thread/INFO]: Bot joined the game";
+        assert!(loaded.matching(bot, 2, None, false).is_none());
+        assert_eq!(loaded.matching(bot, 1, None, false).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn an_ignore_wins_over_a_trigger_matching_the_same_line() {
+        // The ignore is declared after the trigger, and the trigger has the highest priority
+        let loaded = load_str(
+            "[[trigger]]\nname = 'message'\npattern = '^\\[.*\\]: <(?P<player>[^>]+)> (?P<message>.*)'\nfunction = 'on_player_message'\npriority = -100\nallow_self = true\n\
+             [[ignore]]\npattern = '<système> '\n\
+             [[ignore]]\npattern = '<Bot> '\nserverlog_ids = [2]\n\
+             [[ignore]]\npattern = '(unclosed'\n",
+        );
+        assert_eq!(loaded.ignores.len(), 2);
+
+        let plugin = "[10:00:00] [Server thread/INFO]: <système> rechargement de la configuration";
+        for serverlog_id in [1, 2, 3] {
+            assert!(loaded.is_ignored(plugin, serverlog_id));
+            assert!(loaded.matching(plugin, serverlog_id, None, false).is_none());
+            assert!(loaded.matching(plugin, serverlog_id, None, true).is_none());
+        }
+
+        // An ignore with serverlog_ids only drops the lines of its servers
+        let bot = "[10:00:00] [Server thread/INFO]: <Bot> bonjour";
+        assert!(loaded.matching(bot, 2, None, false).is_none());
+        let matches = loaded.matching(bot, 1, None, false).unwrap();
+        assert_eq!(names(&matches), vec!["message"]);
+        assert_eq!(matches[0].captures["player"], "Bot");
+
+        let player = "[10:00:00] [Server thread/INFO]: <Loutre> salut";
+        assert!(!loaded.is_ignored(player, 2));
+        assert_eq!(names(&loaded.matching(player, 2, None, false).unwrap()), vec!["message"]);
+    }
+
+    #[test]
+    fn serverlog_ids_win_over_games() {
+        let loaded = load_str(
+            "[[trigger]]\nname = 'ids'\npattern = 'joined'\nfunction = 'f'\nserverlog_ids = [3]\ngame = 'palworld'\n\
+             [[trigger]]\nname = 'games'\npattern = 'joined'\nfunction = 'f'\ngames = ['Minecraft']\n",
+        );
+        assert!(loaded.need_game());
+        assert_eq!(names(&loaded.matching(LINE, 3, Some("minecraft"), false).unwrap()), vec!["ids", "games"]);
+        assert!(loaded.matching(LINE, 4, Some("palworld"), false).unwrap().is_empty());
+        assert_eq!(names(&loaded.matching(LINE, 4, None, false).unwrap()), vec!["games"], "an unknown game applies");
+    }
+
+    #[test]
+    fn precedence_of_serverlog_ids_games_and_unresolved_servers() {
+        let loaded = load_str(
+            "[[trigger]]\nname = 'both'\npattern = 'x'\nfunction = 'f'\nserverlog_ids = [3]\ngames = ['palworld']\n\
+             [[trigger]]\nname = 'ids'\npattern = 'x'\nfunction = 'f'\nserverlog_ids = [3, 5]\n\
+             [[trigger]]\nname = 'games'\npattern = 'x'\nfunction = 'f'\ngame = 'Palworld'\ngames = ['ark']\n\
+             [[trigger]]\nname = 'all'\npattern = 'x'\nfunction = 'f'\n",
+        );
+        let trigger = |name: &str| loaded.compiled.iter().find(|t| t.name == name).unwrap();
+        // (trigger, serverlog_id, game of the server, applies)
+        let cases = [
+            ("both", 3, Some("minecraft"), true),
+            ("both", 4, Some("palworld"), false),
+            ("both", 4, None, false),
+            ("ids", 5, None, true),
+            ("ids", 4, Some("minecraft"), false),
+            ("games", 9, Some("palworld"), true),
+            ("games", 9, Some("ark"), true),
+            ("games", 9, Some("minecraft"), false),
+            ("games", 9, None, true),
+            ("all", 9, Some("minecraft"), true),
+            ("all", 9, None, true),
+        ];
+        for (name, serverlog_id, game, applies) in cases {
+            assert_eq!(trigger(name).applies_to(serverlog_id, game), applies, "{name} on {serverlog_id} ({game:?})");
+        }
+        assert_eq!(trigger("games").games.as_deref(), Some(&["ark".to_string(), "palworld".to_string()][..]));
+    }
+
+    #[test]
+    fn database_triggers_replace_the_file_triggers_of_the_same_name() {
+        let loaded = load_str(
+            "[[trigger]]\nname = 'join'\npattern = 'joined'\nfunction = 'on_file'\n\
+             [[trigger]]\nname = 'urgent'\npattern = 'joined'\nfunction = 'on_file'\npriority = -1\n\
+             [[trigger]]\nname = 'kept'\npattern = 'joined'\nfunction = 'on_file'\n",
+        );
+        let row = |id, nom: &str, pattern: &str| ServerlogTrigger {
+            id,
+            nom: nom.to_string(),
+            pattern: pattern.to_string(),
+            fonction: "on_database".to_string(),
+            serveur_actif_id: Some(1),
+        };
+        let rows = vec![row(1, "join", "Loutre joined"), row(2, "urgent", "(invalid"), row(3, "extra", "joined")];
+        let merged = Triggers { compiled: with_database_triggers(rows, loaded.compiled), ..Triggers::default() };
+
+        let summary: Vec<(&str, &str)> = merged.compiled.iter().map(|t| (t.name.as_str(), t.function.as_str())).collect();
+        // The invalid database trigger is skipped, so the file one of the same name is kept
+        assert_eq!(summary, vec![("urgent", "on_file"), ("join", "on_database"), ("extra", "on_database"), ("kept", "on_file")]);
+        assert_eq!(merged.compiled[1].serverlog_ids, Some(vec![1]));
+        assert!(merged.matching(LINE, 2, None, false).unwrap().iter().all(|m| m.trigger.function == "on_file"));
+    }
+
+    #[test]
+    fn several_patterns_and_invalid_regexes() {
+        let loaded = load_str(
+            "[[trigger]]\nname = 'any'\npattern = ['(?P<player>\\w+) left', '(?P<player>\\w+) joined', '(bad']\nfunction = 'f'\n\
+             [[trigger]]\nname = 'broken'\npattern = '(bad'\nfunction = 'f'\n\
+             [[trigger]]\nname = 'empty'\npattern = []\nfunction = 'f'\n",
+        );
+        assert_eq!(loaded.compiled.len(), 1);
+        assert_eq!(loaded.compiled[0].regexes.len(), 2);
+        let matches = loaded.matching(LINE, 1, None, false).unwrap();
+        assert_eq!(matches[0].captures.get("player").map(String::as_str), Some("Loutre"));
+    }
+
+    #[test]
+    fn clean_line_starts_at_the_first_bracket() {
+        assert_eq!(clean_line("2025-01-31 [10:00:00] x"), "[10:00:00] x");
+        assert_eq!(clean_line("no bracket"), "no bracket");
+    }
+
+    #[test]
+    fn no_db_is_an_option_of_the_trigger() {
+        let loaded = load_str(
+            "[[trigger]]\nname = 'quiet'\npattern = 'joined'\nfunction = 'f'\nno_db = true\n\
+             [[trigger]]\nname = 'tracked'\npattern = 'joined'\nfunction = 'f'\n",
+        );
+        let matches = loaded.matching(LINE, 1, None, false).unwrap();
+        let no_db: Vec<(String, bool)> = matches.iter().map(|m| (m.trigger.name.clone(), m.trigger.options.no_db)).collect();
+        assert_eq!(no_db, vec![("quiet".to_string(), true), ("tracked".to_string(), false)]);
+    }
+}
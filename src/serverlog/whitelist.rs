@@ -0,0 +1,38 @@
+use regex::Regex;
+use std::sync::LazyLock;
+
+/// `Disconnecting <player> (/<ip>:<port>): You are not white-listed on this server!`. The player is a plain name,
+/// or a `GameProfile{...name=<player>...}` / `[id=...,name=<player>,...]` on the Paper versions logging the profile
+static DENIED_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"Disconnecting (.+?) \((?:/(?P<ip>\[[^\]]+\]|[^:)\]]+)(?::\d+)?)?[^)]*\): You are not white-?listed on this server").unwrap()
+});
+static PROFILE_NAME_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\bname=(\.?[A-Za-z0-9_]{1,16})\b").unwrap());
+
+/// Connection refused by the whitelist of a Minecraft server.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WhitelistDenied<'a> {
+    pub playername: &'a str,
+    /// IP of the player, when the line gives it
+    pub ip: Option<&'a str>,
+}
+
+/// Parses a whitelist refusal of a Paper log line.
+///
+/// # Supported formats
+/// - `Disconnecting Loutre (/1.2.3.4:50000): You are not white-listed on this server!`
+/// - `Disconnecting com.mojang.authlib.GameProfile@1a2b[id=<uuid>,name=Loutre,...] (/1.2.3.4:50000): You are not white-listed on this server!`
+///
+/// # Returns
+/// The player and their IP, or `None` if the line isn't a whitelist refusal.
+pub fn parse_denied_line(line: &str) -> Option<WhitelistDenied<'_>> {
+    let caps = DENIED_RE.captures(line)?;
+    let player = caps.get(1)?.as_str();
+    let playername = match PROFILE_NAME_RE.captures(player) {
+        Some(profile) => profile.get(1)?.as_str(),
+        None => player.trim(),
+    };
+    Some(WhitelistDenied {
+        playername,
+        ip: caps.name("ip").map(|m| m.as_str()),
+    })
+}